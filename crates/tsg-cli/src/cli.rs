@@ -1,30 +1,56 @@
+mod binary;
+mod build;
+mod compare;
+mod diff;
 mod dot;
+mod events;
 mod fa;
+mod filter;
+mod gfa;
 mod gtf;
 mod header;
+mod index;
 mod json;
 mod merge;
+mod output;
+mod quant;
 mod query;
 mod split;
 mod summary;
+mod support;
 mod traverse;
+mod validate;
 mod vcf;
 
+pub use binary::*;
+pub use build::*;
+pub use compare::*;
+pub use diff::*;
 pub use dot::*;
+pub use events::*;
 pub use fa::*;
+pub use filter::*;
+pub use gfa::*;
 pub use gtf::*;
 pub use header::*;
+pub use index::*;
 pub use json::*;
 pub use merge::*;
+pub use output::*;
+pub use quant::*;
 pub use query::*;
 pub use split::*;
 pub use summary::*;
+pub use support::*;
 pub use traverse::*;
+pub use validate::*;
 pub use vcf::*;
 
 use clap::Subcommand;
 use clap::ValueHint;
 use std::path::PathBuf;
+use tsg::graph::{FilterExpr, GenomicRegion};
+use tsg::io::GfaVersion;
 
 /// Command line interface for the TSG tool
 #[derive(Subcommand)]
@@ -46,23 +72,163 @@ pub enum Commands {
         output: Option<PathBuf>,
     },
 
+    /// Classify the alternative splicing events of each graph into a TSV table
+    Events {
+        /// Input TSG file path
+        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        input: PathBuf,
+
+        /// Output file path for the event table, default is stdout
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        output: Option<PathBuf>,
+    },
+
+    /// Annotate edges and paths with junction read support (SR, DP1, DP2) and PSI
+    Support {
+        /// Input TSG file path
+        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        input: PathBuf,
+
+        /// Output TSG file path, default is stdout
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        output: Option<PathBuf>,
+    },
+
+    /// Quantify path abundance with EM over read compatibility and write counts and TPM on P records
+    Quant {
+        /// Input TSG file path
+        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        input: PathBuf,
+
+        /// Also write the abundance of each path as a TSV table
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        table: Option<PathBuf>,
+
+        /// Maximum number of EM iterations
+        #[arg(long, default_value_t = 1000)]
+        max_iterations: usize,
+
+        /// Output TSG file path, default is stdout
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        output: Option<PathBuf>,
+    },
+
+    /// Compare traversed paths with a reference annotation and report class codes and junction accuracy
+    Compare {
+        /// Input TSG file path
+        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        input: PathBuf,
+
+        /// Reference annotation in GTF or GFF3 format
+        #[arg(long, required = true, value_hint = ValueHint::FilePath)]
+        annotation: PathBuf,
+
+        /// Also write the TSG file with `class_code` and `ref_transcript_id` attributes on P records
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        annotate: Option<PathBuf>,
+
+        #[command(flatten)]
+        traversal: TraversalArgs,
+
+        /// Output file path for the comparison table, default is stdout
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        output: Option<PathBuf>,
+    },
+
+    /// Show the structural differences between two TSG files
+    Diff {
+        /// Old TSG file path
+        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        old: PathBuf,
+
+        /// New TSG file path
+        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        new: PathBuf,
+
+        /// Write the differences as JSON instead of a human-readable diff
+        #[arg(long)]
+        json: bool,
+
+        /// Match graphs, nodes and edges by ID only, not by genomic coordinates
+        #[arg(long)]
+        ids_only: bool,
+
+        /// Do not compare the traversed paths of matched graphs
+        #[arg(long)]
+        no_paths: bool,
+
+        #[command(flatten)]
+        traversal: TraversalArgs,
+
+        /// Output file path, default is stdout
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        output: Option<PathBuf>,
+    },
+
     /// Convert a TSG file to FASTA format
+    ///
+    /// The paths of each graph are traversed, as for gtf and vcf; with
+    /// --paths-from-records the P records are written with their node orientations
+    /// instead, and only graphs without P records are traversed.
     Fa {
         /// Input TSG file path
         #[arg(required = true, value_hint = ValueHint::FilePath)]
         input: PathBuf,
 
+        /// Reference genome FASTA (indexed with samtools faidx) to fill node sequences from
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        reference: Option<PathBuf>,
+
+        /// Write the protein of the longest ORF of each path instead of its transcript
+        #[arg(long)]
+        protein: bool,
+
+        /// Write the P records of each graph instead of traversing its paths
+        #[arg(long)]
+        paths_from_records: bool,
+
+        #[command(flatten)]
+        orf_options: OrfArgs,
+
+        #[command(flatten)]
+        traversal: TraversalArgs,
+
         /// Output file path for the FASTA
         #[arg(short, long, value_hint = ValueHint::FilePath)]
         output: Option<PathBuf>,
     },
 
+    /// Fill node sequences from a reference genome and write the TSG file back out
+    AnnotateSeq {
+        /// Input TSG file path
+        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        input: PathBuf,
+
+        /// Reference genome FASTA, indexed with samtools faidx
+        #[arg(short, long, required = true, value_hint = ValueHint::FilePath)]
+        reference: PathBuf,
+
+        /// Also attach the longest ORF, its CDS and PTC flag as attributes of P records
+        #[arg(long)]
+        orf: bool,
+
+        #[command(flatten)]
+        orf_options: OrfArgs,
+
+        /// Output TSG file path, default is stdout
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        output: Option<PathBuf>,
+    },
+
     /// Convert a TSG file to GTF format
     Gtf {
         /// Input TSG file path
         #[arg(required = true, value_hint = ValueHint::FilePath)]
         input: PathBuf,
 
+        #[command(flatten)]
+        traversal: TraversalArgs,
+
         /// Output file path for the GTF
         #[arg(short, long, value_hint = ValueHint::FilePath)]
         output: Option<PathBuf>,
@@ -74,6 +240,9 @@ pub enum Commands {
         #[arg(required = true, value_hint = ValueHint::FilePath)]
         input: PathBuf,
 
+        #[command(flatten)]
+        traversal: TraversalArgs,
+
         /// Output file path for the VCF
         #[arg(short, long, value_hint = ValueHint::FilePath)]
         output: Option<PathBuf>,
@@ -90,6 +259,32 @@ pub enum Commands {
         output: Option<PathBuf>,
     },
 
+    /// Convert a TSG file to GFA format
+    Gfa {
+        /// Input TSG file path
+        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        input: PathBuf,
+
+        /// Version of the GFA specification to write (1 or 2)
+        #[arg(long, default_value = "1")]
+        gfa_version: GfaVersion,
+
+        /// Output GFA directory path
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        output: Option<PathBuf>,
+    },
+
+    /// Convert GFA graphs back to a TSG file
+    FromGfa {
+        /// Input GFA file, or a directory of GFA files written by the gfa command
+        #[arg(required = true, value_hint = ValueHint::AnyPath)]
+        input: PathBuf,
+
+        /// Output file path for the TSG, default is stdout
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        output: Option<PathBuf>,
+    },
+
     /// Convert a TSG file to JSON format
     Json {
         /// Input TSG file path
@@ -105,6 +300,39 @@ pub enum Commands {
         output: Option<PathBuf>,
     },
 
+    /// Convert JSON graphs back to a TSG file
+    FromJson {
+        /// Input JSON file, or a directory of JSON files written by the json command
+        #[arg(required = true, value_hint = ValueHint::AnyPath)]
+        input: PathBuf,
+
+        /// Output file path for the TSG, default is stdout
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        output: Option<PathBuf>,
+    },
+
+    /// Convert a TSG file to the binary TSG encoding for fast loading
+    Binary {
+        /// Input TSG file path
+        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        input: PathBuf,
+
+        /// Output file path for the binary TSG, default is the input with a .tsgb extension
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        output: Option<PathBuf>,
+    },
+
+    /// Convert a binary TSG file back to the text TSG format
+    FromBinary {
+        /// Input binary TSG file path
+        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        input: PathBuf,
+
+        /// Output file path for the TSG, default is stdout
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        output: Option<PathBuf>,
+    },
+
     /// Find and enumerate all valid paths through the graph
     Traverse {
         /// Input TSG file path
@@ -115,6 +343,9 @@ pub enum Commands {
         #[arg(short, long, default_value = "false")]
         text_path: bool,
 
+        #[command(flatten)]
+        traversal: TraversalArgs,
+
         /// Output file path for the paths, default is stdout
         #[arg(short, long, value_hint = ValueHint::FilePath)]
         output: Option<PathBuf>,
@@ -126,6 +357,15 @@ pub enum Commands {
         #[arg(required = true, action=clap::ArgAction::Append, value_hint = ValueHint::FilePath)]
         inputs: Vec<PathBuf>,
 
+        /// Unify graphs overlapping on the same strand, collapsing identical nodes and edges and
+        /// tagging reads with their sample
+        #[arg(long)]
+        by_locus: bool,
+
+        /// Sample names of the inputs for --by-locus, default is the input file names
+        #[arg(long, value_delimiter = ',', requires = "by_locus")]
+        samples: Option<Vec<String>>,
+
         /// Output file path for the merged TSG
         #[arg(short, long, value_hint = ValueHint::FilePath)]
         output: Option<PathBuf>,
@@ -142,7 +382,21 @@ pub enum Commands {
         output: Option<PathBuf>,
     },
 
-    /// Query specific graphs from a TSG file
+    /// Build a random-access index (.tsi) of a plain or BGZF compressed TSG file
+    ///
+    /// query and split use the index automatically when `<input>.tsi` exists.
+    Index {
+        /// Input TSG file path
+        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        input: PathBuf,
+
+        /// Output index path, default is `<input>.tsi`
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        output: Option<PathBuf>,
+    },
+
+    /// Query specific graphs from a TSG file by ID or genomic region
+    #[command(group(clap::ArgGroup::new("selection").required(true).multiple(true).args(["ids", "ids_file", "region"])))]
     Query {
         /// Input TSG file path
         #[arg(required = true, value_hint = ValueHint::FilePath)]
@@ -150,14 +404,86 @@ pub enum Commands {
 
         /// Graph IDs to query, can be separated by commas
         #[arg(short, long)]
-        ids: String,
+        ids: Option<String>,
 
         /// File containing graph IDs to query (one per line)
         #[arg(long, value_hint = ValueHint::FilePath)]
         ids_file: Option<PathBuf>,
 
+        /// Genomic region such as chr7:55,000,000-55,300,000; graphs with a node exon or
+        /// edge breakpoint in the region are selected. Can be repeated
+        #[arg(short, long)]
+        region: Vec<GenomicRegion>,
+
         /// Output file path for the queried graphs
         #[arg(short, long, value_hint = ValueHint::FilePath)]
         output: Option<PathBuf>,
     },
+
+    /// Keep the graphs, nodes, edges and paths matching attribute expressions
+    ///
+    /// Expressions compare built-in fields or attributes, e.g. `nodes > 3 && topo ==
+    /// "HeteroPath"` or `expression > 5`, combined with `&&`, `||`, `!` and parentheses.
+    /// Edges of dropped nodes and groups referring to dropped elements are dropped too.
+    #[command(group(clap::ArgGroup::new("filters").required(true).multiple(true).args(["graph_expr", "node_expr", "edge_expr", "path_expr"])))]
+    Filter {
+        /// Input TSG file path
+        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        input: PathBuf,
+
+        /// Graphs to keep; fields: id, nodes, edges, paths, chains, topo, cyclic, connected
+        #[arg(long)]
+        graph_expr: Option<FilterExpr>,
+
+        /// Nodes to keep; fields: id, chrom, strand, start, end, length, exons, reads,
+        /// in_degree, out_degree
+        #[arg(long)]
+        node_expr: Option<FilterExpr>,
+
+        /// Edges to keep; fields: id, source, sink, sv_type, chrom1, chrom2, breakpoint1,
+        /// breakpoint2
+        #[arg(long)]
+        edge_expr: Option<FilterExpr>,
+
+        /// P records to keep; fields: id, length, nodes
+        #[arg(long)]
+        path_expr: Option<FilterExpr>,
+
+        /// Output file path for the filtered TSG, default is stdout
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        output: Option<PathBuf>,
+    },
+
+    /// Check TSG files and report every problem with its line and severity
+    ///
+    /// Exits with an error if any file has an error, or a warning with --strict.
+    Validate {
+        /// Input TSG file paths
+        #[arg(required = true, action=clap::ArgAction::Append, value_hint = ValueHint::FilePath)]
+        inputs: Vec<PathBuf>,
+
+        /// Fail on warnings as well as errors
+        #[arg(long)]
+        strict: bool,
+
+        /// Output file path for the diagnostics, default is stdout
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        output: Option<PathBuf>,
+    },
+
+    /// Build a TSG file from transcript annotations or long-read alignments
+    #[command(group(clap::ArgGroup::new("source").required(true).args(["gtf", "bam"])))]
+    Build {
+        /// Input GTF or GFF3 annotation file, transcripts are grouped into graphs by gene
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        gtf: Option<PathBuf>,
+
+        /// Input BAM/SAM file of spliced long-read alignments, reads are grouped into graphs by locus
+        #[arg(long, value_hint = ValueHint::FilePath)]
+        bam: Option<PathBuf>,
+
+        /// Output file path for the TSG, default is stdout
+        #[arg(short, long, value_hint = ValueHint::FilePath)]
+        output: Option<PathBuf>,
+    },
 }
@@ -0,0 +1,53 @@
+use std::path::Path;
+
+use anyhow::Result;
+use tracing::info;
+use tsg::graph::TSGraph;
+
+use super::{output_writer, stdout_writer};
+
+/// Convert a TSG file to the binary TSG encoding
+///
+/// # Arguments
+///
+/// * `input` - Path to the input TSG file
+/// * `output` - Optional output path, defaults to the input path with a `.tsgb` extension
+pub fn to_binary<P: AsRef<Path>>(input: P, output: Option<P>) -> Result<()> {
+    let graph = TSGraph::from_file(input.as_ref())?;
+    let output_path = match output {
+        Some(path) => path.as_ref().to_path_buf(),
+        None => input.as_ref().with_extension("tsgb"),
+    };
+
+    info!("Writing binary TSG to file: {}", output_path.display());
+    let mut writer = output_writer(output_path)?;
+    graph.to_binary(&mut writer)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Convert a binary TSG file back to the text TSG format
+///
+/// # Arguments
+///
+/// * `input` - Path to the binary TSG file
+/// * `output` - Optional path for the output TSG file. If None, writes to stdout
+pub fn from_binary<P: AsRef<Path>>(input: P, output: Option<P>) -> Result<()> {
+    let mut reader = tsg::io::open_reader(input.as_ref())?;
+    let graph = TSGraph::from_binary(&mut reader)?;
+    info!("Loaded {} graphs", graph.graphs.len());
+
+    let mut writer = match output {
+        Some(path) => {
+            info!("Writing TSG to file: {}", path.as_ref().display());
+            output_writer(path)?
+        }
+        None => {
+            info!("Writing TSG to stdout");
+            stdout_writer()?
+        }
+    };
+    graph.to_writer(&mut writer)?;
+    writer.finish()?;
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use std::path::PathBuf;
+
+use anyhow::{Result, anyhow};
+use tracing::info;
+use tsg::graph::TSGraph;
+
+use super::{output_writer, stdout_writer};
+
+/// Build a TSG file from transcript annotations or long-read alignments
+///
+/// Transcripts in a GTF file are grouped by `gene_id` into graph sections,
+/// shared exons are collapsed into nodes and every transcript is written as a path.
+///
+/// Reads in a BAM/SAM file are grouped by locus into graph sections, aligned blocks
+/// become nodes, introns become splice junctions and split or supplementary
+/// alignments become structural variant edges.
+///
+/// # Arguments
+///
+/// * `gtf` - Path to the GTF annotation file
+/// * `bam` - Path to the BAM/SAM alignment file
+/// * `output` - Optional path for the output TSG file. If None, writes to stdout
+pub fn build(gtf: Option<PathBuf>, bam: Option<PathBuf>, output: Option<PathBuf>) -> Result<()> {
+    let tsg_graph = match (gtf, bam) {
+        (Some(gtf), None) => {
+            info!("Building TSG from GTF file: {}", gtf.display());
+            TSGraph::from_gtf(&gtf)?
+        }
+        (None, Some(bam)) => {
+            info!("Building TSG from alignment file: {}", bam.display());
+            TSGraph::from_bam(&bam)?
+        }
+        _ => return Err(anyhow!("Exactly one of --gtf or --bam must be provided")),
+    };
+    info!("Built {} graphs", tsg_graph.graphs.len());
+
+    let mut writer = match output {
+        Some(path) => {
+            info!("Writing TSG to file: {}", path.display());
+            output_writer(path)?
+        }
+        None => {
+            info!("Writing TSG to stdout");
+            stdout_writer()?
+        }
+    };
+    tsg_graph.to_writer(&mut writer)?;
+    writer.finish()?;
+    Ok(())
+}
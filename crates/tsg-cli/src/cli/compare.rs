@@ -0,0 +1,78 @@
+use anyhow::Result;
+use bstr::BString;
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+use tracing::info;
+use tsg::graph::{
+    COMPARISON_COLUMNS, JunctionAccuracy, PathComparison, ReferenceAnnotation, TSGraph,
+    TraversalOptions,
+};
+
+use super::{output_writer, stdout_writer};
+
+/// Compare the traversed paths of a TSG file with a reference annotation and write one
+/// row per path; with `annotate`, also write the TSG file with the class of each path
+/// attached to its `P` record
+pub fn compare<P: AsRef<Path>>(
+    input: P,
+    annotation: P,
+    annotate: Option<PathBuf>,
+    traversal: TraversalOptions,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let reference = ReferenceAnnotation::from_path(annotation.as_ref())?;
+    info!(
+        "Loaded {} reference transcripts",
+        reference.transcripts().len()
+    );
+
+    let mut writer = match output {
+        Some(path) => {
+            info!("Writing comparison to file: {:?}", path);
+            output_writer(path)?
+        }
+        None => stdout_writer()?,
+    };
+    writeln!(writer, "{}", COMPARISON_COLUMNS.join("\t"))?;
+
+    let mut accuracy = JunctionAccuracy::new();
+    let mut write_rows = |comparisons: Vec<PathComparison>| -> Result<()> {
+        for comparison in comparisons {
+            writer.write_all(&comparison.to_row())?;
+            accuracy.add(&reference, &comparison);
+        }
+        Ok(())
+    };
+
+    match annotate {
+        Some(path) => {
+            let mut tsg_graph = TSGraph::from_file(input.as_ref())?;
+            let mut ids: Vec<BString> = tsg_graph.graphs.keys().cloned().collect();
+            ids.sort();
+            for id in ids {
+                let graph = tsg_graph.graphs.get_mut(&id).unwrap();
+                write_rows(reference.annotate_graph(graph, &traversal)?)?;
+            }
+            info!("Writing annotated TSG to file: {:?}", path);
+            let mut tsg_writer = output_writer(path)?;
+            tsg_graph.to_writer(&mut tsg_writer)?;
+            tsg_writer.finish()?;
+        }
+        None => {
+            for graph in TSGraph::stream_file(input.as_ref())? {
+                write_rows(reference.compare_graph(&graph?.section, &traversal)?)?;
+            }
+        }
+    }
+    writer.finish()?;
+
+    let ratio = |value: Option<f64>| value.map_or("NA".to_string(), |v| format!("{:.4}", v));
+    info!(
+        "Junction precision: {}, recall: {}",
+        ratio(accuracy.precision()),
+        ratio(accuracy.recall(&reference))
+    );
+    Ok(())
+}
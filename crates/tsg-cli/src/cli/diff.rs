@@ -0,0 +1,42 @@
+use anyhow::Result;
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+use tracing::info;
+use tsg::graph::{DiffOptions, TSGraph};
+
+use super::{output_writer, stdout_writer};
+
+/// Write the structural differences between two TSG files, as a human-readable diff or
+/// as JSON
+pub fn diff<P: AsRef<Path>>(
+    old: P,
+    new: P,
+    json: bool,
+    options: DiffOptions,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let old_graph = TSGraph::from_file(old.as_ref())?;
+    let new_graph = TSGraph::from_file(new.as_ref())?;
+    let diff = old_graph.diff(&new_graph, &options)?;
+    info!("{} graphs differ", diff.graphs.len());
+
+    let mut writer = match output {
+        Some(path) => {
+            info!("Writing diff to file: {:?}", path);
+            output_writer(path)?
+        }
+        None => stdout_writer()?,
+    };
+    if json {
+        serde_json::to_writer_pretty(&mut writer, &diff.to_json())?;
+        writeln!(writer)?;
+    } else {
+        writeln!(writer, "--- {}", old.as_ref().display())?;
+        writeln!(writer, "+++ {}", new.as_ref().display())?;
+        write!(writer, "{}", diff)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
@@ -4,6 +4,8 @@ use anyhow::Result;
 use tracing::info;
 use tsg::graph::TSGraph;
 
+use super::{output_file_path, output_writer};
+
 /// Converts a TSG graph from a file into DOT format and writes it to an output directory.
 ///
 /// # Parameters
@@ -44,11 +46,11 @@ pub fn to_dot<P: AsRef<Path>>(input: P, output: Option<P>) -> Result<()> {
     }
     for (id, graph) in tsg_graph.graphs.iter() {
         // create a dot file for each graph under the output directory
-        let graph_output_file = output_path.join(format!("{}.dot", id));
-        let output_file = std::fs::File::create(graph_output_file)?;
-        let mut writer = std::io::BufWriter::new(output_file);
+        let graph_output_file = output_file_path(output_path.join(format!("{}.dot", id)));
+        let mut writer = output_writer(graph_output_file)?;
         let dot = graph.to_dot(true, true)?;
         writer.write_all(dot.as_bytes())?;
+        writer.finish()?;
     }
     Ok(())
 }
@@ -0,0 +1,37 @@
+use anyhow::Result;
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+use tracing::info;
+use tsg::graph::{EVENT_COLUMNS, TSGraph};
+
+use super::{output_writer, stdout_writer};
+
+/// Write the alternative splicing events of each graph of a TSG file as a TSV table
+pub fn events<P: AsRef<Path>>(input: P, output: Option<PathBuf>) -> Result<()> {
+    info!("parsing TSG graphs from file: {:?}", input.as_ref());
+
+    let mut writer = match output {
+        Some(path) => {
+            info!("Writing events to file: {:?}", path);
+            output_writer(path)?
+        }
+        None => {
+            info!("Writing events to stdout");
+            stdout_writer()?
+        }
+    };
+
+    writeln!(writer, "{}", EVENT_COLUMNS.join("\t"))?;
+    let mut count = 0;
+    for graph in TSGraph::stream_file(input.as_ref())? {
+        for event in graph?.section.splicing_events()? {
+            writer.write_all(&event.to_row())?;
+            count += 1;
+        }
+    }
+    writer.finish()?;
+    info!("Found {} events", count);
+    Ok(())
+}
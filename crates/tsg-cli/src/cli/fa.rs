@@ -1,36 +1,203 @@
 use std::path::{Path, PathBuf};
 
 use anyhow::Result;
+use clap::Args;
 use std::io::Write;
 use tracing::info;
-use tsg::graph::TSGraph;
+use tsg::graph::{
+    DEFAULT_NMD_DISTANCE, GraphSection, OrfOptions, StartCodons, TSGraph, TraversalOptions,
+};
+use tsg::io::ReferenceGenome;
+
+use super::{output_writer, stdout_writer};
+
+/// Number of graphs whose sequences are fetched from the reference in parallel
+const ANNOTATION_BATCH_SIZE: usize = 256;
+
+/// Options of the ORF search shared by `fa --protein` and `annotate-seq --orf`
+#[derive(Args, Debug, Clone)]
+pub struct OrfArgs {
+    /// Start codons of ORFs: atg, near-cognate (ATG, CTG, GTG, TTG) or any (stop to stop)
+    #[arg(long, default_value_t = StartCodons::Atg)]
+    pub start_codons: StartCodons,
+
+    /// Minimum protein length in amino acids
+    #[arg(long, default_value_t = 1)]
+    pub min_protein_length: usize,
+
+    /// Only report ORFs that end with a stop codon
+    #[arg(long)]
+    pub complete_only: bool,
+
+    /// Stop codons more than this many nucleotides upstream of the last exon junction are flagged as PTC
+    #[arg(long, default_value_t = DEFAULT_NMD_DISTANCE)]
+    pub nmd_distance: usize,
+}
+
+impl OrfArgs {
+    pub fn options(&self) -> OrfOptions {
+        OrfOptions::builder()
+            .start_codons(self.start_codons)
+            .min_protein_length(self.min_protein_length)
+            .allow_incomplete(!self.complete_only)
+            .nmd_distance(self.nmd_distance)
+            .build()
+    }
+}
+
+/// Write the transcripts of a graph, or their proteins when ORF options are given
+fn write_graph<W: Write>(
+    graph: &GraphSection,
+    traversal: &TraversalOptions,
+    paths_from_records: bool,
+    orf_options: Option<&OrfOptions>,
+    writer: &mut W,
+) -> Result<()> {
+    match orf_options {
+        Some(options) => {
+            tsg::io::graph_to_protein_fa(graph, traversal, paths_from_records, options, writer)
+        }
+        None => tsg::io::graph_to_fa(graph, traversal, paths_from_records, writer),
+    }
+}
 
 /// Converts a TSGraph to FA (Finite Automaton) format
 ///
 /// This function reads a TSGraph from the specified input file,
-/// converts it to FA format, and writes the result to the specified
+/// converts the traversed paths of each graph, or its `P` records when `paths_from_records` is set, to FA format, and writes the result to the specified
 /// output or to stdout if no output is provided.
 ///
 /// # Arguments
 ///
 /// * `input` - Path to the input TSGraph file
+/// * `reference` - Optional reference genome to fill node sequences from
+/// * `traversal` - Limits of the path traversal of each graph
+/// * `paths_from_records` - Write the `P` records of graphs that have any instead of traversing them
+/// * `orf_options` - When set, write the protein of the longest ORF of each path
 /// * `output` - Optional path for the output file. If None, output is written to stdout
 ///
 /// # Returns
 ///
 /// * `Result<()>` - Ok if the conversion was successful, Err otherwise
-pub fn to_fa<P: AsRef<Path>>(input: P, output: Option<PathBuf>) -> Result<()> {
-    let mut tsg_graph = TSGraph::from_file(input.as_ref())?;
-    let mut writer: Box<dyn Write> = match output {
+pub fn to_fa<P: AsRef<Path>>(
+    input: P,
+    reference: Option<PathBuf>,
+    traversal: TraversalOptions,
+    paths_from_records: bool,
+    orf_options: Option<OrfOptions>,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let mut writer = match output {
         Some(path) => {
             info!("Writing to file: {:?}", path);
-            Box::new(std::io::BufWriter::new(std::fs::File::create(path)?))
+            output_writer(path)?
         }
         None => {
             info!("Writing to stdout");
-            Box::new(std::io::BufWriter::new(std::io::stdout().lock()))
+            stdout_writer()?
+        }
+    };
+
+    let Some(reference) = reference else {
+        for graph in TSGraph::stream_file(input.as_ref())? {
+            write_graph(
+                &graph?.section,
+                &traversal,
+                paths_from_records,
+                orf_options.as_ref(),
+                &mut writer,
+            )?;
+        }
+        return writer.finish();
+    };
+
+    // annotate batches of graphs in parallel while still streaming the input
+    info!("Filling node sequences from: {}", reference.display());
+    let reference = ReferenceGenome::from_path(reference)?;
+    let mut graphs = TSGraph::stream_file(input.as_ref())?.peekable();
+    while graphs.peek().is_some() {
+        let mut batch = graphs
+            .by_ref()
+            .take(ANNOTATION_BATCH_SIZE)
+            .collect::<Result<Vec<_>>>()?;
+        reference.annotate_graphs(batch.iter_mut().map(|graph| &mut graph.section).collect())?;
+        for graph in &batch {
+            write_graph(
+                &graph.section,
+                &traversal,
+                paths_from_records,
+                orf_options.as_ref(),
+                &mut writer,
+            )?;
         }
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Fill node sequences from a reference genome and write the TSG file back out,
+/// optionally attaching the longest ORF of each `P` record as its attributes
+pub fn annotate_seq<P: AsRef<Path>>(
+    input: P,
+    reference: PathBuf,
+    orf_options: Option<OrfOptions>,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let mut tsg_graph = TSGraph::from_file(input.as_ref())?;
+    info!("Filling node sequences from: {}", reference.display());
+    tsg_graph.annotate_node_with_sequence(&reference)?;
+
+    if let Some(options) = orf_options {
+        let mut annotated = 0;
+        for graph in tsg_graph.graphs.values_mut() {
+            annotated += graph.annotate_orfs(&options)?;
+        }
+        info!("Annotated ORFs of {} paths", annotated);
+    }
+
+    let mut writer = match output {
+        Some(path) => {
+            info!("Writing to file: {:?}", path);
+            output_writer(path)?
+        }
+        None => stdout_writer()?,
     };
-    tsg::io::to_fa(&mut tsg_graph, &mut writer)?;
+    tsg_graph.to_writer(&mut writer)?;
+    writer.finish()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fa_inverted_node() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("tsg_cli_fa_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let input = dir.join("inversion.tsg");
+        let output = dir.join("inversion.fa");
+        // t1 reads n2 in reverse across an inversion
+        std::fs::write(
+            &input,
+            "G\tg1\n\
+             N\tn1\tchr1:+:100-103\tr1:SO\tAACC\n\
+             N\tn2\tchr1:+:200-203\tr1:SI\tGGGT\n\
+             E\te1\tn1\tn2\tchr1,chr1,103,203,INV\n\
+             P\tt1\tn1+\te1+\tn2-\n",
+        )?;
+
+        to_fa(
+            &input,
+            None,
+            TraversalOptions::default(),
+            true,
+            None,
+            Some(output.clone()),
+        )?;
+        assert_eq!(std::fs::read_to_string(&output)?, ">t1\nAACCACCC\n");
+
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
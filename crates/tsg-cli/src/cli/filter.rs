@@ -0,0 +1,33 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use tracing::info;
+use tsg::graph::{FilterOptions, TSGraph};
+
+use super::{output_writer, stdout_writer};
+
+/// Write the graphs, nodes, edges and paths of a TSG file that pass the filters as a
+/// new TSG file
+pub fn filter<P: AsRef<Path>>(
+    input: P,
+    options: FilterOptions,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let tsg_graph = TSGraph::from_file(input.as_ref())?;
+    let filtered = tsg_graph.filter(&options)?;
+    info!(
+        "Kept {} of {} graphs",
+        filtered.graphs.len(),
+        tsg_graph.graphs.len()
+    );
+
+    let mut writer = match output {
+        Some(path) => {
+            info!("Writing filtered TSG to file: {:?}", path);
+            output_writer(path)?
+        }
+        None => stdout_writer()?,
+    };
+    filtered.to_writer(&mut writer)?;
+    writer.finish()?;
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use std::path::Path;
+
+use anyhow::Result;
+use tracing::info;
+use tsg::graph::TSGraph;
+
+use super::{output_file_path, output_writer, stdout_writer};
+use tsg::io::GfaVersion;
+
+/// Converts a TSG graph from a file into GFA format and writes it to an output directory.
+///
+/// # Parameters
+/// - `input`: The path to the input file containing the TSG graph.
+/// - `gfa_version`: The version of the GFA specification to write.
+/// - `output`: An optional path to the output directory. If not provided, a directory
+///   named `<input_file_stem>_gfa` will be created in the same location as the input file.
+///
+/// # Returns
+/// - `Result<()>`: Returns `Ok(())` if the operation succeeds, or an error if it fails.
+///
+/// # Errors
+/// - Returns an error if the input file cannot be read or parsed.
+/// - Returns an error if the output directory cannot be created or written to.
+pub fn to_gfa<P: AsRef<Path>>(input: P, gfa_version: GfaVersion, output: Option<P>) -> Result<()> {
+    let tsg_graph = TSGraph::from_file(input.as_ref())?;
+
+    info!(
+        "parsing {} TSG graph from file: {:?}",
+        tsg_graph.graphs.len(),
+        input.as_ref()
+    );
+    let output_path = match output {
+        Some(path) => path.as_ref().to_path_buf(),
+        None => {
+            let input_path = input.as_ref().to_path_buf();
+            let parent = input_path.parent().unwrap_or(Path::new("."));
+            let stem = input_path
+                .file_stem()
+                .unwrap_or_else(|| std::ffi::OsStr::new("output"));
+            let gfa_dir = format!("{}_gfa", stem.to_string_lossy());
+            parent.join(gfa_dir)
+        }
+    };
+
+    // create a folder for the output if it doesn't exist
+    if !output_path.exists() {
+        std::fs::create_dir_all(&output_path)?;
+    }
+    for (id, graph) in tsg_graph.graphs.iter() {
+        // create a gfa file for each graph under the output directory
+        let graph_output_file = output_file_path(output_path.join(format!("{}.gfa", id)));
+        let mut writer = output_writer(graph_output_file)?;
+        tsg::io::to_gfa(graph, gfa_version, &mut writer)?;
+        writer.finish()?;
+    }
+    Ok(())
+}
+
+/// Convert GFA graphs back to a TSG file
+///
+/// # Arguments
+///
+/// * `input` - A GFA file, or a directory of GFA files written by `to_gfa`
+/// * `output` - Optional path for the output TSG file. If None, writes to stdout
+pub fn from_gfa<P: AsRef<Path>>(input: P, output: Option<P>) -> Result<()> {
+    let graph = TSGraph::from_gfa(input.as_ref())?;
+    info!("Loaded {} graphs", graph.graphs.len());
+
+    let mut writer = match output {
+        Some(path) => {
+            info!("Writing TSG to file: {}", path.as_ref().display());
+            output_writer(path)?
+        }
+        None => {
+            info!("Writing TSG to stdout");
+            stdout_writer()?
+        }
+    };
+    graph.to_writer(&mut writer)?;
+    writer.finish()?;
+    Ok(())
+}
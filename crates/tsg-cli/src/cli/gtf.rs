@@ -1,9 +1,10 @@
-use std::io::Write;
 use std::path::{Path, PathBuf};
 
 use anyhow::Result;
 use tracing::info;
-use tsg::graph::TSGraph;
+use tsg::graph::{TSGraph, TraversalOptions};
+
+use super::{output_writer, stdout_writer};
 
 /// Converts a Transcript Segment Graph (TSG) file to GTF format.
 ///
@@ -14,23 +15,30 @@ use tsg::graph::TSGraph;
 /// # Arguments
 ///
 /// * `input` - A path to the input TSG file
+/// * `traversal` - Limits of the path traversal of each graph
 /// * `output` - An optional path to the output GTF file. If `None`, outputs to stdout
 ///
 /// # Returns
 ///
 /// * `Result<()>` - Ok(()) on success, or an error if file operations fail
-pub fn to_gtf<P: AsRef<Path>>(input: P, output: Option<PathBuf>) -> Result<()> {
-    let tsg_graph = TSGraph::from_file(input.as_ref())?;
-    let mut writer: Box<dyn Write> = match output {
+pub fn to_gtf<P: AsRef<Path>>(
+    input: P,
+    traversal: TraversalOptions,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let mut writer = match output {
         Some(path) => {
             info!("Writing to file: {:?}", path);
-            Box::new(std::io::BufWriter::new(std::fs::File::create(path)?))
+            output_writer(path)?
         }
         None => {
             info!("Writing to stdout");
-            Box::new(std::io::BufWriter::new(std::io::stdout().lock()))
+            stdout_writer()?
         }
     };
-    tsg::io::to_gtf(&tsg_graph, &mut writer)?;
+    for graph in TSGraph::stream_file(input.as_ref())? {
+        tsg::io::graph_to_gtf(&graph?.section, &traversal, &mut writer)?;
+    }
+    writer.finish()?;
     Ok(())
 }
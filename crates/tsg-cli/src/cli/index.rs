@@ -0,0 +1,24 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use tracing::info;
+use tsg::io::TsgIndex;
+
+/// Build a random-access index (`.tsi`) for a TSG file
+///
+/// The index records the byte offset, length and genomic span of every graph section,
+/// so that `query` and `split` can seek straight to the requested graphs.
+/// Only plain text and BGZF compressed TSG files can be indexed.
+pub fn index(input: PathBuf, output: Option<PathBuf>) -> Result<()> {
+    let index = TsgIndex::build(&input)?;
+    let output = output.unwrap_or_else(|| TsgIndex::default_path(&input));
+
+    info!(
+        "Indexed {} graphs and {} links, writing index to: {}",
+        index.graphs.len(),
+        index.links.len(),
+        output.display()
+    );
+    index.to_file(&output)?;
+    Ok(())
+}
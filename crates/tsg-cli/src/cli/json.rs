@@ -2,8 +2,11 @@ use std::io::Write;
 use std::path::Path;
 
 use anyhow::Result;
+use tracing::info;
 use tsg::graph::TSGraph;
 
+use super::{output_file_path, output_writer, stdout_writer};
+
 pub fn to_json<P: AsRef<Path>>(input: P, pretty: bool, output: Option<P>) -> Result<()> {
     let graph = TSGraph::from_file(input.as_ref())?;
     let output_path = match output {
@@ -25,9 +28,8 @@ pub fn to_json<P: AsRef<Path>>(input: P, pretty: bool, output: Option<P>) -> Res
     }
     for (id, graph) in graph.graphs.iter() {
         // create a dot file for each graph under the output directory
-        let graph_output_file = output_path.join(format!("{}.json", id));
-        let output_file = std::fs::File::create(graph_output_file)?;
-        let mut writer = std::io::BufWriter::new(output_file);
+        let graph_output_file = output_file_path(output_path.join(format!("{}.json", id)));
+        let mut writer = output_writer(graph_output_file)?;
         let json = graph.to_json()?;
         if pretty {
             let json = serde_json::to_string_pretty(&json)?;
@@ -36,6 +38,32 @@ pub fn to_json<P: AsRef<Path>>(input: P, pretty: bool, output: Option<P>) -> Res
             let json = serde_json::to_string(&json)?;
             writer.write_all(json.as_bytes())?;
         }
+        writer.finish()?;
     }
     Ok(())
 }
+
+/// Convert JSON graphs back to a TSG file
+///
+/// # Arguments
+///
+/// * `input` - A JSON file, or a directory of JSON files written by `to_json`
+/// * `output` - Optional path for the output TSG file. If None, writes to stdout
+pub fn from_json<P: AsRef<Path>>(input: P, output: Option<P>) -> Result<()> {
+    let graph = TSGraph::from_json(input.as_ref())?;
+    info!("Loaded {} graphs", graph.graphs.len());
+
+    let mut writer = match output {
+        Some(path) => {
+            info!("Writing TSG to file: {}", path.as_ref().display());
+            output_writer(path)?
+        }
+        None => {
+            info!("Writing TSG to stdout");
+            stdout_writer()?
+        }
+    };
+    graph.to_writer(&mut writer)?;
+    writer.finish()?;
+    Ok(())
+}
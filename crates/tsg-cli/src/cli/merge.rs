@@ -1,11 +1,12 @@
-use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Result, anyhow};
 use bstr::ByteSlice;
 use tracing::info;
 use tsg::graph::TSGraph;
 
+use super::{output_writer, stdout_writer};
+
 /// Merge multiple TSG files into a single TSG file
 ///
 /// This function takes multiple TSG files and merges them into a single TSG file.
@@ -65,18 +66,87 @@ pub fn merge<P: AsRef<Path>>(inputs: Vec<P>, output: Option<P>) -> Result<()> {
 
     // Write the merged TSG to the output file
 
-    let mut writer: Box<dyn Write> = match output {
+    let mut writer = match output {
         Some(path) => {
             info!("Writing paths to file: {}", path.as_ref().display());
-            Box::new(std::io::BufWriter::new(std::fs::File::create(path)?))
+            output_writer(path)?
         }
         None => {
             info!("Writing paths to stdout");
-            Box::new(std::io::BufWriter::new(std::io::stdout().lock()))
+            stdout_writer()?
         }
     };
 
     merged_tsg.to_writer(&mut writer)?;
     info!("Merge completed successfully");
+    writer.finish()?;
+    Ok(())
+}
+
+/// Sample name of an input file: its name without the `.tsg` and compression extensions
+fn sample_name(path: &Path) -> String {
+    let mut name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    for extension in [".gz", ".bgz", ".zst", ".zstd", ".tsg", ".tsgb"] {
+        if let Some(stripped) = name.strip_suffix(extension) {
+            name = stripped.to_string();
+        }
+    }
+    name
+}
+
+/// Merge TSG files of several samples, unifying the graphs of each locus
+///
+/// Graphs overlapping on the same strand are merged into one, collapsing nodes with the
+/// same exons and edges with the same structural variant, and the reads of each file are tagged with
+/// its sample name.
+pub fn merge_by_locus<P: AsRef<Path>>(
+    inputs: Vec<P>,
+    samples: Option<Vec<String>>,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    if inputs.is_empty() {
+        return Err(anyhow!("No input files provided"));
+    }
+    let names: Vec<String> = match samples {
+        Some(samples) if samples.len() != inputs.len() => {
+            return Err(anyhow!(
+                "Got {} sample names for {} input files",
+                samples.len(),
+                inputs.len()
+            ));
+        }
+        Some(samples) => samples,
+        None => inputs
+            .iter()
+            .map(|input| sample_name(input.as_ref()))
+            .collect(),
+    };
+    let mut unique = names.clone();
+    unique.sort();
+    unique.dedup();
+    if unique.len() != names.len() {
+        return Err(anyhow!("Sample names must be unique: {}", names.join(",")));
+    }
+
+    let mut tsgs = vec![];
+    for (input, name) in inputs.iter().zip(names) {
+        info!("Loading sample {}: {}", name, input.as_ref().display());
+        tsgs.push((name.into(), TSGraph::from_file(input.as_ref())?));
+    }
+    let merged_tsg = TSGraph::merge_by_locus(tsgs)?;
+    info!("Merged into {} graphs", merged_tsg.graphs.len());
+
+    let mut writer = match output {
+        Some(path) => {
+            info!("Writing merged TSG to file: {}", path.display());
+            output_writer(path)?
+        }
+        None => stdout_writer()?,
+    };
+    merged_tsg.to_writer(&mut writer)?;
+    writer.finish()?;
     Ok(())
 }
@@ -0,0 +1,42 @@
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use anyhow::Result;
+use tsg::io::{CompressedWriter, Compression};
+
+/// Compression requested with `--compress`, applied to every output of the run
+static OUTPUT_COMPRESSION: OnceLock<Compression> = OnceLock::new();
+
+/// Set the compression used for all outputs, called once after parsing the command line
+pub fn set_output_compression(compression: Compression) {
+    let _ = OUTPUT_COMPRESSION.set(compression);
+}
+
+/// Create a writer for an output file.
+///
+/// With `--compress` the requested format is used, otherwise the format is chosen from
+/// the file extension (`.gz`, `.zst`). Call `finish` on the writer once done.
+pub fn output_writer<P: AsRef<Path>>(path: P) -> Result<CompressedWriter<'static>> {
+    tsg::io::create_writer(path, OUTPUT_COMPRESSION.get().copied())
+}
+
+/// Create a writer for stdout, compressed only when `--compress` is given.
+/// Call `finish` on the writer once done.
+pub fn stdout_writer() -> Result<CompressedWriter<'static>> {
+    let compression = OUTPUT_COMPRESSION.get().copied().unwrap_or_default();
+    tsg::io::compressed_writer(std::io::stdout().lock(), compression)
+}
+
+/// Path of a file written into an output directory, with the extension of the
+/// `--compress` format appended (e.g. `gene.dot` becomes `gene.dot.gz`)
+pub fn output_file_path(path: PathBuf) -> PathBuf {
+    match OUTPUT_COMPRESSION.get().and_then(|c| c.extension()) {
+        Some(extension) => {
+            let mut path = path.into_os_string();
+            path.push(".");
+            path.push(extension);
+            path.into()
+        }
+        None => path,
+    }
+}
@@ -0,0 +1,46 @@
+use anyhow::Result;
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+use tracing::info;
+use tsg::graph::{ABUNDANCE_COLUMNS, QuantOptions, TSGraph};
+
+use super::{output_writer, stdout_writer};
+
+/// Quantify the paths of a TSG file and write it back out with `count` and `tpm`
+/// attributes on its `P` records
+pub fn quant<P: AsRef<Path>>(
+    input: P,
+    table: Option<PathBuf>,
+    max_iterations: usize,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let mut tsg_graph = TSGraph::from_file(input.as_ref())?;
+    let options = QuantOptions::builder()
+        .max_iterations(max_iterations)
+        .build();
+    let abundances = tsg_graph.annotate_abundance(&options)?;
+    info!("Quantified {} paths", abundances.len());
+
+    if let Some(table) = table {
+        info!("Writing abundance table to file: {:?}", table);
+        let mut writer = output_writer(table)?;
+        writeln!(writer, "{}", ABUNDANCE_COLUMNS.join("\t"))?;
+        for abundance in &abundances {
+            writer.write_all(&abundance.to_row())?;
+        }
+        writer.finish()?;
+    }
+
+    let mut writer = match output {
+        Some(path) => {
+            info!("Writing to file: {:?}", path);
+            output_writer(path)?
+        }
+        None => stdout_writer()?,
+    };
+    tsg_graph.to_writer(&mut writer)?;
+    writer.finish()?;
+    Ok(())
+}
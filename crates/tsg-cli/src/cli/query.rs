@@ -3,26 +3,31 @@ use std::io::{BufRead, BufReader};
 use std::path::PathBuf;
 
 use anyhow::{Result, anyhow};
-use bstr::BString;
+use bstr::{BString, ByteSlice};
 use tracing::info;
 
-use tsg::graph::TSGraph;
+use tsg::graph::{GenomicRegion, TSGraph};
+use tsg::io::TsgIndex;
+
+use super::{output_writer, stdout_writer};
 
 /// Query specific graphs from a TSG file
 ///
-/// This function extracts specific graphs by their IDs from a TSG file
+/// This function extracts specific graphs by their IDs, or the graphs with a node exon
+/// or edge breakpoint in any of the given regions, from a TSG file
 /// and outputs them in the specified format.
 pub fn query(
     input: PathBuf,
-    ids_str: String,
+    ids_str: Option<String>,
     ids_file: Option<PathBuf>,
+    regions: Vec<GenomicRegion>,
     output: Option<PathBuf>,
 ) -> Result<()> {
     info!("Querying graphs from TSG file: {}", input.display());
-    let tsg = TSGraph::from_file(&input)?;
 
     // Collect all graph IDs to query
     let mut graph_ids = ids_str
+        .unwrap_or_default()
         .split(',')
         .map(|s| s.trim().to_string())
         .filter(|s| !s.is_empty())
@@ -43,11 +48,59 @@ pub fn query(
         }
     }
 
-    if graph_ids.is_empty() {
-        return Err(anyhow!("No graph IDs specified"));
+    if graph_ids.is_empty() && regions.is_empty() {
+        return Err(anyhow!("No graph IDs or regions specified"));
     }
 
-    info!("Querying {} graphs", graph_ids.len());
+    info!(
+        "Querying {} graphs and {} regions",
+        graph_ids.len(),
+        regions.len()
+    );
+
+    // Seek straight to the requested graphs if the file has been indexed
+    let index_path = TsgIndex::default_path(&input);
+    if index_path.exists() {
+        info!("Using index: {}", index_path.display());
+        let index = TsgIndex::from_file(&index_path)?;
+
+        // the index only knows graph spans, so region candidates are checked once loaded
+        let mut ids = graph_ids.iter().map(String::as_str).collect::<Vec<_>>();
+        for region in &regions {
+            for entry in index.overlapping(region.reference_id.to_str()?, region.start, region.end)
+            {
+                ids.push(entry.id.to_str()?);
+            }
+        }
+        let mut queried_tsg = index.read_graphs(&input, &ids)?;
+        queried_tsg.graphs.retain(|id, graph| {
+            graph_ids.iter().any(|graph_id| id == graph_id.as_bytes())
+                || regions.iter().any(|region| graph.overlaps_region(region))
+        });
+        let kept = &queried_tsg.graphs;
+        let links = queried_tsg
+            .links
+            .iter()
+            .filter(|link| {
+                kept.contains_key(&link.source_graph) && kept.contains_key(&link.target_graph)
+            })
+            .cloned()
+            .collect();
+        queried_tsg.links = links;
+        return write_queried(&queried_tsg, output);
+    }
+
+    let tsg = TSGraph::from_file(&input)?;
+
+    // Add the graphs overlapping the regions
+    for region in &regions {
+        for graph in tsg.graphs_in_region(region) {
+            let id = graph.id.to_string();
+            if !graph_ids.contains(&id) {
+                graph_ids.push(id);
+            }
+        }
+    }
 
     // Create a new TSGraph to hold the queried graphs
     let mut queried_tsg = TSGraph::new();
@@ -77,15 +130,20 @@ pub fn query(
         }
     }
 
-    // Output the result
+    write_queried(&queried_tsg, output)
+}
+
+fn write_queried(queried_tsg: &TSGraph, output: Option<PathBuf>) -> Result<()> {
     if let Some(output_path) = output {
         info!("Writing queried graphs to: {}", output_path.display());
-        queried_tsg.to_file(&output_path)?;
+        let mut writer = output_writer(&output_path)?;
+        queried_tsg.to_writer(&mut writer)?;
+        writer.finish()?;
     } else {
         // Print to stdout in TSG format
-        let stdout = std::io::stdout();
-        let mut writer = std::io::BufWriter::new(stdout.lock());
+        let mut writer = stdout_writer()?;
         queried_tsg.to_writer(&mut writer)?;
+        writer.finish()?;
     }
 
     info!("Query completed successfully");
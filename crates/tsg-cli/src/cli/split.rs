@@ -1,19 +1,20 @@
 use std::path::{Path, PathBuf};
 
 use anyhow::{Result, anyhow};
+use bstr::ByteSlice;
 use tracing::info;
 use tsg::graph::TSGraph;
+use tsg::io::TsgIndex;
+
+use super::{output_file_path, output_writer};
 
 /// Split a TSG file containing multiple graphs into multiple TSG files, each containing a single graph
 ///
 /// This function takes a TSG file with multiple graphs and splits it into multiple TSG files,
 /// where each output file contains a single graph from the original file.
 /// The output files will be named based on the graph IDs.
+/// If the file has a `.tsi` index, graphs are loaded one at a time.
 pub fn split<P: AsRef<Path>>(input: P, output_dir: Option<PathBuf>) -> Result<()> {
-    // Load the input TSG file
-    info!("Loading TSG file: {}", input.as_ref().display());
-    let tsg = TSGraph::from_file(input.as_ref())?;
-
     // if output_dir is None, create a default output directory
     let output_dir = match output_dir {
         Some(dir) => dir,
@@ -34,6 +35,26 @@ pub fn split<P: AsRef<Path>>(input: P, output_dir: Option<PathBuf>) -> Result<()
         info!("Created output directory: {}", output_dir.display());
     }
 
+    let index_path = TsgIndex::default_path(input.as_ref());
+    if index_path.exists() {
+        info!("Using index: {}", index_path.display());
+        let index = TsgIndex::from_file(&index_path)?;
+        if index.graphs.is_empty() {
+            return Err(anyhow!("No graphs found in the input TSG file"));
+        }
+        info!("Found {} graphs to split", index.graphs.len());
+        for entry in &index.graphs {
+            let single_graph_tsg = index.read_graphs(input.as_ref(), &[entry.id.to_str()?])?;
+            write_single_graph(&single_graph_tsg, &entry.id.to_string(), &output_dir)?;
+        }
+        info!("Split completed successfully");
+        return Ok(());
+    }
+
+    // Load the input TSG file
+    info!("Loading TSG file: {}", input.as_ref().display());
+    let tsg = TSGraph::from_file(input.as_ref())?;
+
     // Check if there are any graphs to split
     if tsg.graphs.is_empty() {
         return Err(anyhow!("No graphs found in the input TSG file"));
@@ -61,19 +82,20 @@ pub fn split<P: AsRef<Path>>(input: P, output_dir: Option<PathBuf>) -> Result<()
             }
         }
 
-        // Create the output file path
-        let graph_id_str = graph_id.to_string();
-        let output_file = output_dir.join(format!("{}.tsg", graph_id_str));
-
-        // Write the single-graph TSG to a file
-        info!(
-            "Writing graph '{}' to: {}",
-            graph_id_str,
-            output_file.display()
-        );
-        single_graph_tsg.to_file(&output_file)?;
+        write_single_graph(&single_graph_tsg, &graph_id.to_string(), &output_dir)?;
     }
 
     info!("Split completed successfully");
     Ok(())
 }
+
+/// Write a single-graph TSG to `<output_dir>/<graph_id>.tsg`
+fn write_single_graph(tsg: &TSGraph, graph_id: &str, output_dir: &Path) -> Result<()> {
+    let output_file = output_file_path(output_dir.join(format!("{}.tsg", graph_id)));
+
+    info!("Writing graph '{}' to: {}", graph_id, output_file.display());
+    let mut writer = output_writer(&output_file)?;
+    tsg.to_writer(&mut writer)?;
+    writer.finish()?;
+    Ok(())
+}
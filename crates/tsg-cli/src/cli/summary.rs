@@ -5,29 +5,29 @@ use std::{
 };
 use tracing::info;
 use tsg::graph::TSGraph;
-use tsg::graph::TSGraphAnalysis;
 
+use super::{output_writer, stdout_writer};
+use tsg::graph::SUMMARY_COLUMNS;
+
+/// Summarize each graph of a TSG file, streaming the file one graph at a time
 pub fn summary<P: AsRef<Path>>(input: P, output: Option<PathBuf>) -> Result<()> {
-    let tsg_graph = TSGraph::from_file(input.as_ref())?;
-    info!(
-        "parsing {} TSG graph from file: {:?}",
-        tsg_graph.graphs.len(),
-        input.as_ref()
-    );
+    info!("parsing TSG graphs from file: {:?}", input.as_ref());
 
-    let mut writer: Box<dyn Write> = match output {
+    let mut writer = match output {
         Some(path) => {
             info!("Writing summary to file: {:?}", path);
-            Box::new(std::io::BufWriter::new(std::fs::File::create(path)?))
+            output_writer(path)?
         }
         None => {
             info!("Writing summary to stdout");
-            Box::new(std::io::BufWriter::new(std::io::stdout().lock()))
+            stdout_writer()?
         }
     };
 
-    let summary_str = tsg_graph.summarize()?;
-    writer.write_all(&summary_str)?;
-    writer.flush()?;
+    writeln!(writer, "{}", SUMMARY_COLUMNS.join(","))?;
+    for graph in TSGraph::stream_file(input.as_ref())? {
+        writer.write_all(&graph?.section.summary_row()?)?;
+    }
+    writer.finish()?;
     Ok(())
 }
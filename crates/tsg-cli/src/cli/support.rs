@@ -0,0 +1,25 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use tracing::info;
+use tsg::graph::TSGraph;
+
+use super::{output_writer, stdout_writer};
+
+/// Attach junction read support and PSI attributes to the edges and paths of a TSG file
+pub fn support<P: AsRef<Path>>(input: P, output: Option<PathBuf>) -> Result<()> {
+    let mut tsg_graph = TSGraph::from_file(input.as_ref())?;
+    for graph in tsg_graph.graphs.values_mut() {
+        graph.annotate_read_support()?;
+    }
+
+    let mut writer = match output {
+        Some(path) => {
+            info!("Writing to file: {:?}", path);
+            output_writer(path)?
+        }
+        None => stdout_writer()?,
+    };
+    tsg_graph.to_writer(&mut writer)?;
+    writer.finish()?;
+    Ok(())
+}
@@ -2,34 +2,114 @@ use std::io::Write;
 use std::path::{Path, PathBuf};
 
 use anyhow::Result;
+use clap::Args;
+use std::time::Duration;
 use tracing::info;
-use tsg::graph::TSGraph;
+use tsg::graph::{TSGraph, TraversalOptions};
+
+use super::{output_writer, stdout_writer};
+
+/// Limits of the path traversal shared by `traverse`, `fa`, `gtf` and `vcf`
+#[derive(Args, Debug, Clone)]
+pub struct TraversalArgs {
+    /// Stop after this many paths per graph
+    #[arg(long)]
+    pub max_paths: Option<usize>,
+
+    /// Drop paths with more than this many nodes
+    #[arg(long)]
+    pub max_path_length: Option<usize>,
+
+    /// Minimum number of reads continuing through every node of a path
+    #[arg(long, default_value_t = 1)]
+    pub min_reads: usize,
+
+    /// Stop traversing a graph after this many seconds
+    #[arg(long, value_parser = parse_timeout)]
+    pub timeout: Option<Duration>,
+
+    /// Stop traversing a graph after extending this many partial paths
+    #[arg(long)]
+    pub max_work: Option<usize>,
+
+    /// Only report the k paths of each graph with the most continuing reads, found best-first
+    #[arg(long)]
+    pub top_k: Option<usize>,
+
+    /// Number of times a path may go around a cycle back to a node it already went through
+    #[arg(long, default_value_t = 1)]
+    pub max_revisits: usize,
+}
+
+/// Parse a timeout given as a positive, finite number of seconds
+fn parse_timeout(value: &str) -> Result<Duration, String> {
+    let seconds: f64 = value.parse().map_err(|e| format!("{}", e))?;
+    if !(seconds.is_finite() && seconds > 0.0) {
+        return Err(format!("{} is not a positive number of seconds", value));
+    }
+    Duration::try_from_secs_f64(seconds).map_err(|e| e.to_string())
+}
+
+impl TraversalArgs {
+    pub fn options(&self) -> TraversalOptions {
+        TraversalOptions::builder()
+            .maybe_max_paths(self.max_paths)
+            .maybe_max_path_length(self.max_path_length)
+            .min_reads(self.min_reads)
+            .maybe_timeout(self.timeout)
+            .maybe_max_work(self.max_work)
+            .maybe_top_k(self.top_k)
+            .max_revisits(self.max_revisits)
+            .build()
+    }
+}
 
 // traverse the graph and output the path to the output file
 // the output file is plain text file each line is a path
 // P transcript1	n1+	e1+	n3+	e2+	n4+
-pub fn traverse<P: AsRef<Path>>(input: P, text_path: bool, output: Option<PathBuf>) -> Result<()> {
-    let tsg_graph = TSGraph::from_file(input.as_ref())?;
-    let mut writer: Box<dyn Write> = match output {
+pub fn traverse<P: AsRef<Path>>(
+    input: P,
+    text_path: bool,
+    traversal: TraversalOptions,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let mut writer = match output {
         Some(path) => {
             info!("Writing paths to file: {:?}", path);
-            Box::new(std::io::BufWriter::new(std::fs::File::create(path)?))
+            output_writer(path)?
         }
         None => {
             info!("Writing paths to stdout");
-            Box::new(std::io::BufWriter::new(std::io::stdout().lock()))
+            stdout_writer()?
         }
     };
 
-    let paths = tsg_graph.traverse_all_graphs()?;
-    for path in paths {
-        if text_path {
-            // write the path
-            writer.write_all(format!("{}\n", path).as_bytes())?;
-        } else {
-            // only write the path id
-            writer.write_all(format!("{}\n", path.id().unwrap()).as_bytes())?;
+    // graphs are read one at a time to keep memory bounded by the largest graph
+    for graph in TSGraph::stream_file(input.as_ref())? {
+        let graph = graph?;
+        for path in graph.section.traverse_with(&traversal)? {
+            if text_path {
+                // write the path
+                writer.write_all(format!("{}\n", path).as_bytes())?;
+            } else {
+                // only write the path id
+                writer.write_all(format!("{}\n", path.id().unwrap()).as_bytes())?;
+            }
         }
     }
+    writer.finish()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_timeout() {
+        assert_eq!(parse_timeout("1.5"), Ok(Duration::from_millis(1500)));
+        for invalid in ["0", "-1", "NaN", "inf", "1e400", "soon"] {
+            assert!(parse_timeout(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
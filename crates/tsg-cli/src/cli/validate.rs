@@ -0,0 +1,42 @@
+use anyhow::{Result, bail};
+use std::{io::Write, path::PathBuf};
+use tracing::info;
+use tsg::graph::{Severity, ValidationReport};
+
+use super::{output_writer, stdout_writer};
+
+/// Validate TSG files and write every problem found as `file:line: severity: message`;
+/// fails if any file has an error, or a warning when `strict` is set
+pub fn validate(inputs: Vec<PathBuf>, strict: bool, output: Option<PathBuf>) -> Result<()> {
+    let mut writer = match output {
+        Some(path) => {
+            info!("Writing diagnostics to file: {:?}", path);
+            output_writer(path)?
+        }
+        None => stdout_writer()?,
+    };
+
+    let (mut errors, mut warnings) = (0, 0);
+    for input in &inputs {
+        let report = ValidationReport::from_file(input)?;
+        info!(
+            "{}: {} errors, {} warnings",
+            input.display(),
+            report.count(Severity::Error),
+            report.count(Severity::Warning)
+        );
+        errors += report.count(Severity::Error);
+        warnings += report.count(Severity::Warning);
+        write!(writer, "{}", report)?;
+    }
+    writer.finish()?;
+
+    if errors > 0 || (strict && warnings > 0) {
+        bail!(
+            "Validation failed with {} errors and {} warnings",
+            errors,
+            warnings
+        );
+    }
+    Ok(())
+}
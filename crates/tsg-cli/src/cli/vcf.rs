@@ -1,9 +1,10 @@
 use std::path::{Path, PathBuf};
 
 use anyhow::Result;
-use std::io::Write;
 use tracing::info;
-use tsg::graph::TSGraph;
+use tsg::graph::{TSGraph, TraversalOptions};
+
+use super::{output_writer, stdout_writer};
 
 /// Convert a TSGraph to VCF format
 ///
@@ -12,23 +13,31 @@ use tsg::graph::TSGraph;
 /// # Arguments
 ///
 /// * `input` - Path to the input TSGraph file
+/// * `traversal` - Limits of the path traversal of each graph
 /// * `output` - Optional path for the output VCF file. If None, writes to stdout
 ///
 /// # Returns
 ///
 /// * `Result<()>` - Ok if successful, or an error
-pub fn to_vcf<P: AsRef<Path>>(input: P, output: Option<PathBuf>) -> Result<()> {
-    let tsg_graph = TSGraph::from_file(input.as_ref())?;
-    let mut writer: Box<dyn Write> = match output {
+pub fn to_vcf<P: AsRef<Path>>(
+    input: P,
+    traversal: TraversalOptions,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let mut writer = match output {
         Some(path) => {
             info!("Writing to file: {:?}", path);
-            Box::new(std::io::BufWriter::new(std::fs::File::create(path)?))
+            output_writer(path)?
         }
         None => {
             info!("Writing to stdout");
-            Box::new(std::io::BufWriter::new(std::io::stdout().lock()))
+            stdout_writer()?
         }
     };
-    tsg::io::to_vcf(&tsg_graph, &mut writer)?;
+    tsg::io::write_vcf_header(&mut writer)?;
+    for graph in TSGraph::stream_file(input.as_ref())? {
+        tsg::io::graph_to_vcf(&graph?.section, &traversal, &mut writer)?;
+    }
+    writer.finish()?;
     Ok(())
 }
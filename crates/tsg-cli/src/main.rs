@@ -7,6 +7,8 @@ use clap::{Command, CommandFactory, Parser};
 use clap_complete::aot::{Generator, Shell, generate};
 use cli::Commands;
 use std::io::stdout;
+use tsg::graph::{DiffOptions, FilterOptions};
+use tsg::io::Compression;
 
 #[derive(Parser)]
 #[command(author, version, about = "Transcript Segment Graph (TSG) CLI tool")]
@@ -24,6 +26,11 @@ struct Cli {
     #[arg(long, hide = true)]
     markdown_help: bool,
 
+    /// Compress all outputs with the given format (none, gzip, bgzf or zstd);
+    /// by default output files are compressed according to their extension (.gz, .zst)
+    #[arg(long, global = true)]
+    compress: Option<Compression>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -59,6 +66,10 @@ fn run() -> Result<()> {
     // Set verbosity level
     tracing_subscriber::fmt().with_max_level(cli.verbose).init();
 
+    if let Some(compression) = cli.compress {
+        cli::set_output_compression(compression);
+    }
+
     match command {
         Commands::Header { input } => {
             info!("Printing header for TSG file: {}", input.display());
@@ -71,6 +82,63 @@ fn run() -> Result<()> {
             Ok(())
         }
 
+        Commands::Events { input, output } => {
+            info!(
+                "Classifying splicing events of TSG file: {}",
+                input.display()
+            );
+            cli::events(input, output)?;
+            Ok(())
+        }
+
+        Commands::Support { input, output } => {
+            info!("Computing read support of TSG file: {}", input.display());
+            cli::support(input, output)?;
+            Ok(())
+        }
+
+        Commands::Quant {
+            input,
+            table,
+            max_iterations,
+            output,
+        } => {
+            info!("Quantifying paths of TSG file: {}", input.display());
+            cli::quant(input, table, max_iterations, output)?;
+            Ok(())
+        }
+
+        Commands::Compare {
+            input,
+            annotation,
+            annotate,
+            traversal,
+            output,
+        } => {
+            info!("Comparing paths of TSG file: {}", input.display());
+            cli::compare(input, annotation, annotate, traversal.options(), output)?;
+            Ok(())
+        }
+
+        Commands::Diff {
+            old,
+            new,
+            json,
+            ids_only,
+            no_paths,
+            traversal,
+            output,
+        } => {
+            info!("Comparing TSG files: {} {}", old.display(), new.display());
+            let options = DiffOptions::builder()
+                .match_coordinates(!ids_only)
+                .paths(!no_paths)
+                .traversal(traversal.options())
+                .build();
+            cli::diff(old, new, json, options, output)?;
+            Ok(())
+        }
+
         Commands::Dot { input, output } => {
             cli::to_dot(input, output)?;
             Ok(())
@@ -79,30 +147,83 @@ fn run() -> Result<()> {
         Commands::Traverse {
             input,
             text_path,
+            traversal,
             output,
         } => {
-            cli::traverse(input, text_path, output)?;
+            cli::traverse(input, text_path, traversal.options(), output)?;
             Ok(())
         }
 
-        Commands::Fa { input, output } => {
+        Commands::Fa {
+            input,
+            reference,
+            protein,
+            paths_from_records,
+            orf_options,
+            traversal,
+            output,
+        } => {
             info!("Converting TSG file to FASTA: {}", input.display());
-            cli::to_fa(input, output)?;
+            let orf_options = protein.then(|| orf_options.options());
+            cli::to_fa(
+                input,
+                reference,
+                traversal.options(),
+                paths_from_records,
+                orf_options,
+                output,
+            )?;
             Ok(())
         }
 
-        Commands::Gtf { input, output } => {
+        Commands::AnnotateSeq {
+            input,
+            reference,
+            orf,
+            orf_options,
+            output,
+        } => {
+            info!("Annotating node sequences of TSG file: {}", input.display());
+            let orf_options = orf.then(|| orf_options.options());
+            cli::annotate_seq(input, reference, orf_options, output)?;
+            Ok(())
+        }
+
+        Commands::Gtf {
+            input,
+            traversal,
+            output,
+        } => {
             info!("Converting TSG file to GTF: {}", input.display());
-            cli::to_gtf(input, output)?;
+            cli::to_gtf(input, traversal.options(), output)?;
             Ok(())
         }
 
-        Commands::Vcf { input, output } => {
+        Commands::Vcf {
+            input,
+            traversal,
+            output,
+        } => {
             info!("Converting TSG file to VCF: {}", input.display());
-            cli::to_vcf(input, output)?;
+            cli::to_vcf(input, traversal.options(), output)?;
+            Ok(())
+        }
+
+        Commands::Gfa {
+            input,
+            gfa_version,
+            output,
+        } => {
+            info!("Converting TSG file to GFA: {}", input.display());
+            cli::to_gfa(input, gfa_version, output)?;
             Ok(())
         }
 
+        Commands::FromGfa { input, output } => {
+            info!("Converting GFA to TSG file: {}", input.display());
+            cli::from_gfa(input, output)?;
+            Ok(())
+        }
         Commands::Json {
             input,
             pretty,
@@ -112,10 +233,34 @@ fn run() -> Result<()> {
             cli::to_json(input, pretty, output)?;
             Ok(())
         }
+        Commands::Binary { input, output } => {
+            info!("Converting TSG file to binary: {}", input.display());
+            cli::to_binary(input, output)?;
+            Ok(())
+        }
+        Commands::FromBinary { input, output } => {
+            info!("Converting binary TSG file to text: {}", input.display());
+            cli::from_binary(input, output)?;
+            Ok(())
+        }
+        Commands::FromJson { input, output } => {
+            info!("Converting JSON to TSG file: {}", input.display());
+            cli::from_json(input, output)?;
+            Ok(())
+        }
 
-        Commands::Merge { inputs, output } => {
+        Commands::Merge {
+            inputs,
+            by_locus,
+            samples,
+            output,
+        } => {
             info!("Merging TSG files: {:?}", inputs);
-            cli::merge(inputs, output)?;
+            if by_locus {
+                cli::merge_by_locus(inputs, samples, output)?;
+            } else {
+                cli::merge(inputs, output)?;
+            }
             Ok(())
         }
 
@@ -125,14 +270,55 @@ fn run() -> Result<()> {
             Ok(())
         }
 
+        Commands::Index { input, output } => {
+            info!("Indexing TSG file: {}", input.display());
+            cli::index(input, output)?;
+            Ok(())
+        }
+
         Commands::Query {
             input,
             ids,
             ids_file,
+            region,
             output,
         } => {
             info!("Querying TSG file: {}", input.display());
-            cli::query(input, ids, ids_file, output)?;
+            cli::query(input, ids, ids_file, region, output)?;
+            Ok(())
+        }
+
+        Commands::Filter {
+            input,
+            graph_expr,
+            node_expr,
+            edge_expr,
+            path_expr,
+            output,
+        } => {
+            info!("Filtering TSG file: {}", input.display());
+            let options = FilterOptions::builder()
+                .maybe_graph(graph_expr)
+                .maybe_node(node_expr)
+                .maybe_edge(edge_expr)
+                .maybe_path(path_expr)
+                .build();
+            cli::filter(input, options, output)?;
+            Ok(())
+        }
+
+        Commands::Validate {
+            inputs,
+            strict,
+            output,
+        } => {
+            info!("Validating TSG files: {:?}", inputs);
+            cli::validate(inputs, strict, output)?;
+            Ok(())
+        }
+
+        Commands::Build { gtf, bam, output } => {
+            cli::build(gtf, bam, output)?;
             Ok(())
         }
     }
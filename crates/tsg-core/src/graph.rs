@@ -1,15 +1,23 @@
 mod analysis;
 mod attr;
+mod diff;
 mod edge;
+mod filter;
 mod group;
 mod header;
+mod merge;
 mod node;
+mod orf;
 mod path;
+mod reader;
+mod region;
+mod traversal;
 mod utils;
+mod validate;
 
-use noodles::fasta;
+use std::fmt::Write;
 use std::fs::File;
-use std::io::{BufRead, BufReader, BufWriter};
+use std::io::{BufRead, BufReader};
 use std::path::Path;
 use std::str::FromStr;
 use tracing::debug;
@@ -21,12 +29,19 @@ use bstr::{BStr, BString, ByteSlice};
 
 pub use analysis::*;
 pub use attr::*;
+pub use diff::*;
 pub use edge::*;
+pub use filter::*;
 pub use group::*;
 pub use header::*;
 pub use node::*;
+pub use orf::*;
 pub use path::*;
+pub use reader::*;
+pub use region::*;
+pub use traversal::*;
 pub use utils::*;
+pub use validate::*;
 
 use bon::Builder;
 use petgraph::dot::{Config, Dot};
@@ -34,7 +49,6 @@ use petgraph::graph::{DiGraph, EdgeIndex, NodeIndex};
 use petgraph::visit::EdgeRef;
 use rayon::prelude::*;
 use serde_json::json;
-use std::collections::VecDeque;
 
 pub const DEFAULT_GRAPH_ID: &str = "TSG_DEFAULT_GRAPH";
 /// Represents a graph section within the TSG file
@@ -80,10 +94,28 @@ impl GraphSection {
         self._graph.node_weight(node_idx)
     }
 
+    pub fn node_weight_mut(&mut self, node_idx: NodeIndex) -> Option<&mut NodeData> {
+        self._graph.node_weight_mut(node_idx)
+    }
+
     pub fn edge_weight(&self, edge_idx: EdgeIndex) -> Option<&EdgeData> {
         self._graph.edge_weight(edge_idx)
     }
 
+    pub fn edge_weight_mut(&mut self, edge_idx: EdgeIndex) -> Option<&mut EdgeData> {
+        self._graph.edge_weight_mut(edge_idx)
+    }
+
+    /// Get the source and sink node of an edge
+    pub fn edge_endpoints(&self, edge_idx: EdgeIndex) -> Option<(NodeIndex, NodeIndex)> {
+        self._graph.edge_endpoints(edge_idx)
+    }
+
+    /// Find the edge connecting `source` to `sink`, if any
+    pub fn find_edge(&self, source: NodeIndex, sink: NodeIndex) -> Option<EdgeIndex> {
+        self._graph.find_edge(source, sink)
+    }
+
     pub fn in_degree(&self, node_idx: NodeIndex) -> usize {
         self._graph
             .edges_directed(node_idx, petgraph::Direction::Incoming)
@@ -162,6 +194,44 @@ impl GraphSection {
 
     // Methods from old TSGraph that should now belong to GraphSection
 
+    /// Complete a parsed section: register its chains and build the graph from them if needed
+    fn finalize(&mut self) -> Result<()> {
+        // Populate chains hash map from groups if needed
+        for (id, group) in &self.groups {
+            if let Group::Chain { .. } = group
+                && !self.chains.contains_key(id)
+            {
+                self.chains.insert(id.clone(), group.clone());
+            }
+        }
+
+        // Ensure graph is built
+        self.ensure_graph_is_built()
+    }
+
+    /// Check that all elements of every path exist in the section
+    fn validate_paths(&self) -> Result<()> {
+        for (id, group) in &self.groups {
+            if let Group::Ordered { elements, .. } = group {
+                for element in elements {
+                    let element_exists = self.node_indices.contains_key(&element.id)
+                        || self.edge_indices.contains_key(&element.id)
+                        || self.groups.contains_key(&element.id);
+
+                    if !element_exists {
+                        return Err(anyhow!(
+                            "Path {} in graph {} references non-existent element {}",
+                            id,
+                            self.id,
+                            element.id
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Build graph based on the current state
     fn ensure_graph_is_built(&mut self) -> Result<()> {
         // If we already have nodes and edges, assume the graph is properly constructed
@@ -265,6 +335,57 @@ impl GraphSection {
 
     // Other methods from TSGraph that make sense at the graph section level
 
+    /// Build the path of an ordered group (`P` record), keeping the orientation of its
+    /// nodes and using the group id as path id
+    pub fn path_from_group(&self, group_id: &str) -> Result<TSGPath<'_>> {
+        let Some(Group::Ordered {
+            id,
+            elements,
+            attributes,
+        }) = self.groups.get(group_id.as_bytes())
+        else {
+            return Err(anyhow!("Path {} not found in graph {}", group_id, self.id));
+        };
+
+        let mut path = TSGPath::builder()
+            .graph(self)
+            .name(id.clone())
+            .attributes(attributes.values().cloned().collect())
+            .build();
+        for element in elements {
+            if let Some(&node_idx) = self.node_indices.get(&element.id) {
+                path.add_oriented_node(
+                    node_idx,
+                    element.orientation.unwrap_or(Orientation::Forward),
+                );
+            } else if let Some(&edge_idx) = self.edge_indices.get(&element.id) {
+                path.add_edge(edge_idx);
+            } else {
+                return Err(anyhow!(
+                    "Path {} in graph {} references non-existent element {}",
+                    id,
+                    self.id,
+                    element.id
+                ));
+            }
+        }
+        Ok(path)
+    }
+
+    /// Paths of all ordered groups (`P` records) of the section, sorted by id
+    pub fn ordered_paths(&self) -> Result<Vec<TSGPath<'_>>> {
+        let mut ids: Vec<&BString> = self
+            .groups
+            .iter()
+            .filter(|(_, group)| matches!(group, Group::Ordered { .. }))
+            .map(|(id, _)| id)
+            .collect();
+        ids.sort();
+        ids.into_iter()
+            .map(|id| self.path_from_group(id.to_str()?))
+            .collect()
+    }
+
     /// Traverse the graph and return all valid paths from source nodes to sink nodes.
     ///
     /// A valid path must respect read continuity, especially for nodes with Intermediary (IN) reads.
@@ -283,138 +404,16 @@ impl GraphSection {
     /// These paths would be invalid:
     /// - n1 -> n3 -> n5 (invalid because n1 and n5 don't share a common read)
     /// - n2 -> n3 -> n4 (invalid because n2 and n4 don't share a common read)
-    pub fn traverse(&self) -> Result<Vec<TSGPath>> {
-        // Find all source nodes (nodes with no incoming edges)
-        let source_nodes: Vec<NodeIndex> = self
-            ._graph
-            .node_indices()
-            .filter(|&idx| {
-                self._graph
-                    .edges_directed(idx, petgraph::Direction::Incoming)
-                    .count()
-                    == 0
-            })
-            .collect();
-
-        if source_nodes.is_empty() {
-            return Ok(Vec::new());
-        }
-
-        let mut all_paths = Vec::new();
-        // Cache node read IDs to avoid repeated lookups
-        let mut node_read_ids_cache: HashMap<NodeIndex, HashSet<BString>> = HashMap::new();
-
-        // Pre-compute node read IDs
-        for node_idx in self._graph.node_indices() {
-            if let Some(node) = self._graph.node_weight(node_idx) {
-                let read_ids: HashSet<BString> =
-                    node.reads.par_iter().map(|r| r.id.clone()).collect();
-                node_read_ids_cache.insert(node_idx, read_ids);
-            }
-        }
-
-        // For each source node, perform a traversal
-        for &start_node in &source_nodes {
-            // Skip nodes with no reads
-            if let Some(read_set) = node_read_ids_cache.get(&start_node) {
-                if read_set.is_empty() {
-                    continue;
-                }
-
-                let mut queue = VecDeque::new();
-                // (node, path_so_far, active_reads)
-                let mut initial_path = TSGPath::builder().graph(self).build();
-                initial_path.add_node(start_node);
-
-                queue.push_back((start_node, initial_path, read_set.clone()));
-
-                while let Some((current_node, path, active_reads)) = queue.pop_front() {
-                    // Get outgoing edges
-                    let outgoing_edges: Vec<_> = self
-                        ._graph
-                        .edges_directed(current_node, petgraph::Direction::Outgoing)
-                        .collect();
-
-                    // If this is a sink node (no outgoing edges), save the path
-                    if outgoing_edges.is_empty() {
-                        path.validate()?;
-                        all_paths.push(path);
-                        continue;
-                    }
-
-                    for edge_ref in outgoing_edges {
-                        let edge_idx = edge_ref.id();
-                        let target_node = edge_ref.target();
-
-                        if let Some(target_read_ids) = node_read_ids_cache.get(&target_node) {
-                            // Calculate reads that continue from current path to target
-                            let continuing_reads: HashSet<_> = active_reads
-                                .par_iter()
-                                .filter(|id| target_read_ids.contains(*id))
-                                .cloned()
-                                .collect();
-
-                            if continuing_reads.is_empty() {
-                                // No read continuity, skip this edge
-                                continue;
-                            }
-
-                            // Check if target node has IN reads
-                            let has_in_reads =
-                                if let Some(target_data) = self._graph.node_weight(target_node) {
-                                    target_data
-                                        .reads
-                                        .par_iter()
-                                        .any(|r| r.identity == ReadIdentity::IN)
-                                } else {
-                                    false
-                                };
-
-                            if has_in_reads {
-                                // For IN nodes, check if there's a valid path forward
-                                let mut can_continue = false;
-                                let outgoing_from_target: Vec<_> = self
-                                    ._graph
-                                    .edges_directed(target_node, petgraph::Direction::Outgoing)
-                                    .map(|e| e.target())
-                                    .collect();
-
-                                for &next_node in &outgoing_from_target {
-                                    if let Some(next_read_ids) = node_read_ids_cache.get(&next_node)
-                                    {
-                                        // Check if there's at least one read that continues through
-                                        if continuing_reads
-                                            .par_iter()
-                                            .any(|id| next_read_ids.contains(id))
-                                        {
-                                            can_continue = true;
-                                            break;
-                                        }
-                                    }
-                                }
-
-                                if !can_continue && !outgoing_from_target.is_empty() {
-                                    // Has outgoing edges but no valid continuation, skip this edge
-                                    continue;
-                                }
-                            }
-
-                            // Create new path and continue traversal
-                            let mut new_path = path.clone();
-                            new_path.add_edge(edge_idx);
-                            new_path.add_node(target_node);
-                            queue.push_back((target_node, new_path, continuing_reads));
-                        }
-                    }
-                }
-            }
-        }
-
-        Ok(all_paths)
+    ///
+    /// Use [`GraphSection::traverse_with`] to bound the traversal of complex graphs.
+    pub fn traverse(&self) -> Result<Vec<TSGPath<'_>>> {
+        self.traverse_with(&TraversalOptions::default())
     }
 
     pub fn to_dot(&self, node_label: bool, edge_label: bool) -> Result<String> {
-        let mut config = vec![];
+        // petgraph writes the nodes and edges only, the superbubbles are added as
+        // clusters inside the same digraph
+        let mut config = vec![Config::GraphContentOnly];
         if node_label {
             config.push(Config::NodeIndexLabel);
         }
@@ -422,20 +421,28 @@ impl GraphSection {
             config.push(Config::EdgeIndexLabel);
         }
 
-        let dot = Dot::with_config(&self._graph, &config);
-        Ok(format!("{:?}", dot))
+        let mut dot = String::from("digraph {\n");
+        write!(dot, "{:?}", Dot::with_config(&self._graph, &config))?;
+        dot.push_str(&self.superbubbles_to_dot(&self.find_superbubbles()));
+        dot.push_str("}\n");
+        Ok(dot)
     }
 
+    /// Convert the graph to Cytoscape-style JSON elements.
+    ///
+    /// Node data carries the node sequence and attributes and edge data the edge
+    /// attributes, converted with [`Attribute::to_json_value`]; [`Self::from_json`]
+    /// reads the output back.
     pub fn to_json(&self) -> Result<serde_json::Value> {
         let mut nodes = Vec::new();
         let mut edges = Vec::new();
 
         // Process all nodes
         for node_idx in self._graph.node_indices() {
-            if let Some(node) = self._graph.node_weight(node_idx) {
-                if let Ok(node_json) = node.to_json(None) {
-                    nodes.push(node_json);
-                }
+            if let Some(node) = self._graph.node_weight(node_idx)
+                && let Ok(node_json) = node.to_json(None)
+            {
+                nodes.push(node_json);
             }
         }
 
@@ -470,7 +477,7 @@ impl GraphSection {
                         .len();
 
                     if let (Some(source_id), Some(target_id)) = (source_id, target_id) {
-                        let edge_data = json!({
+                        let mut edge_data = json!({
                             "data": {
                                 "id": edge.id.to_str().unwrap(),
                                 "source": source_id.to_str().unwrap(),
@@ -479,6 +486,9 @@ impl GraphSection {
                                 "breakpoints": format!("{}", edge.sv)
                             }
                         });
+                        for attr in edge.attributes.values() {
+                            edge_data["data"][attr.tag.to_str().unwrap()] = attr.to_json_value()?;
+                        }
                         edges.push(edge_data);
                     }
                 }
@@ -492,33 +502,73 @@ impl GraphSection {
             "elements": {
             "nodes": nodes,
             "edges": edges
-            }
+            },
+            "bubbles": self.superbubbles_to_json(&self.find_superbubbles()),
         });
 
         Ok(elements)
     }
 
+    /// Rebuild a graph section from the Cytoscape-style JSON written by [`Self::to_json`]
+    ///
+    /// Nodes are restored with [`NodeData::from_json`]. Edge breakpoints are parsed back
+    /// into a [`StructuralVariant`], the derived `weight` is ignored and any other edge
+    /// field is restored as an attribute.
+    pub fn from_json(id: BString, json: &serde_json::Value) -> Result<Self> {
+        let mut section = Self::new(id);
+        let elements = json
+            .get("elements")
+            .context("JSON graph has no elements object")?;
+
+        if let Some(nodes) = elements.get("nodes").and_then(|n| n.as_array()) {
+            for node in nodes {
+                section.add_node(NodeData::from_json(node)?)?;
+            }
+        }
+
+        if let Some(edges) = elements.get("edges").and_then(|e| e.as_array()) {
+            for edge in edges {
+                let data = edge
+                    .get("data")
+                    .and_then(|d| d.as_object())
+                    .context("Edge JSON has no data object")?;
+                let field = |key: &str| {
+                    data.get(key)
+                        .and_then(|v| v.as_str())
+                        .with_context(|| format!("Edge JSON has no string field {}", key))
+                };
+
+                let id = field("id")?;
+                let sv = field("breakpoints")?
+                    .parse::<StructuralVariant>()
+                    .with_context(|| format!("Failed to parse breakpoints of edge {}", id))?;
+                let attributes = data
+                    .iter()
+                    .filter(|(key, _)| {
+                        !["id", "source", "target", "weight", "breakpoints"].contains(&key.as_str())
+                    })
+                    .filter_map(|(key, value)| Attribute::from_json_value(key, value))
+                    .map(|attr| (attr.tag.clone(), attr))
+                    .collect();
+                let edge_data = EdgeData::builder()
+                    .id(id)
+                    .sv(sv)
+                    .attributes(attributes)
+                    .build();
+                section.add_edge(field("source")?.into(), field("target")?.into(), edge_data)?;
+            }
+        }
+
+        Ok(section)
+    }
+
+    /// Fill the node sequences from an indexed reference FASTA,
+    /// see [`crate::io::ReferenceGenome::annotate_graph`]
     pub fn annotate_node_with_sequence<P: AsRef<Path>>(
         &mut self,
         reference_genome_path: P,
     ) -> Result<()> {
-        let mut reader = fasta::io::indexed_reader::Builder::default()
-            .build_from_path(reference_genome_path.as_ref())?;
-
-        for node_idx in self.node_indices.values() {
-            let node_data = self._graph.node_weight_mut(*node_idx).unwrap();
-
-            let region = format!(
-                "{}:{}-{}",
-                node_data.reference_id,
-                node_data.reference_start() - 1, // 0-based to 1-based
-                node_data.reference_end(),
-            )
-            .parse()?;
-            let record = reader.query(&region)?;
-            node_data.sequence = Some(record.sequence().as_ref().into());
-        }
-        Ok(())
+        crate::io::ReferenceGenome::from_path(reference_genome_path)?.annotate_graph(self)
     }
 }
 
@@ -607,16 +657,38 @@ impl TSGraph {
 
     /// Get the current graph section (or error if none is active)
     fn current_graph_mut(&mut self) -> Result<&mut GraphSection> {
-        if let Some(graph_id) = &self.current_graph_id {
-            if let Some(graph) = self.graphs.get_mut(graph_id) {
-                return Ok(graph);
-            }
+        if let Some(graph_id) = &self.current_graph_id
+            && let Some(graph) = self.graphs.get_mut(graph_id)
+        {
+            return Ok(graph);
         }
         Err(anyhow!("No active graph section"))
     }
 
     /// Parse an inter-graph link line
     fn parse_link_line(&mut self, fields: &[&str]) -> Result<()> {
+        let link = Self::parse_link(fields)?;
+
+        // Verify the referenced graphs exist
+        if !self.graphs.contains_key(&link.source_graph) {
+            return Err(anyhow!(
+                "Source graph {} not found",
+                link.source_graph.to_str().unwrap_or("")
+            ));
+        }
+        if !self.graphs.contains_key(&link.target_graph) {
+            return Err(anyhow!(
+                "Target graph {} not found",
+                link.target_graph.to_str().unwrap_or("")
+            ));
+        }
+
+        self.links.push(link);
+        Ok(())
+    }
+
+    /// Parse the fields of a link line without checking the graphs it connects
+    fn parse_link(fields: &[&str]) -> Result<InterGraphLink> {
         if fields.len() < 5 {
             return Err(anyhow!("Invalid link line format"));
         }
@@ -636,20 +708,6 @@ impl TSGraph {
         let target_graph: BString = target_ref[0].into();
         let target_element: BString = target_ref[1].into();
 
-        // Verify the referenced graphs exist
-        if !self.graphs.contains_key(&source_graph) {
-            return Err(anyhow!(
-                "Source graph {} not found",
-                source_graph.to_str().unwrap_or("")
-            ));
-        }
-        if !self.graphs.contains_key(&target_graph) {
-            return Err(anyhow!(
-                "Target graph {} not found",
-                target_graph.to_str().unwrap_or("")
-            ));
-        }
-
         let link_type: BString = fields[4].into();
 
         let mut link = InterGraphLink::builder()
@@ -669,8 +727,7 @@ impl TSGraph {
             }
         }
 
-        self.links.push(link);
-        Ok(())
+        Ok(link)
     }
 
     /// Parse a node line
@@ -784,7 +841,7 @@ impl TSGraph {
             return Err(anyhow!("Chain must contain at least one element"));
         }
 
-        if elements.len() % 2 == 0 {
+        if elements.len().is_multiple_of(2) {
             return Err(anyhow!(
                 "Chain must have an odd number of elements (starting and ending with nodes)"
             ));
@@ -887,27 +944,8 @@ impl TSGraph {
     /// Validate all graphs and their paths
     fn validate(&self) -> Result<()> {
         // Validate each graph section
-        for (graph_id, graph) in &self.graphs {
-            // Validate paths against the graph
-            for (id, group) in &graph.groups {
-                if let Group::Ordered { elements, .. } = group {
-                    // Validate that all elements in the path exist in the graph
-                    for element in elements {
-                        let element_exists = graph.node_indices.contains_key(&element.id)
-                            || graph.edge_indices.contains_key(&element.id)
-                            || graph.groups.contains_key(&element.id);
-
-                        if !element_exists {
-                            return Err(anyhow!(
-                                "Path {} in graph {} references non-existent element {}",
-                                id,
-                                graph_id,
-                                element.id
-                            ));
-                        }
-                    }
-                }
-            }
+        for graph in self.graphs.values() {
+            graph.validate_paths()?;
         }
 
         // Validate all inter-graph links
@@ -960,6 +998,34 @@ impl TSGraph {
         Ok(())
     }
 
+    /// Parse one line of a TSG file into the current graph section
+    fn parse_record(&mut self, line: &str) -> Result<()> {
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(());
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.is_empty() {
+            return Ok(());
+        }
+
+        match fields[0] {
+            "H" => self.parse_header_line(&fields)?,
+            "G" => self.parse_graph_line(&fields)?,
+            "N" => self.parse_node_line(line)?,
+            "E" => self.parse_edge_line(&fields)?,
+            "U" => self.parse_unordered_group_line(&fields)?,
+            "P" => self.parse_path_line(&fields)?,
+            "C" => self.parse_chain_line(&fields)?,
+            "A" => self.parse_attribute_line(&fields)?,
+            "L" => self.parse_link_line(&fields)?,
+            _ => {
+                // ignore unknown record types
+                debug!("Ignoring unknown record type: {}", fields[0]);
+            }
+        }
+        Ok(())
+    }
+
     pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
         let mut tsgraph = TSGraph::new();
 
@@ -974,66 +1040,207 @@ impl TSGraph {
 
         // First pass: Parse all record types
         for line in reader.lines() {
-            let line = line?;
-            if line.is_empty() || line.starts_with('#') {
-                continue;
-            }
-            let fields: Vec<&str> = line.split_whitespace().collect();
-            if fields.is_empty() {
-                continue;
-            }
-
-            match fields[0] {
-                "H" => tsgraph.parse_header_line(&fields)?,
-                "G" => tsgraph.parse_graph_line(&fields)?,
-                "N" => tsgraph.parse_node_line(&line)?,
-                "E" => tsgraph.parse_edge_line(&fields)?,
-                "U" => tsgraph.parse_unordered_group_line(&fields)?,
-                "P" => tsgraph.parse_path_line(&fields)?,
-                "C" => tsgraph.parse_chain_line(&fields)?,
-                "A" => tsgraph.parse_attribute_line(&fields)?,
-                "L" => tsgraph.parse_link_line(&fields)?,
-                _ => {
-                    // ignore unknown record types
-                    debug!("Ignoring unknown record type: {}", fields[0]);
-                }
-            }
+            tsgraph.parse_record(&line?)?;
         }
 
         // Second pass: Ensure all graphs are built and validate
         for graph_section in tsgraph.graphs.values_mut() {
-            // Populate chains hash map from groups if needed
-            for (id, group) in &graph_section.groups {
-                if let Group::Chain { .. } = group {
-                    if !graph_section.chains.contains_key(id) {
-                        graph_section.chains.insert(id.clone(), group.clone());
-                    }
-                }
-            }
-
-            // Ensure graph is built
-            graph_section.ensure_graph_is_built()?;
+            graph_section.finalize()?;
         }
 
         // Validate all graphs and links
         tsgraph.validate()?;
 
         // pop the default graph if it's empty
-        if let Some(default_graph) = tsgraph.graph(DEFAULT_GRAPH_ID) {
-            if default_graph.node_indices.is_empty() {
-                tsgraph.graphs.remove(&BString::from(DEFAULT_GRAPH_ID));
-            }
+        if let Some(default_graph) = tsgraph.graph(DEFAULT_GRAPH_ID)
+            && default_graph.node_indices.is_empty()
+        {
+            tsgraph.graphs.remove(&BString::from(DEFAULT_GRAPH_ID));
         }
         Ok(tsgraph)
     }
 
     /// Parse a TSG file and construct a TSGraph
+    ///
+    /// Gzip, BGZF and zstd compressed files, as well as binary TSG files written by
+    /// [`Self::to_binary`], are detected by their magic bytes.
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let file = File::open(path)?;
-        let reader = BufReader::new(file);
+        let mut reader = crate::io::open_reader(path)?;
+        if crate::io::is_binary(reader.fill_buf()?) {
+            return Self::from_binary(&mut reader);
+        }
         Self::from_reader(reader)
     }
 
+    /// Load only the given graphs of a TSG file using its `.tsi` index.
+    ///
+    /// The index must have been built with [`crate::io::TsgIndex::build`] and saved
+    /// next to the file (see [`crate::io::TsgIndex::default_path`]). Links are kept
+    /// when both of their graphs are loaded.
+    pub fn from_file_with_ids<P: AsRef<Path>>(path: P, ids: &[&str]) -> Result<Self> {
+        let index = crate::io::TsgIndex::from_file(crate::io::TsgIndex::default_path(&path))?;
+        index.read_graphs(path, ids)
+    }
+
+    /// Load only the graphs of a TSG file that overlap `reference_id:start-end`, using its `.tsi` index
+    pub fn from_file_in_region<P: AsRef<Path>>(
+        path: P,
+        reference_id: &str,
+        start: usize,
+        end: usize,
+    ) -> Result<Self> {
+        let index = crate::io::TsgIndex::from_file(crate::io::TsgIndex::default_path(&path))?;
+        index.read_region(path, reference_id, start, end)
+    }
+
+    /// Fill the node sequences of all graphs from an indexed reference FASTA, in parallel
+    /// over graphs, see [`crate::io::ReferenceGenome::annotate_graph`]
+    pub fn annotate_node_with_sequence<P: AsRef<Path>>(
+        &mut self,
+        reference_genome_path: P,
+    ) -> Result<()> {
+        let reference = crate::io::ReferenceGenome::from_path(reference_genome_path)?;
+        reference.annotate_graphs(self.graphs.values_mut().collect())
+    }
+
+    /// Load a TSGraph from the binary TSG encoding
+    pub fn from_binary<R: std::io::Read>(reader: &mut R) -> Result<Self> {
+        crate::io::from_binary(reader)
+    }
+
+    /// Write the TSGraph in the binary TSG encoding, see [`crate::io::to_binary`]
+    pub fn to_binary<W: std::io::Write>(&self, writer: &mut W) -> Result<()> {
+        crate::io::to_binary(self, writer)
+    }
+
+    /// Build a TSGraph from a GTF annotation file, one graph section per gene
+    pub fn from_gtf<P: AsRef<Path>>(path: P) -> Result<Self> {
+        crate::io::from_gtf(crate::io::open_reader(path)?)
+    }
+
+    /// Build a TSGraph from long-read alignments, one graph section per locus.
+    ///
+    /// Files with a `.sam` or `.sam.gz` extension are read as SAM, anything else as BAM.
+    pub fn from_bam<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let is_sam = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| {
+                let name = name.to_ascii_lowercase();
+                name.ends_with(".sam") || name.ends_with(".sam.gz")
+            });
+        if is_sam {
+            crate::io::from_sam(crate::io::open_reader(path)?)
+        } else {
+            crate::io::from_bam(File::open(path)?)
+        }
+    }
+
+    /// Build a TSGraph from GFA 1.0 or 2.0 graphs, see [`crate::io::from_gfa`]
+    ///
+    /// `path` is either a single GFA file or a directory of GFA files, such as the
+    /// one written by `tsg-cli gfa`, and may be compressed. Each file becomes one graph
+    /// section, named by its `gi` header tag or else after the file.
+    pub fn from_gfa<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut files = if path.is_dir() {
+            std::fs::read_dir(path)?
+                .map(|entry| entry.map(|e| e.path()))
+                .collect::<std::io::Result<Vec<_>>>()?
+                .into_iter()
+                .filter(|p| {
+                    let name = p.to_string_lossy();
+                    [".gfa", ".gfa.gz", ".gfa.bgz", ".gfa.zst"]
+                        .iter()
+                        .any(|suffix| name.ends_with(suffix))
+                })
+                .collect()
+        } else {
+            vec![path.to_path_buf()]
+        };
+        files.sort();
+
+        let mut tsgraph = TSGraph::new();
+        tsgraph.graphs.remove(&BString::from(DEFAULT_GRAPH_ID));
+        for file in files {
+            let mut section = crate::io::from_gfa(crate::io::open_reader(&file)?)
+                .with_context(|| format!("Failed to load graph from {}", file.display()))?;
+            if section.id == DEFAULT_GRAPH_ID {
+                let mut name = file.file_name().and_then(|name| name.to_str());
+                if crate::io::Compression::from_path(&file) != crate::io::Compression::None {
+                    name = name.and_then(|name| Path::new(name).file_stem()?.to_str());
+                }
+                if let Some(name) = name {
+                    section.id = name.strip_suffix(".gfa").unwrap_or(name).into();
+                }
+            }
+            if tsgraph.graphs.contains_key(&section.id) {
+                return Err(anyhow!(
+                    "Graph {} is defined by more than one GFA file",
+                    section.id
+                ));
+            }
+            tsgraph.graphs.insert(section.id.clone(), section);
+        }
+        Ok(tsgraph)
+    }
+
+    /// Build a TSGraph from JSON graphs written by [`GraphSection::to_json`]
+    ///
+    /// `path` is either a single JSON file or a directory of JSON files, such as the
+    /// one written by `tsg-cli json`, and may be compressed. Each file becomes a graph
+    /// section named after the file, without its `.json` and compression suffixes.
+    pub fn from_json<P: AsRef<Path>>(path: P) -> Result<Self> {
+        // graph id of a JSON file, `<id>.json` optionally followed by a compression suffix
+        let graph_id = |file: &Path| -> Option<BString> {
+            let mut name = file.file_name()?.to_str()?;
+            if crate::io::Compression::from_path(file) != crate::io::Compression::None {
+                name = Path::new(name).file_stem()?.to_str()?;
+            }
+            Some(name.strip_suffix(".json").unwrap_or(name).into())
+        };
+
+        let path = path.as_ref();
+        let mut files = if path.is_dir() {
+            std::fs::read_dir(path)?
+                .map(|entry| entry.map(|e| e.path()))
+                .collect::<std::io::Result<Vec<_>>>()?
+                .into_iter()
+                .filter(|p| {
+                    let name = p.to_string_lossy();
+                    [".json", ".json.gz", ".json.bgz", ".json.zst"]
+                        .iter()
+                        .any(|suffix| name.ends_with(suffix))
+                })
+                .collect()
+        } else {
+            vec![path.to_path_buf()]
+        };
+        files.sort();
+
+        let mut tsgraph = TSGraph::new();
+        for file in files {
+            let id = graph_id(&file)
+                .with_context(|| format!("Invalid JSON file name: {}", file.display()))?;
+            let json: serde_json::Value =
+                serde_json::from_reader(crate::io::open_reader(&file)?)
+                    .with_context(|| format!("Failed to read JSON file {}", file.display()))?;
+            let section = GraphSection::from_json(id.clone(), &json)
+                .with_context(|| format!("Failed to load graph from {}", file.display()))?;
+            tsgraph.graphs.insert(id, section);
+        }
+
+        if tsgraph
+            .graphs
+            .get(&BString::from(DEFAULT_GRAPH_ID))
+            .is_some_and(|g| g.node_indices.is_empty())
+        {
+            tsgraph.graphs.remove(&BString::from(DEFAULT_GRAPH_ID));
+        }
+        Ok(tsgraph)
+    }
+
     /// Write the TSGraph to writer
     pub fn to_writer<W: std::io::Write>(&self, writer: &mut W) -> Result<()> {
         // Write global headers
@@ -1204,11 +1411,12 @@ impl TSGraph {
         Ok(())
     }
 
-    /// Write the TSGraph to a TSG file
+    /// Write the TSGraph to a file, compressed according to the file extension
+    /// (see [`crate::io::Compression::from_path`])
     pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        let file = File::create(path)?;
-        let mut writer = BufWriter::new(file);
-        self.to_writer(&mut writer)
+        let mut writer = crate::io::create_writer(path, None)?;
+        self.to_writer(&mut writer)?;
+        writer.finish()
     }
 
     // Helper methods for accessing graph elements
@@ -1335,22 +1543,20 @@ impl TSGraph {
     }
 
     /// Traverse the graph and return all valid paths from source nodes to sink nodes.
-    pub fn traverse_by_id(&self, graph_id: &str) -> Result<Vec<TSGPath>> {
+    pub fn traverse_by_id(&self, graph_id: &str) -> Result<Vec<TSGPath<'_>>> {
         let graph = self.graphs.get(&BString::from(graph_id)).unwrap();
         graph.traverse()
     }
 
     /// traverse all graphs
-    pub fn traverse_all_graphs(&self) -> Result<Vec<TSGPath>> {
-        let all_paths = self
-            .graphs
+    pub fn traverse_all_graphs(&self) -> Result<Vec<TSGPath<'_>>> {
+        self.graphs
             .values()
             .try_fold(Vec::new(), |mut all_paths, graph| {
                 let paths = graph.traverse()?;
                 all_paths.extend(paths);
                 Ok(all_paths)
-            });
-        all_paths
+            })
     }
 
     pub fn to_dot_by_id(
@@ -1592,4 +1798,49 @@ mod tests {
         println!("{}", json);
         Ok(())
     }
+
+    #[test]
+    fn test_json_round_trip() -> Result<()> {
+        let graph = TSGraph::from_file("tests/data/test.tsg")?;
+        let section = graph.default_graph().unwrap();
+        let json = section.to_json()?;
+
+        let restored = GraphSection::from_json(DEFAULT_GRAPH_ID.into(), &json)?;
+        assert_eq!(restored.nodes().len(), section.nodes().len());
+        assert_eq!(restored.edges().len(), section.edges().len());
+
+        let n1 = restored.node_by_id("n1").unwrap();
+        assert_eq!(n1.reference_id, "chr1");
+        assert_eq!(n1.strand, Strand::Forward);
+        assert_eq!(n1.exons.to_string(), "1000-1200,1500-1700");
+        assert_eq!(n1.reads.len(), 2);
+        assert_eq!(n1.sequence.as_ref().unwrap(), "ACGTACGT");
+        assert_eq!(n1.attributes[&BString::from("ptc")].as_int()?, 10);
+        assert_eq!(
+            n1.attributes[&BString::from("expression")].as_float()?,
+            10.5
+        );
+
+        let e3 = restored.edge_by_id("e3").unwrap();
+        assert_eq!(e3.sv.breakpoint1, 2200);
+        assert_eq!(e3.sv.breakpoint2, 2500);
+        assert_eq!(e3.sv.sv_type, "TDUP");
+        assert!(e3.attributes.is_empty());
+        let (source, sink) = restored
+            .edge_endpoints(restored.edge_indices[&BString::from("e3")])
+            .unwrap();
+        assert_eq!(restored.node_by_idx(source).unwrap().id, "n2");
+        assert_eq!(restored.node_by_idx(sink).unwrap().id, "n3");
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_json_file() -> Result<()> {
+        let graph = TSGraph::from_json("tests/data/test.json")?;
+        assert_eq!(graph.graphs.len(), 1);
+        let section = graph.graph("test").unwrap();
+        assert_eq!(section.nodes().len(), 5);
+        assert_eq!(section.edges().len(), 4);
+        Ok(())
+    }
 }
@@ -1,5 +1,17 @@
+mod compare;
+mod count;
+mod event;
 mod graph;
 mod path;
+mod quant;
+mod superbubble;
+mod support;
 
+pub use compare::*;
+pub use count::*;
+pub use event::*;
 pub use graph::*;
 pub use path::*;
+pub use quant::*;
+pub use superbubble::*;
+pub use support::*;
@@ -0,0 +1,776 @@
+use crate::graph::{
+    Attribute, GraphSection, Group, Orientation, OrientedElement, Strand, TSGPath, TraversalOptions,
+};
+use ahash::{HashMap, HashMapExt, HashSet, HashSetExt};
+use anyhow::{Context, Result};
+use bstr::{BString, ByteSlice};
+use derive_more::Display;
+use std::io::BufRead;
+use std::path::Path;
+
+/// Column names of the per-path table written by [`PathComparison::to_row`]
+pub const COMPARISON_COLUMNS: [&str; 12] = [
+    "gid",
+    "path_id",
+    "class_code",
+    "category",
+    "ref_gene_id",
+    "ref_transcript_id",
+    "junctions",
+    "known_junctions",
+    "shared_junctions",
+    "ref_junctions",
+    "precision",
+    "recall",
+];
+
+/// Class code of a path relative to its closest reference transcript, following the
+/// codes of gffcompare; `f` is added for fusion-derived paths.
+///
+/// Codes are declared from the closest to the most distant relationship.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Display)]
+pub enum ClassCode {
+    /// Same intron chain, or overlapping single-exon transcripts
+    #[display("=")]
+    Match,
+    /// Contained in the reference: its intron chain is part of the reference chain
+    #[display("c")]
+    Contained,
+    /// Contains the reference: the reference intron chain is part of its chain
+    #[display("k")]
+    Containment,
+    /// Shares at least one junction with the reference
+    #[display("j")]
+    JunctionMatch,
+    /// Other overlap with the reference on the same strand
+    #[display("o")]
+    Overlap,
+    /// Falls entirely within an intron of the reference
+    #[display("i")]
+    Intronic,
+    /// Overlaps exons of the reference on the opposite strand
+    #[display("x")]
+    Antisense,
+    /// Joins parts of different references, strands or loci
+    #[display("f")]
+    Fusion,
+    /// Overlaps no reference transcript
+    #[display("u")]
+    Intergenic,
+}
+
+/// Broad category of a path relative to the reference annotation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display)]
+pub enum PathCategory {
+    /// A reference transcript or a part of one (`=` or `c`)
+    #[display("known")]
+    Known,
+    /// All junctions are annotated, but not in this combination
+    #[display("novel_combination")]
+    NovelCombination,
+    /// At least one junction is not annotated
+    #[display("novel_junction")]
+    NovelJunction,
+    #[display("intronic")]
+    Intronic,
+    #[display("fusion")]
+    Fusion,
+    #[display("antisense")]
+    Antisense,
+    #[display("intergenic")]
+    Intergenic,
+    /// Single-exon paths overlapping reference exons without matching them
+    #[display("other")]
+    Other,
+}
+
+/// An intron between two exons, in 1-based genomic coordinates of the exon ends
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Display)]
+#[display("{reference_id}:{strand}:{donor}-{acceptor}")]
+pub struct Junction {
+    pub reference_id: BString,
+    pub strand: Strand,
+    /// Last base of the upstream exon on the reference
+    pub donor: usize,
+    /// First base of the downstream exon on the reference
+    pub acceptor: usize,
+}
+
+/// Exons of a collinear transcript on one reference and strand, sorted and merged
+#[derive(Debug, Clone)]
+struct Segment {
+    reference_id: BString,
+    strand: Strand,
+    exons: Vec<(usize, usize)>,
+    introns: Vec<(usize, usize)>,
+}
+
+impl Segment {
+    fn new(reference_id: BString, strand: Strand, mut exons: Vec<(usize, usize)>) -> Self {
+        exons.sort();
+        let mut merged: Vec<(usize, usize)> = Vec::with_capacity(exons.len());
+        for (start, end) in exons {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        let introns = merged
+            .windows(2)
+            .map(|pair| (pair[0].1, pair[1].0))
+            .collect();
+        Self {
+            reference_id,
+            strand,
+            exons: merged,
+            introns,
+        }
+    }
+
+    fn start(&self) -> usize {
+        self.exons.first().map_or(0, |exon| exon.0)
+    }
+
+    fn end(&self) -> usize {
+        self.exons.last().map_or(0, |exon| exon.1)
+    }
+
+    fn junctions(&self) -> impl Iterator<Item = Junction> + '_ {
+        self.introns.iter().map(|&(donor, acceptor)| Junction {
+            reference_id: self.reference_id.clone(),
+            strand: self.strand,
+            donor,
+            acceptor,
+        })
+    }
+
+    /// Number of bases shared by the exons of both segments
+    fn exonic_overlap(&self, other: &Segment) -> usize {
+        let mut overlap = 0;
+        for &(start, end) in &self.exons {
+            for &(other_start, other_end) in &other.exons {
+                let (from, to) = (start.max(other_start), end.min(other_end));
+                if from <= to {
+                    overlap += to + 1 - from;
+                }
+            }
+        }
+        overlap
+    }
+}
+
+/// Position of `part` as a contiguous run in `whole`
+fn find_run(whole: &[(usize, usize)], part: &[(usize, usize)]) -> Option<usize> {
+    if part.is_empty() || part.len() > whole.len() {
+        return None;
+    }
+    whole.windows(part.len()).position(|window| window == part)
+}
+
+/// A transcript of the reference annotation
+#[derive(Debug, Clone)]
+pub struct ReferenceTranscript {
+    pub id: BString,
+    pub gene_id: BString,
+    segment: Segment,
+}
+
+impl ReferenceTranscript {
+    /// Class code of a collinear segment relative to this transcript, if they overlap
+    fn classify(&self, query: &Segment) -> Option<ClassCode> {
+        let reference = &self.segment;
+        if query.end() < reference.start() || reference.end() < query.start() {
+            return None;
+        }
+        let exonic_overlap = query.exonic_overlap(reference) > 0;
+        if query.strand != reference.strand {
+            return exonic_overlap.then_some(ClassCode::Antisense);
+        }
+
+        match (query.introns.is_empty(), reference.introns.is_empty()) {
+            (false, false) => {
+                if query.introns == reference.introns {
+                    return Some(ClassCode::Match);
+                }
+                if let Some(k) = find_run(&reference.introns, &query.introns) {
+                    let (first, last) =
+                        (reference.exons[k], reference.exons[k + query.introns.len()]);
+                    if query.start() >= first.0 && query.end() <= last.1 {
+                        return Some(ClassCode::Contained);
+                    }
+                }
+                if find_run(&query.introns, &reference.introns).is_some() {
+                    return Some(ClassCode::Containment);
+                }
+                if query
+                    .introns
+                    .iter()
+                    .any(|intron| reference.introns.contains(intron))
+                {
+                    return Some(ClassCode::JunctionMatch);
+                }
+            }
+            (true, true) if exonic_overlap => return Some(ClassCode::Match),
+            (true, false)
+                if reference
+                    .exons
+                    .iter()
+                    .any(|exon| exon.0 <= query.start() && query.end() <= exon.1) =>
+            {
+                return Some(ClassCode::Contained);
+            }
+            _ => {}
+        }
+
+        if exonic_overlap {
+            Some(ClassCode::Overlap)
+        } else {
+            // spans that overlap without sharing exonic bases are only related when the
+            // segment lies within one intron
+            reference
+                .introns
+                .iter()
+                .any(|intron| intron.0 < query.start() && query.end() < intron.1)
+                .then_some(ClassCode::Intronic)
+        }
+    }
+
+    /// Number of introns shared with a segment
+    fn shared_introns(&self, query: &Segment) -> usize {
+        query
+            .introns
+            .iter()
+            .filter(|intron| self.segment.introns.contains(intron))
+            .count()
+    }
+}
+
+/// Comparison of one path with the reference annotation
+#[derive(Debug, Clone)]
+pub struct PathComparison {
+    pub graph_id: BString,
+    pub path_id: BString,
+    pub class_code: ClassCode,
+    pub category: PathCategory,
+    /// Closest reference genes and transcripts, one per fusion partner
+    pub ref_gene_ids: Vec<BString>,
+    pub ref_transcript_ids: Vec<BString>,
+    /// Junctions of the path
+    pub junctions: Vec<Junction>,
+    /// Number of junctions found in any reference transcript
+    pub known_junctions: usize,
+    /// Number of junctions shared with the closest reference transcripts
+    pub shared_junctions: usize,
+    /// Number of junctions of the closest reference transcripts
+    pub ref_junctions: usize,
+}
+
+impl PathComparison {
+    /// Fraction of the junctions of the path that are annotated
+    pub fn precision(&self) -> Option<f64> {
+        (!self.junctions.is_empty())
+            .then(|| self.known_junctions as f64 / self.junctions.len() as f64)
+    }
+
+    /// Fraction of the junctions of the closest reference transcripts found in the path
+    pub fn recall(&self) -> Option<f64> {
+        (self.ref_junctions > 0).then(|| self.shared_junctions as f64 / self.ref_junctions as f64)
+    }
+
+    /// One tab-separated row of the comparison table, see [`COMPARISON_COLUMNS`]
+    pub fn to_row(&self) -> BString {
+        let ids = |ids: &[BString]| {
+            if ids.is_empty() {
+                ".".to_string()
+            } else {
+                ids.join(&b","[..]).to_str_lossy().into_owned()
+            }
+        };
+        let ratio = |value: Option<f64>| value.map_or(".".to_string(), |v| format!("{:.4}", v));
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            self.graph_id,
+            self.path_id,
+            self.class_code,
+            self.category,
+            ids(&self.ref_gene_ids),
+            ids(&self.ref_transcript_ids),
+            self.junctions.len(),
+            self.known_junctions,
+            self.shared_junctions,
+            self.ref_junctions,
+            ratio(self.precision()),
+            ratio(self.recall()),
+        )
+        .into()
+    }
+
+    /// `class_code` and, when there is a closest reference, `ref_transcript_id` attributes
+    pub fn attributes(&self) -> Vec<Attribute> {
+        let mut attributes = vec![
+            Attribute::builder()
+                .tag("class_code")
+                .value(self.class_code.to_string())
+                .build(),
+        ];
+        if !self.ref_transcript_ids.is_empty() {
+            attributes.push(
+                Attribute::builder()
+                    .tag("ref_transcript_id")
+                    .value(self.ref_transcript_ids.join(&b","[..]))
+                    .build(),
+            );
+        }
+        attributes
+    }
+}
+
+/// Transcripts of a reference annotation, indexed by position and junction
+#[derive(Debug, Clone, Default)]
+pub struct ReferenceAnnotation {
+    transcripts: Vec<ReferenceTranscript>,
+    /// Transcripts of each reference sequence, sorted by start
+    by_reference: HashMap<BString, Vec<usize>>,
+    /// Longest span of a transcript, to bound overlap queries
+    max_span: usize,
+    junctions: HashSet<Junction>,
+    /// Junctions of each gene
+    gene_junctions: HashMap<BString, HashSet<Junction>>,
+}
+
+impl ReferenceAnnotation {
+    /// Load the `exon` records of a GTF or GFF3 annotation.
+    ///
+    /// Transcripts with exons on several references or strands keep only the exons on
+    /// the reference and strand of their first exon.
+    pub fn from_gtf<R: BufRead>(reader: R) -> Result<Self> {
+        let mut annotation = Self::default();
+        for (gene_id, transcripts) in crate::io::read_gtf_genes(reader)? {
+            for transcript in transcripts {
+                let Some(first) = transcript.exons.first() else {
+                    continue;
+                };
+                let (reference_id, strand) = (first.reference_id.clone(), first.strand);
+                let exons = transcript
+                    .exons
+                    .iter()
+                    .filter(|exon| exon.reference_id == reference_id && exon.strand == strand)
+                    .map(|exon| (exon.start, exon.end))
+                    .collect();
+                let segment = Segment::new(reference_id, strand, exons);
+
+                let gene_junctions = annotation
+                    .gene_junctions
+                    .entry(gene_id.clone())
+                    .or_default();
+                for junction in segment.junctions() {
+                    gene_junctions.insert(junction.clone());
+                    annotation.junctions.insert(junction);
+                }
+                annotation.max_span = annotation.max_span.max(segment.end() + 1 - segment.start());
+                annotation
+                    .by_reference
+                    .entry(segment.reference_id.clone())
+                    .or_default()
+                    .push(annotation.transcripts.len());
+                annotation.transcripts.push(ReferenceTranscript {
+                    id: transcript.id,
+                    gene_id: gene_id.clone(),
+                    segment,
+                });
+            }
+        }
+
+        let transcripts = &annotation.transcripts;
+        for indices in annotation.by_reference.values_mut() {
+            indices.sort_by_key(|&idx| (transcripts[idx].segment.start(), idx));
+        }
+        Ok(annotation)
+    }
+
+    /// Load a GTF or GFF3 annotation file, compressed or not
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        Self::from_gtf(crate::io::open_reader(path)?)
+            .with_context(|| format!("Failed to read annotation {}", path.display()))
+    }
+
+    pub fn transcripts(&self) -> &[ReferenceTranscript] {
+        &self.transcripts
+    }
+
+    /// Whether a junction is found in any reference transcript
+    pub fn is_known(&self, junction: &Junction) -> bool {
+        self.junctions.contains(junction)
+    }
+
+    /// Transcripts whose span overlaps the span of a segment, on either strand
+    fn overlapping<'a>(&'a self, query: &Segment) -> impl Iterator<Item = &'a ReferenceTranscript> {
+        let indices = self
+            .by_reference
+            .get(&query.reference_id)
+            .map_or(&[][..], |indices| indices.as_slice());
+        let from = query.start().saturating_sub(self.max_span);
+        let first = indices.partition_point(|&idx| self.transcripts[idx].segment.start() < from);
+        let (start, end) = (query.start(), query.end());
+        indices[first..]
+            .iter()
+            .map(|&idx| &self.transcripts[idx])
+            .take_while(move |transcript| transcript.segment.start() <= end)
+            .filter(move |transcript| transcript.segment.end() >= start)
+    }
+
+    /// Closest reference transcript of a collinear segment and its class code; ties are
+    /// broken by shared junctions, then exonic overlap, then transcript id
+    fn closest(&self, query: &Segment) -> (ClassCode, Option<&ReferenceTranscript>) {
+        self.overlapping(query)
+            .filter_map(|transcript| transcript.classify(query).map(|code| (code, transcript)))
+            .min_by(|(code, a), (other_code, b)| {
+                code.cmp(other_code)
+                    .then(b.shared_introns(query).cmp(&a.shared_introns(query)))
+                    .then(
+                        query
+                            .exonic_overlap(&b.segment)
+                            .cmp(&query.exonic_overlap(&a.segment)),
+                    )
+                    .then(a.id.cmp(&b.id))
+            })
+            .map_or((ClassCode::Intergenic, None), |(code, transcript)| {
+                (code, Some(transcript))
+            })
+    }
+
+    /// Compare a path with the annotation.
+    ///
+    /// The path is split into collinear segments wherever consecutive nodes lie on
+    /// different references or strands or go backwards on the reference; paths with
+    /// more than one segment are fusion-derived.
+    pub fn compare_path(&self, path: &TSGPath) -> Result<PathComparison> {
+        let graph = path.graph().context("Path is not attached to a graph")?;
+        // exons of the collinear runs of nodes, sorted and merged once complete
+        let mut runs: Vec<Segment> = vec![];
+        for node_idx in &path.nodes {
+            let node = graph
+                .node_by_idx(*node_idx)
+                .with_context(|| format!("Node not found for index: {}", node_idx.index()))?;
+            let exons: Vec<(usize, usize)> = node
+                .exons
+                .exons
+                .iter()
+                .map(|exon| (exon.start, exon.end))
+                .collect();
+            let (Some(start), Some(end)) = (
+                exons.iter().map(|exon| exon.0).min(),
+                exons.iter().map(|exon| exon.1).max(),
+            ) else {
+                continue;
+            };
+
+            match runs.last_mut() {
+                Some(run)
+                    if run.reference_id == node.reference_id
+                        && run.strand == node.strand
+                        && match run.strand {
+                            Strand::Forward => run.exons.iter().all(|exon| exon.1 < start),
+                            Strand::Reverse => run.exons.iter().all(|exon| exon.0 > end),
+                        } =>
+                {
+                    run.exons.extend(exons)
+                }
+                _ => runs.push(Segment {
+                    reference_id: node.reference_id.clone(),
+                    strand: node.strand,
+                    exons,
+                    introns: vec![],
+                }),
+            }
+        }
+        let segments: Vec<Segment> = runs
+            .into_iter()
+            .map(|run| Segment::new(run.reference_id, run.strand, run.exons))
+            .collect();
+
+        let mut comparison = PathComparison {
+            graph_id: graph.id.clone(),
+            path_id: path.id()?,
+            class_code: ClassCode::Intergenic,
+            category: PathCategory::Intergenic,
+            ref_gene_ids: vec![],
+            ref_transcript_ids: vec![],
+            junctions: vec![],
+            known_junctions: 0,
+            shared_junctions: 0,
+            ref_junctions: 0,
+        };
+        let mut codes = vec![];
+        for segment in &segments {
+            let (code, closest) = self.closest(segment);
+            codes.push(code);
+            if let Some(transcript) = closest {
+                if !comparison.ref_transcript_ids.contains(&transcript.id) {
+                    comparison.ref_gene_ids.push(transcript.gene_id.clone());
+                    comparison.ref_transcript_ids.push(transcript.id.clone());
+                    comparison.ref_junctions += transcript.segment.introns.len();
+                }
+                comparison.shared_junctions += transcript.shared_introns(segment);
+            }
+            comparison.junctions.extend(segment.junctions());
+        }
+        comparison.known_junctions = comparison
+            .junctions
+            .iter()
+            .filter(|junction| self.is_known(junction))
+            .count();
+
+        comparison.class_code = match codes.as_slice() {
+            [code] => *code,
+            [] => ClassCode::Intergenic,
+            _ => ClassCode::Fusion,
+        };
+        let all_known = comparison.known_junctions == comparison.junctions.len();
+        comparison.category = match comparison.class_code {
+            ClassCode::Match | ClassCode::Contained => PathCategory::Known,
+            ClassCode::Fusion => PathCategory::Fusion,
+            ClassCode::Intronic => PathCategory::Intronic,
+            ClassCode::Antisense => PathCategory::Antisense,
+            ClassCode::Intergenic => PathCategory::Intergenic,
+            _ if comparison.junctions.is_empty() => PathCategory::Other,
+            _ if all_known => PathCategory::NovelCombination,
+            _ => PathCategory::NovelJunction,
+        };
+        Ok(comparison)
+    }
+
+    /// Compare every traversed path of a graph section with the annotation
+    pub fn compare_graph(
+        &self,
+        graph: &GraphSection,
+        traversal: &TraversalOptions,
+    ) -> Result<Vec<PathComparison>> {
+        graph
+            .traverse_with(traversal)?
+            .iter()
+            .map(|path| self.compare_path(path))
+            .collect()
+    }
+
+    /// Compare every traversed path of a graph section with the annotation and attach
+    /// the result to its `P` record, adding a `P` record for paths that have none.
+    ///
+    /// A traversed path belongs to the existing `P` record with the same oriented
+    /// elements, whose id then becomes the path id of the comparison.
+    pub fn annotate_graph(
+        &self,
+        graph: &mut GraphSection,
+        traversal: &TraversalOptions,
+    ) -> Result<Vec<PathComparison>> {
+        let mut results: Vec<(PathComparison, Vec<OrientedElement>)> = vec![];
+        for path in graph.traverse_with(traversal)? {
+            let mut comparison = self.compare_path(&path)?;
+            let elements = graph.path_elements(&path);
+            if let Some(id) = graph.groups.values().find_map(|group| match group {
+                Group::Ordered {
+                    id,
+                    elements: existing,
+                    ..
+                } if same_elements(existing, &elements) => Some(id),
+                _ => None,
+            }) {
+                comparison.path_id = id.clone();
+            }
+            results.push((comparison, elements));
+        }
+
+        let mut comparisons = Vec::with_capacity(results.len());
+        for (comparison, elements) in results {
+            let group = graph
+                .groups
+                .entry(comparison.path_id.clone())
+                .or_insert_with(|| Group::Ordered {
+                    id: comparison.path_id.clone(),
+                    elements,
+                    attributes: HashMap::new(),
+                });
+            if let Group::Ordered { attributes, .. } = group {
+                for attr in comparison.attributes() {
+                    attributes.insert(attr.tag.clone(), attr);
+                }
+            }
+            comparisons.push(comparison);
+        }
+        Ok(comparisons)
+    }
+}
+
+/// Whether two element lists name the same elements in the same orientation, elements
+/// without an orientation being read forward
+fn same_elements(a: &[OrientedElement], b: &[OrientedElement]) -> bool {
+    let orientation =
+        |element: &OrientedElement| element.orientation.unwrap_or(Orientation::Forward);
+    a.len() == b.len()
+        && a.iter()
+            .zip(b)
+            .all(|(x, y)| x.id == y.id && orientation(x) == orientation(y))
+}
+
+/// Junction-level precision and recall over many compared paths
+#[derive(Debug, Clone, Default)]
+pub struct JunctionAccuracy {
+    predicted: HashSet<Junction>,
+    known: usize,
+    genes: HashSet<BString>,
+}
+
+impl JunctionAccuracy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count the junctions of a compared path
+    pub fn add(&mut self, annotation: &ReferenceAnnotation, comparison: &PathComparison) {
+        for junction in &comparison.junctions {
+            if self.predicted.insert(junction.clone()) && annotation.is_known(junction) {
+                self.known += 1;
+            }
+        }
+        self.genes.extend(comparison.ref_gene_ids.iter().cloned());
+    }
+
+    /// Fraction of the distinct junctions of all paths that are annotated
+    pub fn precision(&self) -> Option<f64> {
+        (!self.predicted.is_empty()).then(|| self.known as f64 / self.predicted.len() as f64)
+    }
+
+    /// Fraction of the annotated junctions of the genes matched by any path that are
+    /// found in a path
+    pub fn recall(&self, annotation: &ReferenceAnnotation) -> Option<f64> {
+        let mut reference = HashSet::new();
+        for gene_id in &self.genes {
+            if let Some(junctions) = annotation.gene_junctions.get(gene_id) {
+                reference.extend(junctions.iter());
+            }
+        }
+        let found = reference
+            .iter()
+            .filter(|junction| self.predicted.contains(**junction))
+            .count();
+        (!reference.is_empty()).then(|| found as f64 / reference.len() as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::TSGraph;
+    use std::io::BufReader;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_compare_paths() -> Result<()> {
+        let gtf = "\
+chr1\tref\texon\t100\t200\t.\t+\t.\tgene_id \"G1\"; transcript_id \"T1\";
+chr1\tref\texon\t300\t400\t.\t+\t.\tgene_id \"G1\"; transcript_id \"T1\";
+chr1\tref\texon\t500\t600\t.\t+\t.\tgene_id \"G1\"; transcript_id \"T1\";
+chr1\tref\texon\t100\t200\t.\t+\t.\tgene_id \"G1\"; transcript_id \"T2\";
+chr1\tref\texon\t350\t400\t.\t+\t.\tgene_id \"G1\"; transcript_id \"T2\";
+chr1\tref\texon\t700\t800\t.\t+\t.\tgene_id \"G1\"; transcript_id \"T2\";
+chr2\tref\texon\t100\t200\t.\t-\t.\tgene_id \"G2\"; transcript_id \"T3\";
+";
+        let annotation = ReferenceAnnotation::from_gtf(BufReader::new(gtf.as_bytes()))?;
+        assert_eq!(annotation.transcripts().len(), 3);
+
+        // one graph per path: known, skipped exon, novel junction, intronic, fusion
+        let mut tsg = TSGraph::from_str(
+            "G\tknown\n\
+             N\ta1\tchr1:+:100-200\tr1:SO\n\
+             N\ta2\tchr1:+:300-400\tr1:IN\n\
+             N\ta3\tchr1:+:500-600\tr1:SI\n\
+             E\tae1\ta1\ta2\tchr1,chr1,200,300,SPLICE\n\
+             E\tae2\ta2\ta3\tchr1,chr1,400,500,SPLICE\n\
+             G\tskip\n\
+             N\tb1\tchr1:+:100-200\tr1:SO\n\
+             N\tb2\tchr1:+:500-600\tr1:SI\n\
+             E\tbe1\tb1\tb2\tchr1,chr1,200,500,SPLICE\n\
+             G\tnovel\n\
+             N\tc1\tchr1:+:100-200\tr1:SO\n\
+             N\tc2\tchr1:+:300-400\tr1:IN\n\
+             N\tc3\tchr1:+:550-600\tr1:SI\n\
+             E\tce1\tc1\tc2\tchr1,chr1,200,300,SPLICE\n\
+             E\tce2\tc2\tc3\tchr1,chr1,400,550,SPLICE\n\
+             G\tintronic\n\
+             N\td1\tchr1:+:220-280\tr1:SO\n\
+             G\tfusion\n\
+             N\tf1\tchr1:+:100-200\tr1:SO\n\
+             N\tf2\tchr2:-:100-200\tr1:SI\n\
+             E\tfe1\tf1\tf2\tchr1,chr2,200,200,TRA\n",
+        )?;
+
+        let compare = |id: &str| -> Result<PathComparison> {
+            let graph = tsg.graph(id).unwrap();
+            let mut comparisons = annotation.compare_graph(graph, &TraversalOptions::default())?;
+            assert_eq!(comparisons.len(), 1);
+            Ok(comparisons.remove(0))
+        };
+
+        let known = compare("known")?;
+        assert_eq!(known.class_code, ClassCode::Match);
+        assert_eq!(known.category, PathCategory::Known);
+        assert_eq!(known.ref_transcript_ids, vec!["T1"]);
+        assert_eq!((known.precision(), known.recall()), (Some(1.0), Some(1.0)));
+
+        let skip = compare("skip")?;
+        assert_eq!(skip.class_code, ClassCode::Overlap);
+        assert_eq!(skip.category, PathCategory::NovelJunction);
+
+        let novel = compare("novel")?;
+        assert_eq!(novel.class_code, ClassCode::JunctionMatch);
+        assert_eq!(novel.category, PathCategory::NovelJunction);
+        assert_eq!((novel.known_junctions, novel.shared_junctions), (1, 1));
+        assert_eq!(novel.recall(), Some(0.5));
+
+        assert_eq!(compare("intronic")?.class_code, ClassCode::Intronic);
+
+        let fusion = compare("fusion")?;
+        assert_eq!(fusion.class_code, ClassCode::Fusion);
+        assert_eq!(fusion.ref_transcript_ids, vec!["T1", "T3"]);
+        assert_eq!(fusion.ref_gene_ids, vec!["G1", "G2"]);
+
+        let mut accuracy = JunctionAccuracy::new();
+        for comparison in [&known, &skip, &novel] {
+            accuracy.add(&annotation, comparison);
+        }
+        // 200-500 and 400-550 are novel; 200-300 and 400-500 of G1 are found, 200-350
+        // and 400-700 are not
+        assert_eq!(accuracy.precision(), Some(0.5));
+        assert_eq!(accuracy.recall(&annotation), Some(0.5));
+
+        let graph = tsg.graphs.get_mut(&BString::from("known")).unwrap();
+        annotation.annotate_graph(graph, &TraversalOptions::default())?;
+        let Some(Group::Ordered { attributes, .. }) = graph.groups.values().next() else {
+            panic!("no P record was added");
+        };
+        assert_eq!(attributes[&BString::from("class_code")].value, "=");
+        assert_eq!(attributes[&BString::from("ref_transcript_id")].value, "T1");
+
+        // a named P record is annotated in place instead of getting a hash-named twin
+        let mut named = TSGraph::from_str(
+            "G\tnamed\n\
+             N\ta1\tchr1:+:100-200\tr1:SO\n\
+             N\ta2\tchr1:+:300-400\tr1:IN\n\
+             N\ta3\tchr1:+:500-600\tr1:SI\n\
+             E\tae1\ta1\ta2\tchr1,chr1,200,300,SPLICE\n\
+             E\tae2\ta2\ta3\tchr1,chr1,400,500,SPLICE\n\
+             P\tT1.1\ta1+\tae1+\ta2+\tae2+\ta3+\n",
+        )?;
+        let graph = named.graphs.get_mut(&BString::from("named")).unwrap();
+        let comparisons = annotation.annotate_graph(graph, &TraversalOptions::default())?;
+        assert_eq!(comparisons[0].path_id, "T1.1");
+        assert_eq!(graph.groups.len(), 1);
+        let Some(Group::Ordered { attributes, .. }) = graph.groups.get(&BString::from("T1.1"))
+        else {
+            panic!("the P record was replaced");
+        };
+        assert_eq!(attributes[&BString::from("class_code")].value, "=");
+        Ok(())
+    }
+}
@@ -0,0 +1,216 @@
+use crate::graph::{GraphSection, ReadIdentity, ReadKey};
+use ahash::{HashMap, HashMapExt};
+use anyhow::{Result, anyhow};
+use num_bigint::BigUint;
+use petgraph::algo::toposort;
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+
+/// Number of source-to-sink paths of a graph and the number of nodes of the longest one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathCount {
+    pub paths: BigUint,
+    pub max_path_len: usize,
+}
+
+/// Paths ending at a node with the same reads on all of their nodes
+#[derive(Debug, Clone, Default)]
+struct PathClass {
+    count: BigUint,
+    max_len: usize,
+}
+
+impl PathClass {
+    fn extend(&mut self, other: &PathClass) {
+        self.count += &other.count;
+        self.max_len = self.max_len.max(other.max_len + 1);
+    }
+}
+
+/// Reads found on both sorted read lists
+fn intersect(left: &[u32], right: &[u32]) -> Vec<u32> {
+    let (mut i, mut j) = (0, 0);
+    let mut common = vec![];
+    while i < left.len() && j < right.len() {
+        match left[i].cmp(&right[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                common.push(left[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    common
+}
+
+impl GraphSection {
+    /// Count the paths from nodes of in-degree 0 to nodes of out-degree 0 by dynamic
+    /// programming over a topological order, without enumerating them.
+    ///
+    /// Without `read_continuity`, every path is counted in time linear in the size of the
+    /// graph. With it, the paths are those of [`GraphSection::traverse`]: paths that share
+    /// a read on all of their nodes. Paths reaching a node with the same shared reads are
+    /// counted together, so the time is linear in the number of such distinct read sets,
+    /// which stays small on real loci even when the number of paths does not.
+    ///
+    /// Fails on cyclic graphs, whose number of paths depends on how often cycles are followed.
+    pub fn count_paths(&self, read_continuity: bool) -> Result<PathCount> {
+        let order = toposort(&self._graph, None).map_err(|cycle| {
+            anyhow!(
+                "Cannot count paths of graph {}: node {} is on a cycle",
+                self.id,
+                self._graph[cycle.node_id()].id
+            )
+        })?;
+
+        // reads of each node as sorted indices; without read continuity all paths share
+        // a single placeholder read
+        let mut read_index: HashMap<ReadKey, u32> = HashMap::new();
+        let node_reads: Vec<Vec<u32>> = self
+            ._graph
+            .node_indices()
+            .map(|idx| {
+                if !read_continuity {
+                    return vec![0];
+                }
+                let mut reads: Vec<u32> = self._graph[idx]
+                    .reads
+                    .iter()
+                    .map(|read| {
+                        let next = read_index.len() as u32;
+                        *read_index.entry(read.key()).or_insert(next)
+                    })
+                    .collect();
+                reads.sort_unstable();
+                reads.dedup();
+                reads
+            })
+            .collect();
+        let has_in_reads = |idx: NodeIndex| {
+            read_continuity
+                && self._graph[idx]
+                    .reads
+                    .iter()
+                    .any(|read| read.identity == ReadIdentity::IN)
+        };
+
+        let mut classes: Vec<HashMap<Vec<u32>, PathClass>> =
+            vec![HashMap::new(); self._graph.node_count()];
+        let mut total = PathCount {
+            paths: BigUint::ZERO,
+            max_path_len: 0,
+        };
+
+        for idx in order {
+            let is_source = self
+                ._graph
+                .edges_directed(idx, petgraph::Direction::Incoming)
+                .next()
+                .is_none();
+            if is_source && !node_reads[idx.index()].is_empty() {
+                classes[idx.index()].insert(
+                    node_reads[idx.index()].clone(),
+                    PathClass {
+                        count: BigUint::from(1u8),
+                        max_len: 1,
+                    },
+                );
+            }
+
+            let current = std::mem::take(&mut classes[idx.index()]);
+            let targets: Vec<NodeIndex> = self._graph.edges(idx).map(|e| e.target()).collect();
+            if targets.is_empty() {
+                for class in current.values() {
+                    total.paths += &class.count;
+                    total.max_path_len = total.max_path_len.max(class.max_len);
+                }
+                continue;
+            }
+
+            for target in targets {
+                for (reads, class) in &current {
+                    let continuing = intersect(reads, &node_reads[target.index()]);
+                    if continuing.is_empty() {
+                        continue;
+                    }
+                    // like traverse, only enter nodes with IN reads if the reads go on
+                    if has_in_reads(target) {
+                        let mut next_nodes =
+                            self._graph.edges(target).map(|e| e.target()).peekable();
+                        let is_sink = next_nodes.peek().is_none();
+                        if !is_sink
+                            && !next_nodes.any(|next| {
+                                !intersect(&continuing, &node_reads[next.index()]).is_empty()
+                            })
+                        {
+                            continue;
+                        }
+                    }
+                    classes[target.index()]
+                        .entry(continuing)
+                        .or_default()
+                        .extend(class);
+                }
+            }
+        }
+        Ok(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::TSGraph;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_count_paths() -> Result<()> {
+        let tsg = TSGraph::from_file("tests/data/test.tsg")?;
+        let graph = tsg.default_graph().unwrap();
+        let paths = graph.traverse()?;
+        let count = graph.count_paths(true)?;
+        assert_eq!(count.paths, BigUint::from(paths.len()));
+        assert_eq!(
+            Some(count.max_path_len),
+            paths.iter().map(|path| path.len()).max()
+        );
+        assert_eq!(graph.count_paths(false)?.paths, BigUint::from(4u8));
+
+        // a chain of 70 bubbles has 2^70 paths
+        let mut tsg_str = String::from("G\tg1\nN\ts0\tchr1:+:1-2\tr1:SO\n");
+        for i in 0..70 {
+            let start = 10 * i + 3;
+            tsg_str.push_str(&format!(
+                "N\ta{i}\tchr1:+:{start}-{}\tr1:IN\n\
+                 N\tb{i}\tchr1:+:{start}-{}\tr1:IN\n\
+                 N\ts{}\tchr1:+:{}-{}\tr1:IN\n\
+                 E\tea{i}\ts{i}\ta{i}\tchr1,chr1,1,2,SPLICE\n\
+                 E\teb{i}\ts{i}\tb{i}\tchr1,chr1,1,2,SPLICE\n\
+                 E\tfa{i}\ta{i}\ts{}\tchr1,chr1,1,2,SPLICE\n\
+                 E\tfb{i}\tb{i}\ts{}\tchr1,chr1,1,2,SPLICE\n",
+                start + 2,
+                start + 4,
+                i + 1,
+                start + 6,
+                start + 8,
+                i + 1,
+                i + 1,
+            ));
+        }
+        let tsg = TSGraph::from_str(&tsg_str)?;
+        let count = tsg.graph("g1").unwrap().count_paths(true)?;
+        assert_eq!(count.paths.to_string(), "1180591620717411303424");
+        assert_eq!(count.max_path_len, 141);
+
+        let cyclic = TSGraph::from_str(
+            "N\tn1\tchr1:+:1-2\tr1:SO\n\
+             N\tn2\tchr1:+:3-4\tr1:IN\n\
+             E\te1\tn1\tn2\tchr1,chr1,2,3,SPLICE\n\
+             E\te2\tn2\tn1\tchr1,chr1,4,1,DUP\n",
+        )?;
+        assert!(cyclic.default_graph().unwrap().count_paths(false).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,594 @@
+use crate::graph::{EventSupport, GraphSection, NodeData, Strand, format_psi};
+use crate::io::SPLICE_JUNCTION_TYPE;
+use ahash::{HashSet, HashSetExt};
+use anyhow::{Context, Result, anyhow};
+use bstr::{BString, ByteSlice};
+use derive_more::Display;
+use petgraph::graph::{EdgeIndex, NodeIndex};
+use petgraph::visit::EdgeRef;
+
+/// Local alternative splicing event types, named like rMATS and SUPPA event codes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display)]
+pub enum SplicingEventType {
+    /// One or more exons are included or skipped
+    #[display("SE")]
+    SkippedExon,
+    /// The donor site of the upstream exon moves
+    #[display("A5SS")]
+    Alternative5SpliceSite,
+    /// The acceptor site of the downstream exon moves
+    #[display("A3SS")]
+    Alternative3SpliceSite,
+    /// Either one exon or another is included, but not both
+    #[display("MXE")]
+    MutuallyExclusiveExons,
+    /// An intron is kept in the transcript
+    #[display("RI")]
+    RetainedIntron,
+    /// Transcripts start at different first exons
+    #[display("AFE")]
+    AlternativeFirstExon,
+    /// Transcripts end at different last exons
+    #[display("ALE")]
+    AlternativeLastExon,
+    /// One of the alternatives goes through a non-splice edge, e.g. a fusion or inversion
+    #[display("SV")]
+    StructuralVariant,
+    /// Alternatives that do not fit any of the simple types
+    #[display("COMPLEX")]
+    Complex,
+}
+
+/// Column names of the event table, see [`SplicingEvent::to_row`]
+pub const EVENT_COLUMNS: [&str; 13] = [
+    "gid",
+    "event_id",
+    "type",
+    "region",
+    "strand",
+    "inclusion_nodes",
+    "exclusion_nodes",
+    "inclusion_edges",
+    "exclusion_edges",
+    "sv_types",
+    "inclusion_reads",
+    "exclusion_reads",
+    "psi",
+];
+
+/// A local event between two alternative node paths of a graph section.
+///
+/// Bubble events share their first and last node; alternative first and last exon
+/// events only share their last or first node, respectively. The inclusion form is
+/// the one carrying the extra sequence, e.g. the skipped exon or the retained intron.
+#[derive(Debug, Clone)]
+pub struct SplicingEvent {
+    pub id: BString,
+    pub graph_id: BString,
+    pub event_type: SplicingEventType,
+    /// Node ids of the inclusion form, flanking nodes included
+    pub inclusion_nodes: Vec<BString>,
+    /// Node ids of the exclusion form, flanking nodes included
+    pub exclusion_nodes: Vec<BString>,
+    pub inclusion_edges: Vec<BString>,
+    pub exclusion_edges: Vec<BString>,
+    /// Distinct types of the non-splice edges of the event
+    pub sv_types: Vec<BString>,
+    pub reference_id: BString,
+    /// 1-based inclusive span of the event nodes on `reference_id`
+    pub start: usize,
+    pub end: usize,
+    pub strand: Strand,
+    /// Read counts of both forms, see [`GraphSection::event_support`]
+    pub support: Option<EventSupport>,
+}
+
+impl SplicingEvent {
+    /// One tab-separated row of the event table, with a trailing newline
+    pub fn to_row(&self) -> BString {
+        let join = |ids: &[BString]| {
+            if ids.is_empty() {
+                ".".to_string()
+            } else {
+                ids.iter()
+                    .map(|id| id.to_str_lossy())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            }
+        };
+        let (inclusion_reads, exclusion_reads, psi) = match &self.support {
+            Some(support) => (
+                support.inclusion.to_string(),
+                support.exclusion.to_string(),
+                support.psi.map_or(".".to_string(), format_psi),
+            ),
+            None => (".".to_string(), ".".to_string(), ".".to_string()),
+        };
+        format!(
+            "{}\t{}\t{}\t{}:{}-{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            self.graph_id,
+            self.id,
+            self.event_type,
+            self.reference_id,
+            self.start,
+            self.end,
+            self.strand,
+            join(&self.inclusion_nodes),
+            join(&self.exclusion_nodes),
+            join(&self.inclusion_edges),
+            join(&self.exclusion_edges),
+            join(&self.sv_types),
+            inclusion_reads,
+            exclusion_reads,
+            psi,
+        )
+        .into()
+    }
+}
+
+/// 5' boundary of a node in transcription order
+fn five_prime(node: &NodeData) -> usize {
+    match node.strand {
+        Strand::Forward => node.reference_start(),
+        Strand::Reverse => node.reference_end(),
+    }
+}
+
+/// 3' boundary of a node in transcription order
+fn three_prime(node: &NodeData) -> usize {
+    match node.strand {
+        Strand::Forward => node.reference_end(),
+        Strand::Reverse => node.reference_start(),
+    }
+}
+
+/// Whether `next` directly continues `node` on the genome, without an intron between them
+fn is_contiguous(node: &NodeData, next: &NodeData) -> bool {
+    node.reference_id == next.reference_id
+        && node.strand == next.strand
+        && match node.strand {
+            Strand::Forward => node.reference_end() + 1 == next.reference_start(),
+            Strand::Reverse => next.reference_end() + 1 == node.reference_start(),
+        }
+}
+
+fn overlaps(a: &NodeData, b: &NodeData) -> bool {
+    a.reference_id == b.reference_id
+        && a.reference_start() <= b.reference_end()
+        && b.reference_start() <= a.reference_end()
+}
+
+impl GraphSection {
+    fn event_node(&self, idx: NodeIndex) -> Result<&NodeData> {
+        self.node_by_idx(idx)
+            .with_context(|| format!("Node not found for index: {}", idx.index()))
+    }
+
+    /// Edges between consecutive nodes of a node path
+    fn event_edges(&self, nodes: &[NodeIndex]) -> Result<Vec<EdgeIndex>> {
+        nodes
+            .windows(2)
+            .map(|pair| {
+                self._graph.find_edge(pair[0], pair[1]).ok_or_else(|| {
+                    anyhow!(
+                        "No edge between nodes {} and {}",
+                        pair[0].index(),
+                        pair[1].index()
+                    )
+                })
+            })
+            .collect()
+    }
+
+    /// Exonic length of the interior nodes of a node path
+    fn interior_length(&self, nodes: &[NodeIndex]) -> Result<usize> {
+        let mut length = 0;
+        for idx in &nodes[1..nodes.len() - 1] {
+            length += self.event_node(*idx)?.exons.span();
+        }
+        Ok(length)
+    }
+
+    /// Type a bubble from the exon coordinates of its nodes.
+    ///
+    /// Returns the event type and whether `b` is the inclusion form.
+    fn classify_bubble(
+        &self,
+        a: &[NodeIndex],
+        b: &[NodeIndex],
+    ) -> Result<(SplicingEventType, bool)> {
+        let a_int = a[1..a.len() - 1]
+            .iter()
+            .map(|idx| self.event_node(*idx))
+            .collect::<Result<Vec<_>>>()?;
+        let b_int = b[1..b.len() - 1]
+            .iter()
+            .map(|idx| self.event_node(*idx))
+            .collect::<Result<Vec<_>>>()?;
+        let source = self.event_node(a[0])?;
+        let sink = self.event_node(a[a.len() - 1])?;
+        let b_is_longer = self.interior_length(b)? >= self.interior_length(a)?;
+
+        if [source, sink]
+            .iter()
+            .chain(&a_int)
+            .chain(&b_int)
+            .any(|node| node.exons.is_empty())
+        {
+            return Ok((SplicingEventType::Complex, b_is_longer));
+        }
+
+        // one alternative joins the flanking nodes directly
+        if a_int.is_empty() || b_int.is_empty() {
+            let interior = if a_int.is_empty() { &b_int } else { &a_int };
+            let mut chain = vec![source];
+            chain.extend(interior.iter().copied());
+            chain.push(sink);
+            let contiguous: Vec<bool> = chain
+                .windows(2)
+                .map(|pair| is_contiguous(pair[0], pair[1]))
+                .collect();
+            let event_type = match contiguous[..] {
+                [true, true] => SplicingEventType::RetainedIntron,
+                [true, false] => SplicingEventType::Alternative5SpliceSite,
+                [false, true] => SplicingEventType::Alternative3SpliceSite,
+                _ if contiguous.iter().all(|c| !c) => SplicingEventType::SkippedExon,
+                _ => SplicingEventType::Complex,
+            };
+            return Ok((event_type, a_int.is_empty()));
+        }
+
+        if a_int.iter().all(|x| b_int.iter().all(|y| !overlaps(x, y))) {
+            // the alternative seen first along the transcript is the inclusion form
+            let b_first = match source.strand {
+                Strand::Forward => b_int[0].reference_start() < a_int[0].reference_start(),
+                Strand::Reverse => b_int[0].reference_end() > a_int[0].reference_end(),
+            };
+            return Ok((SplicingEventType::MutuallyExclusiveExons, b_first));
+        }
+
+        if let ([x], [y]) = (&a_int[..], &b_int[..]) {
+            let event_type = match (
+                five_prime(x) == five_prime(y),
+                three_prime(x) == three_prime(y),
+            ) {
+                (true, false) => SplicingEventType::Alternative5SpliceSite,
+                (false, true) => SplicingEventType::Alternative3SpliceSite,
+                _ => SplicingEventType::Complex,
+            };
+            return Ok((event_type, b_is_longer));
+        }
+
+        // a single node spanning several exons of the other alternative and their introns
+        let spans = |single: &NodeData, others: &[&NodeData]| {
+            others.len() > 1
+                && five_prime(single) == five_prime(others[0])
+                && three_prime(single) == three_prime(others[others.len() - 1])
+        };
+        if b_int.len() == 1 && spans(b_int[0], &a_int) {
+            return Ok((SplicingEventType::RetainedIntron, true));
+        }
+        if a_int.len() == 1 && spans(a_int[0], &b_int) {
+            return Ok((SplicingEventType::RetainedIntron, false));
+        }
+        Ok((SplicingEventType::Complex, b_is_longer))
+    }
+
+    fn build_event(
+        &self,
+        event_type: SplicingEventType,
+        inclusion: &[NodeIndex],
+        exclusion: &[NodeIndex],
+    ) -> Result<SplicingEvent> {
+        let inclusion_edges = self.event_edges(inclusion)?;
+        let exclusion_edges = self.event_edges(exclusion)?;
+
+        let mut sv_types: Vec<BString> = vec![];
+        for edge_idx in inclusion_edges.iter().chain(&exclusion_edges) {
+            let sv_type = &self._graph[*edge_idx].sv.sv_type;
+            if !sv_type.eq_ignore_ascii_case(SPLICE_JUNCTION_TYPE.as_bytes())
+                && !sv_types.contains(sv_type)
+            {
+                sv_types.push(sv_type.clone());
+            }
+        }
+        let event_type = if sv_types.is_empty() {
+            event_type
+        } else {
+            SplicingEventType::StructuralVariant
+        };
+
+        let anchor = self.event_node(exclusion[exclusion.len() - 1])?;
+        let nodes = inclusion
+            .iter()
+            .chain(exclusion)
+            .map(|idx| self.event_node(*idx))
+            .collect::<Result<Vec<_>>>()?;
+        let on_reference = nodes
+            .iter()
+            .filter(|node| node.reference_id == anchor.reference_id && !node.exons.is_empty());
+        let start = on_reference
+            .clone()
+            .map(|node| node.reference_start())
+            .min()
+            .unwrap_or_default();
+        let end = on_reference
+            .map(|node| node.reference_end())
+            .max()
+            .unwrap_or_default();
+
+        let ids = |path: &[NodeIndex]| -> Result<Vec<BString>> {
+            path.iter()
+                .map(|idx| Ok(self.event_node(*idx)?.id.clone()))
+                .collect()
+        };
+        let edge_ids = |edges: &[EdgeIndex]| -> Vec<BString> {
+            edges
+                .iter()
+                .map(|idx| self._graph[*idx].id.clone())
+                .collect()
+        };
+        Ok(SplicingEvent {
+            id: BString::default(),
+            graph_id: self.id.clone(),
+            event_type,
+            inclusion_nodes: ids(inclusion)?,
+            exclusion_nodes: ids(exclusion)?,
+            inclusion_edges: edge_ids(&inclusion_edges),
+            exclusion_edges: edge_ids(&exclusion_edges),
+            sv_types,
+            reference_id: anchor.reference_id.clone(),
+            start,
+            end,
+            strand: anchor.strand,
+            support: None,
+        })
+    }
+
+    /// Pairs of alternative first (or last) exons that enter (or leave) the same node
+    fn terminal_exon_pairs(
+        &self,
+        direction: petgraph::Direction,
+    ) -> Vec<(NodeIndex, NodeIndex, NodeIndex)> {
+        let mut pairs = vec![];
+        let mut shared: Vec<NodeIndex> = self._graph.node_indices().collect();
+        shared.sort_by_key(|idx| self.node_by_idx(*idx).map(|node| node.id.clone()));
+        for node in shared {
+            let mut terminals: Vec<NodeIndex> = self
+                ._graph
+                .edges_directed(node, direction)
+                .map(|edge| match direction {
+                    petgraph::Direction::Incoming => edge.source(),
+                    petgraph::Direction::Outgoing => edge.target(),
+                })
+                .filter(|&other| {
+                    self._graph
+                        .edges_directed(other, direction)
+                        .next()
+                        .is_none()
+                })
+                .collect();
+            terminals.sort_by_key(|idx| self.node_by_idx(*idx).map(|node| node.id.clone()));
+            terminals.dedup();
+            for (i, &first) in terminals.iter().enumerate() {
+                for &second in &terminals[i + 1..] {
+                    pairs.push((first, second, node));
+                }
+            }
+        }
+        pairs
+    }
+
+    /// Classify the local alternative splicing events of the section.
+    ///
+    /// Bubbles found by [`GraphSection::collect_bubbles`] are typed from the exon
+    /// coordinates of their nodes as skipped exons, alternative 5'/3' splice sites,
+    /// mutually exclusive exons or retained introns; bubbles that go through a
+    /// non-splice edge are structural-variant events. Source (sink) nodes entering
+    /// (leaving) the same node are alternative first (last) exons. Events are
+    /// numbered along the genome and carry their inclusion and exclusion read counts.
+    pub fn splicing_events(&self) -> Result<Vec<SplicingEvent>> {
+        let mut events = vec![];
+        let mut seen = HashSet::new();
+        for bubble in self.collect_bubbles()? {
+            let (a, b) = (&bubble[0], &bubble[1]);
+            let key = if a <= b {
+                (a.clone(), b.clone())
+            } else {
+                (b.clone(), a.clone())
+            };
+            if !seen.insert(key) {
+                continue;
+            }
+            let (event_type, b_included) = self.classify_bubble(a, b)?;
+            let (inclusion, exclusion) = if b_included { (b, a) } else { (a, b) };
+            events.push(self.build_event(event_type, inclusion, exclusion)?);
+        }
+
+        for (direction, event_type) in [
+            (
+                petgraph::Direction::Incoming,
+                SplicingEventType::AlternativeFirstExon,
+            ),
+            (
+                petgraph::Direction::Outgoing,
+                SplicingEventType::AlternativeLastExon,
+            ),
+        ] {
+            for (first, second, shared) in self.terminal_exon_pairs(direction) {
+                let (inclusion, exclusion) = match direction {
+                    petgraph::Direction::Incoming => ([first, shared], [second, shared]),
+                    petgraph::Direction::Outgoing => ([shared, first], [shared, second]),
+                };
+                events.push(self.build_event(event_type, &inclusion, &exclusion)?);
+            }
+        }
+
+        events.sort_by(|a, b| {
+            (&a.reference_id, a.start, a.end, a.event_type.to_string()).cmp(&(
+                &b.reference_id,
+                b.start,
+                b.end,
+                b.event_type.to_string(),
+            ))
+        });
+        for (n, event) in events.iter_mut().enumerate() {
+            event.id = format!("{}_AS{}", self.id, n + 1).into();
+            event.support = Some(self.event_support(event)?);
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::TSGraph;
+    use std::str::FromStr;
+
+    fn event_types(tsg: &str) -> Vec<String> {
+        let tsg = TSGraph::from_str(tsg).unwrap();
+        let graph = tsg.graphs.values().next().unwrap();
+        graph
+            .splicing_events()
+            .unwrap()
+            .iter()
+            .map(|event| event.event_type.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_classify_splicing_events() {
+        // skipped exon n2 and a retained intron between n2 and n3
+        let tsg = "G\tg1\n\
+                   N\tn1\tchr1:+:100-200\tr1:SO\n\
+                   N\tn2\tchr1:+:300-400\tr1:IN\n\
+                   N\tn3\tchr1:+:500-600\tr1:SI\n\
+                   N\tri\tchr1:+:401-499\tr2:IN\n\
+                   E\te1\tn1\tn2\tchr1,chr1,200,300,SPLICE\n\
+                   E\te2\tn2\tn3\tchr1,chr1,400,500,SPLICE\n\
+                   E\te3\tn1\tn3\tchr1,chr1,200,500,SPLICE\n\
+                   E\te4\tn2\tri\tchr1,chr1,400,401,SPLICE\n\
+                   E\te5\tri\tn3\tchr1,chr1,499,500,SPLICE\n";
+        let tsg = TSGraph::from_str(tsg).unwrap();
+        let graph = tsg.graph("g1").unwrap();
+        let events = graph.splicing_events().unwrap();
+        let skipped = events
+            .iter()
+            .find(|event| event.event_type == SplicingEventType::SkippedExon)
+            .unwrap();
+        assert_eq!(skipped.inclusion_nodes, vec!["n1", "n2", "n3"]);
+        assert_eq!(skipped.exclusion_nodes, vec!["n1", "n3"]);
+        assert_eq!(skipped.inclusion_edges, vec!["e1", "e2"]);
+        assert_eq!(skipped.exclusion_edges, vec!["e3"]);
+        assert_eq!(skipped.id, "g1_AS2");
+        assert_eq!(
+            skipped.to_row(),
+            "g1\tg1_AS2\tSE\tchr1:100-600\t+\tn1,n2,n3\tn1,n3\te1,e2\te3\t.\t1\t0\t1\n"
+        );
+        let retained = events
+            .iter()
+            .find(|event| event.event_type == SplicingEventType::RetainedIntron)
+            .unwrap();
+        assert_eq!(retained.inclusion_nodes, vec!["n2", "ri", "n3"]);
+        // skipping n2 together with the intron retained after it
+        assert_eq!(events.len(), 3);
+        assert!(
+            events
+                .iter()
+                .any(|event| event.event_type == SplicingEventType::Complex)
+        );
+
+        // mutually exclusive exons on the minus strand
+        assert_eq!(
+            event_types(
+                "N\tn1\tchr1:-:900-1000\tr1:SO\n\
+                 N\tn2\tchr1:-:700-800\tr1:IN\n\
+                 N\tn3\tchr1:-:500-600\tr2:IN\n\
+                 N\tn4\tchr1:-:100-200\tr1:SI\n\
+                 E\te1\tn1\tn2\tchr1,chr1,900,800,SPLICE\n\
+                 E\te2\tn1\tn3\tchr1,chr1,900,600,SPLICE\n\
+                 E\te3\tn2\tn4\tchr1,chr1,700,200,SPLICE\n\
+                 E\te4\tn3\tn4\tchr1,chr1,500,200,SPLICE\n"
+            ),
+            vec!["MXE"]
+        );
+
+        // alternative 5' and 3' splice sites of the middle exon
+        let alternative_sites = |a: &str, b: &str| {
+            event_types(&format!(
+                "N\tn1\tchr1:+:100-200\tr1:SO\n\
+                 N\tn2\tchr1:+:{}\tr1:IN\n\
+                 N\tn3\tchr1:+:{}\tr2:IN\n\
+                 N\tn4\tchr1:+:900-1000\tr1:SI\n\
+                 E\te1\tn1\tn2\tchr1,chr1,200,300,SPLICE\n\
+                 E\te2\tn1\tn3\tchr1,chr1,200,300,SPLICE\n\
+                 E\te3\tn2\tn4\tchr1,chr1,400,900,SPLICE\n\
+                 E\te4\tn3\tn4\tchr1,chr1,450,900,SPLICE\n",
+                a, b
+            ))
+        };
+        assert_eq!(alternative_sites("300-400", "300-450"), vec!["A5SS"]);
+        assert_eq!(alternative_sites("300-450", "350-450"), vec!["A3SS"]);
+
+        // alternative first exons and a fusion bubble
+        assert_eq!(
+            event_types(
+                "N\tn1\tchr1:+:100-200\tr1:SO\n\
+                 N\tn2\tchr1:+:250-300\tr2:SO\n\
+                 N\tn3\tchr1:+:500-600\tr1:IN\n\
+                 N\tn4\tchr2:+:100-200\tr1:IN\n\
+                 N\tn5\tchr1:+:900-1000\tr1:SI\n\
+                 E\te1\tn1\tn3\tchr1,chr1,200,500,SPLICE\n\
+                 E\te2\tn2\tn3\tchr1,chr1,300,500,SPLICE\n\
+                 E\te3\tn3\tn4\tchr1,chr2,600,100,TRA\n\
+                 E\te4\tn4\tn5\tchr2,chr1,200,900,TRA\n\
+                 E\te5\tn3\tn5\tchr1,chr1,600,900,SPLICE\n"
+            ),
+            vec!["AFE", "SV"]
+        );
+    }
+
+    #[test]
+    fn test_classify_adjacent_exons() {
+        // exons are closed intervals: a block starting right after an exon end continues it
+        let retained = |ri: &str, n3: &str| {
+            event_types(&format!(
+                "N\tn1\tchr1:+:100-200\tr1:SO\n\
+                 N\tn2\tchr1:+:300-400\tr1:IN\n\
+                 N\tri\tchr1:+:{}\tr2:IN\n\
+                 N\tn3\tchr1:+:{}\tr1:SI\n\
+                 E\te1\tn1\tn2\tchr1,chr1,200,300,SPLICE\n\
+                 E\te2\tn2\tn3\tchr1,chr1,400,500,SPLICE\n\
+                 E\te3\tn2\tri\tchr1,chr1,400,401,SPLICE\n\
+                 E\te4\tri\tn3\tchr1,chr1,401,402,SPLICE\n",
+                ri, n3
+            ))
+        };
+        // a single retained base between the exons
+        assert_eq!(retained("401-401", "402-500"), vec!["RI"]);
+        // one base short of the downstream exon
+        assert_eq!(retained("401-401", "403-500"), vec!["A5SS"]);
+
+        // splice sites one base apart; the longer exon is the inclusion form
+        let tsg = TSGraph::from_str(
+            "N\tn1\tchr1:+:100-200\tr1:SO\n\
+             N\tn2\tchr1:+:300-400\tr1:IN\n\
+             N\tn3\tchr1:+:300-401\tr2:IN\n\
+             N\tn4\tchr1:+:900-1000\tr1:SI\n\
+             E\te1\tn1\tn2\tchr1,chr1,200,300,SPLICE\n\
+             E\te2\tn1\tn3\tchr1,chr1,200,300,SPLICE\n\
+             E\te3\tn2\tn4\tchr1,chr1,400,900,SPLICE\n\
+             E\te4\tn3\tn4\tchr1,chr1,401,900,SPLICE\n",
+        )
+        .unwrap();
+        let graph = tsg.graphs.values().next().unwrap();
+        let events = graph.splicing_events().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            events[0].event_type,
+            SplicingEventType::Alternative5SpliceSite
+        );
+        assert_eq!(events[0].inclusion_nodes, vec!["n1", "n3", "n4"]);
+    }
+}
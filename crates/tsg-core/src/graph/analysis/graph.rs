@@ -1,8 +1,9 @@
-use crate::graph::{GraphSection, PathAnalysis, TSGraph};
+use crate::graph::{GraphSection, PathAnalysis, Superbubble, TSGraph};
 use ahash::{HashMap, HashMapExt, HashSet, HashSetExt};
 use anyhow::{Context, Ok, Result};
 use bstr::BString;
 use derive_more::Display;
+use num_bigint::BigUint;
 use petgraph::graph::NodeIndex;
 use petgraph::visit::EdgeRef;
 use std::collections::VecDeque;
@@ -69,6 +70,18 @@ pub trait GraphAnalysis {
     /// * `Err` - If an error occurs during the analysis
     fn is_bubble(&self) -> Result<bool>;
 
+    /// Finds the superbubbles of the graph, with their entrance, exit and inner nodes, in
+    /// linear time.
+    ///
+    /// Bubbles are returned in topological order of their entrances and link to the
+    /// bubbles they are nested in and contain, forming the nesting tree of the graph.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<Superbubble>)` - The superbubbles with at least two alternative paths
+    /// * `Err` - If an error occurs during the analysis
+    fn superbubbles(&self) -> Result<Vec<Superbubble>>;
+
     /// Determines whether the graph is a directed acyclic graph (DAG).
     ///
     /// A graph is a DAG if it is both connected and does not contain cycles.
@@ -234,11 +247,13 @@ impl GraphAnalysis for GraphSection {
         Ok(!bubbles.is_empty())
     }
 
+    fn superbubbles(&self) -> Result<Vec<Superbubble>> {
+        Ok(self.find_superbubbles())
+    }
+
     fn is_simple(&self) -> Result<bool> {
         // A graph is simple if the maximum path length is 1
-        let paths = self.traverse()?;
-        let max_path_len = paths.iter().map(|path| path.len()).max().unwrap_or(0);
-        Ok(max_path_len == 2)
+        Ok(self.path_stats()?.max_path_len == 2)
     }
 
     fn topo(&self) -> Result<GraphTopology> {
@@ -383,7 +398,9 @@ impl GraphSection {
         false
     }
 
-    fn collect_bubbles(&self) -> Result<Vec<Vec<Vec<NodeIndex>>>> {
+    /// Collect the bubbles of the section as pairs of alternative node paths that
+    /// share their first and last node. The same bubble may be reported more than once.
+    pub fn collect_bubbles(&self) -> Result<Vec<Vec<Vec<NodeIndex>>>> {
         let mut visited = HashSet::new();
         let mut bubble_pairs = Vec::new();
 
@@ -493,24 +510,24 @@ impl GraphSection {
             if !convergence_points.is_empty() {
                 // For each convergence point, construct a bubble pair
                 for &end_point in &convergence_points {
-                    if let Some(path1) = path1_visited.get(&end_point) {
-                        if let Some(path2) = path2_visited.get(&end_point) {
-                            // We have two paths that start at source and end at end_point
-                            // This is a proper bubble with common start and end points
-
-                            // Create a bubble pair if both paths are valid and different
-                            if path1.len() >= 3
-                                && path2.len() >= 3
-                                && path1.first() == Some(&source)
-                                && path1.last() == Some(&end_point)
-                                && path2.first() == Some(&source)
-                                && path2.last() == Some(&end_point)
-                                && path1 != path2
-                            {
-                                // Create a bubble pair as a Vec of two paths
-                                let bubble_pair = vec![path1.clone(), path2.clone()];
-                                bubbles.push(bubble_pair);
-                            }
+                    if let Some(path1) = path1_visited.get(&end_point)
+                        && let Some(path2) = path2_visited.get(&end_point)
+                    {
+                        // We have two paths that start at source and end at end_point
+                        // This is a proper bubble with common start and end points
+
+                        // Create a bubble pair if both paths are valid and different
+                        if path1.len() >= 3
+                            && path2.len() >= 3
+                            && path1.first() == Some(&source)
+                            && path1.last() == Some(&end_point)
+                            && path2.first() == Some(&source)
+                            && path2.last() == Some(&end_point)
+                            && path1 != path2
+                        {
+                            // Create a bubble pair as a Vec of two paths
+                            let bubble_pair = vec![path1.clone(), path2.clone()];
+                            bubbles.push(bubble_pair);
                         }
                     }
                 }
@@ -656,6 +673,85 @@ impl GraphSection {
     }
 }
 
+/// Column names of the summary table written by [`TSGraphAnalysis::summarize`]
+pub const SUMMARY_COLUMNS: [&str; 9] = [
+    "gid",
+    "nodes",
+    "edges",
+    "paths",
+    "max_path_len",
+    "super_path",
+    "is_cyclic",
+    "is_connected",
+    "topology",
+];
+
+/// Path columns of the summary table
+struct PathStats {
+    paths: BigUint,
+    max_path_len: usize,
+    super_path: bool,
+}
+
+impl GraphSection {
+    /// Number of read-consistent paths, the length of the longest one and whether any of
+    /// them is a super path. Paths of acyclic graphs are counted without enumerating them,
+    /// see [`GraphSection::count_paths`]; cyclic graphs are traversed.
+    fn path_stats(&self) -> Result<PathStats> {
+        if !self.is_cyclic()? {
+            let count = self.count_paths(true)?;
+            // nodes of a read-consistent path share a read, so any path of two nodes or
+            // more is a super path
+            return Ok(PathStats {
+                super_path: count.max_path_len >= 2,
+                paths: count.paths,
+                max_path_len: count.max_path_len,
+            });
+        }
+
+        let paths = self.traverse()?;
+        let mut super_path = false;
+        for path in &paths {
+            if path.is_super().context("Failed to check super path")? {
+                super_path = true;
+                break;
+            }
+        }
+        Ok(PathStats {
+            paths: BigUint::from(paths.len()),
+            max_path_len: paths.iter().map(|path| path.len()).max().unwrap_or(0),
+            super_path,
+        })
+    }
+
+    /// Summarize the section as one comma-separated row of the summary table,
+    /// see [`SUMMARY_COLUMNS`]
+    pub fn summary_row(&self) -> Result<BString> {
+        let node_count = self.nodes().len();
+        let edge_count = self.edges().len();
+        let stats = self.path_stats()?;
+
+        let is_cyclic = self.is_cyclic().context("Failed to check cyclic")?;
+        let is_connected = self.is_connected().context("Failed to check connected")?;
+
+        let topo = self.topo()?;
+
+        Ok(format!(
+            "{},{},{},{},{},{},{},{},{}\n",
+            self.id,
+            node_count,
+            edge_count,
+            stats.paths,
+            stats.max_path_len,
+            stats.super_path,
+            is_cyclic,
+            is_connected,
+            topo
+        )
+        .into())
+    }
+}
+
 pub trait TSGraphAnalysis {
     fn summarize(&self) -> Result<BString>;
 }
@@ -669,56 +765,13 @@ impl TSGraphAnalysis for TSGraph {
 
         // Pre-allocate with capacity to avoid reallocations
         let mut summary = Vec::with_capacity(estimated_capacity);
-        let headers = [
-            "gid",
-            "nodes",
-            "edges",
-            "paths",
-            "max_path_len",
-            "super_path",
-            "is_cyclic",
-            "is_connected",
-            "topology",
-        ];
 
         let delimiter = ",";
-        let header_str = headers.join(delimiter) + "\n";
+        let header_str = SUMMARY_COLUMNS.join(delimiter) + "\n";
         summary.extend_from_slice(header_str.as_bytes());
 
-        for (id, graph) in self.graphs.iter() {
-            let node_count = graph.nodes().len();
-            let edge_count = graph.edges().len();
-            let paths = graph.traverse()?;
-
-            let path_count = paths.len();
-            let max_path_len = paths.iter().map(|path| path.nodes.len()).max().unwrap_or(0);
-
-            let include_super_path = paths.iter().any(|path| {
-                path.is_super()
-                    .context("Failed to check super path")
-                    .unwrap()
-            });
-
-            let is_cyclic = graph.is_cyclic().context("Failed to check cyclic")?;
-            let is_connected = graph.is_connected().context("Failed to check connected")?;
-
-            let topo = graph.topo()?;
-
-            // Use write! to format directly into the buffer without intermediate allocations
-            use std::io::Write;
-            writeln!(
-                summary,
-                "{},{},{},{},{},{},{},{},{}",
-                id,
-                node_count,
-                edge_count,
-                path_count,
-                max_path_len,
-                include_super_path,
-                is_cyclic,
-                is_connected,
-                topo
-            )?;
+        for graph in self.graphs.values() {
+            summary.extend_from_slice(&graph.summary_row()?);
         }
         // Convert to BString only once at the end
         Ok(BString::from(summary))
@@ -35,7 +35,7 @@ impl PathAnalysis for TSGPath<'_> {
             // Initialize with reads from first node - use capacity hint for better performance
             let mut common_reads = Vec::with_capacity(first_node_data.reads.len());
             for read in &first_node_data.reads {
-                common_reads.push(&read.id);
+                common_reads.push(read.key());
             }
 
             // Early return if first node has no reads
@@ -53,8 +53,7 @@ impl PathAnalysis for TSGPath<'_> {
                         }
 
                         // Retain only common reads
-                        common_reads
-                            .retain(|read_id| node_data.reads.iter().any(|r| &r.id == *read_id));
+                        common_reads.retain(|key| node_data.reads.iter().any(|r| r.key() == *key));
 
                         // Early return if no common reads left
                         if common_reads.is_empty() {
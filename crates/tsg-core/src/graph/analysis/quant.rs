@@ -0,0 +1,397 @@
+use crate::graph::{
+    Attribute, GraphSection, Group, Orientation, OrientedElement, ReadKey, TSGPath, TSGraph,
+};
+use ahash::{HashMap, HashMapExt};
+use anyhow::{Context, Result};
+use bon::Builder;
+use bstr::BString;
+use petgraph::graph::NodeIndex;
+use tracing::debug;
+
+/// Column names of the abundance table, see [`PathAbundance::to_row`]
+pub const ABUNDANCE_COLUMNS: [&str; 5] = ["gid", "path_id", "length", "count", "tpm"];
+
+/// Options of the expectation-maximization run of [`GraphSection::quantify`]
+#[derive(Debug, Clone, Builder)]
+pub struct QuantOptions {
+    #[builder(default = 1000)]
+    pub max_iterations: usize,
+    /// The EM stops once no path abundance changes by more than this fraction of reads
+    #[builder(default = 1e-8)]
+    pub tolerance: f64,
+}
+
+impl Default for QuantOptions {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+/// Estimated expression of one path
+#[derive(Debug, Clone)]
+pub struct PathAbundance {
+    pub graph_id: BString,
+    pub path_id: BString,
+    /// Transcript length of the path, the sum of its exon lengths
+    pub length: usize,
+    /// Expected number of reads assigned to the path
+    pub count: f64,
+    /// Transcripts per million, normalized over all quantified paths
+    pub tpm: f64,
+}
+
+impl PathAbundance {
+    /// One tab-separated row of the abundance table, with a trailing newline
+    pub fn to_row(&self) -> BString {
+        format!(
+            "{}\t{}\t{}\t{:.4}\t{:.4}\n",
+            self.graph_id, self.path_id, self.length, self.count, self.tpm
+        )
+        .into()
+    }
+
+    /// `count` and `tpm` attributes, as attached to `P` records
+    pub fn attributes(&self) -> Vec<Attribute> {
+        vec![
+            Attribute::builder()
+                .tag("count")
+                .attribute_type('f')
+                .value(format!("{:.4}", self.count))
+                .build(),
+            Attribute::builder()
+                .tag("tpm")
+                .attribute_type('f')
+                .value(format!("{:.4}", self.tpm))
+                .build(),
+        ]
+    }
+}
+
+/// Reads compatible with the same set of paths
+#[derive(Debug, Clone)]
+struct CompatibilityClass {
+    paths: Vec<usize>,
+    reads: usize,
+}
+
+/// Whether a read seen on `nodes` can come from a path: its nodes must form an
+/// uninterrupted run of the path, so that a read skipping an exon of the path is not
+/// compatible with it
+fn is_compatible(nodes: &[NodeIndex], positions: &HashMap<NodeIndex, usize>) -> bool {
+    let Some(mut found) = nodes
+        .iter()
+        .map(|node| positions.get(node).copied())
+        .collect::<Option<Vec<usize>>>()
+    else {
+        return false;
+    };
+    found.sort_unstable();
+    found.windows(2).all(|pair| pair[1] == pair[0] + 1)
+}
+
+/// Run EM over compatibility classes and return the expected read count of each path
+fn expectation_maximization(
+    classes: &[CompatibilityClass],
+    path_count: usize,
+    options: &QuantOptions,
+) -> Vec<f64> {
+    let total: usize = classes.iter().map(|class| class.reads).sum();
+    if total == 0 || path_count == 0 {
+        return vec![0.0; path_count];
+    }
+
+    let mut abundance = vec![1.0 / path_count as f64; path_count];
+    let mut counts = vec![0.0; path_count];
+    for iteration in 0..options.max_iterations {
+        // E-step: split the reads of each class in proportion to the current abundances
+        counts.iter_mut().for_each(|count| *count = 0.0);
+        for class in classes {
+            let weight: f64 = class.paths.iter().map(|&path| abundance[path]).sum();
+            if weight == 0.0 {
+                continue;
+            }
+            for &path in &class.paths {
+                counts[path] += class.reads as f64 * abundance[path] / weight;
+            }
+        }
+
+        // M-step: abundances are the fractions of reads assigned to each path
+        let mut change: f64 = 0.0;
+        for (path, count) in counts.iter().enumerate() {
+            let updated = count / total as f64;
+            change = change.max((updated - abundance[path]).abs());
+            abundance[path] = updated;
+        }
+        if change * (total as f64) < options.tolerance {
+            debug!("EM converged after {} iterations", iteration + 1);
+            break;
+        }
+    }
+    counts
+}
+
+/// Turn read counts into TPM, normalizing by path length
+fn counts_to_tpm(abundances: &mut [PathAbundance]) {
+    let rates: Vec<f64> = abundances
+        .iter()
+        .map(|abundance| abundance.count / abundance.length.max(1) as f64)
+        .collect();
+    let total: f64 = rates.iter().sum();
+    for (abundance, rate) in abundances.iter_mut().zip(rates) {
+        abundance.tpm = if total > 0.0 { rate / total * 1e6 } else { 0.0 };
+    }
+}
+
+impl GraphSection {
+    /// Paths to quantify: the `P` records if there are any, otherwise the traversed paths
+    pub fn quantified_paths(&self) -> Result<Vec<TSGPath<'_>>> {
+        let paths = self.ordered_paths()?;
+        if paths.is_empty() {
+            self.traverse()
+        } else {
+            Ok(paths)
+        }
+    }
+
+    /// Estimate the expression of the paths of the section, see [`quantified_paths`].
+    ///
+    /// Each read is compatible with the paths on which the nodes it was seen on form an
+    /// uninterrupted run. Reads with the same compatible paths form a compatibility
+    /// class, and the EM assigns the reads of each class to its paths in proportion to
+    /// their abundance. Reads compatible with no path are not counted. TPM is normalized
+    /// within the section; use [`TSGraph::quantify`] to normalize over a whole file.
+    ///
+    /// [`quantified_paths`]: GraphSection::quantified_paths
+    pub fn quantify(&self, options: &QuantOptions) -> Result<Vec<PathAbundance>> {
+        let paths = self.quantified_paths()?;
+        // position of each node along each path
+        let path_positions: Vec<HashMap<NodeIndex, usize>> = paths
+            .iter()
+            .map(|path| {
+                let mut positions = HashMap::new();
+                for (position, node_idx) in path.nodes.iter().enumerate() {
+                    positions.entry(*node_idx).or_insert(position);
+                }
+                positions
+            })
+            .collect();
+
+        // nodes each read was seen on, reads of different samples kept apart
+        let mut read_nodes: HashMap<ReadKey, Vec<NodeIndex>> = HashMap::new();
+        for node_idx in self._graph.node_indices() {
+            for read in &self._graph[node_idx].reads {
+                read_nodes.entry(read.key()).or_default().push(node_idx);
+            }
+        }
+
+        let mut class_reads: HashMap<Vec<usize>, usize> = HashMap::new();
+        for nodes in read_nodes.values_mut() {
+            nodes.sort();
+            nodes.dedup();
+            let compatible: Vec<usize> = path_positions
+                .iter()
+                .enumerate()
+                .filter(|(_, positions)| is_compatible(nodes, positions))
+                .map(|(idx, _)| idx)
+                .collect();
+            if !compatible.is_empty() {
+                *class_reads.entry(compatible).or_default() += 1;
+            }
+        }
+        let mut classes: Vec<CompatibilityClass> = class_reads
+            .into_iter()
+            .map(|(paths, reads)| CompatibilityClass { paths, reads })
+            .collect();
+        classes.sort_by(|a, b| a.paths.cmp(&b.paths));
+
+        let counts = expectation_maximization(&classes, paths.len(), options);
+        let mut abundances = paths
+            .iter()
+            .zip(counts)
+            .map(|(path, count)| {
+                let mut length = 0;
+                for node_idx in &path.nodes {
+                    let node = self.node_by_idx(*node_idx).with_context(|| {
+                        format!("Node not found for index: {}", node_idx.index())
+                    })?;
+                    length += node
+                        .exons
+                        .exons
+                        .iter()
+                        .map(|exon| exon.end + 1 - exon.start)
+                        .sum::<usize>();
+                }
+                Ok(PathAbundance {
+                    graph_id: self.id.clone(),
+                    path_id: path.id()?,
+                    length,
+                    count,
+                    tpm: 0.0,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        counts_to_tpm(&mut abundances);
+        Ok(abundances)
+    }
+
+    /// Attach `count` and `tpm` attributes to the `P` records of the given paths,
+    /// adding a `P` record for each path that has none
+    pub fn annotate_abundance(&mut self, abundances: &[PathAbundance]) -> Result<()> {
+        let missing: Vec<(BString, Vec<OrientedElement>)> = self
+            .traverse()?
+            .iter()
+            .filter_map(|path| {
+                let id = path.id().ok()?;
+                if self.groups.contains_key(&id)
+                    || !abundances.iter().any(|abundance| abundance.path_id == id)
+                {
+                    return None;
+                }
+                Some((id, self.path_elements(path)))
+            })
+            .collect();
+        for (id, elements) in missing {
+            self.groups.insert(
+                id.clone(),
+                Group::Ordered {
+                    id,
+                    elements,
+                    attributes: HashMap::new(),
+                },
+            );
+        }
+
+        for abundance in abundances {
+            if let Some(Group::Ordered { attributes, .. }) = self.groups.get_mut(&abundance.path_id)
+            {
+                for attr in abundance.attributes() {
+                    attributes.insert(attr.tag.clone(), attr);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Oriented node and edge ids of a path, as listed on its `P` record
+    pub(crate) fn path_elements(&self, path: &TSGPath) -> Vec<OrientedElement> {
+        let mut elements = vec![];
+        for (idx, node_idx) in path.nodes.iter().enumerate() {
+            elements.push(OrientedElement {
+                id: self._graph[*node_idx].id.clone(),
+                orientation: Some(path.node_orientation(idx)),
+            });
+            if let Some(edge_idx) = path.edges.get(idx) {
+                elements.push(OrientedElement {
+                    id: self._graph[*edge_idx].id.clone(),
+                    orientation: Some(Orientation::Forward),
+                });
+            }
+        }
+        elements
+    }
+}
+
+impl TSGraph {
+    /// Estimate the expression of the paths of every graph, with TPM normalized over
+    /// all graphs of the file; see [`GraphSection::quantify`]
+    pub fn quantify(&self, options: &QuantOptions) -> Result<Vec<PathAbundance>> {
+        let mut ids: Vec<&BString> = self.graphs.keys().collect();
+        ids.sort();
+        let mut abundances = vec![];
+        for id in ids {
+            abundances.extend(self.graphs[id].quantify(options)?);
+        }
+        counts_to_tpm(&mut abundances);
+        Ok(abundances)
+    }
+
+    /// Quantify all graphs and attach the `count` and `tpm` attributes to their paths
+    pub fn annotate_abundance(&mut self, options: &QuantOptions) -> Result<Vec<PathAbundance>> {
+        let abundances = self.quantify(options)?;
+        for graph in self.graphs.values_mut() {
+            let graph_abundances: Vec<PathAbundance> = abundances
+                .iter()
+                .filter(|abundance| abundance.graph_id == graph.id)
+                .cloned()
+                .collect();
+            graph.annotate_abundance(&graph_abundances)?;
+        }
+        Ok(abundances)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_em_quantification() -> Result<()> {
+        // r1, r2 only fit t1 and r4-r6 only fit t2, which skips n2; r7-r9 on n3 fit both
+        // and are split 2:3, and r3 fits neither since n4 is on no path
+        let mut tsg = TSGraph::from_str(
+            "G\tg1\n\
+             N\tn1\tchr1:+:1-100\tr1:SO,r2:SO,r4:SO,r5:SO,r6:SO\n\
+             N\tn2\tchr1:+:201-300\tr1:IN,r2:IN\n\
+             N\tn3\tchr1:+:401-500\tr1:SI,r2:SI,r3:SI,r4:SI,r5:SI,r6:SI,r7:SI,r8:SI,r9:SI\n\
+             N\tn4\tchr1:+:51-100\tr3:SO\n\
+             E\te1\tn1\tn2\tchr1,chr1,100,201,SPLICE\n\
+             E\te2\tn2\tn3\tchr1,chr1,300,401,SPLICE\n\
+             E\te3\tn1\tn3\tchr1,chr1,100,401,SPLICE\n\
+             E\te4\tn4\tn3\tchr1,chr1,100,401,SPLICE\n\
+             P\tt1\tn1+\te1+\tn2+\te2+\tn3+\n\
+             P\tt2\tn1+\te3+\tn3+\n",
+        )?;
+        let abundances = tsg.quantify(&QuantOptions::default())?;
+        assert_eq!(abundances.len(), 2);
+        assert_eq!(abundances[0].path_id, "t1");
+        assert_eq!(abundances[0].length, 300);
+        assert!((abundances[0].count - 3.2).abs() < 1e-4);
+        assert!((abundances[1].count - 4.8).abs() < 1e-4);
+        let total_tpm: f64 = abundances.iter().map(|a| a.tpm).sum();
+        assert!((total_tpm - 1e6).abs() < 1e-3);
+
+        tsg.annotate_abundance(&QuantOptions::default())?;
+        let Some(Group::Ordered { attributes, .. }) =
+            tsg.graph("g1").unwrap().groups.get(&BString::from("t2"))
+        else {
+            panic!("t2 is not a path");
+        };
+        assert_eq!(attributes[&BString::from("count")].value, "4.8000");
+
+        // without P records the traversed paths are quantified and recorded
+        let mut tsg = TSGraph::from_file("tests/data/test_mul.tsg")?;
+        for graph in tsg.graphs.values_mut() {
+            graph.groups.clear();
+        }
+        let abundances = tsg.annotate_abundance(&QuantOptions::default())?;
+        let graph = tsg.graph("gene_a").unwrap();
+        assert_eq!(graph.ordered_paths()?.len(), graph.traverse()?.len());
+        assert_eq!(
+            abundances.len(),
+            tsg.graphs.values().map(|g| g.groups.len()).sum::<usize>()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_quantify_shared_read_ids() -> Result<()> {
+        // r1 of s1 fits t1 and r1 of s2 fits t2; as one read it would fit neither
+        let tsg = TSGraph::from_str(
+            "H\tsample\ts1\n\
+             H\tsample\ts2\n\
+             G\tg1\n\
+             N\tn1\tchr1:+:1-100\tr1:SO:s1,r1:SO:s2\n\
+             N\tn2\tchr1:+:201-300\tr1:SI:s1\n\
+             N\tn3\tchr1:+:401-500\tr1:SI:s2\n\
+             E\te1\tn1\tn2\tchr1,chr1,100,201,SPLICE\n\
+             E\te2\tn1\tn3\tchr1,chr1,100,401,SPLICE\n\
+             P\tt1\tn1+\te1+\tn2+\n\
+             P\tt2\tn1+\te2+\tn3+\n",
+        )?;
+        let abundances = tsg.quantify(&QuantOptions::default())?;
+        let counts: Vec<f64> = abundances.iter().map(|a| a.count).collect();
+        assert_eq!(counts, vec![1.0, 1.0]);
+        Ok(())
+    }
+}
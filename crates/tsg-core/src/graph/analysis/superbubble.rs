@@ -0,0 +1,312 @@
+use crate::graph::GraphSection;
+use bstr::{BString, ByteSlice};
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+use serde_json::json;
+
+/// A superbubble: a subgraph entered only through its entrance and left only through its
+/// exit, whose inner nodes are all reachable from the entrance and all reach the exit.
+///
+/// Only superbubbles with at least two alternative paths are reported, so simple chains
+/// of nodes are not bubbles.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Superbubble {
+    /// `SB{n}`, numbered in topological order of the entrances
+    pub id: BString,
+    pub entrance: NodeIndex,
+    pub exit: NodeIndex,
+    /// Nodes strictly between the entrance and the exit, in topological order
+    pub inner: Vec<NodeIndex>,
+    /// Index of the smallest bubble containing this one
+    pub parent: Option<usize>,
+    /// Indices of the bubbles directly nested in this one
+    pub children: Vec<usize>,
+    /// Number of bubbles containing this one
+    pub depth: usize,
+}
+
+impl Superbubble {
+    /// Whether `node` is the entrance, the exit or an inner node of the bubble
+    pub fn contains(&self, node: NodeIndex) -> bool {
+        node == self.entrance || node == self.exit || self.inner.contains(&node)
+    }
+}
+
+/// Order of the nodes in reverse DFS post-order, in which the nodes of every superbubble
+/// are contiguous. Components without a node of in-degree 0 are entered from their first node.
+fn dfs_order(graph: &GraphSection) -> Vec<NodeIndex> {
+    let g = &graph._graph;
+    let mut visited = vec![false; g.node_count()];
+    let mut postorder = Vec::with_capacity(g.node_count());
+
+    let sources = g.node_indices().filter(|&idx| {
+        g.edges_directed(idx, petgraph::Direction::Incoming)
+            .next()
+            .is_none()
+    });
+    let starts: Vec<NodeIndex> = sources.chain(g.node_indices()).collect();
+
+    for start in starts {
+        if visited[start.index()] {
+            continue;
+        }
+        visited[start.index()] = true;
+        let mut stack = vec![(start, g.neighbors(start).detach())];
+        while let Some((node, neighbors)) = stack.last_mut() {
+            let node = *node;
+            match neighbors.next_node(g) {
+                Some(next) if !visited[next.index()] => {
+                    visited[next.index()] = true;
+                    stack.push((next, g.neighbors(next).detach()));
+                }
+                Some(_) => {}
+                None => {
+                    postorder.push(node);
+                    stack.pop();
+                }
+            }
+        }
+    }
+    postorder.reverse();
+    postorder
+}
+
+impl GraphSection {
+    /// Find the superbubbles of the section and their nesting tree in linear time.
+    ///
+    /// Nodes are ordered by a DFS-based topological sort, in which every superbubble is a
+    /// contiguous range whose nodes have no parent before the entrance and no child after
+    /// the exit. The smallest exit of each candidate entrance is found by jumping over
+    /// the ranges already closed by the nodes after it, so every node is jumped over once.
+    /// Edges going back in the order, i.e. cycles, are not allowed in a superbubble, and
+    /// bubbles inside strongly connected parts of cyclic graphs may be missed.
+    pub(crate) fn find_superbubbles(&self) -> Vec<Superbubble> {
+        let g = &self._graph;
+        let order = dfs_order(self);
+        let n = order.len();
+        let mut rank = vec![0; n];
+        for (i, node) in order.iter().enumerate() {
+            rank[node.index()] = i;
+        }
+
+        // last child and first parent of each node by rank; n and -1 stand for a virtual
+        // sink after the sinks and a virtual source before the sources
+        let mut max_child = vec![n; n];
+        let mut min_parent = vec![-1isize; n];
+        let mut branching = vec![0usize; n + 1];
+        let mut back_sources = vec![0usize; n + 1];
+        let mut back_targets = vec![0usize; n + 1];
+        for (i, node) in order.iter().enumerate() {
+            let mut children = 0;
+            let mut last = None;
+            for edge in g.edges(*node) {
+                let target = rank[edge.target().index()];
+                if target <= i {
+                    back_sources[i + 1] += 1;
+                    back_targets[target + 1] += 1;
+                    continue;
+                }
+                children += 1;
+                last = last.max(Some(target));
+            }
+            if let Some(last) = last {
+                max_child[i] = last;
+            }
+            branching[i + 1] = usize::from(children >= 2);
+
+            let first = g
+                .edges_directed(*node, petgraph::Direction::Incoming)
+                .map(|edge| rank[edge.source().index()])
+                .filter(|&source| source < i)
+                .min();
+            if let Some(first) = first {
+                min_parent[i] = first as isize;
+            }
+        }
+        for i in 0..n {
+            branching[i + 1] += branching[i];
+            back_sources[i + 1] += back_sources[i];
+            back_targets[i + 1] += back_targets[i];
+        }
+
+        // reach[i]: smallest j such that no node in i..j has a child after j;
+        // first[i]: first parent of the nodes in i..reach[i]
+        let mut reach = vec![n; n];
+        let mut first = vec![-1isize; n];
+        let mut ranges = vec![];
+        for i in (0..n).rev() {
+            let mut exit = max_child[i];
+            let mut first_parent = isize::MAX;
+            let mut k = i + 1;
+            while k < exit {
+                exit = exit.max(reach[k]);
+                first_parent = first_parent.min(first[k]);
+                k = reach[k];
+            }
+            reach[i] = exit;
+            first[i] = first_parent.min(min_parent[i]);
+
+            if exit == n {
+                continue;
+            }
+            let closed = first_parent >= i as isize && min_parent[exit] >= i as isize;
+            let acyclic = back_sources[exit] == back_sources[i]
+                && back_targets[exit + 1] == back_targets[i + 1]
+                && !g.contains_edge(order[exit], order[i]);
+            let alternative = branching[exit] > branching[i];
+            if closed && acyclic && alternative {
+                ranges.push((i, exit));
+            }
+        }
+        ranges.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+
+        // bubbles are nested or disjoint, so the enclosing ones form a stack
+        let mut bubbles: Vec<Superbubble> = Vec::with_capacity(ranges.len());
+        let mut enclosing: Vec<usize> = vec![];
+        for (index, (start, end)) in ranges.into_iter().enumerate() {
+            while let Some(&top) = enclosing.last() {
+                if rank[bubbles[top].exit.index()] <= start {
+                    enclosing.pop();
+                } else {
+                    break;
+                }
+            }
+            let parent = enclosing.last().copied();
+            if let Some(parent) = parent {
+                bubbles[parent].children.push(index);
+            }
+            bubbles.push(Superbubble {
+                id: format!("SB{}", index + 1).into(),
+                entrance: order[start],
+                exit: order[end],
+                inner: order[start + 1..end].to_vec(),
+                parent,
+                children: vec![],
+                depth: enclosing.len(),
+            });
+            enclosing.push(index);
+        }
+        bubbles
+    }
+
+    /// Superbubbles of the section as JSON objects with node ids
+    pub(crate) fn superbubbles_to_json(&self, bubbles: &[Superbubble]) -> Vec<serde_json::Value> {
+        let id = |idx: NodeIndex| self._graph[idx].id.to_str_lossy().into_owned();
+        bubbles
+            .iter()
+            .map(|bubble| {
+                json!({
+                    "id": bubble.id.to_str_lossy(),
+                    "entrance": id(bubble.entrance),
+                    "exit": id(bubble.exit),
+                    "inner": bubble.inner.iter().map(|idx| id(*idx)).collect::<Vec<_>>(),
+                    "parent": bubble.parent.map(|parent| bubbles[parent].id.to_str_lossy()),
+                    "depth": bubble.depth,
+                })
+            })
+            .collect()
+    }
+
+    /// Superbubbles as nested DOT clusters of their inner nodes, as named by petgraph
+    pub(crate) fn superbubbles_to_dot(&self, bubbles: &[Superbubble]) -> String {
+        fn cluster(bubbles: &[Superbubble], index: usize, indent: usize, dot: &mut String) {
+            let bubble = &bubbles[index];
+            let pad = "    ".repeat(indent);
+            dot.push_str(&format!("{pad}subgraph cluster_{} {{\n", bubble.id));
+            dot.push_str(&format!("{pad}    label = \"{}\"\n", bubble.id));
+            let nested: Vec<NodeIndex> = bubble
+                .children
+                .iter()
+                .flat_map(|child| bubbles[*child].inner.iter().copied())
+                .collect();
+            for node in bubble.inner.iter().filter(|node| !nested.contains(node)) {
+                dot.push_str(&format!("{pad}    {}\n", node.index()));
+            }
+            for child in &bubble.children {
+                cluster(bubbles, *child, indent + 1, dot);
+            }
+            dot.push_str(&format!("{pad}}}\n"));
+        }
+
+        let mut dot = String::new();
+        for (index, bubble) in bubbles.iter().enumerate() {
+            if bubble.parent.is_none() {
+                cluster(bubbles, index, 1, &mut dot);
+            }
+        }
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{GraphAnalysis, TSGraph};
+    use anyhow::Result;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_nested_superbubbles() -> Result<()> {
+        // n2..n5 is a bubble nested in n1..n6; n6..n8 follows it
+        let tsg = TSGraph::from_str(
+            "N\tn1\tchr1:+:1-10\tr1:SO\n\
+             N\tn2\tchr1:+:20-30\tr1:IN\n\
+             N\tn3\tchr1:+:40-50\tr1:IN\n\
+             N\tn4\tchr1:+:60-70\tr1:IN\n\
+             N\tn5\tchr1:+:80-90\tr1:IN\n\
+             N\tn6\tchr1:+:100-110\tr1:IN\n\
+             N\tn7\tchr1:+:120-130\tr1:IN\n\
+             N\tn8\tchr1:+:140-150\tr1:SI\n\
+             E\te1\tn1\tn2\tchr1,chr1,10,20,SPLICE\n\
+             E\te2\tn1\tn6\tchr1,chr1,10,100,SPLICE\n\
+             E\te3\tn2\tn3\tchr1,chr1,30,40,SPLICE\n\
+             E\te4\tn2\tn4\tchr1,chr1,30,60,SPLICE\n\
+             E\te5\tn3\tn5\tchr1,chr1,50,80,SPLICE\n\
+             E\te6\tn4\tn5\tchr1,chr1,70,80,SPLICE\n\
+             E\te7\tn5\tn6\tchr1,chr1,90,100,SPLICE\n\
+             E\te8\tn6\tn7\tchr1,chr1,110,120,SPLICE\n\
+             E\te9\tn6\tn8\tchr1,chr1,110,140,SPLICE\n\
+             E\te10\tn7\tn8\tchr1,chr1,130,140,SPLICE\n",
+        )?;
+        let graph = tsg.default_graph().unwrap();
+        let id = |idx: NodeIndex| graph.node_by_idx(idx).unwrap().id.to_string();
+
+        let bubbles = graph.superbubbles()?;
+        let summary: Vec<(String, String, usize, Option<usize>)> = bubbles
+            .iter()
+            .map(|b| (id(b.entrance), id(b.exit), b.inner.len(), b.parent))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("n1".to_string(), "n6".to_string(), 4, None),
+                ("n2".to_string(), "n5".to_string(), 2, Some(0)),
+                ("n6".to_string(), "n8".to_string(), 1, None),
+            ]
+        );
+        assert_eq!(bubbles[0].children, vec![1]);
+        assert_eq!(bubbles[1].depth, 1);
+
+        let json = graph.to_json()?;
+        assert_eq!(json["bubbles"][1]["parent"], "SB1");
+        let dot = graph.to_dot(true, true)?;
+        assert!(dot.starts_with("digraph {\n"));
+        assert!(dot.contains("subgraph cluster_SB2"));
+        assert!(dot.ends_with("    }\n}\n"));
+
+        // cycles break superbubbles
+        let cyclic = TSGraph::from_str(
+            "N\tn1\tchr1:+:1-10\tr1:SO\n\
+             N\tn2\tchr1:+:20-30\tr1:IN\n\
+             N\tn3\tchr1:+:40-50\tr1:IN\n\
+             N\tn4\tchr1:+:60-70\tr1:SI\n\
+             E\te1\tn1\tn2\tchr1,chr1,10,20,SPLICE\n\
+             E\te2\tn1\tn3\tchr1,chr1,10,40,SPLICE\n\
+             E\te3\tn2\tn4\tchr1,chr1,30,60,SPLICE\n\
+             E\te4\tn3\tn4\tchr1,chr1,50,60,SPLICE\n\
+             E\te5\tn4\tn2\tchr1,chr1,70,20,DUP\n",
+        )?;
+        assert!(cyclic.default_graph().unwrap().superbubbles()?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,355 @@
+use crate::graph::{Attribute, GraphSection, Group, ReadData, ReadKey, SplicingEvent, TSGPath};
+use ahash::{HashMap, HashMapExt, HashSet, HashSetExt};
+use anyhow::{Context, Result, anyhow};
+use bstr::BString;
+use petgraph::graph::{EdgeIndex, NodeIndex};
+
+/// Read support of a junction or a path.
+///
+/// Reads are tracked per node, so a read supports a junction when it is found on both
+/// the source and the target node, and a path when it is found on all of its nodes.
+/// Reads are told apart by their ID and sample, see [`ReadKey`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReadSupport {
+    /// Number of supporting reads (`SR`)
+    pub sr: usize,
+    /// Number of reads at the first breakpoint, i.e. on the source or first node (`DP1`)
+    pub dp1: usize,
+    /// Number of reads at the second breakpoint, i.e. on the target or last node (`DP2`)
+    pub dp2: usize,
+    /// Supporting reads over all reads touching the junction or path (`PSI`), or `None`
+    /// when no read touches it
+    pub psi: Option<f64>,
+}
+
+impl ReadSupport {
+    /// `SR`, `DP1`, `DP2` and, when defined, `PSI` attributes
+    pub fn attributes(&self) -> Vec<Attribute> {
+        let int = |tag: &str, value: usize| {
+            Attribute::builder()
+                .tag(tag)
+                .attribute_type('i')
+                .value(value.to_string())
+                .build()
+        };
+        let mut attributes = vec![
+            int("SR", self.sr),
+            int("DP1", self.dp1),
+            int("DP2", self.dp2),
+        ];
+        if let Some(psi) = self.psi {
+            attributes.push(psi_attribute(psi));
+        }
+        attributes
+    }
+}
+
+/// Inclusion and exclusion read counts of a splicing event
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EventSupport {
+    pub inclusion: usize,
+    pub exclusion: usize,
+    /// `inclusion / (inclusion + exclusion)`, or `None` without any informative read
+    pub psi: Option<f64>,
+}
+
+fn psi_attribute(psi: f64) -> Attribute {
+    Attribute::builder()
+        .tag("PSI")
+        .attribute_type('f')
+        .value(format_psi(psi))
+        .build()
+}
+
+/// Format a PSI value with at most four decimals
+pub fn format_psi(psi: f64) -> String {
+    let formatted = format!("{:.4}", psi);
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+fn ratio(part: usize, total: usize) -> Option<f64> {
+    (total > 0).then(|| part as f64 / total as f64)
+}
+
+impl GraphSection {
+    fn read_keys(&self, node_idx: NodeIndex) -> Result<HashSet<ReadKey<'_>>> {
+        let node = self
+            .node_by_idx(node_idx)
+            .with_context(|| format!("Node not found for index: {}", node_idx.index()))?;
+        Ok(node.reads.iter().map(ReadData::key).collect())
+    }
+
+    /// Reads found on every node, minus those found on any of the `excluded` nodes
+    fn reads_through(
+        &self,
+        nodes: &[NodeIndex],
+        excluded: &[NodeIndex],
+    ) -> Result<HashSet<ReadKey<'_>>> {
+        let Some((first, rest)) = nodes.split_first() else {
+            return Ok(HashSet::new());
+        };
+        let mut reads = self.read_keys(*first)?;
+        for node_idx in rest {
+            let node_reads = self.read_keys(*node_idx)?;
+            reads.retain(|read| node_reads.contains(read));
+        }
+        for node_idx in excluded {
+            let node_reads = self.read_keys(*node_idx)?;
+            reads.retain(|read| !node_reads.contains(read));
+        }
+        Ok(reads)
+    }
+
+    /// Read support of the nodes of a path, see [`ReadSupport`]
+    fn nodes_support(&self, nodes: &[NodeIndex]) -> Result<ReadSupport> {
+        let (Some(first), Some(last)) = (nodes.first(), nodes.last()) else {
+            return Err(anyhow!("No nodes to compute read support for"));
+        };
+        let sr = self.reads_through(nodes, &[])?.len();
+        let mut touching = HashSet::new();
+        for node_idx in nodes {
+            touching.extend(self.read_keys(*node_idx)?);
+        }
+        Ok(ReadSupport {
+            sr,
+            dp1: self.read_keys(*first)?.len(),
+            dp2: self.read_keys(*last)?.len(),
+            psi: ratio(sr, touching.len()),
+        })
+    }
+
+    /// Ids of the reads supporting an edge, i.e. found on both its source and target node;
+    /// an ID shared by reads of several samples is listed once per sample
+    pub fn edge_supporting_reads(&self, edge_idx: EdgeIndex) -> Result<Vec<BString>> {
+        let (source, target) = self
+            .edge_endpoints(edge_idx)
+            .with_context(|| format!("Edge not found for index: {}", edge_idx.index()))?;
+        let mut reads: Vec<BString> = self
+            .reads_through(&[source, target], &[])?
+            .into_iter()
+            .map(|(id, _)| id.clone())
+            .collect();
+        reads.sort();
+        Ok(reads)
+    }
+
+    /// Number of reads of each sample found on every node, by the samples of the reads
+    /// on the first node; reads without a sample are not counted
+    fn nodes_sample_support(&self, nodes: &[NodeIndex]) -> Result<HashMap<BString, usize>> {
+        let Some(first) = nodes.first() else {
+            return Ok(HashMap::new());
+        };
+        let through = self.reads_through(nodes, &[])?;
+        let node = self
+            .node_by_idx(*first)
+            .with_context(|| format!("Node not found for index: {}", first.index()))?;
+        let mut reads: HashMap<&BString, HashSet<&BString>> = HashMap::new();
+        for read in node
+            .reads
+            .iter()
+            .filter(|read| through.contains(&read.key()))
+        {
+            if let Some(sample) = &read.sample {
+                reads.entry(sample).or_default().insert(&read.id);
+            }
+        }
+        Ok(reads
+            .into_iter()
+            .map(|(sample, ids)| (sample.clone(), ids.len()))
+            .collect())
+    }
+
+    /// Number of reads of each sample supporting an edge, i.e. found on both its source
+    /// and target node
+    pub fn edge_sample_support(&self, edge_idx: EdgeIndex) -> Result<HashMap<BString, usize>> {
+        let (source, target) = self
+            .edge_endpoints(edge_idx)
+            .with_context(|| format!("Edge not found for index: {}", edge_idx.index()))?;
+        self.nodes_sample_support(&[source, target])
+    }
+
+    /// Number of reads of each sample found on all nodes of a path
+    pub fn path_sample_support(&self, path: &TSGPath) -> Result<HashMap<BString, usize>> {
+        self.nodes_sample_support(&path.nodes)
+    }
+
+    /// Junction read support of an edge; its PSI is the fraction of the reads on either
+    /// end of the junction that go through it
+    pub fn edge_support(&self, edge_idx: EdgeIndex) -> Result<ReadSupport> {
+        let (source, target) = self
+            .edge_endpoints(edge_idx)
+            .with_context(|| format!("Edge not found for index: {}", edge_idx.index()))?;
+        self.nodes_support(&[source, target])
+    }
+
+    /// Read support of a path; its PSI is the fraction of the reads touching the path
+    /// that go through all of its nodes
+    pub fn path_support(&self, path: &TSGPath) -> Result<ReadSupport> {
+        self.nodes_support(&path.nodes)
+    }
+
+    /// Inclusion and exclusion read counts and PSI of a splicing event.
+    ///
+    /// A read supports a form of the event when it is found on all nodes of that form
+    /// and on none of the nodes that only belong to the other form.
+    pub fn event_support(&self, event: &SplicingEvent) -> Result<EventSupport> {
+        let indices = |ids: &[BString]| -> Result<Vec<NodeIndex>> {
+            ids.iter()
+                .map(|id| {
+                    self.node_indices
+                        .get(id)
+                        .copied()
+                        .ok_or_else(|| anyhow!("Node {} not found in graph {}", id, self.id))
+                })
+                .collect()
+        };
+        let inclusion = indices(&event.inclusion_nodes)?;
+        let exclusion = indices(&event.exclusion_nodes)?;
+        let only = |nodes: &[NodeIndex], other: &[NodeIndex]| -> Vec<NodeIndex> {
+            nodes
+                .iter()
+                .filter(|idx| !other.contains(idx))
+                .copied()
+                .collect()
+        };
+
+        let inclusion_reads = self
+            .reads_through(&inclusion, &only(&exclusion, &inclusion))?
+            .len();
+        let exclusion_reads = self
+            .reads_through(&exclusion, &only(&inclusion, &exclusion))?
+            .len();
+        Ok(EventSupport {
+            inclusion: inclusion_reads,
+            exclusion: exclusion_reads,
+            psi: ratio(inclusion_reads, inclusion_reads + exclusion_reads),
+        })
+    }
+
+    /// Attach `SR`, `DP1`, `DP2` and `PSI` attributes to every edge and every `P` record
+    pub fn annotate_read_support(&mut self) -> Result<()> {
+        let mut edge_attributes = vec![];
+        for edge_idx in self._graph.edge_indices() {
+            edge_attributes.push((edge_idx, self.edge_support(edge_idx)?.attributes()));
+        }
+        let mut path_attributes = vec![];
+        for path in self.ordered_paths()? {
+            path_attributes.push((path.id()?, self.path_support(&path)?.attributes()));
+        }
+
+        for (edge_idx, attributes) in edge_attributes {
+            let edge = self._graph.edge_weight_mut(edge_idx).unwrap();
+            for attr in attributes {
+                edge.attributes.insert(attr.tag.clone(), attr);
+            }
+        }
+        for (id, support) in path_attributes {
+            if let Some(Group::Ordered { attributes, .. }) = self.groups.get_mut(&id) {
+                for attr in support {
+                    attributes.insert(attr.tag.clone(), attr);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TSGPath<'_> {
+    /// Number of reads of each sample supporting the path, see
+    /// [`GraphSection::path_sample_support`]
+    pub fn sample_support(&self) -> Result<HashMap<BString, usize>> {
+        self.graph()
+            .context("Path is not attached to a graph")?
+            .path_sample_support(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::TSGraph;
+    use bstr::ByteSlice;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_read_support_and_psi() -> Result<()> {
+        // r1, r2 and r3 include n2; r4 skips it
+        let mut tsg = TSGraph::from_str(
+            "G\tg1\n\
+             N\tn1\tchr1:+:100-200\tr1:SO,r2:SO,r3:SO,r4:SO\n\
+             N\tn2\tchr1:+:300-400\tr1:IN,r2:IN,r3:IN\n\
+             N\tn3\tchr1:+:500-600\tr1:SI,r2:SI,r4:SI\n\
+             E\te1\tn1\tn2\tchr1,chr1,200,300,SPLICE\n\
+             E\te2\tn2\tn3\tchr1,chr1,400,500,SPLICE\n\
+             E\te3\tn1\tn3\tchr1,chr1,200,500,SPLICE\n\
+             P\tt1\tn1+\te1+\tn2+\te2+\tn3+\n",
+        )?;
+        let graph = tsg.graph("g1").unwrap();
+
+        let e2 = graph.edge_indices[&BString::from("e2")];
+        assert_eq!(graph.edge_supporting_reads(e2)?, vec!["r1", "r2"]);
+        let support = graph.edge_support(e2)?;
+        assert_eq!((support.sr, support.dp1, support.dp2), (2, 3, 3));
+        assert_eq!(support.psi, Some(0.5));
+
+        let events = graph.splicing_events()?;
+        assert_eq!(events.len(), 1);
+        let event_support = graph.event_support(&events[0])?;
+        assert_eq!((event_support.inclusion, event_support.exclusion), (2, 1));
+        assert_eq!(format_psi(event_support.psi.unwrap()), "0.6667");
+
+        let path = graph.path_from_group("t1")?;
+        assert_eq!(graph.path_support(&path)?.sr, 2);
+
+        let graph = tsg.graphs.values_mut().next().unwrap();
+        graph.annotate_read_support()?;
+        let e3 = graph.edge_by_id("e3").unwrap();
+        assert_eq!(e3.attributes[&BString::from("SR")].value, "3");
+        assert_eq!(e3.attributes[&BString::from("PSI")].value, "0.75");
+        let Some(Group::Ordered { attributes, .. }) = graph.groups.get(b"t1".as_bstr()) else {
+            panic!("t1 is not a path");
+        };
+        assert_eq!(attributes[&BString::from("SR")].value, "2");
+        Ok(())
+    }
+
+    #[test]
+    fn test_sample_support() -> Result<()> {
+        let tsg = TSGraph::from_str(
+            "H\tsample\ttumor\n\
+             H\tsample\tnormal\n\
+             G\tg1\n\
+             N\tn1\tchr1:+:100-200\tr1:SO:tumor,r2:SO:tumor,r3:SO:normal,r4:SO\n\
+             N\tn2\tchr1:+:300-400\tr1:SI:tumor,r3:SI:normal,r4:SI\n\
+             E\te1\tn1\tn2\tchr1,chr1,200,300,SPLICE\n",
+        )?;
+        assert_eq!(tsg.samples(), vec!["tumor", "normal"]);
+        let graph = tsg.graph("g1").unwrap();
+
+        let n1 = graph.node_by_id("n1").unwrap();
+        assert_eq!(n1.reads[0].sample.as_ref().unwrap(), "tumor");
+        assert_eq!(n1.reads[0].to_string(), "r1:SO:tumor");
+        assert_eq!(n1.reads[3].sample, None);
+        let support = n1.sample_support();
+        assert_eq!(
+            (support[b"tumor".as_bstr()], support[b"normal".as_bstr()]),
+            (2, 1)
+        );
+
+        // r2 stops at n1 and r4 has no sample
+        let e1 = graph.edge_indices[&BString::from("e1")];
+        let support = graph.edge_sample_support(e1)?;
+        assert_eq!(support.len(), 2);
+        assert_eq!(
+            (support[b"tumor".as_bstr()], support[b"normal".as_bstr()]),
+            (1, 1)
+        );
+
+        let path = graph.traverse()?.remove(0);
+        assert_eq!(path.sample_support()?, support);
+        Ok(())
+    }
+}
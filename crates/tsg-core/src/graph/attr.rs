@@ -140,6 +140,71 @@ impl Attribute {
         serde_json::from_str(self.value.to_str()?)
             .map_err(|e| anyhow!("Failed to parse JSON: {}", e))
     }
+
+    /// Convert the attribute value to a JSON value
+    ///
+    /// Integers and floats become JSON numbers, strings ('Z') JSON strings and 'J'
+    /// objects or arrays are embedded as parsed JSON. Every other value is written as
+    /// `{"type": <type>, "value": <value>}` so that [`Attribute::from_json_value`]
+    /// restores its declared type.
+    pub fn to_json_value(&self) -> Result<serde_json::Value> {
+        let typed = || -> Result<serde_json::Value> {
+            Ok(serde_json::json!({
+                "type": self.attribute_type.to_string(),
+                "value": self.value.to_str()?,
+            }))
+        };
+        Ok(match self.attribute_type {
+            'i' => self.as_int()?.into(),
+            'f' => self.as_float()?.into(),
+            'Z' => self.value.to_str()?.into(),
+            'J' => {
+                let json = self.as_json()?;
+                if (json.is_object() || json.is_array()) && typed_json_value(&json).is_none() {
+                    json
+                } else {
+                    typed()?
+                }
+            }
+            _ => typed()?,
+        })
+    }
+
+    /// Create an attribute from a JSON value written by [`Attribute::to_json_value`]
+    ///
+    /// `{"type", "value"}` objects keep their type. Otherwise the type is inferred:
+    /// integers become 'i', other numbers 'f', strings and booleans 'Z', and objects
+    /// or arrays 'J'. Returns `None` for JSON `null`.
+    pub fn from_json_value(tag: &str, value: &serde_json::Value) -> Option<Self> {
+        let (attribute_type, value) = match value {
+            serde_json::Value::Null => return None,
+            serde_json::Value::Number(n) if n.is_i64() || n.is_u64() => ('i', n.to_string()),
+            serde_json::Value::Number(n) => ('f', n.to_string()),
+            serde_json::Value::String(s) => ('Z', s.clone()),
+            serde_json::Value::Bool(b) => ('Z', b.to_string()),
+            other => match typed_json_value(other) {
+                Some((attribute_type, value)) => (attribute_type, value.to_string()),
+                None => ('J', other.to_string()),
+            },
+        };
+        Some(Attribute {
+            tag: tag.into(),
+            attribute_type,
+            value: value.into(),
+        })
+    }
+}
+
+/// Split a `{"type": <type>, "value": <value>}` JSON object into the attribute type
+/// and value
+fn typed_json_value(json: &serde_json::Value) -> Option<(char, &str)> {
+    let object = json.as_object().filter(|object| object.len() == 2)?;
+    let mut attribute_type = object.get("type")?.as_str()?.chars();
+    let value = object.get("value")?.as_str()?;
+    match (attribute_type.next(), attribute_type.next()) {
+        (Some(attribute_type), None) => Some((attribute_type, value)),
+        _ => None,
+    }
 }
 
 impl fmt::Display for Attribute {
@@ -176,6 +241,24 @@ mod tests {
         assert_eq!(attr.value, "{\"key\":\"value\"}");
     }
 
+    #[test]
+    fn test_attribute_json_value() -> Result<()> {
+        for attr in [
+            "ptc:i:10",
+            "ptf:f:0.5",
+            "name:Z:test",
+            "data:J:{\"key\":[1,2]}",
+        ] {
+            let attr = Attribute::from_str(attr)?;
+            let value = attr.to_json_value()?;
+            let parsed = Attribute::from_json_value("tag", &value).unwrap();
+            assert_eq!(parsed.attribute_type, attr.attribute_type);
+            assert_eq!(parsed.value, attr.value);
+        }
+        assert!(Attribute::from_json_value("tag", &serde_json::Value::Null).is_none());
+        Ok(())
+    }
+
     #[test]
     fn test_attribute_from_str_invalid_format() {
         let result = Attribute::from_str("ptc:i");
@@ -298,4 +381,33 @@ mod tests {
             .build();
         assert_eq!(attr.to_string(), "ptf:f:0.5");
     }
+
+    #[test]
+    fn test_attribute_json_round_trip() -> Result<()> {
+        for attr in ["n:i:3", "x:f:0.5", "s:Z:text", "h:H:1AE3", "b:B:i,1,2"] {
+            let attr = Attribute::from_str(attr)?;
+            let json = attr.to_json_value()?;
+            let restored = Attribute::from_json_value(attr.tag.to_str()?, &json).unwrap();
+            assert_eq!(restored.to_string(), attr.to_string());
+        }
+        for value in [
+            r#"{"key":"value"}"#,
+            "[1,2]",
+            "5",
+            r#"{"type":"A","value":"x"}"#,
+        ] {
+            let attr = Attribute::builder()
+                .tag("j")
+                .attribute_type('J')
+                .value(value)
+                .build();
+            let restored = Attribute::from_json_value("j", &attr.to_json_value()?).unwrap();
+            assert_eq!(restored.attribute_type, 'J');
+            assert_eq!(restored.value, value);
+        }
+
+        let json = Attribute::from_str("h:H:1AE3")?.to_json_value()?;
+        assert_eq!(json, serde_json::json!({"type": "H", "value": "1AE3"}));
+        Ok(())
+    }
 }
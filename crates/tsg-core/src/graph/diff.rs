@@ -0,0 +1,1304 @@
+use std::fmt;
+
+use ahash::{HashMap, HashMapExt, HashSet};
+use anyhow::Result;
+use bon::Builder;
+use bstr::{BString, ByteSlice};
+use petgraph::graph::{EdgeIndex, NodeIndex};
+use serde_json::json;
+
+use super::{Attribute, GraphSection, Group, TSGraph, TraversalOptions};
+
+/// Options of [`TSGraph::diff`]
+#[derive(Debug, Clone, Builder)]
+pub struct DiffOptions {
+    /// Match graphs, nodes and edges left unmatched by ID by their genomic coordinates
+    #[builder(default = true)]
+    pub match_coordinates: bool,
+    /// Compare the traversed paths of matched graphs
+    #[builder(default = true)]
+    pub paths: bool,
+    /// Bounds of the traversal used to compare paths
+    #[builder(default)]
+    pub traversal: TraversalOptions,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+/// How an element differs between the old and the new file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChangeKind {
+    Removed,
+    Added,
+    Modified,
+}
+
+impl ChangeKind {
+    /// Prefix of the change in the human-readable diff
+    pub fn symbol(&self) -> char {
+        match self {
+            ChangeKind::Removed => '-',
+            ChangeKind::Added => '+',
+            ChangeKind::Modified => '~',
+        }
+    }
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangeKind::Removed => write!(f, "removed"),
+            ChangeKind::Added => write!(f, "added"),
+            ChangeKind::Modified => write!(f, "modified"),
+        }
+    }
+}
+
+/// Kind of a changed element, in the order changes are reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ElementKind {
+    Header,
+    Link,
+    Graph,
+    Node,
+    Edge,
+    Group,
+    Chain,
+    Path,
+}
+
+impl fmt::Display for ElementKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ElementKind::Header => "header",
+            ElementKind::Link => "link",
+            ElementKind::Graph => "graph",
+            ElementKind::Node => "node",
+            ElementKind::Edge => "edge",
+            ElementKind::Group => "group",
+            ElementKind::Chain => "chain",
+            ElementKind::Path => "path",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A field of a modified element.
+///
+/// For `reads`, `old` lists the reads only found in the old file and `new` those only
+/// found in the new one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    pub field: BString,
+    pub old: Option<BString>,
+    pub new: Option<BString>,
+}
+
+/// An added, removed or modified element
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElementChange {
+    pub element: ElementKind,
+    pub change: ChangeKind,
+    /// ID in the new file, or in the old file for removed elements
+    pub id: BString,
+    /// ID in the old file when the element was matched by coordinates under another ID
+    pub old_id: Option<BString>,
+    /// Short description of added and removed elements
+    pub summary: Option<BString>,
+    /// Changed fields of modified elements
+    pub fields: Vec<FieldChange>,
+}
+
+impl ElementChange {
+    fn new(element: ElementKind, change: ChangeKind, id: BString) -> Self {
+        Self {
+            element,
+            change,
+            id,
+            old_id: None,
+            summary: None,
+            fields: vec![],
+        }
+    }
+
+    fn with_summary(mut self, summary: impl Into<BString>) -> Self {
+        self.summary = Some(summary.into());
+        self
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "element": self.element.to_string(),
+            "change": self.change.to_string(),
+            "id": self.id.to_str_lossy(),
+            "old_id": self.old_id.as_ref().map(|id| id.to_str_lossy()),
+            "summary": self.summary.as_ref().map(|summary| summary.to_str_lossy()),
+            "fields": self.fields.iter().map(|field| json!({
+                "field": field.field.to_str_lossy(),
+                "old": field.old.as_ref().map(|old| old.to_str_lossy()),
+                "new": field.new.as_ref().map(|new| new.to_str_lossy()),
+            })).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl fmt::Display for ElementChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.change.symbol(), self.element, self.id)?;
+        if let Some(old_id) = &self.old_id {
+            write!(f, " (was {})", old_id)?;
+        }
+        if let Some(summary) = &self.summary {
+            write!(f, " {}", summary)?;
+        }
+        for field in &self.fields {
+            let value = |value: &Option<BString>| {
+                value
+                    .as_ref()
+                    .map_or(".".to_string(), |value| value.to_string())
+            };
+            write!(
+                f,
+                "\n    {}: {} -> {}",
+                field.field,
+                value(&field.old),
+                value(&field.new)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Differences between a graph of the old file and its match in the new file
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphDiff {
+    pub old_id: Option<BString>,
+    pub new_id: Option<BString>,
+    /// Changes of the graph attributes and of its elements
+    pub changes: Vec<ElementChange>,
+    /// Numbers of traversed paths in the old and new graph, when paths are compared
+    pub paths: Option<(usize, usize)>,
+}
+
+impl GraphDiff {
+    pub fn change(&self) -> ChangeKind {
+        match (&self.old_id, &self.new_id) {
+            (None, _) => ChangeKind::Added,
+            (_, None) => ChangeKind::Removed,
+            _ => ChangeKind::Modified,
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "old_id": self.old_id.as_ref().map(|id| id.to_str_lossy()),
+            "new_id": self.new_id.as_ref().map(|id| id.to_str_lossy()),
+            "change": self.change().to_string(),
+            "paths": self.paths.map(|(old, new)| json!({"old": old, "new": new})),
+            "changes": self.changes.iter().map(|change| change.to_json()).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl fmt::Display for GraphDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.old_id, &self.new_id) {
+            (Some(old), Some(new)) if old != new => write!(f, "@@ graph {} -> {} @@", old, new)?,
+            (Some(id), _) | (_, Some(id)) => {
+                write!(f, "@@ graph {} @@", id)?;
+                if self.change() != ChangeKind::Modified {
+                    write!(f, "\n{} graph {}", self.change().symbol(), id)?;
+                }
+            }
+            (None, None) => {}
+        }
+        if let Some((old, new)) = self.paths.filter(|(old, new)| old != new) {
+            write!(f, "\n  paths: {} -> {}", old, new)?;
+        }
+        for change in &self.changes {
+            write!(f, "\n{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Structural differences between two TSG files
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TSGraphDiff {
+    pub headers: Vec<ElementChange>,
+    pub links: Vec<ElementChange>,
+    /// Graphs that were added, removed or have changed, sorted by ID
+    pub graphs: Vec<GraphDiff>,
+}
+
+impl TSGraphDiff {
+    pub fn is_empty(&self) -> bool {
+        self.headers.is_empty() && self.links.is_empty() && self.graphs.is_empty()
+    }
+
+    /// Number of changes of each kind, counting added and removed graphs once
+    pub fn counts(&self) -> HashMap<(ElementKind, ChangeKind), usize> {
+        let mut counts = HashMap::new();
+        let graphs = self
+            .graphs
+            .iter()
+            .map(|graph| (ElementKind::Graph, graph.change()));
+        let elements = self
+            .headers
+            .iter()
+            .chain(&self.links)
+            .chain(self.graphs.iter().flat_map(|graph| &graph.changes))
+            .map(|change| (change.element, change.change));
+        for key in graphs.chain(elements) {
+            *counts.entry(key).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut counts: Vec<_> = self.counts().into_iter().collect();
+        counts.sort();
+        let mut summary = serde_json::Map::new();
+        for ((element, change), count) in counts {
+            summary.insert(format!("{}_{}", element, change), json!(count));
+        }
+        json!({
+            "summary": summary,
+            "headers": self.headers.iter().map(|change| change.to_json()).collect::<Vec<_>>(),
+            "links": self.links.iter().map(|change| change.to_json()).collect::<Vec<_>>(),
+            "graphs": self.graphs.iter().map(|graph| graph.to_json()).collect::<Vec<_>>(),
+        })
+    }
+}
+
+impl fmt::Display for TSGraphDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in self.headers.iter().chain(&self.links) {
+            writeln!(f, "{}", change)?;
+        }
+        for graph in &self.graphs {
+            writeln!(f, "{}", graph)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reference and strand of a node with its exons, used to match nodes across files
+fn node_location(graph: &GraphSection, idx: NodeIndex) -> BString {
+    let node = &graph._graph[idx];
+    format!("{}:{}:{}", node.reference_id, node.strand, node.exons).into()
+}
+
+/// Pair the unmatched graphs of both files by decreasing overlap of their spans
+fn match_graphs_by_overlap(
+    old: &TSGraph,
+    new: &TSGraph,
+    old_ids: &[&BString],
+    new_ids: &[&BString],
+) -> Vec<(BString, BString)> {
+    // spans of the new graphs on each reference, sorted by start
+    let mut new_spans: HashMap<BString, Vec<(usize, usize, usize)>> = HashMap::new();
+    let mut max_span = 0;
+    for (idx, id) in new_ids.iter().enumerate() {
+        for (reference_id, (start, end)) in new.graphs[*id].reference_spans() {
+            max_span = max_span.max(end + 1 - start);
+            new_spans
+                .entry(reference_id)
+                .or_default()
+                .push((start, end, idx));
+        }
+    }
+    for spans in new_spans.values_mut() {
+        spans.sort();
+    }
+
+    let mut pairs = vec![];
+    for (old_idx, id) in old_ids.iter().enumerate() {
+        let mut overlaps: HashMap<usize, usize> = HashMap::new();
+        for (reference_id, (start, end)) in old.graphs[*id].reference_spans() {
+            let Some(spans) = new_spans.get(&reference_id) else {
+                continue;
+            };
+            let first = spans.partition_point(|span| span.0 + max_span < start);
+            for &(new_start, new_end, new_idx) in &spans[first..] {
+                if new_start > end {
+                    break;
+                }
+                if new_end >= start {
+                    *overlaps.entry(new_idx).or_insert(0) +=
+                        end.min(new_end) + 1 - start.max(new_start);
+                }
+            }
+        }
+        pairs.extend(
+            overlaps
+                .into_iter()
+                .map(|(new_idx, overlap)| (overlap, old_idx, new_idx)),
+        );
+    }
+    pairs.sort_by(|a, b| b.0.cmp(&a.0).then((a.1, a.2).cmp(&(b.1, b.2))));
+
+    let mut used_old = vec![false; old_ids.len()];
+    let mut used_new = vec![false; new_ids.len()];
+    let mut matches = vec![];
+    for (_, old_idx, new_idx) in pairs {
+        if used_old[old_idx] || used_new[new_idx] {
+            continue;
+        }
+        used_old[old_idx] = true;
+        used_new[new_idx] = true;
+        matches.push((old_ids[old_idx].clone(), new_ids[new_idx].clone()));
+    }
+    matches
+}
+
+fn attribute_changes(
+    old: &HashMap<BString, Attribute>,
+    new: &HashMap<BString, Attribute>,
+    fields: &mut Vec<FieldChange>,
+) {
+    let mut tags: Vec<&BString> = old.keys().chain(new.keys()).collect();
+    tags.sort();
+    tags.dedup();
+    for tag in tags {
+        let (old, new) = (
+            old.get(tag).map(|attr| attr.to_string()),
+            new.get(tag).map(|attr| attr.to_string()),
+        );
+        if old != new {
+            fields.push(FieldChange {
+                field: format!("attributes.{}", tag).into(),
+                old: old.map(Into::into),
+                new: new.map(Into::into),
+            });
+        }
+    }
+}
+
+fn field_change(fields: &mut Vec<FieldChange>, field: &str, old: BString, new: BString) {
+    if old != new {
+        fields.push(FieldChange {
+            field: field.into(),
+            old: Some(old),
+            new: Some(new),
+        });
+    }
+}
+
+/// Matching between the elements of two versions of a graph
+struct SectionMatcher<'a> {
+    old: &'a GraphSection,
+    new: &'a GraphSection,
+    nodes: HashMap<NodeIndex, NodeIndex>,
+    edges: HashMap<EdgeIndex, EdgeIndex>,
+    /// New IDs of the matched old elements
+    renames: HashMap<BString, BString>,
+}
+
+impl<'a> SectionMatcher<'a> {
+    fn new(old: &'a GraphSection, new: &'a GraphSection, match_coordinates: bool) -> Self {
+        let mut matcher = Self {
+            old,
+            new,
+            nodes: HashMap::new(),
+            edges: HashMap::new(),
+            renames: HashMap::new(),
+        };
+        matcher.match_nodes(match_coordinates);
+        matcher.match_edges(match_coordinates);
+        matcher
+    }
+
+    fn match_nodes(&mut self, match_coordinates: bool) {
+        for (id, &old_idx) in &self.old.node_indices {
+            if let Some(&new_idx) = self.new.node_indices.get(id) {
+                self.nodes.insert(old_idx, new_idx);
+            }
+        }
+        if !match_coordinates {
+            return;
+        }
+
+        let matched: HashSet<NodeIndex> = self.nodes.values().copied().collect();
+        let mut locations: HashMap<BString, Vec<NodeIndex>> = HashMap::new();
+        for &new_idx in self.new.node_indices.values() {
+            if !matched.contains(&new_idx) {
+                locations
+                    .entry(node_location(self.new, new_idx))
+                    .or_default()
+                    .push(new_idx);
+            }
+        }
+        for candidates in locations.values_mut() {
+            candidates.sort_by_key(|idx| std::cmp::Reverse(self.new._graph[*idx].id.clone()));
+        }
+        let mut unmatched: Vec<(&BString, NodeIndex)> = self
+            .old
+            .node_indices
+            .iter()
+            .filter(|(_, idx)| !self.nodes.contains_key(idx))
+            .map(|(id, idx)| (id, *idx))
+            .collect();
+        unmatched.sort();
+        for (id, old_idx) in unmatched {
+            let location = node_location(self.old, old_idx);
+            if let Some(new_idx) = locations.get_mut(&location).and_then(|c| c.pop()) {
+                self.nodes.insert(old_idx, new_idx);
+                self.renames
+                    .insert(id.clone(), self.new._graph[new_idx].id.clone());
+            }
+        }
+    }
+
+    fn match_edges(&mut self, match_coordinates: bool) {
+        for (id, &old_idx) in &self.old.edge_indices {
+            if let Some(&new_idx) = self.new.edge_indices.get(id) {
+                self.edges.insert(old_idx, new_idx);
+            }
+        }
+        if !match_coordinates {
+            return;
+        }
+
+        // unmatched edges are matched by their endpoints and structural variant
+        let matched: HashSet<EdgeIndex> = self.edges.values().copied().collect();
+        let mut keys: HashMap<(NodeIndex, NodeIndex, BString), Vec<EdgeIndex>> = HashMap::new();
+        for &new_idx in self.new.edge_indices.values() {
+            if matched.contains(&new_idx) {
+                continue;
+            }
+            let (source, sink) = self.new._graph.edge_endpoints(new_idx).unwrap();
+            let sv = self.new._graph[new_idx].sv.to_string().into();
+            keys.entry((source, sink, sv)).or_default().push(new_idx);
+        }
+        let mut unmatched: Vec<(&BString, EdgeIndex)> = self
+            .old
+            .edge_indices
+            .iter()
+            .filter(|(_, idx)| !self.edges.contains_key(idx))
+            .map(|(id, idx)| (id, *idx))
+            .collect();
+        unmatched.sort();
+        for (id, old_idx) in unmatched {
+            let (source, sink) = self.old._graph.edge_endpoints(old_idx).unwrap();
+            let (Some(&source), Some(&sink)) = (self.nodes.get(&source), self.nodes.get(&sink))
+            else {
+                continue;
+            };
+            let key = (source, sink, self.old._graph[old_idx].sv.to_string().into());
+            if let Some(new_idx) = keys.get_mut(&key).and_then(|c| c.pop()) {
+                self.edges.insert(old_idx, new_idx);
+                self.renames
+                    .insert(id.clone(), self.new._graph[new_idx].id.clone());
+            }
+        }
+    }
+
+    fn rename(&self, id: &BString) -> BString {
+        self.renames.get(id).unwrap_or(id).clone()
+    }
+
+    fn node_changes(&self, changes: &mut Vec<ElementChange>) {
+        let matched: HashSet<NodeIndex> = self.nodes.values().copied().collect();
+        for (id, &old_idx) in &self.old.node_indices {
+            if !self.nodes.contains_key(&old_idx) {
+                changes.push(
+                    ElementChange::new(ElementKind::Node, ChangeKind::Removed, id.clone())
+                        .with_summary(node_location(self.old, old_idx)),
+                );
+            }
+        }
+        for (id, &new_idx) in &self.new.node_indices {
+            if !matched.contains(&new_idx) {
+                changes.push(
+                    ElementChange::new(ElementKind::Node, ChangeKind::Added, id.clone())
+                        .with_summary(node_location(self.new, new_idx)),
+                );
+            }
+        }
+
+        for (&old_idx, &new_idx) in &self.nodes {
+            let (old, new) = (&self.old._graph[old_idx], &self.new._graph[new_idx]);
+            let mut fields = vec![];
+            field_change(
+                &mut fields,
+                "location",
+                node_location(self.old, old_idx),
+                node_location(self.new, new_idx),
+            );
+
+            let old_reads: HashSet<String> = old.reads.iter().map(|r| r.to_string()).collect();
+            let new_reads: HashSet<String> = new.reads.iter().map(|r| r.to_string()).collect();
+            let only = |a: &HashSet<String>, b: &HashSet<String>| {
+                let mut reads: Vec<&String> = a.difference(b).collect();
+                reads.sort();
+                (!reads.is_empty()).then(|| {
+                    BString::from(
+                        reads
+                            .into_iter()
+                            .map(String::as_str)
+                            .collect::<Vec<_>>()
+                            .join(","),
+                    )
+                })
+            };
+            let (removed, added) = (only(&old_reads, &new_reads), only(&new_reads, &old_reads));
+            if removed.is_some() || added.is_some() {
+                fields.push(FieldChange {
+                    field: "reads".into(),
+                    old: removed,
+                    new: added,
+                });
+            }
+
+            if old.sequence != new.sequence {
+                fields.push(FieldChange {
+                    field: "sequence".into(),
+                    old: old.sequence.clone(),
+                    new: new.sequence.clone(),
+                });
+            }
+            attribute_changes(&old.attributes, &new.attributes, &mut fields);
+            self.push_modified(changes, ElementKind::Node, &old.id, &new.id, fields);
+        }
+    }
+
+    fn edge_changes(&self, changes: &mut Vec<ElementChange>) {
+        let endpoints = |graph: &GraphSection, idx: EdgeIndex, rename: bool| -> BString {
+            let (source, sink) = graph._graph.edge_endpoints(idx).unwrap();
+            let id = |node: NodeIndex| {
+                let id = &graph._graph[node].id;
+                if rename { self.rename(id) } else { id.clone() }
+            };
+            format!("{}->{}", id(source), id(sink)).into()
+        };
+
+        let matched: HashSet<EdgeIndex> = self.edges.values().copied().collect();
+        for (id, &old_idx) in &self.old.edge_indices {
+            if !self.edges.contains_key(&old_idx) {
+                changes.push(
+                    ElementChange::new(ElementKind::Edge, ChangeKind::Removed, id.clone())
+                        .with_summary(endpoints(self.old, old_idx, false)),
+                );
+            }
+        }
+        for (id, &new_idx) in &self.new.edge_indices {
+            if !matched.contains(&new_idx) {
+                changes.push(
+                    ElementChange::new(ElementKind::Edge, ChangeKind::Added, id.clone())
+                        .with_summary(endpoints(self.new, new_idx, false)),
+                );
+            }
+        }
+
+        for (&old_idx, &new_idx) in &self.edges {
+            let (old, new) = (&self.old._graph[old_idx], &self.new._graph[new_idx]);
+            let mut fields = vec![];
+            field_change(
+                &mut fields,
+                "endpoints",
+                endpoints(self.old, old_idx, true),
+                endpoints(self.new, new_idx, false),
+            );
+            field_change(
+                &mut fields,
+                "sv",
+                old.sv.to_string().into(),
+                new.sv.to_string().into(),
+            );
+            attribute_changes(&old.attributes, &new.attributes, &mut fields);
+            self.push_modified(changes, ElementKind::Edge, &old.id, &new.id, fields);
+        }
+    }
+
+    /// Type, elements with old IDs renamed, and attributes of a group
+    fn group_parts(
+        &self,
+        group: &'a Group,
+        rename: bool,
+    ) -> (&'static str, BString, &'a HashMap<BString, Attribute>) {
+        let id = |id: &BString| if rename { self.rename(id) } else { id.clone() };
+        let join = |ids: Vec<String>| BString::from(ids.join(" "));
+        match group {
+            Group::Unordered {
+                elements,
+                attributes,
+                ..
+            } => (
+                "U",
+                join(elements.iter().map(|e| id(e).to_string()).collect()),
+                attributes,
+            ),
+            Group::Ordered {
+                elements,
+                attributes,
+                ..
+            } => {
+                let elements = elements
+                    .iter()
+                    .map(|e| {
+                        let mut element = e.clone();
+                        element.id = id(&e.id);
+                        element.to_string()
+                    })
+                    .collect();
+                ("P", join(elements), attributes)
+            }
+            Group::Chain {
+                elements,
+                attributes,
+                ..
+            } => (
+                "C",
+                join(elements.iter().map(|e| id(e).to_string()).collect()),
+                attributes,
+            ),
+        }
+    }
+
+    fn group_changes(
+        &self,
+        element: ElementKind,
+        old: &HashMap<BString, Group>,
+        new: &HashMap<BString, Group>,
+        changes: &mut Vec<ElementChange>,
+    ) {
+        for (id, group) in old {
+            if !new.contains_key(id) {
+                let (kind, elements, _) = self.group_parts(group, false);
+                changes.push(
+                    ElementChange::new(element, ChangeKind::Removed, id.clone())
+                        .with_summary(format!("{} {}", kind, elements)),
+                );
+            }
+        }
+        for (id, new_group) in new {
+            let Some(old_group) = old.get(id) else {
+                let (kind, elements, _) = self.group_parts(new_group, false);
+                changes.push(
+                    ElementChange::new(element, ChangeKind::Added, id.clone())
+                        .with_summary(format!("{} {}", kind, elements)),
+                );
+                continue;
+            };
+            let (old_kind, old_elements, old_attributes) = self.group_parts(old_group, true);
+            let (new_kind, new_elements, new_attributes) = self.group_parts(new_group, false);
+            let mut fields = vec![];
+            field_change(&mut fields, "type", old_kind.into(), new_kind.into());
+            field_change(&mut fields, "elements", old_elements, new_elements);
+            attribute_changes(old_attributes, new_attributes, &mut fields);
+            self.push_modified(changes, element, id, id, fields);
+        }
+    }
+
+    /// Traversed paths only found in one of the graphs, compared by ID and by the
+    /// locations of their nodes; returns the numbers of paths of both graphs
+    fn path_changes(
+        &self,
+        traversal: &TraversalOptions,
+        changes: &mut Vec<ElementChange>,
+    ) -> Result<(usize, usize)> {
+        let signatures = |graph: &GraphSection| -> Result<HashMap<Vec<BString>, ElementChange>> {
+            let mut signatures = HashMap::new();
+            for path in graph.traverse_with(traversal)? {
+                let signature = path
+                    .nodes
+                    .iter()
+                    .map(|idx| node_location(graph, *idx))
+                    .collect();
+                let elements: Vec<String> = graph
+                    .path_elements(&path)
+                    .iter()
+                    .map(|e| e.to_string())
+                    .collect();
+                let change = ElementChange::new(ElementKind::Path, ChangeKind::Added, path.id()?)
+                    .with_summary(elements.join(" "));
+                signatures.insert(signature, change);
+            }
+            Ok(signatures)
+        };
+        let old = signatures(self.old)?;
+        let new = signatures(self.new)?;
+        let old_ids: HashSet<&BString> = old.values().map(|change| &change.id).collect();
+        let new_ids: HashSet<&BString> = new.values().map(|change| &change.id).collect();
+
+        for (signature, change) in &old {
+            if !new.contains_key(signature) && !new_ids.contains(&change.id) {
+                let mut change = change.clone();
+                change.change = ChangeKind::Removed;
+                changes.push(change);
+            }
+        }
+        for (signature, change) in &new {
+            if !old.contains_key(signature) && !old_ids.contains(&change.id) {
+                changes.push(change.clone());
+            }
+        }
+        Ok((old.len(), new.len()))
+    }
+
+    fn push_modified(
+        &self,
+        changes: &mut Vec<ElementChange>,
+        element: ElementKind,
+        old_id: &BString,
+        new_id: &BString,
+        fields: Vec<FieldChange>,
+    ) {
+        if fields.is_empty() && old_id == new_id {
+            return;
+        }
+        let mut change = ElementChange::new(element, ChangeKind::Modified, new_id.clone());
+        change.old_id = (old_id != new_id).then(|| old_id.clone());
+        change.fields = fields;
+        changes.push(change);
+    }
+}
+
+fn sort_changes(changes: &mut [ElementChange]) {
+    changes.sort_by(|a, b| (a.element, &a.id, a.change).cmp(&(b.element, &b.id, b.change)));
+}
+
+impl GraphSection {
+    /// Differences between this graph and a newer version of it.
+    ///
+    /// Nodes and edges are matched by ID, then, with `match_coordinates`, nodes by their
+    /// reference, strand and exons and edges by their matched endpoints and structural
+    /// variant. Groups and chains are matched by ID, with the IDs of their elements
+    /// translated to the new graph before comparing them.
+    pub fn diff(&self, new: &GraphSection, options: &DiffOptions) -> Result<GraphDiff> {
+        let matcher = SectionMatcher::new(self, new, options.match_coordinates);
+        let mut changes = vec![];
+
+        let mut fields = vec![];
+        attribute_changes(&self.attributes, &new.attributes, &mut fields);
+        matcher.push_modified(&mut changes, ElementKind::Graph, &self.id, &new.id, fields);
+        // the graph itself is reported by the GraphDiff
+        changes.retain(|change| !change.fields.is_empty());
+
+        matcher.node_changes(&mut changes);
+        matcher.edge_changes(&mut changes);
+        matcher.group_changes(ElementKind::Group, &self.groups, &new.groups, &mut changes);
+        matcher.group_changes(ElementKind::Chain, &self.chains, &new.chains, &mut changes);
+        let paths = if options.paths {
+            Some(matcher.path_changes(&options.traversal, &mut changes)?)
+        } else {
+            None
+        };
+        sort_changes(&mut changes);
+
+        Ok(GraphDiff {
+            old_id: Some(self.id.clone()),
+            new_id: Some(new.id.clone()),
+            changes,
+            paths,
+        })
+    }
+}
+
+impl TSGraph {
+    /// Differences between this file and a newer version of it.
+    ///
+    /// Graphs are matched by ID, then, with `match_coordinates`, by decreasing overlap
+    /// of their genomic spans; see [`GraphSection::diff`] for the elements of matched
+    /// graphs. Headers are compared as a multiset of records and links by ID.
+    pub fn diff(&self, new: &TSGraph, options: &DiffOptions) -> Result<TSGraphDiff> {
+        let mut diff = TSGraphDiff::default();
+
+        let mut new_headers: Vec<String> = new.headers.iter().map(|h| h.to_string()).collect();
+        for header in &self.headers {
+            let record = header.to_string();
+            match new_headers.iter().position(|h| *h == record) {
+                Some(pos) => {
+                    new_headers.remove(pos);
+                }
+                None => diff.headers.push(
+                    ElementChange::new(
+                        ElementKind::Header,
+                        ChangeKind::Removed,
+                        header.tag.clone(),
+                    )
+                    .with_summary(header.value.clone()),
+                ),
+            }
+        }
+        for header in &new.headers {
+            if let Some(pos) = new_headers.iter().position(|h| *h == header.to_string()) {
+                new_headers.remove(pos);
+                diff.headers.push(
+                    ElementChange::new(ElementKind::Header, ChangeKind::Added, header.tag.clone())
+                        .with_summary(header.value.clone()),
+                );
+            }
+        }
+
+        // graphs matched by ID, then by overlap
+        let mut matches: Vec<(BString, BString)> = self
+            .graphs
+            .keys()
+            .filter(|id| new.graphs.contains_key(*id))
+            .map(|id| (id.clone(), id.clone()))
+            .collect();
+        let mut old_ids: Vec<&BString> = self
+            .graphs
+            .keys()
+            .filter(|id| !new.graphs.contains_key(*id))
+            .collect();
+        let mut new_ids: Vec<&BString> = new
+            .graphs
+            .keys()
+            .filter(|id| !self.graphs.contains_key(*id))
+            .collect();
+        old_ids.sort();
+        new_ids.sort();
+        if options.match_coordinates {
+            matches.extend(match_graphs_by_overlap(self, new, &old_ids, &new_ids));
+        }
+        let matched_old: HashSet<&BString> = matches.iter().map(|(old, _)| old).collect();
+        let matched_new: HashSet<&BString> = matches.iter().map(|(_, new)| new).collect();
+
+        for id in old_ids.iter().filter(|id| !matched_old.contains(**id)) {
+            diff.graphs.push(GraphDiff {
+                old_id: Some((*id).clone()),
+                new_id: None,
+                changes: vec![],
+                paths: None,
+            });
+        }
+        for id in new_ids.iter().filter(|id| !matched_new.contains(**id)) {
+            diff.graphs.push(GraphDiff {
+                old_id: None,
+                new_id: Some((*id).clone()),
+                changes: vec![],
+                paths: None,
+            });
+        }
+        for (old_id, new_id) in &matches {
+            let graph_diff = self.graphs[old_id].diff(&new.graphs[new_id], options)?;
+            if old_id != new_id || !graph_diff.changes.is_empty() {
+                diff.graphs.push(graph_diff);
+            }
+        }
+        diff.graphs.sort_by(|a, b| {
+            let id = |graph: &GraphDiff| graph.new_id.clone().or(graph.old_id.clone());
+            id(a).cmp(&id(b))
+        });
+
+        // links by ID, with graph IDs translated to the new file
+        let renamed_graphs: HashMap<&BString, &BString> =
+            matches.iter().map(|(old, new)| (old, new)).collect();
+        let endpoint = |graph: &BString, element: &BString, rename: bool| -> BString {
+            let graph = if rename {
+                renamed_graphs.get(graph).copied().unwrap_or(graph)
+            } else {
+                graph
+            };
+            format!("{}:{}", graph, element).into()
+        };
+        let old_links: HashMap<&BString, _> = self.links.iter().map(|l| (&l.id, l)).collect();
+        let new_links: HashMap<&BString, _> = new.links.iter().map(|l| (&l.id, l)).collect();
+        for link in &self.links {
+            if !new_links.contains_key(&link.id) {
+                diff.links.push(
+                    ElementChange::new(ElementKind::Link, ChangeKind::Removed, link.id.clone())
+                        .with_summary(link.link_type.clone()),
+                );
+            }
+        }
+        for link in &new.links {
+            let Some(old) = old_links.get(&link.id) else {
+                diff.links.push(
+                    ElementChange::new(ElementKind::Link, ChangeKind::Added, link.id.clone())
+                        .with_summary(link.link_type.clone()),
+                );
+                continue;
+            };
+            let mut fields = vec![];
+            field_change(
+                &mut fields,
+                "source",
+                endpoint(&old.source_graph, &old.source_element, true),
+                endpoint(&link.source_graph, &link.source_element, false),
+            );
+            field_change(
+                &mut fields,
+                "target",
+                endpoint(&old.target_graph, &old.target_element, true),
+                endpoint(&link.target_graph, &link.target_element, false),
+            );
+            field_change(
+                &mut fields,
+                "type",
+                old.link_type.clone(),
+                link.link_type.clone(),
+            );
+            attribute_changes(&old.attributes, &link.attributes, &mut fields);
+            if !fields.is_empty() {
+                let mut change =
+                    ElementChange::new(ElementKind::Link, ChangeKind::Modified, link.id.clone());
+                change.fields = fields;
+                diff.links.push(change);
+            }
+        }
+        sort_changes(&mut diff.headers);
+        sort_changes(&mut diff.links);
+        Ok(diff)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_diff() -> Result<()> {
+        let old = TSGraph::from_str(
+            "H\tVN\t1.0\n\
+             G\tg1\n\
+             N\tn1\tchr1:+:100-200\tr1:SO,r2:SO\n\
+             N\tn2\tchr1:+:300-400\tr1:SI\n\
+             N\tn3\tchr1:+:500-600\tr2:SI\n\
+             E\te1\tn1\tn2\tchr1,chr1,200,300,SPLICE\n\
+             E\te2\tn1\tn3\tchr1,chr1,200,500,SPLICE\n\
+             U\tu1\tn1 n2\n\
+             G\told_name\n\
+             N\tm1\tchr2:+:100-200\tr3:SO\n\
+             N\tm2\tchr2:+:300-400\tr3:SI\n\
+             E\tf1\tm1\tm2\tchr2,chr2,200,300,SPLICE\n\
+             G\tgone\n\
+             N\tk1\tchr3:+:100-200\tr4:SO\n",
+        )?;
+        // n2 renamed to x2, n3 removed, n4 added, n1 supported by r5 instead of r2
+        let new = TSGraph::from_str(
+            "H\tVN\t1.0\n\
+             G\tg1\n\
+             N\tn1\tchr1:+:100-200\tr1:SO,r5:SO\n\
+             N\tx2\tchr1:+:300-400\tr1:SI\n\
+             N\tn4\tchr1:+:700-800\tr5:SI\n\
+             E\te1\tn1\tx2\tchr1,chr1,200,300,SPLICE\n\
+             E\te3\tn1\tn4\tchr1,chr1,200,700,SPLICE\n\
+             U\tu1\tn1 x2\n\
+             G\tnew_name\n\
+             N\tm1\tchr2:+:150-200\tr3:SO\n\
+             N\tm2\tchr2:+:300-400\tr3:SI\n\
+             E\tf1\tm1\tm2\tchr2,chr2,200,300,SPLICE\n",
+        )?;
+
+        let diff = old.diff(&new, &DiffOptions::default())?;
+        assert!(diff.headers.is_empty());
+        let graphs: Vec<(Option<&str>, Option<&str>, ChangeKind)> = diff
+            .graphs
+            .iter()
+            .map(|g| {
+                (
+                    g.old_id.as_ref().map(|id| id.to_str().unwrap()),
+                    g.new_id.as_ref().map(|id| id.to_str().unwrap()),
+                    g.change(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            graphs,
+            vec![
+                (Some("g1"), Some("g1"), ChangeKind::Modified),
+                (Some("gone"), None, ChangeKind::Removed),
+                (Some("old_name"), Some("new_name"), ChangeKind::Modified),
+            ]
+        );
+
+        let g1 = &diff.graphs[0];
+        let changes: Vec<(ElementKind, ChangeKind, &str)> = g1
+            .changes
+            .iter()
+            .filter(|c| c.element != ElementKind::Path)
+            .map(|c| (c.element, c.change, c.id.to_str().unwrap()))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (ElementKind::Node, ChangeKind::Modified, "n1"),
+                (ElementKind::Node, ChangeKind::Removed, "n3"),
+                (ElementKind::Node, ChangeKind::Added, "n4"),
+                (ElementKind::Node, ChangeKind::Modified, "x2"),
+                (ElementKind::Edge, ChangeKind::Removed, "e2"),
+                (ElementKind::Edge, ChangeKind::Added, "e3"),
+            ]
+        );
+        // n1 -> n3 is replaced by n1 -> n4; n1 -> x2 is the same path as n1 -> n2
+        let mut paths: Vec<ChangeKind> = g1.changes[6..].iter().map(|c| c.change).collect();
+        paths.sort();
+        assert_eq!(paths, vec![ChangeKind::Removed, ChangeKind::Added]);
+        // the renamed node keeps its group and edge unchanged
+        assert_eq!(g1.changes[3].old_id, Some("n2".into()));
+        assert!(g1.changes[3].fields.is_empty());
+        let reads = &g1.changes[0].fields[0];
+        assert_eq!(reads.field, "reads");
+        assert_eq!(
+            (reads.old.clone(), reads.new.clone()),
+            (Some("r2:SO".into()), Some("r5:SO".into()))
+        );
+        assert_eq!(g1.paths, Some((2, 2)));
+
+        // the path through the moved node keeps its ID, so only the node changes
+        let renamed = &diff.graphs[2];
+        assert_eq!(renamed.changes.len(), 1);
+        assert_eq!(renamed.changes[0].fields[0].field, "location");
+
+        let json = diff.to_json();
+        assert_eq!(json["summary"]["node_added"], 1);
+        assert_eq!(json["graphs"][1]["change"], "removed");
+        assert!(
+            diff.to_string()
+                .contains("@@ graph old_name -> new_name @@")
+        );
+
+        assert!(old.diff(&old, &DiffOptions::default())?.is_empty());
+        Ok(())
+    }
+    fn graph_ids(diff: &TSGraphDiff) -> Vec<(Option<&str>, Option<&str>, ChangeKind)> {
+        diff.graphs
+            .iter()
+            .map(|g| {
+                (
+                    g.old_id.as_ref().map(|id| id.to_str().unwrap()),
+                    g.new_id.as_ref().map(|id| id.to_str().unwrap()),
+                    g.change(),
+                )
+            })
+            .collect()
+    }
+
+    fn element_changes(
+        diff: &GraphDiff,
+        element: ElementKind,
+    ) -> Vec<(ChangeKind, &str, Option<&str>)> {
+        diff.changes
+            .iter()
+            .filter(|c| c.element == element)
+            .map(|c| {
+                (
+                    c.change,
+                    c.id.to_str().unwrap(),
+                    c.old_id.as_ref().map(|id| id.to_str().unwrap()),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_overlap_on_several_references() -> Result<()> {
+        // a overlaps x by 101 bases on chr1 and z by 401 on chr2; c overlaps p by 101
+        // bases on chr3 and q by 51 on each of chr3 and chr4
+        let old = TSGraph::from_str(
+            "G\ta\n\
+             N\ta1\tchr1:+:100-200\tr1:SO\n\
+             N\ta2\tchr2:+:100-500\tr1:SI\n\
+             G\tc\n\
+             N\tc1\tchr3:+:100-200\tr2:SO\n\
+             N\tc2\tchr4:+:100-200\tr2:SI\n",
+        )?;
+        let new = TSGraph::from_str(
+            "G\tx\n\
+             N\tx1\tchr1:+:100-200\tr1:SO\n\
+             G\tz\n\
+             N\tz1\tchr2:+:100-500\tr1:SO\n\
+             G\tp\n\
+             N\tp1\tchr3:+:100-250\tr2:SO\n\
+             G\tq\n\
+             N\tq1\tchr3:+:150-200\tr2:SO\n\
+             N\tq2\tchr4:+:150-200\tr2:SI\n",
+        )?;
+
+        let diff = old.diff(&new, &DiffOptions::default())?;
+        assert_eq!(
+            graph_ids(&diff),
+            vec![
+                (None, Some("p"), ChangeKind::Added),
+                (Some("c"), Some("q"), ChangeKind::Modified),
+                (None, Some("x"), ChangeKind::Added),
+                (Some("a"), Some("z"), ChangeKind::Modified),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_edges_after_node_rename() -> Result<()> {
+        let old = TSGraph::from_str(
+            "G\tg1\n\
+             N\tn1\tchr1:+:100-200\tr1:SO,r2:SO\n\
+             N\tn2\tchr1:+:300-400\tr1:SI\n\
+             N\tn3\tchr1:+:500-600\tr2:SI\n\
+             E\te1\tn1\tn2\tchr1,chr1,200,300,SPLICE\n\
+             E\te2\tn1\tn3\tchr1,chr1,200,500,SPLICE\n",
+        )?;
+        // y2 and y3 are n2 and n3 renamed; j1 joins the same nodes as e1 with the same
+        // structural variant, while k2 joins n1 and y3 with another one
+        let new = TSGraph::from_str(
+            "G\tg1\n\
+             N\tn1\tchr1:+:100-200\tr1:SO,r2:SO\n\
+             N\ty2\tchr1:+:300-400\tr1:SI\n\
+             N\ty3\tchr1:+:500-600\tr2:SI\n\
+             E\tj1\tn1\ty2\tchr1,chr1,200,300,SPLICE\n\
+             E\tk2\tn1\ty3\tchr1,chr1,200,500,DEL\n",
+        )?;
+
+        let options = DiffOptions::builder().paths(false).build();
+        let diff = old.diff(&new, &options)?;
+        let g1 = &diff.graphs[0];
+        assert_eq!(
+            element_changes(g1, ElementKind::Edge),
+            vec![
+                (ChangeKind::Removed, "e2", None),
+                (ChangeKind::Modified, "j1", Some("e1")),
+                (ChangeKind::Added, "k2", None),
+            ]
+        );
+        let j1 = g1.changes.iter().find(|c| c.id == "j1").unwrap();
+        assert!(j1.fields.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_group_element_translation() -> Result<()> {
+        let old = TSGraph::from_str(
+            "G\tg1\n\
+             N\tn1\tchr1:+:100-200\tr1:SO\n\
+             N\tn2\tchr1:+:300-400\tr1:SI\n\
+             E\te1\tn1\tn2\tchr1,chr1,200,300,SPLICE\n\
+             U\tu1\tn1 n2\n\
+             C\tc1\tn1 e1 n2\n\
+             P\tp1\tn1+ e1+ n2+\n\
+             P\tp2\tn1+ e1+ n2+\n",
+        )?;
+        // every element is renamed; only p2 reads its last node in reverse
+        let new = TSGraph::from_str(
+            "G\tg1\n\
+             N\tm1\tchr1:+:100-200\tr1:SO\n\
+             N\tm2\tchr1:+:300-400\tr1:SI\n\
+             E\tf1\tm1\tm2\tchr1,chr1,200,300,SPLICE\n\
+             U\tu1\tm1 m2\n\
+             C\tc1\tm1 f1 m2\n\
+             P\tp1\tm1+ f1+ m2+\n\
+             P\tp2\tm1+ f1+ m2-\n",
+        )?;
+
+        let options = DiffOptions::builder().paths(false).build();
+        let diff = old.diff(&new, &options)?;
+        let g1 = &diff.graphs[0];
+        assert_eq!(
+            element_changes(g1, ElementKind::Group),
+            vec![(ChangeKind::Modified, "p2", None)]
+        );
+        assert!(element_changes(g1, ElementKind::Chain).is_empty());
+        let p2 = g1.changes.iter().find(|c| c.id == "p2").unwrap();
+        assert_eq!(
+            p2.fields,
+            vec![FieldChange {
+                field: "elements".into(),
+                old: Some("m1+ f1+ m2+".into()),
+                new: Some("m1+ f1+ m2-".into()),
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_headers() -> Result<()> {
+        // headers are a multiset: one of the two PG records and the old VN are gone
+        let old = TSGraph::from_str(
+            "H\tVN\t1.0\n\
+             H\tPG\ttsg\n\
+             H\tPG\ttsg\n\
+             G\tg1\n\
+             N\tn1\tchr1:+:100-200\tr1:SO\n",
+        )?;
+        let new = TSGraph::from_str(
+            "H\tVN\t1.1\n\
+             H\tPG\ttsg\n\
+             G\tg1\n\
+             N\tn1\tchr1:+:100-200\tr1:SO\n",
+        )?;
+
+        let diff = old.diff(&new, &DiffOptions::default())?;
+        let headers: Vec<(ChangeKind, &str, &str)> = diff
+            .headers
+            .iter()
+            .map(|h| {
+                (
+                    h.change,
+                    h.id.to_str().unwrap(),
+                    h.summary.as_ref().unwrap().to_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            headers,
+            vec![
+                (ChangeKind::Removed, "PG", "tsg"),
+                (ChangeKind::Removed, "VN", "1.0"),
+                (ChangeKind::Added, "VN", "1.1"),
+            ]
+        );
+        assert!(diff.graphs.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_without_coordinate_matching() -> Result<()> {
+        let old = TSGraph::from_str(
+            "G\tg1\n\
+             N\tn1\tchr1:+:100-200\tr1:SO\n\
+             N\tn2\tchr1:+:300-400\tr1:SI\n\
+             E\te1\tn1\tn2\tchr1,chr1,200,300,SPLICE\n\
+             G\told_name\n\
+             N\tm1\tchr2:+:100-200\tr2:SO\n",
+        )?;
+        let new = TSGraph::from_str(
+            "G\tg1\n\
+             N\tn1\tchr1:+:100-200\tr1:SO\n\
+             N\tx2\tchr1:+:300-400\tr1:SI\n\
+             E\te1\tn1\tx2\tchr1,chr1,200,300,SPLICE\n\
+             G\tnew_name\n\
+             N\tm1\tchr2:+:100-200\tr2:SO\n",
+        )?;
+
+        let options = DiffOptions::builder()
+            .match_coordinates(false)
+            .paths(false)
+            .build();
+        let diff = old.diff(&new, &options)?;
+        // renamed graphs and nodes are only matched by ID
+        assert_eq!(
+            graph_ids(&diff),
+            vec![
+                (Some("g1"), Some("g1"), ChangeKind::Modified),
+                (None, Some("new_name"), ChangeKind::Added),
+                (Some("old_name"), None, ChangeKind::Removed),
+            ]
+        );
+        let g1 = &diff.graphs[0];
+        assert_eq!(
+            element_changes(g1, ElementKind::Node),
+            vec![
+                (ChangeKind::Removed, "n2", None),
+                (ChangeKind::Added, "x2", None),
+            ]
+        );
+        let e1 = g1.changes.iter().find(|c| c.id == "e1").unwrap();
+        assert_eq!(
+            e1.fields,
+            vec![FieldChange {
+                field: "endpoints".into(),
+                old: Some("n1->n2".into()),
+                new: Some("n1->x2".into()),
+            }]
+        );
+        Ok(())
+    }
+}
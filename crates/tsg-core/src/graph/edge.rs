@@ -137,8 +137,12 @@ impl EdgeData {
             self.sv.breakpoint2,
         ));
 
+        // attributes given by the caller take precedence over those of the edge
         let mut info = BString::from("");
         for attr in self.attributes.values() {
+            if attributes.is_some_and(|attributes| attributes.iter().any(|a| a.tag == attr.tag)) {
+                continue;
+            }
             info.push_str(format!("{}={};", attr.tag, attr.value));
         }
 
@@ -0,0 +1,853 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use ahash::{HashMap, HashMapExt, HashSet};
+use anyhow::{Result, anyhow};
+use bon::Builder;
+use bstr::{BStr, BString, ByteSlice};
+use petgraph::graph::NodeIndex;
+use petgraph::visit::EdgeRef;
+
+use super::{
+    Attribute, EdgeData, GraphAnalysis, GraphSection, Group, InterGraphLink, NodeData, TSGraph,
+};
+
+/// Value of a field or literal in a [`FilterExpr`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterValue {
+    /// The field is not defined for the element, e.g. an attribute it does not carry
+    Missing,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(BString),
+}
+
+impl FilterValue {
+    /// Whether the value holds when used as a condition on its own: missing values,
+    /// `false`, zero and empty strings do not
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            FilterValue::Missing => false,
+            FilterValue::Bool(value) => *value,
+            FilterValue::Int(value) => *value != 0,
+            FilterValue::Float(value) => *value != 0.0,
+            FilterValue::String(value) => !value.is_empty(),
+        }
+    }
+
+    fn as_number(&self) -> Option<f64> {
+        match self {
+            FilterValue::Bool(value) => Some(if *value { 1.0 } else { 0.0 }),
+            FilterValue::Int(value) => Some(*value as f64),
+            FilterValue::Float(value) => Some(*value),
+            FilterValue::String(value) => value.to_str().ok()?.parse().ok(),
+            FilterValue::Missing => None,
+        }
+    }
+
+    /// Order two values; numbers compare numerically, strings byte-wise, and a string
+    /// compares with a number only if it parses as one
+    fn compare(&self, other: &FilterValue) -> Option<Ordering> {
+        match (self, other) {
+            (FilterValue::Missing, _) | (_, FilterValue::Missing) => None,
+            (FilterValue::Int(a), FilterValue::Int(b)) => Some(a.cmp(b)),
+            (FilterValue::String(a), FilterValue::String(b)) => Some(a.cmp(b)),
+            (FilterValue::Bool(a), FilterValue::Bool(b)) => Some(a.cmp(b)),
+            _ => self.as_number()?.partial_cmp(&other.as_number()?),
+        }
+    }
+}
+
+impl From<&Attribute> for FilterValue {
+    fn from(attribute: &Attribute) -> Self {
+        let value = match attribute.attribute_type {
+            'i' => attribute
+                .as_int()
+                .map(|value| FilterValue::Int(value as i64)),
+            'f' => attribute
+                .as_float()
+                .map(|value| FilterValue::Float(value as f64)),
+            'Z' => attribute
+                .as_string()
+                .map(|value| FilterValue::String(value.to_owned())),
+            _ => Ok(FilterValue::String(attribute.value.clone())),
+        };
+        value.unwrap_or(FilterValue::Missing)
+    }
+}
+
+impl From<usize> for FilterValue {
+    fn from(value: usize) -> Self {
+        FilterValue::Int(value as i64)
+    }
+}
+
+impl From<bool> for FilterValue {
+    fn from(value: bool) -> Self {
+        FilterValue::Bool(value)
+    }
+}
+
+impl From<&BStr> for FilterValue {
+    fn from(value: &BStr) -> Self {
+        FilterValue::String(value.to_owned())
+    }
+}
+
+impl From<String> for FilterValue {
+    fn from(value: String) -> Self {
+        FilterValue::String(value.into())
+    }
+}
+
+/// Comparison operators of a [`FilterExpr`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CompareOp {
+    fn holds(&self, left: &FilterValue, right: &FilterValue) -> bool {
+        let Some(ordering) = left.compare(right) else {
+            return false;
+        };
+        match self {
+            CompareOp::Eq => ordering == Ordering::Equal,
+            CompareOp::Ne => ordering != Ordering::Equal,
+            CompareOp::Lt => ordering == Ordering::Less,
+            CompareOp::Le => ordering != Ordering::Greater,
+            CompareOp::Gt => ordering == Ordering::Greater,
+            CompareOp::Ge => ordering != Ordering::Less,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(FilterValue),
+    Str(String),
+    Compare(CompareOp),
+    And,
+    Or,
+    Not,
+    Minus,
+    LParen,
+    RParen,
+}
+
+/// Split an expression into tokens, each with the column it starts at
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = vec![];
+    let mut pos = 0;
+    let error = |pos: usize, message: &str| {
+        anyhow!(
+            "Invalid filter expression {:?} at column {}: {}",
+            source,
+            pos + 1,
+            message
+        )
+    };
+
+    while pos < chars.len() {
+        let start = pos;
+        let c = chars[pos];
+        let next = chars.get(pos + 1).copied();
+        let (token, width) = match c {
+            c if c.is_whitespace() => {
+                pos += 1;
+                continue;
+            }
+            '(' => (Token::LParen, 1),
+            ')' => (Token::RParen, 1),
+            '-' => (Token::Minus, 1),
+            '&' if next == Some('&') => (Token::And, 2),
+            '|' if next == Some('|') => (Token::Or, 2),
+            '=' if next == Some('=') => (Token::Compare(CompareOp::Eq), 2),
+            '!' if next == Some('=') => (Token::Compare(CompareOp::Ne), 2),
+            '<' if next == Some('=') => (Token::Compare(CompareOp::Le), 2),
+            '>' if next == Some('=') => (Token::Compare(CompareOp::Ge), 2),
+            '!' => (Token::Not, 1),
+            '<' => (Token::Compare(CompareOp::Lt), 1),
+            '>' => (Token::Compare(CompareOp::Gt), 1),
+            '"' | '\'' => {
+                let mut value = String::new();
+                pos += 1;
+                loop {
+                    match chars.get(pos) {
+                        None => return Err(error(start, "unterminated string")),
+                        Some(&q) if q == c => break,
+                        Some('\\') if pos + 1 < chars.len() => {
+                            value.push(chars[pos + 1]);
+                            pos += 2;
+                        }
+                        Some(&other) => {
+                            value.push(other);
+                            pos += 1;
+                        }
+                    }
+                }
+                // past the closing quote
+                pos += 1;
+                tokens.push((start, Token::Str(value)));
+                continue;
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                while pos < chars.len()
+                    && (chars[pos].is_ascii_alphanumeric()
+                        || chars[pos] == '.'
+                        || (matches!(chars[pos], '+' | '-') && matches!(chars[pos - 1], 'e' | 'E')))
+                {
+                    pos += 1;
+                }
+                let text: String = chars[start..pos].iter().collect();
+                let number = match text.parse::<i64>() {
+                    Ok(value) => FilterValue::Int(value),
+                    Err(_) => FilterValue::Float(
+                        text.parse()
+                            .map_err(|_| error(start, &format!("invalid number {}", text)))?,
+                    ),
+                };
+                tokens.push((start, Token::Number(number)));
+                continue;
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                while pos < chars.len()
+                    && (chars[pos].is_alphanumeric() || matches!(chars[pos], '_' | '.'))
+                {
+                    pos += 1;
+                }
+                tokens.push((start, Token::Ident(chars[start..pos].iter().collect())));
+                continue;
+            }
+            other => return Err(error(start, &format!("unexpected character {:?}", other))),
+        };
+        pos += width;
+        tokens.push((start, token));
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Literal(FilterValue),
+    Field(String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, CompareOp, Box<Expr>),
+}
+
+impl Expr {
+    fn evaluate(&self, resolve: &dyn Fn(&str) -> FilterValue) -> FilterValue {
+        match self {
+            Expr::Literal(value) => value.clone(),
+            Expr::Field(name) => resolve(name),
+            Expr::Not(expr) => FilterValue::Bool(!expr.evaluate(resolve).is_truthy()),
+            Expr::And(left, right) => FilterValue::Bool(
+                left.evaluate(resolve).is_truthy() && right.evaluate(resolve).is_truthy(),
+            ),
+            Expr::Or(left, right) => FilterValue::Bool(
+                left.evaluate(resolve).is_truthy() || right.evaluate(resolve).is_truthy(),
+            ),
+            Expr::Compare(left, op, right) => {
+                FilterValue::Bool(op.holds(&left.evaluate(resolve), &right.evaluate(resolve)))
+            }
+        }
+    }
+}
+
+/// Recursive-descent parser over the tokens of an expression
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> anyhow::Error {
+        let column = self
+            .tokens
+            .get(self.pos)
+            .map_or(self.source.chars().count(), |(column, _)| *column);
+        anyhow!(
+            "Invalid filter expression {:?} at column {}: {}",
+            self.source,
+            column + 1,
+            message
+        )
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, token)| token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(_, token)| token.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.peek() == Some(&Token::Not) {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        let left = self.operand()?;
+        if let Some(&Token::Compare(op)) = self.peek() {
+            self.pos += 1;
+            let right = self.operand()?;
+            return Ok(Expr::Compare(Box::new(left), op, Box::new(right)));
+        }
+        Ok(left)
+    }
+
+    fn operand(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some(Token::LParen) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    _ => {
+                        self.pos -= 1;
+                        Err(self.error("expected ')'"))
+                    }
+                }
+            }
+            Some(Token::Number(value)) => Ok(Expr::Literal(value)),
+            Some(Token::Minus) => match self.next() {
+                Some(Token::Number(FilterValue::Int(value))) => {
+                    Ok(Expr::Literal(FilterValue::Int(-value)))
+                }
+                Some(Token::Number(FilterValue::Float(value))) => {
+                    Ok(Expr::Literal(FilterValue::Float(-value)))
+                }
+                _ => {
+                    self.pos -= 1;
+                    Err(self.error("expected a number after '-'"))
+                }
+            },
+            Some(Token::Str(value)) => Ok(Expr::Literal(FilterValue::String(value.into()))),
+            Some(Token::Ident(name)) => Ok(match name.as_str() {
+                "true" => Expr::Literal(FilterValue::Bool(true)),
+                "false" => Expr::Literal(FilterValue::Bool(false)),
+                _ => Expr::Field(name),
+            }),
+            _ => {
+                self.pos -= 1;
+                Err(self.error("expected a field, a value or '('"))
+            }
+        }
+    }
+}
+
+/// A boolean expression over the fields of a graph element.
+///
+/// Fields are the built-in fields of the element, such as `nodes`, `strand` or
+/// `sv_type`, or otherwise its attributes, typed by their attribute type. Values compare
+/// with `==`, `!=`, `<`, `<=`, `>` and `>=`, and conditions combine with `&&`, `||`, `!`
+/// and parentheses. Strings are quoted, e.g. `topo == "HeteroPath" && nodes > 3`.
+/// A comparison involving a missing field is false, and a field on its own holds if it
+/// is set and neither `false`, zero nor empty.
+#[derive(Debug, Clone)]
+pub struct FilterExpr {
+    source: String,
+    expr: Expr,
+}
+
+impl FilterExpr {
+    /// Evaluate the expression, looking up the value of each field with `resolve`
+    pub fn matches(&self, resolve: &dyn Fn(&str) -> FilterValue) -> bool {
+        self.expr.evaluate(resolve).is_truthy()
+    }
+}
+
+impl FromStr for FilterExpr {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = Parser {
+            source: s,
+            tokens: tokenize(s)?,
+            pos: 0,
+        };
+        if parser.tokens.is_empty() {
+            return Err(anyhow!("Empty filter expression"));
+        }
+        let expr = parser.or()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(parser.error("unexpected trailing input"));
+        }
+        Ok(Self {
+            source: s.to_string(),
+            expr,
+        })
+    }
+}
+
+impl fmt::Display for FilterExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+/// Built-in fields of graphs in a `--graph-expr`
+pub const GRAPH_FILTER_FIELDS: &[&str] = &[
+    "id",
+    "nodes",
+    "edges",
+    "paths",
+    "chains",
+    "topo",
+    "cyclic",
+    "connected",
+];
+/// Built-in fields of nodes in a `--node-expr`
+pub const NODE_FILTER_FIELDS: &[&str] = &[
+    "id",
+    "chrom",
+    "strand",
+    "start",
+    "end",
+    "length",
+    "exons",
+    "reads",
+    "in_degree",
+    "out_degree",
+];
+/// Built-in fields of edges in an `--edge-expr`
+pub const EDGE_FILTER_FIELDS: &[&str] = &[
+    "id",
+    "source",
+    "sink",
+    "sv_type",
+    "chrom1",
+    "chrom2",
+    "breakpoint1",
+    "breakpoint2",
+];
+/// Built-in fields of `P` records in a `--path-expr`
+pub const PATH_FILTER_FIELDS: &[&str] = &["id", "length", "nodes"];
+
+fn attribute_value(attributes: &HashMap<BString, Attribute>, name: &str) -> FilterValue {
+    attributes
+        .get(name.as_bytes().as_bstr())
+        .map_or(FilterValue::Missing, FilterValue::from)
+}
+
+/// Options of [`TSGraph::filter`]; elements pass a filter that is not set
+#[derive(Debug, Clone, Default, Builder)]
+pub struct FilterOptions {
+    /// Graphs to keep, tested on the graphs as read
+    pub graph: Option<FilterExpr>,
+    /// Nodes to keep; edges incident to a dropped node are dropped too
+    pub node: Option<FilterExpr>,
+    /// Edges to keep
+    pub edge: Option<FilterExpr>,
+    /// `P` records to keep
+    pub path: Option<FilterExpr>,
+}
+
+impl GraphSection {
+    /// Value of a built-in field or attribute of the graph
+    pub fn filter_field(&self, name: &str) -> FilterValue {
+        match name {
+            "id" => self.id.as_bstr().into(),
+            "nodes" => self.node_indices.len().into(),
+            "edges" => self.edge_indices.len().into(),
+            "paths" => self
+                .groups
+                .values()
+                .filter(|group| matches!(group, Group::Ordered { .. }))
+                .count()
+                .into(),
+            "chains" => self.chains.len().into(),
+            "topo" => self
+                .topo()
+                .map_or(FilterValue::Missing, |topo| topo.to_string().into()),
+            "cyclic" => self.is_cyclic().map_or(FilterValue::Missing, Into::into),
+            "connected" => self.is_connected().map_or(FilterValue::Missing, Into::into),
+            _ => attribute_value(&self.attributes, name),
+        }
+    }
+
+    /// Value of a built-in field or attribute of a node of the graph
+    pub fn node_filter_field(&self, node_idx: NodeIndex, name: &str) -> FilterValue {
+        let Some(node) = self.node_weight(node_idx) else {
+            return FilterValue::Missing;
+        };
+        let located = !node.exons.is_empty();
+        match name {
+            "id" => node.id.as_bstr().into(),
+            "chrom" if located => node.reference_id.as_bstr().into(),
+            "strand" if located => node.strand.to_string().into(),
+            "start" if located => node.reference_start().into(),
+            "end" if located => node.reference_end().into(),
+            "length" if located => exonic_length(node).into(),
+            "exons" => node.exons.len().into(),
+            "reads" => node.reads.len().into(),
+            "in_degree" => self.in_degree(node_idx).into(),
+            "out_degree" => self.out_degree(node_idx).into(),
+            "chrom" | "strand" | "start" | "end" | "length" => FilterValue::Missing,
+            _ => attribute_value(&node.attributes, name),
+        }
+    }
+
+    /// Whether a group passes the filters: its elements must all be kept, except for
+    /// unordered groups, which only lose their dropped elements
+    fn filter_group(&self, group: &Group, kept: &dyn Fn(&BStr) -> bool) -> Option<Group> {
+        match group {
+            Group::Unordered {
+                id,
+                elements,
+                attributes,
+            } => {
+                let retained: Vec<BString> = elements
+                    .iter()
+                    .filter(|element| kept(element.as_bstr()))
+                    .cloned()
+                    .collect();
+                (retained.len() == elements.len() || !retained.is_empty()).then(|| {
+                    Group::Unordered {
+                        id: id.clone(),
+                        elements: retained,
+                        attributes: attributes.clone(),
+                    }
+                })
+            }
+            Group::Ordered { elements, .. } => elements
+                .iter()
+                .all(|element| kept(element.id.as_bstr()))
+                .then(|| group.clone()),
+            Group::Chain { elements, .. } => elements
+                .iter()
+                .all(|element| kept(element.as_bstr()))
+                .then(|| group.clone()),
+        }
+    }
+
+    /// Copy of the graph with the nodes, edges and `P` records that pass the filters.
+    ///
+    /// Groups and chains that refer to a dropped element are dropped as well, except for
+    /// unordered groups, which only lose that element, so the result stays valid. The
+    /// graph filter is not applied here, see [`TSGraph::filter`].
+    pub fn filter(&self, options: &FilterOptions) -> Result<GraphSection> {
+        let mut filtered = GraphSection::new(self.id.clone());
+        filtered.attributes = self.attributes.clone();
+
+        for node_idx in self._graph.node_indices() {
+            let keep = options.node.as_ref().is_none_or(|expr| {
+                expr.matches(&|name: &str| self.node_filter_field(node_idx, name))
+            });
+            if keep {
+                filtered.add_node(self._graph[node_idx].clone())?;
+            }
+        }
+
+        for edge_ref in self._graph.edge_references() {
+            let source: &NodeData = &self._graph[edge_ref.source()];
+            let sink: &NodeData = &self._graph[edge_ref.target()];
+            if !filtered.node_indices.contains_key(&source.id)
+                || !filtered.node_indices.contains_key(&sink.id)
+            {
+                continue;
+            }
+            let edge: &EdgeData = edge_ref.weight();
+            let keep = options.edge.as_ref().is_none_or(|expr| {
+                expr.matches(&|name: &str| edge_filter_field(edge, source, sink, name))
+            });
+            if keep {
+                filtered.add_edge(source.id.as_bstr(), sink.id.as_bstr(), edge.clone())?;
+            }
+        }
+
+        // groups may refer to each other, so drop them until none refers to a dropped one
+        let mut groups: HashMap<BString, Group> = self
+            .groups
+            .iter()
+            .filter(|(_, group)| match group {
+                Group::Ordered { attributes, .. } => options.path.as_ref().is_none_or(|expr| {
+                    expr.matches(&|name: &str| path_filter_field(self, group, attributes, name))
+                }),
+                _ => true,
+            })
+            .map(|(id, group)| (id.clone(), group.clone()))
+            .collect();
+        loop {
+            let group_ids: HashSet<BString> = groups.keys().cloned().collect();
+            let kept = |element: &BStr| {
+                filtered.node_indices.contains_key(element)
+                    || filtered.edge_indices.contains_key(element)
+                    || group_ids.contains(element)
+            };
+            let retained: HashMap<BString, Group> = groups
+                .iter()
+                .filter_map(|(id, group)| Some((id.clone(), self.filter_group(group, &kept)?)))
+                .collect();
+            let stable = retained.len() == groups.len();
+            groups = retained;
+            if stable {
+                break;
+            }
+        }
+
+        for (id, chain) in &self.chains {
+            if let Some(group) = groups.get(id) {
+                filtered.chains.insert(id.clone(), group.clone());
+            } else if !self.groups.contains_key(id)
+                && let Some(chain) = self.filter_group(chain, &|element: &BStr| {
+                    filtered.node_indices.contains_key(element)
+                        || filtered.edge_indices.contains_key(element)
+                })
+            {
+                filtered.chains.insert(id.clone(), chain);
+            }
+        }
+        filtered.groups = groups;
+        Ok(filtered)
+    }
+
+    /// Whether the graph has a node, edge or group with the ID
+    fn has_element(&self, id: &BStr) -> bool {
+        self.node_indices.contains_key(id)
+            || self.edge_indices.contains_key(id)
+            || self.groups.contains_key(id)
+            || self.chains.contains_key(id)
+    }
+}
+
+/// Value of a built-in field or attribute of an edge
+fn edge_filter_field(
+    edge: &EdgeData,
+    source: &NodeData,
+    sink: &NodeData,
+    name: &str,
+) -> FilterValue {
+    match name {
+        "id" => edge.id.as_bstr().into(),
+        "source" => source.id.as_bstr().into(),
+        "sink" => sink.id.as_bstr().into(),
+        "sv_type" => edge.sv.sv_type.as_bstr().into(),
+        "chrom1" => edge.sv.reference_name1.as_bstr().into(),
+        "chrom2" => edge.sv.reference_name2.as_bstr().into(),
+        "breakpoint1" => edge.sv.breakpoint1.into(),
+        "breakpoint2" => edge.sv.breakpoint2.into(),
+        _ => attribute_value(&edge.attributes, name),
+    }
+}
+
+/// Number of bases covered by the closed exon intervals of a node
+fn exonic_length(node: &NodeData) -> usize {
+    node.exons
+        .exons
+        .iter()
+        .map(|exon| exon.end + 1 - exon.start)
+        .sum()
+}
+
+/// Value of a built-in field or attribute of a `P` record; its `length` is the
+/// number of exonic bases of its nodes, as in [`crate::graph::PathAbundance`]
+fn path_filter_field(
+    graph: &GraphSection,
+    group: &Group,
+    attributes: &HashMap<BString, Attribute>,
+    name: &str,
+) -> FilterValue {
+    let Group::Ordered { id, elements, .. } = group else {
+        return FilterValue::Missing;
+    };
+    match name {
+        "id" => id.as_bstr().into(),
+        "length" => elements
+            .iter()
+            .filter_map(|element| graph.node_indices.get(&element.id))
+            .filter_map(|node_idx| graph.node_weight(*node_idx))
+            .map(exonic_length)
+            .sum::<usize>()
+            .into(),
+        "nodes" => elements
+            .iter()
+            .filter(|element| graph.node_indices.contains_key(&element.id))
+            .count()
+            .into(),
+        _ => attribute_value(attributes, name),
+    }
+}
+
+impl TSGraph {
+    /// Subset the graphs, keeping those that pass the graph filter and, within them, the
+    /// nodes, edges and `P` records that pass theirs (see [`GraphSection::filter`]).
+    ///
+    /// Graphs left without nodes by the node filter are dropped, and so are links to
+    /// dropped graphs or elements. Headers are kept as they are.
+    pub fn filter(&self, options: &FilterOptions) -> Result<TSGraph> {
+        let mut graphs = HashMap::new();
+        for (id, graph) in &self.graphs {
+            if let Some(expr) = &options.graph
+                && !expr.matches(&|name: &str| graph.filter_field(name))
+            {
+                continue;
+            }
+            let filtered = graph.filter(options)?;
+            if filtered.node_indices.is_empty() && !graph.node_indices.is_empty() {
+                continue;
+            }
+            graphs.insert(id.clone(), filtered);
+        }
+
+        let has_element = |graph_id: &BString, element: &BString| {
+            graphs
+                .get(graph_id)
+                .is_some_and(|graph: &GraphSection| graph.has_element(element.as_bstr()))
+        };
+        let links: Vec<InterGraphLink> = self
+            .links
+            .iter()
+            .filter(|link| {
+                has_element(&link.source_graph, &link.source_element)
+                    && has_element(&link.target_graph, &link.target_element)
+            })
+            .cloned()
+            .collect();
+
+        Ok(TSGraph {
+            headers: self.headers.clone(),
+            graphs,
+            links,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TSG: &str = "H\tVN\t1.0
+G\tg1\ttype:Z:fusion
+N\tn1\tchr1:+:100-200\tr1:SO,r2:SO
+N\tn2\tchr1:+:300-400\tr1:IN
+N\tn3\tchr2:-:500-600\tr1:SI,r2:SI
+E\te1\tn1\tn2\tchr1,chr1,200,300,splice
+E\te2\tn2\tn3\tchr1,chr2,400,600,TRA
+P\tp1\tn1+ n2+ n3+
+P\tp2\tn1+ n2+
+U\tu1\tn1 n3
+A\tN\tn1\texpression:f:7.5
+A\tN\tn3\texpression:f:2
+A\tP\tp1\ttpm:f:10
+A\tP\tp2\ttpm:f:1
+G\tg2
+N\tm1\tchr3:+:100-200\tr3:SO
+L\tl1\tg1:n1\tg2:m1\tfusion
+";
+
+    #[test]
+    fn test_filter_expr() -> Result<()> {
+        let fields = |name: &str| match name {
+            "nodes" => FilterValue::Int(3),
+            "topo" => FilterValue::String("HeteroPath".into()),
+            "tpm" => FilterValue::Float(2.5),
+            _ => FilterValue::Missing,
+        };
+        let holds =
+            |expr: &str| -> Result<bool> { Ok(expr.parse::<FilterExpr>()?.matches(&fields)) };
+
+        assert!(holds("nodes > 2 && topo == 'HeteroPath'")?);
+        assert!(holds("tpm >= 2.5 && !(nodes < 3)")?);
+        assert!(holds("missing > 1 || nodes == 3.0")?);
+        assert!(!holds("missing != 1")?);
+        assert!(holds("!missing && tpm > -1")?);
+        assert!(holds("nodes")?);
+
+        assert!("nodes >".parse::<FilterExpr>().is_err());
+        assert!("(nodes > 1".parse::<FilterExpr>().is_err());
+        assert!("nodes = 1".parse::<FilterExpr>().is_err());
+        assert!("topo == \"Hetero".parse::<FilterExpr>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_length() -> Result<()> {
+        let tsg = TSGraph::from_str(TSG)?;
+        let g1 = tsg.graph("g1").unwrap();
+
+        // exons are closed intervals, chr1:+:100-200 covers 101 bases
+        let n1 = g1.node_indices[&BString::from("n1")];
+        assert_eq!(g1.node_filter_field(n1, "length"), FilterValue::Int(101));
+
+        let p2 = &g1.groups[&BString::from("p2")];
+        assert_eq!(
+            path_filter_field(g1, p2, &HashMap::new(), "length"),
+            FilterValue::Int(202)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_graph() -> Result<()> {
+        let tsg = TSGraph::from_str(TSG)?;
+
+        let options = FilterOptions::builder()
+            .node("expression > 5 || strand == '+'".parse()?)
+            .path("tpm > 5".parse()?)
+            .build();
+        let filtered = tsg.filter(&options)?;
+        let g1 = filtered.graph("g1").unwrap();
+        assert_eq!(g1.node_indices.len(), 2);
+        assert!(g1.edge_by_id("e1").is_some());
+        assert!(g1.edge_by_id("e2").is_none());
+        // p1 crosses the dropped node and p2 fails the path filter
+        assert!(!g1.groups.contains_key("p1".as_bytes().as_bstr()));
+        assert!(!g1.groups.contains_key("p2".as_bytes().as_bstr()));
+        match &g1.groups["u1".as_bytes().as_bstr()] {
+            Group::Unordered { elements, .. } => assert_eq!(elements, &vec![BString::from("n1")]),
+            _ => panic!("u1 should stay unordered"),
+        }
+        assert_eq!(filtered.links.len(), 1);
+
+        let options = FilterOptions::builder()
+            .graph("type == 'fusion'".parse()?)
+            .edge("sv_type == 'TRA'".parse()?)
+            .build();
+        let filtered = tsg.filter(&options)?;
+        assert!(filtered.graph("g2").is_none());
+        assert!(filtered.links.is_empty());
+        let g1 = filtered.graph("g1").unwrap();
+        assert_eq!(g1.edge_indices.len(), 1);
+        assert!(g1.edge_by_id("e2").is_some());
+
+        // the output reads back as a valid TSG
+        let mut buffer = vec![];
+        filtered.to_writer(&mut buffer)?;
+        let reread = TSGraph::from_reader(buffer.as_slice())?;
+        assert_eq!(reread.graph("g1").unwrap().node_indices.len(), 3);
+        Ok(())
+    }
+}
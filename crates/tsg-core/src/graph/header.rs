@@ -3,6 +3,8 @@ use std::fmt;
 use bon::Builder;
 use bstr::BString;
 
+use super::TSGraph;
+
 /// Header information in the TSG file
 #[derive(Debug, Clone, PartialEq, Builder)]
 #[builder(on(BString, into))]
@@ -16,3 +18,29 @@ impl fmt::Display for Header {
         write!(f, "H\t{}\t{}", self.tag, self.value)
     }
 }
+
+/// Tag of the headers declaring the samples or libraries of a multi-sample file,
+/// `H sample <name>`; reads refer to them through [`ReadData::sample`](super::ReadData::sample)
+pub const SAMPLE_HEADER_TAG: &str = "sample";
+
+impl TSGraph {
+    /// Samples declared by `H sample` headers, in order
+    pub fn samples(&self) -> Vec<&BString> {
+        self.headers
+            .iter()
+            .filter(|header| header.tag == SAMPLE_HEADER_TAG)
+            .map(|header| &header.value)
+            .collect()
+    }
+
+    /// Declare a sample with an `H sample` header, unless it is already declared
+    pub fn add_sample(&mut self, sample: impl Into<BString>) {
+        let sample = sample.into();
+        if !self.samples().contains(&&sample) {
+            self.headers.push(Header {
+                tag: SAMPLE_HEADER_TAG.into(),
+                value: sample,
+            });
+        }
+    }
+}
@@ -0,0 +1,539 @@
+use ahash::{HashMap, HashMapExt, HashSet, HashSetExt};
+use anyhow::{Result, anyhow};
+use bstr::{BStr, BString, ByteSlice};
+use petgraph::unionfind::UnionFind;
+
+use super::{
+    DEFAULT_GRAPH_ID, GraphSection, Group, InterGraphLink, OrientedElement, ReadData, Strand,
+    TSGraph,
+};
+
+/// Node key of the merge: nodes with the same reference, strand and exons are collapsed
+fn node_key(
+    reference_id: &BStr,
+    strand: impl std::fmt::Display,
+    exons: impl std::fmt::Display,
+) -> BString {
+    format!("{}:{}:{}", reference_id, strand, exons).into()
+}
+
+/// `id`, or `id_{suffix}` and `id_{suffix}_{n}` when `id` is already taken
+fn unique_id(id: &BString, suffix: usize, taken: impl Fn(&BString) -> bool) -> BString {
+    if !taken(id) {
+        return id.clone();
+    }
+    let mut candidate: BString = format!("{}_{}", id, suffix).into();
+    let mut n = 1;
+    while taken(&candidate) {
+        candidate = format!("{}_{}_{}", id, suffix, n).into();
+        n += 1;
+    }
+    candidate
+}
+
+/// Reference and strand of a locus
+type LocusKey = (BString, Strand);
+
+/// Groups of graphs whose node exons overlap on the same reference and strand, as
+/// indices into `graphs`
+fn overlapping_loci(graphs: &[&GraphSection]) -> Vec<Vec<usize>> {
+    let mut spans: HashMap<LocusKey, Vec<(usize, usize, usize)>> = HashMap::new();
+    for (idx, graph) in graphs.iter().enumerate() {
+        let mut graph_spans: HashMap<LocusKey, (usize, usize)> = HashMap::new();
+        for node in graph._graph.node_weights() {
+            if node.exons.exons.is_empty() {
+                continue;
+            }
+            let (start, end) = (node.reference_start(), node.reference_end());
+            graph_spans
+                .entry((node.reference_id.clone(), node.strand))
+                .and_modify(|span| *span = (span.0.min(start), span.1.max(end)))
+                .or_insert((start, end));
+        }
+        for (key, (start, end)) in graph_spans {
+            spans.entry(key).or_default().push((start, end, idx));
+        }
+    }
+
+    let mut loci = UnionFind::new(graphs.len());
+    for spans in spans.values_mut() {
+        spans.sort();
+        let mut current: Option<(usize, usize)> = None;
+        for &(start, end, idx) in spans.iter() {
+            match current {
+                Some((last, cluster_end)) if start <= cluster_end => {
+                    loci.union(last, idx);
+                    current = Some((idx, cluster_end.max(end)));
+                }
+                _ => current = Some((idx, end)),
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for idx in 0..graphs.len() {
+        groups.entry(loci.find(idx)).or_default().push(idx);
+    }
+    let mut groups: Vec<Vec<usize>> = groups.into_values().collect();
+    groups.sort();
+    groups
+}
+
+/// Graph section built from the graphs of one locus
+struct LocusMerger {
+    section: GraphSection,
+    nodes: HashMap<BString, BString>,
+    edges: HashMap<(BString, BString, BString), BString>,
+}
+
+impl LocusMerger {
+    fn new(id: BString) -> Self {
+        Self {
+            section: GraphSection::new(id),
+            nodes: HashMap::new(),
+            edges: HashMap::new(),
+        }
+    }
+
+    /// Add a graph of a sample, returning the new IDs of its nodes, edges and groups
+    fn add(
+        &mut self,
+        graph: &GraphSection,
+        sample: &BStr,
+        sample_idx: usize,
+    ) -> Result<HashMap<BString, BString>> {
+        let mut renames: HashMap<BString, BString> = HashMap::new();
+        for (id, value) in &graph.attributes {
+            self.section
+                .attributes
+                .entry(id.clone())
+                .or_insert_with(|| value.clone());
+        }
+
+        let mut node_ids: Vec<&BString> = graph.node_indices.keys().collect();
+        node_ids.sort();
+        for id in node_ids {
+            let node = &graph._graph[graph.node_indices[id]];
+            let reads = node.reads.iter().map(|read| ReadData {
+                id: read.id.clone(),
+                identity: read.identity.clone(),
+                sample: read.sample.clone().or_else(|| Some(sample.to_owned())),
+            });
+
+            let key = node_key(node.reference_id.as_bstr(), node.strand, &node.exons);
+            if let Some(merged_id) = self.nodes.get(&key) {
+                let idx = self.section.node_indices[merged_id];
+                let merged = &mut self.section._graph[idx];
+                for read in reads {
+                    if !merged.reads.contains(&read) {
+                        merged.reads.push(read);
+                    }
+                }
+                if merged.sequence.is_none() {
+                    merged.sequence = node.sequence.clone();
+                }
+                for (tag, attr) in &node.attributes {
+                    merged
+                        .attributes
+                        .entry(tag.clone())
+                        .or_insert_with(|| attr.clone());
+                }
+                renames.insert(id.clone(), merged_id.clone());
+                continue;
+            }
+
+            let merged_id = unique_id(id, sample_idx, |id| {
+                self.section.node_indices.contains_key(id)
+            });
+            let mut merged = node.clone();
+            merged.id = merged_id.clone();
+            merged.reads = reads.collect();
+            self.section.add_node(merged)?;
+            self.nodes.insert(key, merged_id.clone());
+            renames.insert(id.clone(), merged_id);
+        }
+
+        let mut edge_ids: Vec<&BString> = graph.edge_indices.keys().collect();
+        edge_ids.sort();
+        for id in edge_ids {
+            let idx = graph.edge_indices[id];
+            let edge = &graph._graph[idx];
+            let (source, sink) = graph._graph.edge_endpoints(idx).unwrap();
+            let source = renames[&graph._graph[source].id].clone();
+            let sink = renames[&graph._graph[sink].id].clone();
+
+            let key = (source.clone(), sink.clone(), edge.sv.to_string().into());
+            if let Some(merged_id) = self.edges.get(&key) {
+                let idx = self.section.edge_indices[merged_id];
+                let merged = &mut self.section._graph[idx];
+                for (tag, attr) in &edge.attributes {
+                    merged
+                        .attributes
+                        .entry(tag.clone())
+                        .or_insert_with(|| attr.clone());
+                }
+                renames.insert(id.clone(), merged_id.clone());
+                continue;
+            }
+
+            let source_idx = self.section.node_indices[&source];
+            let sink_idx = self.section.node_indices[&sink];
+            if let Some(existing) = self.section._graph.find_edge(source_idx, sink_idx) {
+                // the graph holds one edge per pair of nodes, so neither can be kept
+                let existing = &self.section._graph[existing];
+                return Err(anyhow!(
+                    "Edge {} ({}) of sample {} conflicts with edge {} ({}) between {} and {}",
+                    id,
+                    edge.sv,
+                    sample,
+                    existing.id,
+                    existing.sv,
+                    source,
+                    sink
+                ));
+            }
+
+            let merged_id = unique_id(id, sample_idx, |id| {
+                self.section.edge_indices.contains_key(id)
+            });
+            let mut merged = edge.clone();
+            merged.id = merged_id.clone();
+            self.section
+                .add_edge(source.as_bstr(), sink.as_bstr(), merged)?;
+            self.edges.insert(key, merged_id.clone());
+            renames.insert(id.clone(), merged_id);
+        }
+
+        let mut group_ids: Vec<&BString> = graph.groups.keys().collect();
+        group_ids.sort();
+        for id in group_ids {
+            let group = rename_group(&graph.groups[id], &renames);
+            let existing = self.section.groups.get(id);
+            if existing.is_some_and(|existing| same_elements(existing, &group)) {
+                continue;
+            }
+            let merged_id = unique_id(id, sample_idx, |id| self.section.groups.contains_key(id));
+            let group = with_group_id(group, merged_id.clone());
+            if let Group::Chain { .. } = group {
+                self.section.chains.insert(merged_id.clone(), group.clone());
+            }
+            self.section.groups.insert(merged_id.clone(), group);
+            renames.insert(id.clone(), merged_id);
+        }
+        Ok(renames)
+    }
+}
+
+fn rename_group(group: &Group, renames: &HashMap<BString, BString>) -> Group {
+    let rename = |id: &BString| renames.get(id).unwrap_or(id).clone();
+    match group {
+        Group::Unordered {
+            id,
+            elements,
+            attributes,
+        } => Group::Unordered {
+            id: id.clone(),
+            elements: elements.iter().map(rename).collect(),
+            attributes: attributes.clone(),
+        },
+        Group::Ordered {
+            id,
+            elements,
+            attributes,
+        } => Group::Ordered {
+            id: id.clone(),
+            elements: elements
+                .iter()
+                .map(|element| OrientedElement {
+                    id: rename(&element.id),
+                    orientation: element.orientation,
+                })
+                .collect(),
+            attributes: attributes.clone(),
+        },
+        Group::Chain {
+            id,
+            elements,
+            attributes,
+        } => Group::Chain {
+            id: id.clone(),
+            elements: elements.iter().map(rename).collect(),
+            attributes: attributes.clone(),
+        },
+    }
+}
+
+fn with_group_id(group: Group, new_id: BString) -> Group {
+    match group {
+        Group::Unordered {
+            elements,
+            attributes,
+            ..
+        } => Group::Unordered {
+            id: new_id,
+            elements,
+            attributes,
+        },
+        Group::Ordered {
+            elements,
+            attributes,
+            ..
+        } => Group::Ordered {
+            id: new_id,
+            elements,
+            attributes,
+        },
+        Group::Chain {
+            elements,
+            attributes,
+            ..
+        } => Group::Chain {
+            id: new_id,
+            elements,
+            attributes,
+        },
+    }
+}
+
+fn same_elements(a: &Group, b: &Group) -> bool {
+    match (a, b) {
+        (Group::Unordered { elements: a, .. }, Group::Unordered { elements: b, .. })
+        | (Group::Chain { elements: a, .. }, Group::Chain { elements: b, .. }) => a == b,
+        (Group::Ordered { elements: a, .. }, Group::Ordered { elements: b, .. }) => {
+            a.len() == b.len()
+                && a.iter()
+                    .zip(b)
+                    .all(|(a, b)| a.id == b.id && a.orientation == b.orientation)
+        }
+        _ => false,
+    }
+}
+
+impl TSGraph {
+    /// Merge the graphs of several samples into one multi-sample TSG.
+    ///
+    /// Graphs whose node exons overlap on the same reference and strand, within or
+    /// across samples, are unified into one graph named after the first of them. Nodes with the same
+    /// reference, strand and exons are collapsed and their reads united, each read tagged
+    /// with its sample unless it already has one, so that reads of different samples
+    /// sharing an ID stay distinct. Samples are declared by `H sample` headers, keeping
+    /// those of inputs that already declare their samples. Edges between
+    /// collapsed nodes with the same structural variant are collapsed too; edges between
+    /// the same nodes with different structural variants are an error. Colliding IDs
+    /// of distinct elements get the index of their sample as a suffix. Headers are
+    /// deduplicated and links follow the renamed graphs and elements.
+    pub fn merge_by_locus(samples: Vec<(BString, TSGraph)>) -> Result<TSGraph> {
+        let mut merged = TSGraph::new();
+
+        // graphs of all samples, in sample order then by ID
+        let mut graphs: Vec<(usize, &GraphSection)> = vec![];
+        for (sample_idx, (_, tsg)) in samples.iter().enumerate() {
+            let mut sections: Vec<&GraphSection> = tsg
+                .graphs
+                .values()
+                .filter(|graph| graph.id != DEFAULT_GRAPH_ID || !graph.node_indices.is_empty())
+                .collect();
+            sections.sort_by(|a, b| a.id.cmp(&b.id));
+            graphs.extend(sections.into_iter().map(|graph| (sample_idx, graph)));
+        }
+        let sections: Vec<&GraphSection> = graphs.iter().map(|(_, graph)| *graph).collect();
+
+        // renames of the graphs and their elements, by sample and original graph ID
+        let mut renames: HashMap<(usize, BString), (BString, HashMap<BString, BString>)> =
+            HashMap::new();
+        for locus in overlapping_loci(&sections) {
+            let (first_sample, first) = graphs[locus[0]];
+            let id = unique_id(&first.id, first_sample, |id| merged.graphs.contains_key(id));
+            let mut merger = LocusMerger::new(id.clone());
+            for idx in locus {
+                let (sample_idx, graph) = graphs[idx];
+                let element_renames =
+                    merger.add(graph, samples[sample_idx].0.as_bstr(), sample_idx)?;
+                renames.insert(
+                    (sample_idx, graph.id.clone()),
+                    (id.clone(), element_renames),
+                );
+            }
+            merged.graphs.insert(id, merger.section);
+        }
+
+        let mut link_ids: HashSet<BString> = HashSet::new();
+        for (name, tsg) in &samples {
+            if tsg.samples().is_empty() {
+                merged.add_sample(name.clone());
+            }
+        }
+        for (sample_idx, (_, tsg)) in samples.iter().enumerate() {
+            for header in &tsg.headers {
+                if !merged
+                    .headers
+                    .iter()
+                    .any(|h| h.tag == header.tag && h.value == header.value)
+                {
+                    merged.headers.push(header.clone());
+                }
+            }
+
+            for link in &tsg.links {
+                let rename = |graph: &BString, element: &BString| -> (BString, BString) {
+                    match renames.get(&(sample_idx, graph.clone())) {
+                        Some((graph, elements)) => (
+                            graph.clone(),
+                            elements.get(element).unwrap_or(element).clone(),
+                        ),
+                        None => (graph.clone(), element.clone()),
+                    }
+                };
+                let (source_graph, source_element) =
+                    rename(&link.source_graph, &link.source_element);
+                let (target_graph, target_element) =
+                    rename(&link.target_graph, &link.target_element);
+                let renamed = InterGraphLink {
+                    id: unique_id(&link.id, sample_idx, |id| link_ids.contains(id)),
+                    source_graph,
+                    source_element,
+                    target_graph,
+                    target_element,
+                    link_type: link.link_type.clone(),
+                    attributes: link.attributes.clone(),
+                };
+                let duplicate = merged.links.iter().any(|l| {
+                    l.source_graph == renamed.source_graph
+                        && l.source_element == renamed.source_element
+                        && l.target_graph == renamed.target_graph
+                        && l.target_element == renamed.target_element
+                        && l.link_type == renamed.link_type
+                });
+                if !duplicate {
+                    link_ids.insert(renamed.id.clone());
+                    merged.links.push(renamed);
+                }
+            }
+        }
+        Ok(merged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_merge_by_locus() -> Result<()> {
+        let sample1 = TSGraph::from_str(
+            "H\tVN\t1.0\n\
+             G\tg1\n\
+             N\tn1\tchr1:+:100-200\tr1:SO\n\
+             N\tn2\tchr1:+:300-400\tr1:SI\n\
+             E\te1\tn1\tn2\tchr1,chr1,200,300,SPLICE\n\
+             P\tp1\tn1+ e1+ n2+\n\
+             G\tother\n\
+             N\tm1\tchr2:+:100-200\tr2:SO\n",
+        )?;
+        // the same junction under other IDs, a new exon whose ID collides with n2, and
+        // an unrelated graph with the ID of an existing one
+        let sample2 = TSGraph::from_str(
+            "H\tVN\t1.0\n\
+             G\tlocus\n\
+             N\ta1\tchr1:+:100-200\tr1:SO,r3:SO\n\
+             N\ta2\tchr1:+:300-400\tr1:IN,r3:SI\n\
+             N\tn2\tchr1:+:500-600\tr1:SI\n\
+             E\tb1\ta1\ta2\tchr1,chr1,200,300,SPLICE\n\
+             E\tb2\ta2\tn2\tchr1,chr1,400,500,SPLICE\n\
+             G\tg1\n\
+             N\tk1\tchr3:+:100-200\tr4:SO\n",
+        )?;
+
+        let merged = TSGraph::merge_by_locus(vec![("s1".into(), sample1), ("s2".into(), sample2)])?;
+        assert_eq!(merged.samples(), vec!["s1", "s2"]);
+        assert_eq!(merged.headers.len(), 3);
+        let mut ids: Vec<&BString> = merged
+            .graphs
+            .iter()
+            .filter(|(_, graph)| !graph.node_indices.is_empty())
+            .map(|(id, _)| id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec!["g1", "g1_1", "other"]);
+
+        let graph = merged.graph("g1").unwrap();
+        assert_eq!(graph.node_indices.len(), 3);
+        assert_eq!(graph.edge_indices.len(), 2);
+        let reads: Vec<String> = graph
+            .node_by_id("n1")
+            .unwrap()
+            .reads
+            .iter()
+            .map(|read| read.to_string())
+            .collect();
+        assert_eq!(reads, vec!["r1:SO:s1", "r1:SO:s2", "r3:SO:s2"]);
+        assert_eq!(graph.node_by_id("n2_1").unwrap().reference_start(), 500);
+        assert!(graph.edge_by_id("b2").is_some());
+        assert!(graph.groups.contains_key(&BString::from("p1")));
+
+        // reads of the second sample continue the junction of the first one
+        let paths = graph.traverse()?;
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].nodes.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_shared_read_ids() -> Result<()> {
+        // r1 names a different read in each sample
+        let sample1 = TSGraph::from_str(
+            "G\tg1\n\
+             N\tn1\tchr1:+:100-200\tr1:SO\n\
+             N\tn2\tchr1:+:300-400\tr1:SI\n\
+             E\te1\tn1\tn2\tchr1,chr1,200,300,SPLICE\n",
+        )?;
+        let sample2 = TSGraph::from_str(
+            "G\tg1\n\
+             N\tn2\tchr1:+:300-400\tr1:SO\n\
+             N\tn3\tchr1:+:500-600\tr1:SI\n\
+             E\te2\tn2\tn3\tchr1,chr1,400,500,SPLICE\n",
+        )?;
+        let merged = TSGraph::merge_by_locus(vec![("s1".into(), sample1), ("s2".into(), sample2)])?;
+        let graph = merged.graph("g1").unwrap();
+        assert_eq!(graph.node_by_id("n2").unwrap().reads.len(), 2);
+
+        // neither read goes through all three nodes
+        assert!(graph.traverse()?.iter().all(|path| path.nodes.len() < 3));
+        let e1 = graph.edge_indices[&BString::from("e1")];
+        let support = graph.edge_support(e1)?;
+        assert_eq!((support.sr, support.dp1, support.dp2), (1, 1, 2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_strands_and_conflicts() -> Result<()> {
+        let plus = TSGraph::from_str(
+            "G\tg1\n\
+             N\tn1\tchr1:+:100-200\tr1:SO\n\
+             N\tn2\tchr1:+:300-400\tr1:SI\n\
+             E\te1\tn1\tn2\tchr1,chr1,200,300,SPLICE\n",
+        )?;
+        // an antisense gene over the same span is a separate locus
+        let minus = TSGraph::from_str(
+            "G\tg2\n\
+             N\tm1\tchr1:-:150-250\tr2:SO\n",
+        )?;
+        let merged =
+            TSGraph::merge_by_locus(vec![("s1".into(), plus.clone()), ("s2".into(), minus)])?;
+        assert!(merged.graph("g1").is_some());
+        assert_eq!(merged.graph("g2").unwrap().node_indices.len(), 1);
+
+        // the same nodes joined by a different structural variant
+        let conflicting = TSGraph::from_str(
+            "G\tg1\n\
+             N\tn1\tchr1:+:100-200\tr3:SO\n\
+             N\tn2\tchr1:+:300-400\tr3:SI\n\
+             E\te1\tn1\tn2\tchr1,chr1,200,300,DUP\n",
+        )?;
+        let error = TSGraph::merge_by_locus(vec![("s1".into(), plus), ("s2".into(), conflicting)])
+            .unwrap_err();
+        assert!(error.to_string().contains("conflicts with edge e1"));
+        Ok(())
+    }
+}
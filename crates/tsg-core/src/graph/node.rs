@@ -1,12 +1,11 @@
 use std::fmt;
 use std::str::FromStr;
 
-use crate::graph::Attribute;
+use crate::graph::{Attribute, Orientation, reverse_complement};
 use ahash::HashMap;
 use anyhow::Context;
 use anyhow::Result;
 use bon::Builder;
-use bon::builder;
 use bstr::BString;
 use bstr::ByteSlice;
 use rayon::prelude::*;
@@ -14,6 +13,18 @@ use serde_json::json;
 use std::io;
 use tracing::debug;
 
+/// Fields written by [`NodeData::to_json`] that are not node attributes
+const NODE_JSON_FIELDS: &[&str] = &[
+    "id",
+    "chrom",
+    "ref_start",
+    "ref_end",
+    "strand",
+    "exons",
+    "reads",
+    "sequence",
+];
+
 /// Represents a simple interval with start and end positions.
 ///
 /// An interval is defined by two positions:
@@ -197,11 +208,28 @@ impl Exons {
 pub struct ReadData {
     pub id: BString,
     pub identity: ReadIdentity,
+    /// Sample or library of the read, declared by an `H sample` header
+    pub sample: Option<BString>,
+}
+
+/// Key of a read: its ID within its sample, as read IDs of different samples may
+/// collide
+pub type ReadKey<'a> = (&'a BString, Option<&'a BString>);
+
+impl ReadData {
+    /// Key identifying the read, see [`ReadKey`]
+    pub fn key(&self) -> ReadKey<'_> {
+        (&self.id, self.sample.as_ref())
+    }
 }
 
 impl fmt::Display for ReadData {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}:{:?}", self.id, self.identity)
+        write!(f, "{}:{:?}", self.id, self.identity)?;
+        if let Some(sample) = &self.sample {
+            write!(f, ":{}", sample)?;
+        }
+        Ok(())
     }
 }
 
@@ -209,9 +237,9 @@ impl FromStr for ReadData {
     type Err = io::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        // <id>:<identity>
-        let fields: Vec<&str> = s.split(':').collect();
-        if fields.len() != 2 {
+        // <id>:<identity>[:<sample>]
+        let fields: Vec<&str> = s.splitn(3, ':').collect();
+        if fields.len() < 2 || fields.get(2).is_some_and(|sample| sample.is_empty()) {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
                 format!("Invalid read line format: {}", s),
@@ -220,11 +248,16 @@ impl FromStr for ReadData {
 
         let id: BString = fields[0].into();
         let identity = fields[1].parse()?;
-        Ok(Self { id, identity })
+        let sample = fields.get(2).map(|sample| BString::from(*sample));
+        Ok(Self {
+            id,
+            identity,
+            sample,
+        })
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ReadIdentity {
     SO, // source
     IN, // intermediate
@@ -264,7 +297,7 @@ impl From<&str> for ReadIdentity {
 }
 
 /// Represents DNA strand orientation
-#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
 pub enum Strand {
     #[default]
     Forward,
@@ -305,18 +338,48 @@ pub struct NodeData {
     pub exons: Exons,
     #[builder(default)]
     pub reads: Vec<ReadData>,
+    /// Bases of the exons read 5' to 3' along the node `strand`
     pub sequence: Option<BString>,
     #[builder(default)]
     pub attributes: HashMap<BString, Attribute>,
 }
 
 impl NodeData {
+    /// The node sequence read in the given orientation along the transcript.
+    ///
+    /// Sequences are stored along the node strand, so they are reverse complemented
+    /// when the node is traversed in reverse.
+    pub fn oriented_sequence(&self, orientation: Orientation) -> Option<BString> {
+        let sequence = self.sequence.as_ref()?;
+        if orientation == Orientation::Reverse {
+            Some(reverse_complement(sequence))
+        } else {
+            Some(sequence.clone())
+        }
+    }
+
     pub fn reference_start(&self) -> usize {
         self.exons.first_exon().start
     }
     pub fn reference_end(&self) -> usize {
         self.exons.last_exon().end
     }
+
+    /// Number of distinct reads of each sample on the node; reads without a sample are
+    /// not counted
+    pub fn sample_support(&self) -> HashMap<BString, usize> {
+        let mut reads: HashMap<&BString, ahash::HashSet<&BString>> = HashMap::default();
+        for read in &self.reads {
+            if let Some(sample) = &read.sample {
+                reads.entry(sample).or_default().insert(&read.id);
+            }
+        }
+        reads
+            .into_iter()
+            .map(|(sample, ids)| (sample.clone(), ids.len()))
+            .collect()
+    }
+
     /// Converts the node data to a JSON representation
     ///
     /// # Arguments
@@ -335,27 +398,81 @@ impl NodeData {
             "id": self.id.to_str().unwrap(),
         });
 
+        if let Some(sequence) = &self.sequence {
+            data["sequence"] = sequence.to_str()?.into();
+        }
+
         for attr in self.attributes.values() {
-            data[attr.tag.to_str().unwrap()] = match attr.attribute_type {
-                'f' => attr.as_float()?.into(),
-                'i' => attr.as_int()?.into(),
-                _ => attr.value.to_str().unwrap().into(),
-            };
+            data[attr.tag.to_str().unwrap()] = attr.to_json_value()?;
         }
 
         if let Some(attributes) = attributes.as_ref() {
             for attr in attributes.iter() {
-                data[attr.tag.to_str().unwrap()] = match attr.attribute_type {
-                    'f' => attr.as_float()?.into(),
-                    'i' => attr.as_int()?.into(),
-                    _ => attr.value.to_str().unwrap().into(),
-                };
+                data[attr.tag.to_str().unwrap()] = attr.to_json_value()?;
             }
         }
         let json = json!({"data": data});
         Ok(json)
     }
 
+    /// Creates node data from its JSON representation produced by [`NodeData::to_json`]
+    ///
+    /// The derived `ref_start` and `ref_end` fields are ignored, and every field that
+    /// is not part of the node itself is restored as an attribute.
+    pub fn from_json(json: &serde_json::Value) -> Result<Self> {
+        let data = json
+            .get("data")
+            .and_then(|d| d.as_object())
+            .context("Node JSON has no data object")?;
+        let field = |key: &str| {
+            data.get(key)
+                .and_then(|v| v.as_str())
+                .with_context(|| format!("Node JSON has no string field {}", key))
+        };
+
+        let id = field("id")?;
+        let exons = field("exons")?;
+        let exons = exons
+            .strip_prefix('[')
+            .and_then(|e| e.strip_suffix(']'))
+            .unwrap_or(exons)
+            .parse()
+            .with_context(|| format!("Failed to parse exons of node {}", id))?;
+
+        let reads = match data.get("reads") {
+            Some(serde_json::Value::Array(reads)) => reads
+                .iter()
+                .map(|r| {
+                    r.as_str()
+                        .context("Read is not a string")?
+                        .parse::<ReadData>()
+                        .with_context(|| format!("Failed to parse reads of node {}", id))
+                })
+                .collect::<Result<Vec<_>>>()?,
+            _ => Vec::new(),
+        };
+
+        let attributes = data
+            .iter()
+            .filter(|(key, _)| !NODE_JSON_FIELDS.contains(&key.as_str()))
+            .filter_map(|(key, value)| Attribute::from_json_value(key, value))
+            .map(|attr| (attr.tag.clone(), attr))
+            .collect();
+
+        Ok(NodeData {
+            id: id.into(),
+            reference_id: field("chrom")?.into(),
+            strand: field("strand")?.parse()?,
+            exons,
+            reads,
+            sequence: data
+                .get("sequence")
+                .and_then(|s| s.as_str())
+                .map(|s| s.into()),
+            attributes,
+        })
+    }
+
     pub fn to_gtf(&self, attributes: Option<&[Attribute]>) -> Result<BString> {
         // chr1    scannls exon    173867960       173867991       .       -       .       exon_id "001"; segment_id "0001"; ptc "1"; ptf "1.0"; transcript_id "3x1"; gene_id "3";
         let mut res = vec![];
@@ -0,0 +1,521 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{Context, Result, anyhow};
+use bon::Builder;
+use bstr::{BString, ByteSlice};
+
+use super::{Attribute, Exons, GraphSection, Group, Interval, Orientation, Strand, TSGPath};
+
+/// Default distance, in nucleotides, between a stop codon and the last exon junction
+/// beyond which the stop codon is considered premature (the 50 nt NMD rule)
+pub const DEFAULT_NMD_DISTANCE: usize = 50;
+
+/// Translate a codon with the standard genetic code.
+///
+/// Stop codons are translated to `*`; codons with ambiguous bases to `X`.
+pub fn translate_codon(codon: &[u8]) -> u8 {
+    let index = |base: u8| match base.to_ascii_uppercase() {
+        b'T' | b'U' => Some(0),
+        b'C' => Some(1),
+        b'A' => Some(2),
+        b'G' => Some(3),
+        _ => None,
+    };
+    const CODE: &[u8; 64] = b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG";
+    match codon {
+        [a, b, c] => match (index(*a), index(*b), index(*c)) {
+            (Some(a), Some(b), Some(c)) => CODE[a * 16 + b * 4 + c],
+            _ => b'X',
+        },
+        _ => b'X',
+    }
+}
+
+/// Translate a nucleotide sequence in frame 0; trailing bases of an incomplete codon are ignored.
+///
+/// ```
+/// use tsg_core::graph::translate;
+/// assert_eq!(translate(b"ATGGCCTAA"), "MA*");
+/// ```
+pub fn translate(seq: &[u8]) -> BString {
+    seq.chunks_exact(3)
+        .map(translate_codon)
+        .collect::<Vec<_>>()
+        .into()
+}
+
+fn is_stop_codon(codon: &[u8]) -> bool {
+    translate_codon(codon) == b'*'
+}
+
+/// Codons accepted as translation start sites when searching ORFs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StartCodons {
+    /// Only the canonical `ATG`
+    #[default]
+    Atg,
+    /// `ATG` and the near-cognate `CTG`, `GTG` and `TTG`
+    NearCognate,
+    /// Any codon: ORFs run from stop to stop and may be open at the 5' end
+    Any,
+}
+
+impl StartCodons {
+    fn is_start(&self, codon: &[u8]) -> bool {
+        let codon = codon.to_ascii_uppercase().replace("U", "T");
+        match self {
+            StartCodons::Atg => codon == b"ATG",
+            StartCodons::NearCognate => matches!(&codon[..], b"ATG" | b"CTG" | b"GTG" | b"TTG"),
+            StartCodons::Any => true,
+        }
+    }
+}
+
+impl FromStr for StartCodons {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "atg" => Ok(StartCodons::Atg),
+            "near-cognate" => Ok(StartCodons::NearCognate),
+            "any" => Ok(StartCodons::Any),
+            _ => Err(anyhow!(
+                "Invalid start codon rule: {} (expected atg, near-cognate or any)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for StartCodons {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StartCodons::Atg => write!(f, "atg"),
+            StartCodons::NearCognate => write!(f, "near-cognate"),
+            StartCodons::Any => write!(f, "any"),
+        }
+    }
+}
+
+/// Options of the ORF search
+#[derive(Debug, Clone, Builder)]
+pub struct OrfOptions {
+    #[builder(default)]
+    pub start_codons: StartCodons,
+    /// Minimum number of amino acids of a reported ORF, stop codon excluded
+    #[builder(default)]
+    pub min_protein_length: usize,
+    /// ORFs without a stop codon before the end of the transcript are only reported when set
+    #[builder(default = true)]
+    pub allow_incomplete: bool,
+    /// Stop codons more than this many nucleotides upstream of the last exon junction are premature
+    #[builder(default = DEFAULT_NMD_DISTANCE)]
+    pub nmd_distance: usize,
+}
+
+impl Default for OrfOptions {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+/// Coding part of one node of a path, in genomic coordinates
+#[derive(Debug, Clone)]
+pub struct CdsSegment {
+    pub node_id: BString,
+    pub reference_id: BString,
+    pub strand: Strand,
+    /// 1-based inclusive coding intervals, sorted by genomic position
+    pub exons: Exons,
+}
+
+impl fmt::Display for CdsSegment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}:{}",
+            self.node_id, self.reference_id, self.strand, self.exons
+        )
+    }
+}
+
+/// An open reading frame of a transcript path
+#[derive(Debug, Clone)]
+pub struct Orf {
+    /// 0-based start of the ORF on the transcript sequence
+    pub start: usize,
+    /// 0-based exclusive end of the ORF on the transcript, including the stop codon
+    pub end: usize,
+    /// Whether the ORF ends with a stop codon rather than running off the transcript
+    pub has_stop: bool,
+    /// Translated protein, without the terminal `*`
+    pub protein: BString,
+    /// CDS projected onto the exons of the path nodes, 5' to 3'; empty when the node
+    /// sequences do not match their exon lengths
+    pub cds: Vec<CdsSegment>,
+    /// Transcript position of the last exon-exon junction, if the path is spliced
+    pub last_junction: Option<usize>,
+    /// Whether the stop codon is premature, i.e. lies more than the NMD distance
+    /// upstream of the last exon junction and should trigger nonsense-mediated decay
+    pub ptc: bool,
+}
+
+impl Orf {
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Distance in nucleotides from the end of the stop codon to the last exon junction;
+    /// negative when the stop codon lies in the last exon
+    pub fn stop_to_last_junction(&self) -> Option<isize> {
+        if !self.has_stop {
+            return None;
+        }
+        self.last_junction
+            .map(|junction| junction as isize - self.end as isize)
+    }
+
+    /// Attributes describing the ORF, as attached to `P` records.
+    ///
+    /// Transcript coordinates are 1-based and inclusive.
+    pub fn attributes(&self) -> Vec<Attribute> {
+        let int = |tag: &str, value: String| {
+            Attribute::builder()
+                .tag(tag)
+                .attribute_type('i')
+                .value(value)
+                .build()
+        };
+        let mut attributes = vec![
+            int("orf_start", (self.start + 1).to_string()),
+            int("orf_end", self.end.to_string()),
+            int("protein_length", self.protein.len().to_string()),
+            int("orf_complete", u8::from(self.has_stop).to_string()),
+            int("ptc", u8::from(self.ptc).to_string()),
+        ];
+        if !self.cds.is_empty() {
+            let cds = self
+                .cds
+                .iter()
+                .map(|segment| segment.to_string())
+                .collect::<Vec<_>>()
+                .join(";");
+            attributes.push(Attribute::builder().tag("cds").value(cds).build());
+        }
+        attributes
+    }
+}
+
+/// A run of transcript bases that maps to a single exon
+#[derive(Debug, Clone)]
+struct Block {
+    node_id: BString,
+    reference_id: BString,
+    strand: Strand,
+    /// Whether the transcript reads the exon from its end to its start
+    reverse: bool,
+    exon: Interval,
+    /// 0-based start of the block on the transcript
+    offset: usize,
+}
+
+impl Block {
+    fn len(&self) -> usize {
+        self.exon.end + 1 - self.exon.start
+    }
+
+    /// Whether `next` continues this block on the genome, i.e. is not across a junction
+    fn is_contiguous(&self, next: &Block) -> bool {
+        self.reference_id == next.reference_id
+            && self.reverse == next.reverse
+            && if self.reverse {
+                next.exon.end + 1 == self.exon.start
+            } else {
+                self.exon.end + 1 == next.exon.start
+            }
+    }
+
+    /// Genomic interval of the transcript range `[start, end)`, clipped to the block
+    fn project(&self, start: usize, end: usize) -> Option<Interval> {
+        let start = start.max(self.offset) - self.offset;
+        let end = end.min(self.offset + self.len()).checked_sub(self.offset)?;
+        if start >= end {
+            return None;
+        }
+        Some(if self.reverse {
+            Interval {
+                start: self.exon.end + 1 - end,
+                end: self.exon.end - start,
+            }
+        } else {
+            Interval {
+                start: self.exon.start + start,
+                end: self.exon.start + end - 1,
+            }
+        })
+    }
+}
+
+/// Find the longest ORF of a sequence as `(start, end, has_stop)`; ties go to the most 5' one
+fn longest_orf(seq: &[u8], options: &OrfOptions) -> Option<(usize, usize, bool)> {
+    let mut best: Option<(usize, usize, bool)> = None;
+    let mut consider = |start: usize, end: usize, has_stop: bool| {
+        let protein_length = (end - start) / 3 - usize::from(has_stop);
+        if protein_length < options.min_protein_length.max(1)
+            || (!has_stop && !options.allow_incomplete)
+        {
+            return;
+        }
+        let better = match best {
+            Some((s, e, _)) => end - start > e - s || (end - start == e - s && start < s),
+            None => true,
+        };
+        if better {
+            best = Some((start, end, has_stop));
+        }
+    };
+
+    for frame in 0..3.min(seq.len()) {
+        let mut open: Option<usize> = None;
+        if options.start_codons == StartCodons::Any {
+            open = Some(frame);
+        }
+        let mut pos = frame;
+        while pos + 3 <= seq.len() {
+            let codon = &seq[pos..pos + 3];
+            if is_stop_codon(codon) {
+                if let Some(start) = open.take() {
+                    consider(start, pos + 3, true);
+                }
+                if options.start_codons == StartCodons::Any {
+                    open = Some(pos + 3);
+                }
+            } else if open.is_none() && options.start_codons.is_start(codon) {
+                open = Some(pos);
+            }
+            pos += 3;
+        }
+        if let Some(start) = open
+            && pos > start
+        {
+            consider(start, pos, false);
+        }
+    }
+    best
+}
+
+impl TSGPath<'_> {
+    /// Exons of the path in transcript order, or `None` when a node sequence does not
+    /// match the length of its exons and transcript positions cannot be projected
+    fn transcript_blocks(&self) -> Result<Option<Vec<Block>>> {
+        let graph = self.graph().ok_or_else(|| anyhow!("Graph not available"))?;
+        let mut blocks = vec![];
+        let mut offset = 0;
+        for (idx, node_idx) in self.nodes.iter().enumerate() {
+            let node = graph
+                .node_by_idx(*node_idx)
+                .with_context(|| format!("Node not found for index: {}", node_idx.index()))?;
+            let exon_length: usize = node.exons.exons.iter().map(|e| e.end + 1 - e.start).sum();
+            if node.exons.is_empty() || node.sequence.as_ref().map(|s| s.len()) != Some(exon_length)
+            {
+                return Ok(None);
+            }
+
+            let reverse = (node.strand == Strand::Reverse)
+                != (self.node_orientation(idx) == Orientation::Reverse);
+            let mut exons = node.exons.exons.clone();
+            if reverse {
+                exons.reverse();
+            }
+            for exon in exons {
+                let block = Block {
+                    node_id: node.id.clone(),
+                    reference_id: node.reference_id.clone(),
+                    strand: node.strand,
+                    reverse,
+                    exon,
+                    offset,
+                };
+                offset += block.len();
+                blocks.push(block);
+            }
+        }
+        Ok(Some(blocks))
+    }
+
+    /// Find the longest open reading frame of the path sequence.
+    ///
+    /// All three frames of the transcript are searched, 5' to 3'. The CDS is projected
+    /// back onto the exons of the path nodes, and the stop codon is flagged as premature
+    /// when it lies more than [`OrfOptions::nmd_distance`] nucleotides upstream of the
+    /// last exon-exon junction. Returns `None` when the path has no ORF.
+    pub fn find_orf(&self, options: &OrfOptions) -> Result<Option<Orf>> {
+        let seq = self.to_fa()?;
+        let Some((start, end, has_stop)) = longest_orf(&seq, options) else {
+            return Ok(None);
+        };
+        let mut protein = translate(&seq[start..end]);
+        if has_stop {
+            protein.pop();
+        }
+
+        let blocks = self.transcript_blocks()?.unwrap_or_default();
+        let last_junction = blocks
+            .windows(2)
+            .filter(|pair| !pair[0].is_contiguous(&pair[1]))
+            .map(|pair| pair[1].offset)
+            .next_back();
+
+        let mut cds: Vec<CdsSegment> = vec![];
+        for block in &blocks {
+            let Some(interval) = block.project(start, end) else {
+                continue;
+            };
+            match cds.last_mut() {
+                Some(segment) if segment.node_id == block.node_id => {
+                    segment.exons.exons.push(interval)
+                }
+                _ => cds.push(CdsSegment {
+                    node_id: block.node_id.clone(),
+                    reference_id: block.reference_id.clone(),
+                    strand: block.strand,
+                    exons: Exons {
+                        exons: vec![interval],
+                    },
+                }),
+            }
+        }
+        for segment in &mut cds {
+            segment.exons.exons.sort_by_key(|exon| exon.start);
+        }
+
+        let mut orf = Orf {
+            start,
+            end,
+            has_stop,
+            protein,
+            cds,
+            last_junction,
+            ptc: false,
+        };
+        orf.ptc = orf
+            .stop_to_last_junction()
+            .is_some_and(|distance| distance > options.nmd_distance as isize);
+        Ok(Some(orf))
+    }
+
+    /// Protein sequence of the longest ORF of the path, see [`TSGPath::find_orf`]
+    pub fn to_protein(&self, options: &OrfOptions) -> Result<Option<BString>> {
+        Ok(self.find_orf(options)?.map(|orf| orf.protein))
+    }
+}
+
+impl GraphSection {
+    /// Find the longest ORF of every `P` record and attach it as attributes of the record,
+    /// see [`Orf::attributes`]. Returns the number of paths with an ORF.
+    ///
+    /// Node sequences must be filled, e.g. from a reference genome.
+    pub fn annotate_orfs(&mut self, options: &OrfOptions) -> Result<usize> {
+        let mut annotations = vec![];
+        for path in self.ordered_paths()? {
+            if let Some(orf) = path.find_orf(options)? {
+                annotations.push((path.id()?, orf.attributes()));
+            }
+        }
+
+        let annotated = annotations.len();
+        for (id, orf_attributes) in annotations {
+            if let Some(Group::Ordered { attributes, .. }) = self.groups.get_mut(&id) {
+                for attr in orf_attributes {
+                    attributes.insert(attr.tag.clone(), attr);
+                }
+            }
+        }
+        Ok(annotated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::TSGraph;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_longest_orf() {
+        let options = OrfOptions::default();
+        // ATG AAA TGA in frame 1 is shorter than ATG CCC GGG TAA in frame 0
+        let seq = b"ATGCCCGGGTAAGATGAAATGA";
+        assert_eq!(longest_orf(seq, &options), Some((0, 12, true)));
+        assert_eq!(translate(&seq[0..12]), "MPG*");
+
+        // no ATG, but CTG is accepted as a near-cognate start
+        let seq = b"CTGAAACCCTAG";
+        assert_eq!(longest_orf(seq, &options), None);
+        let near = OrfOptions::builder()
+            .start_codons(StartCodons::NearCognate)
+            .build();
+        assert_eq!(longest_orf(seq, &near), Some((0, 12, true)));
+
+        // a 3' incomplete ORF
+        assert_eq!(longest_orf(b"CCATGAAACC", &options), Some((2, 8, false)));
+        let complete = OrfOptions::builder().allow_incomplete(false).build();
+        assert_eq!(longest_orf(b"CCATGAAACC", &complete), None);
+        assert!("stop".parse::<StartCodons>().is_err());
+    }
+
+    #[test]
+    fn test_find_orf_with_ptc() -> Result<()> {
+        // minus-strand gene: n1 is the first exon, the stop codon falls in n2, far
+        // upstream of the n2-n3 junction
+        let n2 = "C".repeat(54);
+        let tsg = TSGraph::from_str(&format!(
+            "G\tg1\n\
+             N\tn1\tchr1:-:1001-1006\tr1:SO\tATGGAA\n\
+             N\tn2\tchr1:-:501-560\tr1:IN\tCCCTAA{}\n\
+             N\tn3\tchr1:-:101-160\tr1:SI\t{}\n\
+             E\te1\tn1\tn2\tchr1,chr1,1001,560,splice\n\
+             E\te2\tn2\tn3\tchr1,chr1,501,160,splice\n\
+             P\tt1\tn1+\te1+\tn2+\te2+\tn3+\n",
+            n2,
+            "G".repeat(60)
+        ))?;
+        let path = tsg.graph("g1").unwrap().path_from_group("t1")?;
+        let orf = path.find_orf(&OrfOptions::default())?.unwrap();
+
+        // ATG GAA CCC TAA
+        assert_eq!((orf.start, orf.end), (0, 12));
+        assert!(orf.has_stop);
+        assert_eq!(orf.protein, "MEP");
+        assert_eq!(orf.last_junction, Some(66));
+        assert!(orf.ptc);
+
+        let cds: Vec<String> = orf.cds.iter().map(|s| s.to_string()).collect();
+        assert_eq!(cds, vec!["n1:chr1:-:1001-1006", "n2:chr1:-:555-560"]);
+
+        let attributes = orf.attributes();
+        assert_eq!(attributes.len(), 6);
+        assert_eq!(attributes[0].value, "1");
+        assert_eq!(attributes[4].tag, "ptc");
+        assert_eq!(attributes[4].value, "1");
+        assert_eq!(path.to_protein(&OrfOptions::default())?.unwrap(), "MEP");
+
+        let lenient = OrfOptions::builder().nmd_distance(100).build();
+        assert!(!path.find_orf(&lenient)?.unwrap().ptc);
+
+        let mut tsg = tsg;
+        let graph = tsg.graphs.values_mut().next().unwrap();
+        assert_eq!(graph.annotate_orfs(&OrfOptions::default())?, 1);
+        let mut output = vec![];
+        tsg.to_writer(&mut output)?;
+        let output = output.to_str()?;
+        assert!(output.contains("A\tP\tt1\tptc:i:1"));
+        assert!(output.contains("A\tP\tt1\tprotein_length:i:3"));
+        Ok(())
+    }
+}
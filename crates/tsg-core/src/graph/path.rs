@@ -4,7 +4,7 @@ use crate::graph::to_numeric_identifier;
 
 use super::Attribute;
 use super::GraphSection;
-use ahash::HashSet;
+use super::Orientation;
 use anyhow::{Context, Result, anyhow};
 use bon::Builder;
 use bstr::{BString, ByteSlice, ByteVec};
@@ -23,6 +23,12 @@ pub struct TSGPath<'a> {
     /// The edges connecting the nodes in the path
     #[builder(default)]
     pub edges: Vec<EdgeIndex>,
+    /// Orientation of each node along the path; empty when every node is read forward
+    #[builder(default)]
+    pub orientations: Vec<Orientation>,
+    /// Explicit path id, e.g. of the `P` record the path was built from
+    #[builder(into)]
+    pub name: Option<BString>,
     graph: Option<&'a GraphSection>,
     #[builder(default)]
     pub attributes: Vec<Attribute>,
@@ -45,7 +51,11 @@ impl fmt::Display for TSGPath<'_> {
                 .unwrap();
 
             let node_id = &node_data.id;
-            res.push(format!("{}+", node_id));
+            let sign = match self.node_orientation(idx) {
+                Orientation::Forward => '+',
+                Orientation::Reverse => '-',
+            };
+            res.push(format!("{}{}", node_id, sign));
             if idx < self.nodes.len() - 1 {
                 let edge_data = self
                     .graph
@@ -81,9 +91,28 @@ impl<'a> TSGPath<'a> {
 
     /// Add a node to the path
     pub fn add_node(&mut self, node: NodeIndex) {
+        self.add_oriented_node(node, Orientation::Forward);
+    }
+
+    /// Add a node to the path, read in the given orientation
+    pub fn add_oriented_node(&mut self, node: NodeIndex, orientation: Orientation) {
+        if self.orientations.is_empty() && orientation == Orientation::Reverse {
+            self.orientations = vec![Orientation::Forward; self.nodes.len()];
+        }
+        if !self.orientations.is_empty() {
+            self.orientations.push(orientation);
+        }
         self.nodes.push(node);
     }
 
+    /// Orientation of the `index`-th node of the path
+    pub fn node_orientation(&self, index: usize) -> Orientation {
+        self.orientations
+            .get(index)
+            .copied()
+            .unwrap_or(Orientation::Forward)
+    }
+
     /// Add an edge to the path
     pub fn add_edge(&mut self, edge: EdgeIndex) {
         self.edges.push(edge);
@@ -99,7 +128,11 @@ impl<'a> TSGPath<'a> {
         self.nodes.len()
     }
 
+    /// The path id: its explicit name if set, otherwise a hash of its node ids
     pub fn id(&self) -> Result<BString> {
+        if let Some(name) = &self.name {
+            return Ok(name.clone());
+        }
         if self.nodes.is_empty() {
             return Err(anyhow!("No nodes in path"));
         }
@@ -201,21 +234,13 @@ impl<'a> TSGPath<'a> {
                 format!("Node not found for index: {}", target_node_idx.index())
             })?;
 
-            let source_read_ids = &source_node_data
-                .reads
-                .iter()
-                .map(|r| r.id.clone())
-                .collect::<HashSet<_>>();
-            let target_read_ids = &target_node_data
-                .reads
+            // reads shared by the source and target node support the junction
+            let sharing_read_ids = graph
+                .edge_supporting_reads(*edge_idx)?
                 .iter()
-                .map(|r| r.id.clone())
-                .collect::<HashSet<_>>();
-            // get sharing read ids
-            let sharing_read_ids = source_read_ids
-                .intersection(target_read_ids)
                 .map(|r| r.to_string())
                 .collect::<Vec<_>>();
+            let support = graph.edge_support(*edge_idx)?;
 
             let mut node_attributes = vec![
                 Attribute::builder()
@@ -239,6 +264,7 @@ impl<'a> TSGPath<'a> {
                     .value(sharing_read_ids.join(","))
                     .build(),
             ];
+            node_attributes.extend(support.attributes());
             node_attributes.extend_from_slice(&sharing_attributes);
 
             let edge_data = graph
@@ -253,21 +279,22 @@ impl<'a> TSGPath<'a> {
         Ok(edge_strs.join("\n").into())
     }
 
+    /// The transcript sequence of the path, 5' to 3'.
+    ///
+    /// Node sequences are stored along their strand; nodes read in reverse in the path
+    /// are reverse complemented, see [`super::NodeData::oriented_sequence`].
     pub fn to_fa(&self) -> Result<BString> {
         let mut seq = BString::from("");
-        for node_idx in &self.nodes {
+        for (idx, node_idx) in self.nodes.iter().enumerate() {
             let node_data = self
                 .graph
-                .ok_or_else(|| anyhow!("Graph not available"))
-                .unwrap()
+                .ok_or_else(|| anyhow!("Graph not available"))?
                 .node_by_idx(*node_idx)
-                .context(format!("Node not found for index: {}", node_idx.index()))
-                .unwrap();
+                .context(format!("Node not found for index: {}", node_idx.index()))?;
 
             let node_seq = node_data
-                .sequence
-                .as_ref()
-                .ok_or_else(|| anyhow!("Node sequence not found"))?;
+                .oriented_sequence(self.node_orientation(idx))
+                .ok_or_else(|| anyhow!("Node sequence not found for node {}", node_data.id))?;
             seq.push_str(node_seq);
         }
         Ok(seq)
@@ -286,4 +313,36 @@ mod tests {
         assert_eq!(path.edges.len(), 0);
         assert!(path.graph().is_none());
     }
+
+    #[test]
+    fn test_strand_aware_sequence() -> Result<()> {
+        use crate::graph::TSGraph;
+        use std::str::FromStr;
+
+        // a minus-strand transcript listed 5' to 3', and an inversion read through n2-
+        let tsg = TSGraph::from_str(
+            "G\tg1\n\
+             N\tn1\tchr1:-:300-305\tr1:SO\tCCGGTT\n\
+             N\tn2\tchr1:-:100-103\tr1:SI\tGCAT\n\
+             N\tn3\tchr1:+:500-503\tr2:SI\tTTTA\n\
+             E\te1\tn1\tn2\tchr1,chr1,300,103,splice\n\
+             E\te2\tn1\tn3\tchr1,chr1,300,500,INV\n\
+             P\tt1\tn1+\te1+\tn2+\n\
+             P\tt2\tn1+\te2+\tn3-\n",
+        )?;
+        let graph = tsg.graph("g1").unwrap();
+
+        let t1 = graph.path_from_group("t1")?;
+        assert_eq!(t1.id()?, "t1");
+        assert_eq!(t1.to_fa()?, "CCGGTTGCAT");
+
+        // n3 is on the forward strand but read in reverse
+        let t2 = graph.path_from_group("t2")?;
+        assert_eq!(t2.to_string(), "P\tt2\tn1+\te2+\tn3-");
+        assert_eq!(t2.to_fa()?, "CCGGTTTAAA");
+
+        assert_eq!(graph.ordered_paths()?.len(), 2);
+        assert!(graph.path_from_group("t3").is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,248 @@
+use std::io::{BufRead, Lines};
+use std::path::Path;
+
+use anyhow::Result;
+use bstr::BString;
+
+use super::{DEFAULT_GRAPH_ID, GraphSection, Header, InterGraphLink, TSGraph};
+
+/// One graph section read by [`TSGraphReader`]
+#[derive(Debug, Clone)]
+pub struct StreamedGraph {
+    /// Global headers read so far
+    pub headers: Vec<Header>,
+    pub section: GraphSection,
+    /// Links whose `L` lines were read together with this section, i.e. after its `G`
+    /// line and before the next one. They are not validated, since the graphs they
+    /// connect may not have been read yet.
+    pub links: Vec<InterGraphLink>,
+}
+
+impl StreamedGraph {
+    /// Wrap the section in a TSGraph of its own, e.g. to use whole-graph APIs on it
+    pub fn into_tsgraph(self) -> TSGraph {
+        let mut tsgraph = TSGraph {
+            headers: self.headers,
+            links: self.links,
+            ..Default::default()
+        };
+        tsgraph.graphs.insert(self.section.id.clone(), self.section);
+        tsgraph
+    }
+}
+
+enum Source<R> {
+    Text(Lines<R>),
+    /// Sections of a file that had to be loaded at once, such as the binary encoding
+    Loaded(std::vec::IntoIter<StreamedGraph>),
+}
+
+/// Streaming reader that yields one complete graph section at a time.
+///
+/// Sections are delimited by `G` lines; records before the first `G` line form the
+/// default graph. Only the section being read is kept in memory, so memory use is
+/// bounded by the largest graph rather than the whole file.
+///
+/// ```
+/// use tsg_core::graph::TSGraph;
+///
+/// for graph in TSGraph::stream_file("tests/data/test_mul.tsg").unwrap() {
+///     let graph = graph.unwrap();
+///     println!("{}: {} nodes", graph.section.id, graph.section.nodes().len());
+/// }
+/// ```
+pub struct TSGraphReader<R> {
+    source: Source<R>,
+    /// Parser state holding the headers and the section being read
+    state: TSGraph,
+    /// `G` line that starts the next section
+    pending: Option<String>,
+    done: bool,
+}
+
+impl<R: BufRead> TSGraphReader<R> {
+    /// Create a reader over TSG text
+    pub fn new(reader: R) -> Self {
+        Self {
+            source: Source::Text(reader.lines()),
+            state: TSGraph::new(),
+            pending: None,
+            done: false,
+        }
+    }
+
+    /// Global headers read so far
+    pub fn headers(&self) -> &[Header] {
+        &self.state.headers
+    }
+
+    /// Whether the parser state holds anything worth yielding
+    fn has_section(&self) -> bool {
+        self.state.graphs.values().any(|graph| {
+            graph.id != DEFAULT_GRAPH_ID
+                || !graph.node_indices.is_empty()
+                || !graph.edge_indices.is_empty()
+                || !graph.groups.is_empty()
+        })
+    }
+
+    /// Finish the section being read and reset the parser state for the next one
+    fn take_section(&mut self) -> Result<Option<StreamedGraph>> {
+        let links = std::mem::take(&mut self.state.links);
+        let Some(mut section) = self.state.graphs.drain().map(|(_, graph)| graph).next() else {
+            return Ok(None);
+        };
+        section.finalize()?;
+        section.validate_paths()?;
+
+        // an implicit default graph without nodes is not a graph
+        if section.id == DEFAULT_GRAPH_ID && section.node_indices.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(StreamedGraph {
+            headers: self.state.headers.clone(),
+            section,
+            links,
+        }))
+    }
+
+    fn next_line(&mut self) -> Option<std::io::Result<String>> {
+        match &mut self.source {
+            Source::Text(lines) => lines.next(),
+            Source::Loaded(_) => None,
+        }
+    }
+
+    fn next_section(&mut self) -> Result<Option<StreamedGraph>> {
+        if let Source::Loaded(sections) = &mut self.source {
+            return Ok(sections.next());
+        }
+
+        if let Some(line) = self.pending.take() {
+            self.state.parse_record(&line)?;
+        }
+
+        while let Some(line) = self.next_line() {
+            let line = line?;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.first() {
+                Some(&"G") => {
+                    if self.has_section() {
+                        let finished = self.take_section()?;
+                        if finished.is_some() {
+                            self.pending = Some(line);
+                            return Ok(finished);
+                        }
+                    }
+                    self.state.graphs.clear();
+                    self.state.parse_record(&line)?;
+                }
+                Some(&"L") => {
+                    let link = TSGraph::parse_link(&fields)?;
+                    self.state.links.push(link);
+                }
+                _ => self.state.parse_record(&line)?,
+            }
+        }
+
+        self.done = true;
+        self.take_section()
+    }
+}
+
+impl<R: BufRead> Iterator for TSGraphReader<R> {
+    type Item = Result<StreamedGraph>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done && matches!(self.source, Source::Text(_)) {
+            return None;
+        }
+        match self.next_section() {
+            Ok(graph) => graph.map(Ok),
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl TSGraph {
+    /// Stream the graph sections of a TSG file one at a time, see [`TSGraphReader`].
+    ///
+    /// Compressed files are decompressed on the fly. Binary TSG files cannot be read
+    /// section by section and are loaded at once.
+    pub fn stream_file<P: AsRef<Path>>(path: P) -> Result<TSGraphReader<Box<dyn BufRead>>> {
+        let mut reader = crate::io::open_reader(path)?;
+        if !crate::io::is_binary(reader.fill_buf()?) {
+            return Ok(TSGraphReader::new(reader));
+        }
+
+        let mut tsgraph = Self::from_binary(&mut reader)?;
+        let mut ids: Vec<BString> = tsgraph.graphs.keys().cloned().collect();
+        ids.sort();
+        let sections = ids
+            .into_iter()
+            .filter_map(|id| tsgraph.graphs.remove(&id))
+            .map(|section| StreamedGraph {
+                headers: tsgraph.headers.clone(),
+                links: tsgraph
+                    .links
+                    .iter()
+                    .filter(|link| link.source_graph == section.id)
+                    .cloned()
+                    .collect(),
+                section,
+            })
+            .collect::<Vec<_>>();
+
+        Ok(TSGraphReader {
+            source: Source::Loaded(sections.into_iter()),
+            state: TSGraph::default(),
+            pending: None,
+            done: true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_multiple_graphs() -> Result<()> {
+        let full = TSGraph::from_file("tests/data/test_mul.tsg")?;
+        let graphs =
+            TSGraph::stream_file("tests/data/test_mul.tsg")?.collect::<Result<Vec<_>>>()?;
+
+        let ids: Vec<_> = graphs.iter().map(|g| g.section.id.to_string()).collect();
+        assert_eq!(ids, vec!["gene_a", "gene_b"]);
+        for graph in &graphs {
+            let expected = full.graph(&graph.section.id.to_string()).unwrap();
+            assert_eq!(graph.headers.len(), 2);
+            assert_eq!(graph.section.nodes().len(), expected.nodes().len());
+            assert_eq!(graph.section.edges().len(), expected.edges().len());
+            assert_eq!(graph.section.traverse()?.len(), expected.traverse()?.len());
+        }
+        // the trailing link is read with the last section
+        assert!(graphs[0].links.is_empty());
+        assert_eq!(graphs[1].links.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_default_graph() -> Result<()> {
+        let graphs = TSGraph::stream_file("tests/data/test.tsg")?.collect::<Result<Vec<_>>>()?;
+        assert_eq!(graphs.len(), 1);
+        assert_eq!(graphs[0].section.id, DEFAULT_GRAPH_ID);
+
+        let empty = TSGraphReader::new("H\tTSG\t1.0\n".as_bytes()).collect::<Result<Vec<_>>>()?;
+        assert!(empty.is_empty());
+
+        let mut reader =
+            TSGraphReader::new("G\tg1\nN\tn1\tchr1:+:1-10\tr1:SO\nE\te1\tn1\tn2\n".as_bytes());
+        assert!(reader.next().unwrap().is_err());
+        assert!(reader.next().is_none());
+        Ok(())
+    }
+}
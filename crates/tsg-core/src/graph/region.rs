@@ -0,0 +1,449 @@
+use std::fmt;
+use std::str::FromStr;
+
+use ahash::{HashMap, HashMapExt, HashSet, HashSetExt};
+use anyhow::{Result, anyhow};
+use bstr::BString;
+use petgraph::graph::{EdgeIndex, NodeIndex};
+
+use super::{EdgeData, GraphSection, NodeData, TSGraph};
+
+/// A genomic region, 1-based and inclusive on both ends
+///
+/// Regions are written as `chrom`, `chrom:pos` or `chrom:start-end`; thousands
+/// separators are allowed, so `chr7:55,000,000-55,300,000` is a valid region.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenomicRegion {
+    pub reference_id: BString,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl GenomicRegion {
+    pub fn new(reference_id: impl Into<BString>, start: usize, end: usize) -> Self {
+        Self {
+            reference_id: reference_id.into(),
+            start,
+            end,
+        }
+    }
+
+    /// Check whether the region overlaps `[start, end]` on `reference_id`
+    pub fn overlaps(&self, reference_id: &[u8], start: usize, end: usize) -> bool {
+        self.reference_id == reference_id && self.start <= end && start <= self.end
+    }
+}
+
+impl FromStr for GenomicRegion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parse_position = |p: &str| -> Option<usize> { p.replace([',', '_'], "").parse().ok() };
+
+        // reference names may contain ':' themselves, so only a parsable suffix is a range
+        if let Some((reference_id, range)) = s.rsplit_once(':') {
+            let range = match range.split_once('-') {
+                Some((start, end)) => parse_position(start).zip(parse_position(end)),
+                None => parse_position(range).map(|pos| (pos, pos)),
+            };
+            if let Some((start, end)) = range {
+                if reference_id.is_empty() || start > end {
+                    return Err(anyhow!("Invalid region: {}", s));
+                }
+                return Ok(Self::new(reference_id, start, end));
+            }
+        }
+
+        if s.is_empty() {
+            return Err(anyhow!("Invalid region: empty reference name"));
+        }
+        Ok(Self::new(s, 0, usize::MAX))
+    }
+}
+
+impl fmt::Display for GenomicRegion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.start == 0 && self.end == usize::MAX {
+            write!(f, "{}", self.reference_id)
+        } else {
+            write!(f, "{}:{}-{}", self.reference_id, self.start, self.end)
+        }
+    }
+}
+
+/// Static interval tree over closed intervals.
+///
+/// The intervals are sorted by start and laid out as an implicit balanced binary
+/// tree, where every subtree records the largest end it contains. Queries take
+/// O(log n + k) time for k hits.
+#[derive(Debug, Clone)]
+pub struct IntervalTree<T> {
+    entries: Vec<(usize, usize, T)>,
+    max_end: Vec<usize>,
+}
+
+impl<T> IntervalTree<T> {
+    /// Build the tree from `(start, end, value)` triples
+    pub fn new(mut entries: Vec<(usize, usize, T)>) -> Self {
+        entries.sort_by_key(|(start, end, _)| (*start, *end));
+        let mut tree = Self {
+            max_end: vec![0; entries.len()],
+            entries,
+        };
+        tree.fill_max_end(0, tree.entries.len());
+        tree
+    }
+
+    fn fill_max_end(&mut self, lo: usize, hi: usize) -> usize {
+        if lo >= hi {
+            return 0;
+        }
+        let mid = lo + (hi - lo) / 2;
+        let max_end = self.entries[mid]
+            .1
+            .max(self.fill_max_end(lo, mid))
+            .max(self.fill_max_end(mid + 1, hi));
+        self.max_end[mid] = max_end;
+        max_end
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Values of all intervals overlapping `[start, end]`, in order of their start
+    pub fn query(&self, start: usize, end: usize) -> Vec<&T> {
+        let mut hits = Vec::new();
+        self.query_range(0, self.entries.len(), start, end, &mut hits);
+        hits
+    }
+
+    fn query_range<'a>(
+        &'a self,
+        lo: usize,
+        hi: usize,
+        start: usize,
+        end: usize,
+        hits: &mut Vec<&'a T>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let mid = lo + (hi - lo) / 2;
+        if self.max_end[mid] < start {
+            return;
+        }
+        self.query_range(lo, mid, start, end, hits);
+        let (entry_start, entry_end, value) = &self.entries[mid];
+        if *entry_start > end {
+            return;
+        }
+        if *entry_end >= start {
+            hits.push(value);
+        }
+        self.query_range(mid + 1, hi, start, end, hits);
+    }
+}
+
+/// A graph element located by a [`RegionIndex`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RegionElement {
+    /// A node, located by its exons
+    Node(NodeIndex),
+    /// An edge, located by its two breakpoints
+    Edge(EdgeIndex),
+}
+
+/// An element found by a region query, with the graph it belongs to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RegionHit {
+    pub graph_id: BString,
+    pub element: RegionElement,
+}
+
+/// Interval-tree index over node exons and edge breakpoints of one or more graphs,
+/// with one tree per reference sequence
+#[derive(Debug, Clone, Default)]
+pub struct RegionIndex {
+    trees: HashMap<BString, IntervalTree<RegionHit>>,
+}
+
+impl RegionIndex {
+    /// Index the node exons and edge breakpoints of the given graphs
+    pub fn new<'a>(graphs: impl IntoIterator<Item = &'a GraphSection>) -> Self {
+        let mut entries: HashMap<BString, Vec<(usize, usize, RegionHit)>> = HashMap::new();
+        for graph in graphs {
+            for node_idx in graph._graph.node_indices() {
+                let node = &graph._graph[node_idx];
+                for exon in &node.exons.exons {
+                    entries.entry(node.reference_id.clone()).or_default().push((
+                        exon.start,
+                        exon.end,
+                        RegionHit {
+                            graph_id: graph.id.clone(),
+                            element: RegionElement::Node(node_idx),
+                        },
+                    ));
+                }
+            }
+            for edge_idx in graph._graph.edge_indices() {
+                let sv = &graph._graph[edge_idx].sv;
+                for (reference_id, breakpoint) in [
+                    (&sv.reference_name1, sv.breakpoint1),
+                    (&sv.reference_name2, sv.breakpoint2),
+                ] {
+                    if reference_id.is_empty() {
+                        continue;
+                    }
+                    entries.entry(reference_id.clone()).or_default().push((
+                        breakpoint,
+                        breakpoint,
+                        RegionHit {
+                            graph_id: graph.id.clone(),
+                            element: RegionElement::Edge(edge_idx),
+                        },
+                    ));
+                }
+            }
+        }
+
+        Self {
+            trees: entries
+                .into_iter()
+                .map(|(reference_id, entries)| (reference_id, IntervalTree::new(entries)))
+                .collect(),
+        }
+    }
+
+    /// All elements overlapping the region, each reported once
+    pub fn query(&self, region: &GenomicRegion) -> Vec<&RegionHit> {
+        let Some(tree) = self.trees.get(&region.reference_id) else {
+            return Vec::new();
+        };
+        let mut seen = HashSet::new();
+        tree.query(region.start, region.end)
+            .into_iter()
+            .filter(|hit| seen.insert(*hit))
+            .collect()
+    }
+
+    /// Nodes of `graph` with at least one exon overlapping the region
+    pub fn nodes_in_region<'g>(
+        &self,
+        graph: &'g GraphSection,
+        region: &GenomicRegion,
+    ) -> Vec<&'g NodeData> {
+        self.query(region)
+            .into_iter()
+            .filter(|hit| hit.graph_id == graph.id)
+            .filter_map(|hit| match hit.element {
+                RegionElement::Node(idx) => graph.node_by_idx(idx),
+                RegionElement::Edge(_) => None,
+            })
+            .collect()
+    }
+
+    /// Edges of `graph` with a breakpoint inside the region
+    pub fn edges_in_region<'g>(
+        &self,
+        graph: &'g GraphSection,
+        region: &GenomicRegion,
+    ) -> Vec<&'g EdgeData> {
+        self.query(region)
+            .into_iter()
+            .filter(|hit| hit.graph_id == graph.id)
+            .filter_map(|hit| match hit.element {
+                RegionElement::Edge(idx) => graph.edge_by_idx(idx),
+                RegionElement::Node(_) => None,
+            })
+            .collect()
+    }
+
+    /// Ids of the graphs with at least one element overlapping the region, sorted
+    pub fn graph_ids(&self, region: &GenomicRegion) -> Vec<&BString> {
+        let mut ids: Vec<&BString> = self
+            .query(region)
+            .into_iter()
+            .map(|hit| &hit.graph_id)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        ids.sort();
+        ids
+    }
+}
+
+impl GraphSection {
+    /// Build an interval-tree index over the node exons and edge breakpoints of the section.
+    ///
+    /// Keep the index around when running many queries against the same section.
+    pub fn region_index(&self) -> RegionIndex {
+        RegionIndex::new([self])
+    }
+
+    /// Nodes with at least one exon overlapping the region.
+    ///
+    /// This scans the section; for repeated queries build a [`RegionIndex`] once with
+    /// [`Self::region_index`] and use [`RegionIndex::nodes_in_region`].
+    pub fn nodes_in_region(&self, region: &GenomicRegion) -> Vec<&NodeData> {
+        self._graph
+            .node_weights()
+            .filter(|node| {
+                node.exons
+                    .exons
+                    .iter()
+                    .any(|exon| region.overlaps(&node.reference_id, exon.start, exon.end))
+            })
+            .collect()
+    }
+
+    /// Edges with a breakpoint inside the region.
+    ///
+    /// This scans the section; for repeated queries build a [`RegionIndex`] once with
+    /// [`Self::region_index`] and use [`RegionIndex::edges_in_region`].
+    pub fn edges_in_region(&self, region: &GenomicRegion) -> Vec<&EdgeData> {
+        self._graph
+            .edge_weights()
+            .filter(|edge| {
+                region.overlaps(
+                    &edge.sv.reference_name1,
+                    edge.sv.breakpoint1,
+                    edge.sv.breakpoint1,
+                ) || region.overlaps(
+                    &edge.sv.reference_name2,
+                    edge.sv.breakpoint2,
+                    edge.sv.breakpoint2,
+                )
+            })
+            .collect()
+    }
+
+    /// Check whether any node exon or edge breakpoint of the section overlaps the region
+    pub fn overlaps_region(&self, region: &GenomicRegion) -> bool {
+        self._graph.node_weights().any(|node| {
+            node.exons
+                .exons
+                .iter()
+                .any(|exon| region.overlaps(&node.reference_id, exon.start, exon.end))
+        }) || self.edges().iter().any(|edge| {
+            region.overlaps(
+                &edge.sv.reference_name1,
+                edge.sv.breakpoint1,
+                edge.sv.breakpoint1,
+            ) || region.overlaps(
+                &edge.sv.reference_name2,
+                edge.sv.breakpoint2,
+                edge.sv.breakpoint2,
+            )
+        })
+    }
+
+    /// Span of the node exons of the section on each reference
+    pub fn reference_spans(&self) -> HashMap<BString, (usize, usize)> {
+        let mut spans: HashMap<BString, (usize, usize)> = HashMap::new();
+        for node in self._graph.node_weights() {
+            if node.exons.exons.is_empty() {
+                continue;
+            }
+            let (start, end) = (node.reference_start(), node.reference_end());
+            spans
+                .entry(node.reference_id.clone())
+                .and_modify(|span| *span = (span.0.min(start), span.1.max(end)))
+                .or_insert((start, end));
+        }
+        spans
+    }
+}
+
+impl TSGraph {
+    /// Build an interval-tree index over the node exons and edge breakpoints of all graphs
+    pub fn region_index(&self) -> RegionIndex {
+        RegionIndex::new(self.graphs.values())
+    }
+
+    /// Graphs with at least one node exon or edge breakpoint overlapping the region, sorted by id
+    pub fn graphs_in_region(&self, region: &GenomicRegion) -> Vec<&GraphSection> {
+        let mut graphs: Vec<&GraphSection> = self
+            .graphs
+            .values()
+            .filter(|graph| graph.overlaps_region(region))
+            .collect();
+        graphs.sort_by(|a, b| a.id.cmp(&b.id));
+        graphs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_region() -> Result<()> {
+        let region: GenomicRegion = "chr7:55,000,000-55,300,000".parse()?;
+        assert_eq!(region, GenomicRegion::new("chr7", 55_000_000, 55_300_000));
+        assert_eq!(region.to_string(), "chr7:55000000-55300000");
+
+        let point: GenomicRegion = "chr1:100".parse()?;
+        assert_eq!((point.start, point.end), (100, 100));
+
+        let whole: GenomicRegion = "chrUn_KI270302v1".parse()?;
+        assert_eq!(whole.to_string(), "chrUn_KI270302v1");
+        let hla: GenomicRegion = "HLA-A*01:01:1-100".parse()?;
+        assert_eq!(hla.reference_id, "HLA-A*01:01");
+
+        assert!("chr1:200-100".parse::<GenomicRegion>().is_err());
+        assert!("".parse::<GenomicRegion>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_interval_tree() {
+        let entries: Vec<(usize, usize, usize)> = (0..200)
+            .map(|i| (i * 10, i * 10 + (i % 7) * 5, i))
+            .collect();
+        let tree = IntervalTree::new(entries.clone());
+        for (start, end) in [(0, 0), (15, 35), (995, 1001), (3000, 4000), (1, 1999)] {
+            let mut expected: Vec<usize> = entries
+                .iter()
+                .filter(|(s, e, _)| *s <= end && start <= *e)
+                .map(|(_, _, v)| *v)
+                .collect();
+            let mut hits: Vec<usize> = tree.query(start, end).into_iter().copied().collect();
+            expected.sort();
+            hits.sort();
+            assert_eq!(hits, expected);
+        }
+    }
+
+    #[test]
+    fn test_region_queries() -> Result<()> {
+        let tsg = TSGraph::from_file("tests/data/test_mul.tsg")?;
+
+        let region: GenomicRegion = "chr17:41,199,700-41,199,800".parse()?;
+        let graphs = tsg.graphs_in_region(&region);
+        assert_eq!(graphs.len(), 1);
+        assert_eq!(graphs[0].id, "gene_a");
+        let nodes = graphs[0].nodes_in_region(&region);
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].id, "n2");
+        // the e2 junction starts at 41199720
+        let edges = graphs[0].edges_in_region(&region);
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].id, "e2");
+
+        let index = tsg.region_index();
+        let gene_a = tsg.graph("gene_a").unwrap();
+        assert_eq!(index.nodes_in_region(gene_a, &region)[0].id, "n2");
+        assert_eq!(index.edges_in_region(gene_a, &region)[0].id, "e2");
+        let gene_b = tsg.graph("gene_b").unwrap();
+        assert!(index.nodes_in_region(gene_b, &region).is_empty());
+        assert_eq!(index.graph_ids(&"chr13".parse()?).len(), 1);
+        assert!(index.query(&"chr13:1-100".parse()?).is_empty());
+        assert!(tsg.graphs_in_region(&"chrX".parse()?).is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,483 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, VecDeque};
+use std::time::{Duration, Instant};
+
+use ahash::{HashMap, HashSet, HashSetExt};
+use anyhow::Result;
+use bon::Builder;
+use petgraph::algo::tarjan_scc;
+use petgraph::graph::{EdgeIndex, NodeIndex};
+use petgraph::unionfind::UnionFind;
+use petgraph::visit::EdgeRef;
+use tracing::warn;
+
+use crate::graph::{GraphSection, ReadData, ReadIdentity, ReadKey, TSGPath};
+
+/// Limits and search order of [`GraphSection::traverse_with`].
+///
+/// The default options enumerate every path breadth-first, like [`GraphSection::traverse`],
+/// and let a path go around a cycle once.
+/// When a limit stops the search early, the paths found so far are returned and a warning
+/// is logged, so a single complex locus cannot stall the processing of a whole file.
+#[derive(Debug, Clone, Builder)]
+pub struct TraversalOptions {
+    /// Stop after this many paths
+    pub max_paths: Option<usize>,
+    /// Drop paths with more than this many nodes
+    pub max_path_length: Option<usize>,
+    /// Minimum number of reads that must continue through every node of a path
+    #[builder(default = 1)]
+    pub min_reads: usize,
+    /// Stop when the traversal of a graph section takes longer than this
+    pub timeout: Option<Duration>,
+    /// Stop after extending this many partial paths
+    pub max_work: Option<usize>,
+    /// Search best-first and return the `k` paths with the most continuing reads
+    pub top_k: Option<usize>,
+    /// Number of times a path may come back to a node it already went through
+    #[builder(default = 1)]
+    pub max_revisits: usize,
+}
+
+impl Default for TraversalOptions {
+    fn default() -> Self {
+        Self::builder().build()
+    }
+}
+
+/// One node of a partial path, linked to the node before it
+struct Step {
+    node: NodeIndex,
+    edge: Option<EdgeIndex>,
+    parent: Option<usize>,
+    length: usize,
+}
+
+/// A partial path waiting to be extended, with the reads seen on all of its nodes
+struct Candidate<'a> {
+    step: usize,
+    reads: HashSet<ReadKey<'a>>,
+    order: usize,
+}
+
+impl Candidate<'_> {
+    /// Paths with more reads first, then in the order they were found
+    fn rank(&self) -> (usize, Reverse<usize>) {
+        (self.reads.len(), Reverse(self.order))
+    }
+}
+
+impl PartialEq for Candidate<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.rank() == other.rank()
+    }
+}
+
+impl Eq for Candidate<'_> {}
+
+impl PartialOrd for Candidate<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
+/// Breadth-first queue or best-first heap of partial paths
+enum Frontier<'a> {
+    BreadthFirst(VecDeque<Candidate<'a>>),
+    BestFirst(BinaryHeap<Candidate<'a>>),
+}
+
+impl<'a> Frontier<'a> {
+    fn push(&mut self, candidate: Candidate<'a>) {
+        match self {
+            Frontier::BreadthFirst(queue) => queue.push_back(candidate),
+            Frontier::BestFirst(heap) => heap.push(candidate),
+        }
+    }
+
+    fn pop(&mut self) -> Option<Candidate<'a>> {
+        match self {
+            Frontier::BreadthFirst(queue) => queue.pop_front(),
+            Frontier::BestFirst(heap) => heap.pop(),
+        }
+    }
+}
+
+impl GraphSection {
+    /// Traverse the graph like [`GraphSection::traverse`], within the limits of `options`.
+    ///
+    /// Cycles, such as the back-edges of tandem duplications, are followed as long as reads
+    /// continue through them, up to `max_revisits` times per node. On a cycle, a path of
+    /// more than one node may also end at a node where some of its reads end (`SI`), even
+    /// if the node has outgoing edges. Components without any node of in-degree 0 are entered from the
+    /// nodes where reads start (`SO`).
+    ///
+    /// In best-first mode (`top_k`), partial paths with the most continuing reads are
+    /// extended first. As reads can only drop out along a path, the first `k` paths to
+    /// reach a sink node are the `k` best supported ones, returned in decreasing order.
+    pub fn traverse_with(&self, options: &TraversalOptions) -> Result<Vec<TSGPath<'_>>> {
+        let started = Instant::now();
+        let min_reads = options.min_reads.max(1);
+        let max_paths = match (options.max_paths, options.top_k) {
+            (Some(max_paths), Some(k)) => Some(max_paths.min(k)),
+            (max_paths, k) => max_paths.or(k),
+        };
+
+        let node_reads: HashMap<NodeIndex, HashSet<ReadKey>> = self
+            ._graph
+            .node_indices()
+            .map(|idx| {
+                (
+                    idx,
+                    self._graph[idx].reads.iter().map(ReadData::key).collect(),
+                )
+            })
+            .collect();
+
+        let source_nodes: Vec<NodeIndex> = self
+            .start_nodes()
+            .into_iter()
+            .filter(|idx| node_reads[idx].len() >= min_reads)
+            .collect();
+        let cyclic_nodes = self.cyclic_nodes();
+
+        // breadth-first traversal explores one source at a time, best-first all at once
+        let batches: Vec<Vec<NodeIndex>> = if options.top_k.is_some() {
+            vec![source_nodes]
+        } else {
+            source_nodes.into_iter().map(|idx| vec![idx]).collect()
+        };
+
+        let mut steps: Vec<Step> = Vec::new();
+        let mut all_paths = Vec::new();
+        let mut work = 0;
+
+        for batch in batches {
+            let mut frontier = if options.top_k.is_some() {
+                Frontier::BestFirst(BinaryHeap::new())
+            } else {
+                Frontier::BreadthFirst(VecDeque::new())
+            };
+            for node in batch {
+                steps.push(Step {
+                    node,
+                    edge: None,
+                    parent: None,
+                    length: 1,
+                });
+                frontier.push(Candidate {
+                    step: steps.len() - 1,
+                    reads: node_reads[&node].clone(),
+                    order: steps.len(),
+                });
+            }
+
+            while let Some(candidate) = frontier.pop() {
+                if max_paths.is_some_and(|max| all_paths.len() >= max) {
+                    if options.max_paths.is_some_and(|max| all_paths.len() >= max) {
+                        warn!(
+                            "Stopped traversal of graph {} after {} paths",
+                            self.id,
+                            all_paths.len()
+                        );
+                    }
+                    return Ok(all_paths);
+                }
+                if options.max_work.is_some_and(|max| work >= max) {
+                    warn!(
+                        "Stopped traversal of graph {} after extending {} partial paths",
+                        self.id, work
+                    );
+                    return Ok(all_paths);
+                }
+                if options
+                    .timeout
+                    .is_some_and(|timeout| started.elapsed() > timeout)
+                {
+                    warn!(
+                        "Stopped traversal of graph {} after {:?}",
+                        self.id,
+                        started.elapsed()
+                    );
+                    return Ok(all_paths);
+                }
+                work += 1;
+
+                let current = &steps[candidate.step];
+                let (current_node, length) = (current.node, current.length);
+                let outgoing_edges: Vec<_> = self
+                    ._graph
+                    .edges_directed(current_node, petgraph::Direction::Outgoing)
+                    .collect();
+
+                // If this is a sink node (no outgoing edges), save the path
+                if outgoing_edges.is_empty() {
+                    let path = self.path_from_steps(&steps, candidate.step);
+                    path.validate()?;
+                    all_paths.push(path);
+                    continue;
+                }
+                if length > 1
+                    && cyclic_nodes.contains(&current_node)
+                    && self._graph[current_node].reads.iter().any(|r| {
+                        r.identity == ReadIdentity::SI && candidate.reads.contains(&r.key())
+                    })
+                {
+                    let path = self.path_from_steps(&steps, candidate.step);
+                    path.validate()?;
+                    all_paths.push(path);
+                }
+                if options.max_path_length.is_some_and(|max| length >= max) {
+                    continue;
+                }
+
+                for edge_ref in outgoing_edges {
+                    let target_node = edge_ref.target();
+                    let target_reads = &node_reads[&target_node];
+
+                    // reads that continue from the current path to the target
+                    let continuing_reads: HashSet<ReadKey> = candidate
+                        .reads
+                        .iter()
+                        .filter(|id| target_reads.contains(*id))
+                        .copied()
+                        .collect();
+                    if continuing_reads.len() < min_reads {
+                        continue;
+                    }
+                    if cyclic_nodes.contains(&target_node)
+                        && visits(&steps, candidate.step, target_node) > options.max_revisits
+                    {
+                        continue;
+                    }
+
+                    // For IN nodes, check if there's a valid path forward
+                    let has_in_reads = self._graph[target_node]
+                        .reads
+                        .iter()
+                        .any(|r| r.identity == ReadIdentity::IN);
+                    if has_in_reads {
+                        let mut next_nodes = self
+                            ._graph
+                            .edges_directed(target_node, petgraph::Direction::Outgoing)
+                            .map(|e| e.target())
+                            .peekable();
+                        let is_sink = next_nodes.peek().is_none();
+                        let can_continue = next_nodes.any(|next| {
+                            continuing_reads
+                                .iter()
+                                .filter(|id| node_reads[&next].contains(*id))
+                                .count()
+                                >= min_reads
+                        });
+                        if !can_continue && !is_sink {
+                            continue;
+                        }
+                    }
+
+                    steps.push(Step {
+                        node: target_node,
+                        edge: Some(edge_ref.id()),
+                        parent: Some(candidate.step),
+                        length: length + 1,
+                    });
+                    frontier.push(Candidate {
+                        step: steps.len() - 1,
+                        reads: continuing_reads,
+                        order: steps.len(),
+                    });
+                }
+            }
+        }
+
+        Ok(all_paths)
+    }
+
+    /// Nodes where traversals start: nodes of in-degree 0, and in components without any,
+    /// the nodes where reads start, or the first node of the component if there are none
+    fn start_nodes(&self) -> Vec<NodeIndex> {
+        let is_source = |idx: NodeIndex| {
+            self._graph
+                .edges_directed(idx, petgraph::Direction::Incoming)
+                .next()
+                .is_none()
+        };
+
+        let mut components = UnionFind::new(self._graph.node_count());
+        for edge in self._graph.edge_references() {
+            components.union(edge.source().index(), edge.target().index());
+        }
+        let mut members: HashMap<usize, Vec<NodeIndex>> = HashMap::default();
+        for idx in self._graph.node_indices() {
+            members
+                .entry(components.find(idx.index()))
+                .or_default()
+                .push(idx);
+        }
+
+        let mut start_nodes: Vec<NodeIndex> = vec![];
+        for nodes in members.values() {
+            if nodes.iter().any(|idx| is_source(*idx)) {
+                start_nodes.extend(nodes.iter().filter(|idx| is_source(**idx)));
+                continue;
+            }
+            let read_starts: Vec<NodeIndex> = nodes
+                .iter()
+                .filter(|idx| {
+                    self._graph[**idx]
+                        .reads
+                        .iter()
+                        .any(|r| r.identity == ReadIdentity::SO)
+                })
+                .copied()
+                .collect();
+            if read_starts.is_empty() {
+                start_nodes.push(nodes[0]);
+            } else {
+                start_nodes.extend(read_starts);
+            }
+        }
+        start_nodes.sort();
+        start_nodes
+    }
+
+    /// Nodes that lie on a cycle
+    fn cyclic_nodes(&self) -> HashSet<NodeIndex> {
+        let mut cyclic_nodes = HashSet::new();
+        for component in tarjan_scc(&self._graph) {
+            if component.len() > 1 || self._graph.contains_edge(component[0], component[0]) {
+                cyclic_nodes.extend(component);
+            }
+        }
+        cyclic_nodes
+    }
+
+    /// Build the path ending at `last` by following the steps back to its source node
+    fn path_from_steps(&self, steps: &[Step], last: usize) -> TSGPath<'_> {
+        let mut chain = vec![];
+        let mut current = Some(last);
+        while let Some(idx) = current {
+            chain.push(&steps[idx]);
+            current = steps[idx].parent;
+        }
+
+        let mut path = TSGPath::builder().graph(self).build();
+        for step in chain.into_iter().rev() {
+            if let Some(edge) = step.edge {
+                path.add_edge(edge);
+            }
+            path.add_node(step.node);
+        }
+        path
+    }
+}
+
+/// Number of times the path ending at `last` goes through `node`
+fn visits(steps: &[Step], last: usize, node: NodeIndex) -> usize {
+    let mut count = 0;
+    let mut current = Some(last);
+    while let Some(idx) = current {
+        if steps[idx].node == node {
+            count += 1;
+        }
+        current = steps[idx].parent;
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::TSGraph;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_bounded_traversal() -> Result<()> {
+        // n1 -> n2 -> n4 is supported by three reads, n1 -> n3 -> n4 by one
+        let tsg = TSGraph::from_str(
+            "G\tg1\n\
+             N\tn1\tchr1:+:100-200\tr1:SO,r2:SO,r3:SO,r4:SO\n\
+             N\tn2\tchr1:+:300-400\tr1:IN,r2:IN,r3:IN\n\
+             N\tn3\tchr1:+:500-600\tr4:IN\n\
+             N\tn4\tchr1:+:700-800\tr1:SI,r2:SI,r3:SI,r4:SI\n\
+             E\te1\tn1\tn3\tchr1,chr1,200,500,SPLICE\n\
+             E\te2\tn1\tn2\tchr1,chr1,200,300,SPLICE\n\
+             E\te3\tn2\tn4\tchr1,chr1,400,700,SPLICE\n\
+             E\te4\tn3\tn4\tchr1,chr1,600,700,SPLICE\n",
+        )?;
+        let graph = tsg.graph("g1").unwrap();
+        let node_ids = |path: &TSGPath| -> Vec<String> {
+            path.nodes
+                .iter()
+                .map(|idx| graph.node_by_idx(*idx).unwrap().id.to_string())
+                .collect()
+        };
+
+        let all = graph.traverse_with(&TraversalOptions::default())?;
+        assert_eq!(all.len(), graph.traverse()?.len());
+        assert_eq!(all.len(), 2);
+
+        let best = graph.traverse_with(&TraversalOptions::builder().top_k(1).build())?;
+        assert_eq!(best.len(), 1);
+        assert_eq!(node_ids(&best[0]), vec!["n1", "n2", "n4"]);
+
+        let supported = graph.traverse_with(&TraversalOptions::builder().min_reads(2).build())?;
+        assert_eq!(supported.len(), 1);
+
+        let short = TraversalOptions::builder().max_path_length(2).build();
+        assert!(graph.traverse_with(&short)?.is_empty());
+        let one = TraversalOptions::builder().max_paths(1).build();
+        assert_eq!(graph.traverse_with(&one)?.len(), 1);
+        let budget = TraversalOptions::builder().max_work(3).build();
+        assert!(graph.traverse_with(&budget)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cyclic_traversal() -> Result<()> {
+        // e3 is the back-edge of a tandem duplication of n2 and n3
+        let tsg = TSGraph::from_str(
+            "G\tg1\n\
+             N\tn1\tchr1:+:100-200\tr1:SO,r2:SO\n\
+             N\tn2\tchr1:+:300-400\tr1:IN,r2:IN\n\
+             N\tn3\tchr1:+:500-600\tr1:IN,r2:IN\n\
+             N\tn4\tchr1:+:700-800\tr1:SI,r2:SI\n\
+             E\te1\tn1\tn2\tchr1,chr1,200,300,SPLICE\n\
+             E\te2\tn2\tn3\tchr1,chr1,400,500,SPLICE\n\
+             E\te3\tn3\tn2\tchr1,chr1,600,300,DUP\n\
+             E\te4\tn3\tn4\tchr1,chr1,600,700,SPLICE\n\
+             G\tg2\n\
+             N\tm1\tchr2:+:100-200\tr3:SO,r3:SI\n\
+             N\tm2\tchr2:+:300-400\tr3:IN\n\
+             E\tf1\tm1\tm2\tchr2,chr2,200,300,SPLICE\n\
+             E\tf2\tm2\tm1\tchr2,chr2,400,100,DUP\n",
+        )?;
+        let lengths = |graph: &GraphSection, options: &TraversalOptions| -> Result<Vec<usize>> {
+            let mut lengths: Vec<usize> = graph
+                .traverse_with(options)?
+                .iter()
+                .map(|path| path.nodes.len())
+                .collect();
+            lengths.sort();
+            Ok(lengths)
+        };
+
+        let g1 = tsg.graph("g1").unwrap();
+        assert_eq!(lengths(g1, &TraversalOptions::default())?, vec![4, 6]);
+        let no_revisit = TraversalOptions::builder().max_revisits(0).build();
+        assert_eq!(lengths(g1, &no_revisit)?, vec![4]);
+        let twice = TraversalOptions::builder().max_revisits(2).build();
+        assert_eq!(lengths(g1, &twice)?, vec![4, 6, 8]);
+
+        // no node of in-degree 0: start where the read starts and stop where it ends
+        let g2 = tsg.graph("g2").unwrap();
+        assert_eq!(lengths(g2, &TraversalOptions::default())?, vec![3]);
+        Ok(())
+    }
+}
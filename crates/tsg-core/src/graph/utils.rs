@@ -1,5 +1,6 @@
 use anyhow::Result;
 use anyhow::anyhow;
+use bstr::BString;
 use regex::Regex;
 use sha2::{Digest, Sha256};
 
@@ -112,10 +113,63 @@ pub fn to_hash_identifier(input_string: &str, length: Option<usize>) -> Result<S
     Ok(result)
 }
 
+/// Reverse complement a nucleotide sequence.
+///
+/// IUPAC ambiguity codes are complemented and case is preserved; any other byte is
+/// kept as is.
+///
+/// # Examples
+///
+/// ```
+/// use tsg_core::graph::reverse_complement;
+///
+/// assert_eq!(reverse_complement(b"AACGTn"), "nACGTT");
+/// ```
+pub fn reverse_complement(seq: &[u8]) -> BString {
+    seq.iter()
+        .rev()
+        .map(|&base| match base {
+            b'A' => b'T',
+            b'T' | b'U' => b'A',
+            b'C' => b'G',
+            b'G' => b'C',
+            b'R' => b'Y',
+            b'Y' => b'R',
+            b'K' => b'M',
+            b'M' => b'K',
+            b'B' => b'V',
+            b'V' => b'B',
+            b'D' => b'H',
+            b'H' => b'D',
+            b'a' => b't',
+            b't' | b'u' => b'a',
+            b'c' => b'g',
+            b'g' => b'c',
+            b'r' => b'y',
+            b'y' => b'r',
+            b'k' => b'm',
+            b'm' => b'k',
+            b'b' => b'v',
+            b'v' => b'b',
+            b'd' => b'h',
+            b'h' => b'd',
+            other => other,
+        })
+        .collect::<Vec<u8>>()
+        .into()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_reverse_complement() {
+        assert_eq!(reverse_complement(b"ACGTRYKMBDHVN"), "NBDHVKMRYACGT");
+        assert_eq!(reverse_complement(b"acgU-"), "-Acgt");
+        assert_eq!(reverse_complement(b""), "");
+    }
+
     #[test]
     fn test_basic_hash() {
         let result = to_hash_identifier("Hello World!", Some(16)).unwrap();
@@ -0,0 +1,989 @@
+use std::fmt;
+use std::io::BufRead;
+use std::path::Path;
+
+use ahash::{HashMap, HashMapExt, HashSet, HashSetExt};
+use anyhow::{Result, anyhow};
+
+use super::{
+    Attribute, DEFAULT_GRAPH_ID, OrientedElement, ReadData, ReadIdentity, SAMPLE_HEADER_TAG,
+    StructuralVariant,
+};
+
+/// How serious a problem found by the validator is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The file loads, but probably not as intended
+    Warning,
+    /// The file does not load, or breaks an invariant of the format
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found on one line of a TSG file
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// 1-based line number of the record the problem is reported on
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.line, self.severity, self.message)
+    }
+}
+
+/// Every problem found in a TSG file, ordered by line
+#[derive(Debug, Clone)]
+pub struct ValidationReport {
+    /// Name of the validated file, used as the prefix of each diagnostic
+    pub source: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl ValidationReport {
+    /// Validate a plain or compressed TSG file; binary TSG files have no lines to report
+    /// on and are rejected
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut reader = crate::io::open_reader(path.as_ref())?;
+        if crate::io::is_binary(reader.fill_buf()?) {
+            return Err(anyhow!(
+                "Cannot validate binary TSG file {}, convert it to text first",
+                path.as_ref().display()
+            ));
+        }
+        Self::from_reader(reader, path.as_ref().display().to_string())
+    }
+
+    /// Validate the TSG records read from `reader`, collecting every problem instead of
+    /// stopping at the first one
+    pub fn from_reader<R: BufRead>(reader: R, source: impl Into<String>) -> Result<Self> {
+        let mut validator = Validator::new();
+        for (idx, line) in reader.lines().enumerate() {
+            validator.check_line(idx + 1, &line?);
+        }
+        Ok(Self {
+            source: source.into(),
+            diagnostics: validator.finish(),
+        })
+    }
+
+    /// Number of diagnostics of the given severity
+    pub fn count(&self, severity: Severity) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == severity)
+            .count()
+    }
+
+    /// Whether no error was found; warnings are allowed
+    pub fn is_valid(&self) -> bool {
+        self.count(Severity::Error) == 0
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for diagnostic in &self.diagnostics {
+            writeln!(f, "{}:{}", self.source, diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+/// Elements of a `U`, `P` or `C` record as written, with the line of the record
+struct GroupRecord {
+    line: usize,
+    kind: char,
+    elements: Vec<OrientedElement>,
+}
+
+/// A read, by ID and sample
+type ReadKey = (String, Option<String>);
+
+/// Records of one graph section, by ID with the line that defined them
+#[derive(Default)]
+struct GraphRecords {
+    line: usize,
+    nodes: HashMap<String, usize>,
+    edges: HashMap<String, (usize, String, String)>,
+    node_pairs: HashMap<(String, String), String>,
+    groups: HashMap<String, GroupRecord>,
+    /// roles of each read, by read ID and sample, with the lines of their nodes
+    reads: HashMap<ReadKey, Vec<(ReadIdentity, usize)>>,
+    attributes: HashSet<(char, String, String)>,
+}
+
+impl GraphRecords {
+    fn kind_of(&self, id: &str) -> Option<&'static str> {
+        if self.nodes.contains_key(id) {
+            Some("node")
+        } else if self.edges.contains_key(id) {
+            Some("edge")
+        } else if self.groups.contains_key(id) {
+            Some("group")
+        } else {
+            None
+        }
+    }
+
+    /// Whether an edge joins the two nodes, in either direction
+    fn connects(&self, edge: &str, first: &str, second: &str) -> bool {
+        self.edges.get(edge).is_some_and(|(_, source, sink)| {
+            (source == first && sink == second) || (source == second && sink == first)
+        })
+    }
+}
+
+/// Line-by-line checker of TSG records; references are resolved once all lines are read
+struct Validator {
+    diagnostics: Vec<Diagnostic>,
+    graphs: HashMap<String, GraphRecords>,
+    graph_order: Vec<String>,
+    current: String,
+    samples: HashSet<String>,
+    read_samples: Vec<(usize, String)>,
+    links: HashMap<String, usize>,
+    link_ends: Vec<(usize, String, String, String)>,
+}
+
+impl Validator {
+    fn new() -> Self {
+        let mut graphs = HashMap::new();
+        graphs.insert(DEFAULT_GRAPH_ID.to_string(), GraphRecords::default());
+        Self {
+            diagnostics: vec![],
+            graphs,
+            graph_order: vec![DEFAULT_GRAPH_ID.to_string()],
+            current: DEFAULT_GRAPH_ID.to_string(),
+            samples: HashSet::new(),
+            read_samples: vec![],
+            links: HashMap::new(),
+            link_ends: vec![],
+        }
+    }
+
+    fn report(&mut self, severity: Severity, line: usize, message: String) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            line,
+            message,
+        });
+    }
+
+    fn graph(&mut self) -> &mut GraphRecords {
+        self.graphs.get_mut(&self.current).unwrap()
+    }
+
+    fn check_line(&mut self, line_number: usize, line: &str) {
+        if line.is_empty() || line.starts_with('#') {
+            return;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let Some(&record) = fields.first() else {
+            return;
+        };
+        let min_fields = match record {
+            "H" | "U" | "P" | "C" => 3,
+            "G" => 2,
+            "N" | "A" => 4,
+            "E" | "L" => 5,
+            _ => {
+                self.report(
+                    Severity::Warning,
+                    line_number,
+                    format!("unknown record type {} is ignored", record),
+                );
+                return;
+            }
+        };
+        if fields.len() < min_fields {
+            self.report(
+                Severity::Error,
+                line_number,
+                format!(
+                    "{} record needs at least {} fields, found {}",
+                    record,
+                    min_fields,
+                    fields.len()
+                ),
+            );
+            return;
+        }
+
+        match record {
+            "H" => {
+                if fields[1] == SAMPLE_HEADER_TAG {
+                    self.samples.insert(fields[2].to_string());
+                }
+            }
+            "G" => self.check_graph(line_number, &fields),
+            "N" => self.check_node(line_number, &fields),
+            "E" => self.check_edge(line_number, &fields),
+            "U" | "P" | "C" => self.check_group(line_number, &fields),
+            "A" => self.check_attribute_line(line_number, &fields),
+            _ => self.check_link(line_number, &fields),
+        }
+    }
+
+    fn check_graph(&mut self, line: usize, fields: &[&str]) {
+        let id = fields[1].to_string();
+        match self.graphs.get(&id) {
+            Some(graph)
+                if id != DEFAULT_GRAPH_ID || !graph.nodes.is_empty() || !graph.edges.is_empty() =>
+            {
+                let first = graph.line;
+                self.report(
+                    Severity::Error,
+                    line,
+                    format!("duplicate graph ID {}, first defined on line {}", id, first),
+                );
+            }
+            _ => {
+                self.graphs.insert(
+                    id.clone(),
+                    GraphRecords {
+                        line,
+                        ..Default::default()
+                    },
+                );
+                if id != DEFAULT_GRAPH_ID {
+                    self.graph_order.push(id.clone());
+                }
+            }
+        }
+        self.current = id.clone();
+        self.check_attributes(line, 'G', &id, &fields[2..]);
+    }
+
+    /// Report an ID already used by another element of the current graph
+    fn check_duplicate(&mut self, line: usize, kind: &str, id: &str) {
+        let graph = self.graph();
+        let first = match graph.kind_of(id) {
+            Some("node") => graph.nodes.get(id).map(|line| ("node", *line)),
+            Some("edge") => graph.edges.get(id).map(|(line, ..)| ("edge", *line)),
+            Some(_) => graph.groups.get(id).map(|group| ("group", group.line)),
+            None => None,
+        };
+        if let Some((other, first)) = first {
+            let (severity, message) = if other == kind {
+                (
+                    Severity::Error,
+                    format!(
+                        "duplicate {} ID {}, first defined on line {}",
+                        kind, id, first
+                    ),
+                )
+            } else {
+                (
+                    Severity::Warning,
+                    format!(
+                        "{} ID {} is also the ID of the {} defined on line {}",
+                        kind, id, other, first
+                    ),
+                )
+            };
+            self.report(severity, line, message);
+        }
+    }
+
+    fn check_node(&mut self, line: usize, fields: &[&str]) {
+        let id = fields[1];
+        self.check_duplicate(line, "node", id);
+
+        let location: Vec<&str> = fields[2].split(':').collect();
+        if location.len() != 3 {
+            self.report(
+                Severity::Error,
+                line,
+                format!(
+                    "node {} location {} is not chrom:strand:exons",
+                    id, fields[2]
+                ),
+            );
+        } else {
+            if location[1] != "+" && location[1] != "-" {
+                self.report(
+                    Severity::Error,
+                    line,
+                    format!("node {} has invalid strand {}", id, location[1]),
+                );
+            }
+            self.check_exons(line, id, location[2]);
+        }
+
+        for read in fields[3].split(',') {
+            match read.parse::<ReadData>() {
+                Ok(read) => {
+                    if let Some(sample) = &read.sample {
+                        self.read_samples.push((line, sample.to_string()));
+                    }
+                    self.graph()
+                        .reads
+                        .entry((read.id.to_string(), read.sample.map(|s| s.to_string())))
+                        .or_default()
+                        .push((read.identity, line));
+                }
+                Err(e) => self.report(
+                    Severity::Error,
+                    line,
+                    format!("node {} has invalid read {}: {}", id, read, e),
+                ),
+            }
+        }
+        self.graph().nodes.insert(id.to_string(), line);
+    }
+
+    fn check_exons(&mut self, line: usize, id: &str, exons: &str) {
+        let mut previous: Option<(usize, usize)> = None;
+        for exon in exons.split(',') {
+            let bounds = exon
+                .split_once('-')
+                .and_then(|(start, end)| Some((start.parse().ok()?, end.parse().ok()?)));
+            let Some((start, end)) = bounds else {
+                self.report(
+                    Severity::Error,
+                    line,
+                    format!("node {} has malformed exon {}", id, exon),
+                );
+                return;
+            };
+            // exons are closed intervals, a single-base exon has start == end
+            if start > end {
+                self.report(
+                    Severity::Error,
+                    line,
+                    format!("node {} has exon {} whose start is after its end", id, exon),
+                );
+            }
+            if let Some((previous_start, previous_end)) = previous {
+                if start < previous_start {
+                    self.report(
+                        Severity::Error,
+                        line,
+                        format!("node {} has exons out of order at {}", id, exon),
+                    );
+                } else if start <= previous_end {
+                    self.report(
+                        Severity::Error,
+                        line,
+                        format!(
+                            "node {} has exon {} overlapping {}-{}",
+                            id, exon, previous_start, previous_end
+                        ),
+                    );
+                }
+            }
+            previous = Some((start, end));
+        }
+    }
+
+    fn check_edge(&mut self, line: usize, fields: &[&str]) {
+        let (id, source, sink) = (fields[1], fields[2], fields[3]);
+        self.check_duplicate(line, "edge", id);
+        if let Err(e) = fields[4].parse::<StructuralVariant>() {
+            self.report(
+                Severity::Error,
+                line,
+                format!("edge {} has invalid structural variant: {}", id, e),
+            );
+        }
+
+        let pair = (source.to_string(), sink.to_string());
+        if let Some(other) = self.graph().node_pairs.get(&pair).cloned()
+            && other != id
+        {
+            self.report(
+                Severity::Warning,
+                line,
+                format!(
+                    "edge {} joins the same nodes {} and {} as edge {}, which it replaces",
+                    id, source, sink, other
+                ),
+            );
+        }
+        let graph = self.graph();
+        graph.node_pairs.insert(pair, id.to_string());
+        graph
+            .edges
+            .insert(id.to_string(), (line, source.to_string(), sink.to_string()));
+    }
+
+    fn check_group(&mut self, line: usize, fields: &[&str]) {
+        let kind = fields[0].chars().next().unwrap_or_default();
+        let id = fields[1];
+        if self.graph().groups.contains_key(id) {
+            let first = self.graph().groups[id].line;
+            self.report(
+                Severity::Error,
+                line,
+                format!("duplicate group ID {}, first defined on line {}", id, first),
+            );
+            return;
+        }
+        self.check_duplicate(line, "group", id);
+
+        let elements: Vec<OrientedElement> = if kind == 'P' {
+            fields[2..]
+                .iter()
+                .filter_map(|element| element.parse().ok())
+                .collect()
+        } else {
+            fields[2..]
+                .iter()
+                .map(|element| OrientedElement {
+                    id: (*element).into(),
+                    orientation: None,
+                })
+                .collect()
+        };
+        if kind == 'C' && elements.len().is_multiple_of(2) {
+            self.report(
+                Severity::Error,
+                line,
+                format!(
+                    "chain {} has an even number of elements, it must start and end with a node",
+                    id
+                ),
+            );
+        }
+        self.graph().groups.insert(
+            id.to_string(),
+            GroupRecord {
+                line,
+                kind,
+                elements,
+            },
+        );
+    }
+
+    fn check_attribute_line(&mut self, line: usize, fields: &[&str]) {
+        let (element_type, id) = (fields[1], fields[2]);
+        let (defined, kind) = match element_type {
+            "N" => (self.graph().nodes.contains_key(id), "node"),
+            "E" => (self.graph().edges.contains_key(id), "edge"),
+            "U" | "P" | "C" => {
+                let group_kind = self.graph().groups.get(id).map(|group| group.kind);
+                if let Some(group_kind) = group_kind
+                    && !element_type.starts_with(group_kind)
+                {
+                    self.report(
+                        Severity::Warning,
+                        line,
+                        format!(
+                            "attribute of {} {} refers to a {} record",
+                            element_type, id, group_kind
+                        ),
+                    );
+                }
+                (group_kind.is_some(), "group")
+            }
+            "G" => (self.graphs.contains_key(id), "graph"),
+            _ => {
+                self.report(
+                    Severity::Error,
+                    line,
+                    format!("unknown attribute element type {}", element_type),
+                );
+                return;
+            }
+        };
+        if !defined {
+            self.report(
+                Severity::Error,
+                line,
+                format!("attribute refers to {} {} not defined before it", kind, id),
+            );
+        }
+        let element = element_type.chars().next().unwrap_or_default();
+        self.check_attributes(line, element, id, &fields[3..]);
+    }
+
+    /// Check the format and typed value of attributes, and that none is set twice
+    fn check_attributes(&mut self, line: usize, element: char, id: &str, attributes: &[&str]) {
+        for field in attributes {
+            let attribute = match field.parse::<Attribute>() {
+                Ok(attribute) => attribute,
+                Err(e) => {
+                    self.report(Severity::Error, line, format!("{}", e));
+                    continue;
+                }
+            };
+            let typed = match attribute.attribute_type {
+                'i' => attribute.as_int().map(|_| ()),
+                'f' => attribute.as_float().map(|_| ()),
+                'J' => attribute.as_json().map(|_| ()),
+                'H' if attribute.value.len() % 2 == 1
+                    || !attribute.value.iter().all(u8::is_ascii_hexdigit) =>
+                {
+                    Err(anyhow!("Value is not a hexadecimal byte array"))
+                }
+                _ => Ok(()),
+            };
+            if let Err(e) = typed {
+                self.report(
+                    Severity::Error,
+                    line,
+                    format!(
+                        "attribute {} does not match its type {}: {}",
+                        attribute.tag, attribute.attribute_type, e
+                    ),
+                );
+            }
+
+            let key = (element, id.to_string(), attribute.tag.to_string());
+            let graph = if element == 'G' {
+                self.graphs.get_mut(id)
+            } else {
+                self.graphs.get_mut(&self.current)
+            };
+            if let Some(graph) = graph
+                && !graph.attributes.insert(key)
+            {
+                self.report(
+                    Severity::Warning,
+                    line,
+                    format!(
+                        "attribute {} of {} is set again and replaces the previous value",
+                        attribute.tag, id
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_link(&mut self, line: usize, fields: &[&str]) {
+        let id = fields[1];
+        if let Some(first) = self.links.get(id) {
+            let first = *first;
+            self.report(
+                Severity::Error,
+                line,
+                format!("duplicate link ID {}, first defined on line {}", id, first),
+            );
+        } else {
+            self.links.insert(id.to_string(), line);
+        }
+
+        for end in &fields[2..4] {
+            let Some((graph, element)) = end.split_once(':') else {
+                self.report(
+                    Severity::Error,
+                    line,
+                    format!("link {} end {} is not graph:element", id, end),
+                );
+                continue;
+            };
+            if !self.graphs.contains_key(graph) {
+                self.report(
+                    Severity::Error,
+                    line,
+                    format!(
+                        "link {} refers to graph {} not defined before it",
+                        id, graph
+                    ),
+                );
+                continue;
+            }
+            self.link_ends
+                .push((line, id.to_string(), graph.to_string(), element.to_string()));
+        }
+        self.check_attributes(line, 'L', id, &fields[5..]);
+    }
+
+    /// Resolve the references between records and return all problems by line
+    fn finish(mut self) -> Vec<Diagnostic> {
+        let mut diagnostics = vec![];
+        let mut report = |severity, line, message| {
+            diagnostics.push(Diagnostic {
+                severity,
+                line,
+                message,
+            })
+        };
+
+        for id in &self.graph_order {
+            let graph = &self.graphs[id];
+            if id != DEFAULT_GRAPH_ID && graph.nodes.is_empty() && graph.groups.is_empty() {
+                report(
+                    Severity::Warning,
+                    graph.line,
+                    format!("graph {} has no nodes", id),
+                );
+            }
+            // nodes and edges of a graph without N or E records are built from its chains
+            let from_chains = graph.nodes.is_empty() || graph.edges.is_empty();
+
+            let mut edges: Vec<(&String, &(usize, String, String))> = graph.edges.iter().collect();
+            edges.sort_by_key(|(_, (line, ..))| *line);
+            for (edge, (line, source, sink)) in edges {
+                for node in [source, sink] {
+                    if !graph.nodes.contains_key(node) {
+                        report(
+                            Severity::Warning,
+                            *line,
+                            format!(
+                                "edge {} refers to undefined node {}, an empty node is created",
+                                edge, node
+                            ),
+                        );
+                    }
+                }
+            }
+
+            let mut groups: Vec<(&String, &GroupRecord)> = graph.groups.iter().collect();
+            groups.sort_by_key(|(_, group)| group.line);
+            for (group_id, group) in groups {
+                match group.kind {
+                    'P' => check_path(graph, group_id, group, &mut report),
+                    'C' => check_chain(graph, group_id, group, from_chains, &mut report),
+                    _ => {
+                        for element in &group.elements {
+                            if graph.kind_of(element.id.to_string().as_str()).is_none() {
+                                report(
+                                    Severity::Warning,
+                                    group.line,
+                                    format!(
+                                        "group {} refers to undefined element {}",
+                                        group_id, element.id
+                                    ),
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+
+            let mut reads: Vec<_> = graph.reads.iter().collect();
+            reads.sort_by_key(|(_, roles)| roles[0].1);
+            for ((read, sample), roles) in reads {
+                let read = match sample {
+                    Some(sample) => format!("{} of sample {}", read, sample),
+                    None => read.clone(),
+                };
+                check_read_roles(&read, roles, &mut report);
+            }
+        }
+
+        for (line, id, graph, element) in &self.link_ends {
+            if self.graphs[graph].kind_of(element).is_none() {
+                report(
+                    Severity::Error,
+                    *line,
+                    format!(
+                        "link {} refers to undefined element {}:{}",
+                        id, graph, element
+                    ),
+                );
+            }
+        }
+
+        for (line, sample) in &self.read_samples {
+            if !self.samples.contains(sample) {
+                report(
+                    Severity::Warning,
+                    *line,
+                    format!("sample {} is not declared by an H sample header", sample),
+                );
+                self.samples.insert(sample.clone());
+            }
+        }
+
+        self.diagnostics.extend(diagnostics);
+        self.diagnostics.sort_by_key(|d| d.line);
+        self.diagnostics
+    }
+}
+
+/// Check that the elements of a path exist and that each of its edges joins the nodes
+/// around it, or that consecutive nodes are joined by an edge
+fn check_path(
+    graph: &GraphRecords,
+    id: &str,
+    group: &GroupRecord,
+    report: &mut impl FnMut(Severity, usize, String),
+) {
+    let ids: Vec<String> = group.elements.iter().map(|e| e.id.to_string()).collect();
+    let kinds: Vec<Option<&str>> = ids.iter().map(|e| graph.kind_of(e)).collect();
+    let mut complete = true;
+    for (element, kind) in ids.iter().zip(&kinds) {
+        if kind.is_none() {
+            complete = false;
+            report(
+                Severity::Error,
+                group.line,
+                format!("path {} refers to undefined element {}", id, element),
+            );
+        }
+    }
+    if !complete {
+        return;
+    }
+
+    for idx in 0..ids.len() {
+        match kinds[idx] {
+            Some("edge") => {
+                let neighbours = (
+                    idx.checked_sub(1).filter(|&i| kinds[i] == Some("node")),
+                    (idx + 1 < ids.len() && kinds[idx + 1] == Some("node")).then_some(idx + 1),
+                );
+                let joined = match neighbours {
+                    (Some(before), Some(after)) => {
+                        graph.connects(&ids[idx], &ids[before], &ids[after])
+                    }
+                    _ => false,
+                };
+                if !joined {
+                    report(
+                        Severity::Error,
+                        group.line,
+                        format!(
+                            "path {} uses edge {} that does not connect the nodes around it",
+                            id, ids[idx]
+                        ),
+                    );
+                }
+            }
+            Some("node") if idx + 1 < ids.len() && kinds[idx + 1] == Some("node") => {
+                let joined = graph
+                    .edges
+                    .keys()
+                    .any(|edge| graph.connects(edge, &ids[idx], &ids[idx + 1]));
+                if !joined {
+                    report(
+                        Severity::Error,
+                        group.line,
+                        format!(
+                            "path {} has consecutive nodes {} and {} not joined by an edge",
+                            id,
+                            ids[idx],
+                            ids[idx + 1]
+                        ),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Check that a chain alternates nodes and edges, each edge joining its neighbours
+fn check_chain(
+    graph: &GraphRecords,
+    id: &str,
+    group: &GroupRecord,
+    from_chains: bool,
+    report: &mut impl FnMut(Severity, usize, String),
+) {
+    let ids: Vec<String> = group.elements.iter().map(|e| e.id.to_string()).collect();
+    for (idx, element) in ids.iter().enumerate() {
+        let (expected, article) = if idx % 2 == 0 {
+            ("node", "a")
+        } else {
+            ("edge", "an")
+        };
+        match graph.kind_of(element) {
+            Some(kind) if kind != expected => report(
+                Severity::Error,
+                group.line,
+                format!(
+                    "chain {} has {} {} where {} {} is expected",
+                    id, kind, element, article, expected
+                ),
+            ),
+            None if !from_chains => report(
+                Severity::Error,
+                group.line,
+                format!("chain {} refers to undefined element {}", id, element),
+            ),
+            Some("edge")
+                if idx + 1 < ids.len()
+                    && !graph.connects(element, &ids[idx - 1], &ids[idx + 1]) =>
+            {
+                report(
+                    Severity::Error,
+                    group.line,
+                    format!(
+                        "chain {} uses edge {} that does not connect {} and {}",
+                        id,
+                        element,
+                        ids[idx - 1],
+                        ids[idx + 1]
+                    ),
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Check that a read has one source node, plus one sink node when it spans several
+/// nodes, and intermediate nodes only between them
+fn check_read_roles(
+    read: &str,
+    roles: &[(ReadIdentity, usize)],
+    report: &mut impl FnMut(Severity, usize, String),
+) {
+    let lines_of = |identity: ReadIdentity| -> Vec<usize> {
+        let mut lines: Vec<usize> = roles
+            .iter()
+            .filter(|(role, _)| *role == identity)
+            .map(|(_, line)| *line)
+            .collect();
+        lines.dedup();
+        lines
+    };
+    let sources = lines_of(ReadIdentity::SO);
+    let sinks = lines_of(ReadIdentity::SI);
+    let first = roles[0].1;
+
+    // a read may pass a node twice along a cycle, but not twice in the same role
+    let mut seen = HashSet::new();
+    for (role, line) in roles {
+        if !seen.insert((role, *line)) {
+            report(
+                Severity::Warning,
+                *line,
+                format!("read {} is listed twice as {} on the node", read, role),
+            );
+        }
+    }
+    let nodes: HashSet<usize> = roles.iter().map(|(_, line)| *line).collect();
+    if sources.len() > 1 {
+        report(
+            Severity::Error,
+            sources[1],
+            format!(
+                "read {} has more than one source (SO) node, first on line {}",
+                read, sources[0]
+            ),
+        );
+    }
+    if sinks.len() > 1 {
+        report(
+            Severity::Error,
+            sinks[1],
+            format!(
+                "read {} has more than one sink (SI) node, first on line {}",
+                read, sinks[0]
+            ),
+        );
+    }
+    if sources.is_empty() {
+        report(
+            Severity::Error,
+            first,
+            format!("read {} has no source (SO) node", read),
+        );
+    }
+    if nodes.len() > 1 && sinks.is_empty() {
+        report(
+            Severity::Error,
+            first,
+            format!(
+                "read {} spans several nodes but has no sink (SI) node",
+                read
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn validate(tsg: &str) -> Vec<(usize, Severity, String)> {
+        let report = ValidationReport::from_reader(tsg.as_bytes(), "test.tsg").unwrap();
+        report
+            .diagnostics
+            .into_iter()
+            .map(|d| (d.line, d.severity, d.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_file() -> Result<()> {
+        let report = ValidationReport::from_file("tests/data/test.tsg")?;
+        // read3 of the test file has no source node
+        assert_eq!(report.count(Severity::Error), 1);
+        // one node of the sampling file has two exons sharing chrY:13462849
+        let report = ValidationReport::from_file("tests/data/sampling.tsg")?;
+        assert_eq!(report.count(Severity::Error), 1, "{}", report);
+        assert!(report.diagnostics[0].message.contains("overlapping"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_imported_file() -> Result<()> {
+        // the GTF importer writes closed coordinates, including single-base exons
+        let gtf = "\
+chr1\ttest\texon\t100\t200\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";
+chr1\ttest\texon\t300\t300\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";
+chr1\ttest\texon\t400\t500\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";
+";
+        let tsg = crate::io::from_gtf(gtf.as_bytes())?;
+        let mut buffer = vec![];
+        tsg.to_writer(&mut buffer)?;
+        let report = ValidationReport::from_reader(buffer.as_slice(), "gtf.tsg")?;
+        assert!(report.is_valid(), "{}", report);
+        Ok(())
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let tsg = "H\tVN\t1.0
+G\tg1
+N\tn1\tchr1:+:100-200,150-300\tr1:SO,r2:SO
+N\tn2\tchr1:+:500-400\tr1:IN,r2:SI
+N\tn3\tchr1:+:600-700,550-580\tr1:SI,r2:SI,r2:SI
+N\tn1\tchr1:+:800-900\tr3:XX
+E\te1\tn1\tn2\tchr1,chr1,200,500,splice
+E\te2\tn2\tn3\tchr1,chr1,400,600,splice
+E\te3\tn1\tn3\tchr1,chr1,200,600
+P\tp1\tn1+ e2+ n2+ e1+ n3+
+C\tc1\tn1 n2 e1
+A\tN\tn2\tcount:i:1.5\tpsi:f:0.5
+A\tE\te9\tsr:i:1
+A\tN\tn3\tpsi:f:x
+Q\tunknown
+";
+        let diagnostics = validate(tsg);
+        let has = |line: usize, severity: Severity, text: &str| {
+            diagnostics
+                .iter()
+                .any(|(l, s, m)| *l == line && *s == severity && m.contains(text))
+        };
+        assert!(has(3, Severity::Error, "overlapping"));
+        assert!(has(4, Severity::Error, "start is after its end"));
+        assert!(has(5, Severity::Error, "out of order"));
+        assert!(has(5, Severity::Warning, "r2 is listed twice as SI"));
+        assert!(has(5, Severity::Error, "r2 has more than one sink"));
+        assert!(has(6, Severity::Error, "duplicate node ID n1"));
+        assert!(has(6, Severity::Error, "invalid read"));
+        assert!(has(9, Severity::Error, "invalid structural variant"));
+        assert!(has(10, Severity::Error, "edge e2 that does not connect"));
+        assert!(has(10, Severity::Error, "edge e1 that does not connect"));
+        assert!(has(
+            11,
+            Severity::Error,
+            "node n2 where an edge is expected"
+        ));
+        assert!(has(11, Severity::Error, "edge e1 where a node is expected"));
+        assert!(has(12, Severity::Error, "count does not match its type i"));
+        assert!(has(13, Severity::Error, "edge e9 not defined"));
+        assert!(has(14, Severity::Error, "psi does not match its type f"));
+        assert!(has(15, Severity::Warning, "unknown record type Q"));
+        assert!(!has(12, Severity::Error, "psi"));
+        assert!(diagnostics.windows(2).all(|w| w[0].0 <= w[1].0));
+    }
+}
@@ -1,7 +1,21 @@
+mod bam;
+mod binary;
+mod builder;
+mod compress;
 mod fa;
+mod gfa;
 mod gtf;
+mod index;
+mod reference;
 mod vcf;
 
+pub use bam::*;
+pub use binary::*;
+pub use builder::*;
+pub use compress::*;
 pub use fa::*;
+pub use gfa::*;
 pub use gtf::*;
+pub use index::*;
+pub use reference::*;
 pub use vcf::*;
@@ -0,0 +1,466 @@
+use std::io::{BufRead, Read};
+
+use ahash::{HashMap, HashMapExt};
+use anyhow::{Context, Result, anyhow};
+use bstr::BString;
+use noodles::sam::alignment::Record;
+use noodles::sam::alignment::record::cigar::op::Kind;
+use noodles::sam::alignment::record::data::field::{Tag, Value};
+use noodles::{bam, sam};
+use petgraph::unionfind::UnionFind;
+
+use super::builder::{
+    ExonBlock, SPLICE_JUNCTION_TYPE, add_exon_node, add_junction_edge, junction_type,
+};
+use crate::graph::{DEFAULT_GRAPH_ID, GraphSection, ReadData, ReadIdentity, Strand, TSGraph};
+
+/// SV type assigned to collinear junctions between split alignments of a read
+pub const SPLIT_DELETION_TYPE: &str = "DEL";
+
+/// Transcript strand relative to the read, as written by minimap2
+const READ_STRAND_TAG: Tag = Tag::new(b't', b's');
+/// Transcript strand on the reference, as written by HISAT2 and STAR
+const REFERENCE_STRAND_TAG: Tag = Tag::new(b'X', b'S');
+
+/// A primary or supplementary alignment of a read
+#[derive(Debug, Clone)]
+struct AlignedSegment {
+    reference_id: BString,
+    /// Strand of the transcript the read comes from
+    strand: Strand,
+    /// Whether the read is aligned reverse complemented
+    reverse: bool,
+    /// Offset of the first aligned base in the original read sequence
+    query_start: usize,
+    /// Aligned blocks in reference order, separated by `N` operations in the CIGAR
+    blocks: Vec<(usize, usize)>,
+}
+
+impl AlignedSegment {
+    fn span(&self) -> (usize, usize) {
+        (self.blocks[0].0, self.blocks[self.blocks.len() - 1].1)
+    }
+}
+
+/// Whether the strand tag of a record puts the transcript on the reverse strand;
+/// `None` when the tag is missing or unknown
+fn tag_reverse_strand<R: Record>(record: &R, tag: &Tag) -> Result<Option<bool>> {
+    match record.data().get(tag).transpose()? {
+        Some(Value::Character(b'+')) => Ok(Some(false)),
+        Some(Value::Character(b'-')) => Ok(Some(true)),
+        _ => Ok(None),
+    }
+}
+
+/// Strand of the transcript a record comes from.
+///
+/// It is taken from the `ts` tag, which is relative to the read, or from the `XS` tag.
+/// Without either tag the read is assumed to be sequenced along the transcript, so
+/// the strand follows the FLAG.
+fn transcript_strand<R: Record>(record: &R, reverse: bool) -> Result<Strand> {
+    let on_reverse = match tag_reverse_strand(record, &READ_STRAND_TAG)? {
+        Some(antisense) => antisense != reverse,
+        None => tag_reverse_strand(record, &REFERENCE_STRAND_TAG)?.unwrap_or(reverse),
+    };
+    Ok(if on_reverse {
+        Strand::Reverse
+    } else {
+        Strand::Forward
+    })
+}
+
+/// Extract the read name and aligned blocks of a record.
+///
+/// Unmapped and secondary alignments are skipped, supplementary alignments are kept
+/// as additional segments of their read.
+fn parse_segment<R: Record>(
+    record: &R,
+    header: &sam::Header,
+) -> Result<Option<(BString, AlignedSegment)>> {
+    let flags = record.flags()?;
+    if flags.is_unmapped() || flags.is_secondary() {
+        return Ok(None);
+    }
+
+    let name: BString = record
+        .name()
+        .ok_or_else(|| anyhow!("Alignment record without a read name"))?
+        .into();
+    let (reference_id, _) = record
+        .reference_sequence(header)
+        .ok_or_else(|| anyhow!("Mapped read {} has no reference sequence", name))??;
+    let start = usize::from(
+        record
+            .alignment_start()
+            .ok_or_else(|| anyhow!("Mapped read {} has no alignment start", name))??,
+    );
+    let reverse = flags.is_reverse_complemented();
+    let strand = transcript_strand(record, reverse)?;
+
+    // walk the CIGAR, splitting the alignment into blocks at skipped regions (introns)
+    let mut blocks = Vec::new();
+    let (mut position, mut block_start) = (start, start);
+    let (mut leading_clip, mut trailing_clip) = (0, 0);
+    let mut aligned = false;
+    for op in record.cigar().iter() {
+        let op = op?;
+        match op.kind() {
+            Kind::SoftClip | Kind::HardClip => {
+                if aligned {
+                    trailing_clip += op.len();
+                } else {
+                    leading_clip += op.len();
+                }
+            }
+            Kind::Skip => {
+                if position > block_start {
+                    blocks.push((block_start, position - 1));
+                }
+                position += op.len();
+                block_start = position;
+            }
+            kind => {
+                aligned = true;
+                if kind.consumes_reference() {
+                    position += op.len();
+                }
+            }
+        }
+    }
+    if position > block_start {
+        blocks.push((block_start, position - 1));
+    }
+    if blocks.is_empty() {
+        return Ok(None);
+    }
+
+    // clips are reported in reference orientation, so the start of a reverse
+    // alignment in the original read is its trailing clip
+    let query_start = if reverse { trailing_clip } else { leading_clip };
+
+    Ok(Some((
+        name,
+        AlignedSegment {
+            reference_id: reference_id.into(),
+            strand,
+            reverse,
+            query_start,
+            blocks,
+        },
+    )))
+}
+
+/// Collect the segments of every read, keeping reads in the order they first appear
+fn collect_segments<R, I>(
+    records: I,
+    header: &sam::Header,
+) -> Result<Vec<(BString, Vec<AlignedSegment>)>>
+where
+    R: Record,
+    I: Iterator<Item = std::io::Result<R>>,
+{
+    let mut reads: Vec<(BString, Vec<AlignedSegment>)> = Vec::new();
+    let mut read_index: HashMap<BString, usize> = HashMap::new();
+
+    for record in records {
+        let record = record?;
+        if let Some((name, segment)) = parse_segment(&record, header)? {
+            let idx = *read_index.entry(name.clone()).or_insert_with(|| {
+                reads.push((name, Vec::new()));
+                reads.len() - 1
+            });
+            reads[idx].1.push(segment);
+        }
+    }
+    Ok(reads)
+}
+
+/// Group reads into loci.
+///
+/// Reads whose segments overlap on the reference end up in the same locus, and a read
+/// with segments at several loci (e.g. a gene fusion) joins them together. Returns
+/// the name of every locus (`chrom_start_end` of its leftmost cluster) together with
+/// the indices of its reads.
+fn group_reads(reads: &[(BString, Vec<AlignedSegment>)]) -> Vec<(BString, Vec<usize>)> {
+    let mut spans: Vec<(&BString, usize, usize, usize)> = reads
+        .iter()
+        .enumerate()
+        .flat_map(|(read_idx, (_, segments))| {
+            segments.iter().map(move |segment| {
+                let (start, end) = segment.span();
+                (&segment.reference_id, start, end, read_idx)
+            })
+        })
+        .collect();
+    spans.sort();
+
+    let mut read_loci = UnionFind::new(reads.len());
+    // (reference, start, end, first read) of each cluster of overlapping segments
+    let mut clusters: Vec<(&BString, usize, usize, usize)> = Vec::new();
+    for (reference_id, start, end, read_idx) in spans {
+        match clusters.last_mut() {
+            Some(cluster) if cluster.0 == reference_id && start <= cluster.2 => {
+                cluster.2 = cluster.2.max(end);
+                read_loci.union(cluster.3, read_idx);
+            }
+            _ => clusters.push((reference_id, start, end, read_idx)),
+        }
+    }
+
+    let mut loci: Vec<(BString, Vec<usize>)> = Vec::new();
+    let mut locus_index: HashMap<usize, usize> = HashMap::new();
+    for (reference_id, start, end, read_idx) in &clusters {
+        let root = read_loci.find_mut(*read_idx);
+        locus_index.entry(root).or_insert_with(|| {
+            loci.push((
+                format!("{}_{}_{}", reference_id, start, end).into(),
+                Vec::new(),
+            ));
+            loci.len() - 1
+        });
+    }
+    for read_idx in 0..reads.len() {
+        let root = read_loci.find_mut(read_idx);
+        loci[locus_index[&root]].1.push(read_idx);
+    }
+    loci
+}
+
+/// Build the graph section of one locus.
+///
+/// The segments of a read are ordered by their position in the read, and their blocks
+/// are visited in read order, reversed when the read is antisense to its transcript so
+/// that reads of either orientation share nodes and edges. Every block becomes an exon node and consecutive blocks
+/// are connected by edges: blocks of the same alignment by splice junctions, blocks of
+/// split or supplementary alignments by structural variant junctions. The read is
+/// attached to its nodes as SO for the first block, SI for the last and IN in between.
+fn build_locus_section(
+    id: BString,
+    reads: &mut [(BString, Vec<AlignedSegment>)],
+    read_indices: &[usize],
+) -> Result<GraphSection> {
+    let mut section = GraphSection::new(id);
+
+    for &read_idx in read_indices {
+        let (name, segments) = &mut reads[read_idx];
+        segments.sort_by_key(|segment| segment.query_start);
+
+        let mut blocks: Vec<(usize, ExonBlock)> = Vec::new();
+        for (segment_idx, segment) in segments.iter().enumerate() {
+            let mut segment_blocks: Vec<_> = segment
+                .blocks
+                .iter()
+                .map(|&(start, end)| {
+                    (
+                        segment_idx,
+                        ExonBlock {
+                            reference_id: segment.reference_id.clone(),
+                            strand: segment.strand,
+                            start,
+                            end,
+                        },
+                    )
+                })
+                .collect();
+            if segment.reverse {
+                segment_blocks.reverse();
+            }
+            blocks.extend(segment_blocks);
+        }
+        if segments
+            .first()
+            .is_some_and(|segment| segment.reverse != (segment.strand == Strand::Reverse))
+        {
+            blocks.reverse();
+        }
+
+        let mut node_ids: Vec<BString> = Vec::with_capacity(blocks.len());
+        for (idx, (_, block)) in blocks.iter().enumerate() {
+            let identity = if idx == 0 {
+                ReadIdentity::SO
+            } else if idx == blocks.len() - 1 {
+                ReadIdentity::SI
+            } else {
+                ReadIdentity::IN
+            };
+            let read = ReadData {
+                id: name.clone(),
+                identity,
+                sample: None,
+            };
+            node_ids.push(add_exon_node(&mut section, block, read)?);
+        }
+
+        for (idx, pair) in blocks.windows(2).enumerate() {
+            let ((donor_segment, donor), (acceptor_segment, acceptor)) = (&pair[0], &pair[1]);
+            let sv_type = if donor_segment == acceptor_segment {
+                SPLICE_JUNCTION_TYPE
+            } else {
+                // a collinear jump between split alignments is a deletion
+                match junction_type(donor, acceptor) {
+                    SPLICE_JUNCTION_TYPE => SPLIT_DELETION_TYPE,
+                    sv_type => sv_type,
+                }
+            };
+            add_junction_edge(
+                &mut section,
+                (node_ids[idx].as_ref(), donor),
+                (node_ids[idx + 1].as_ref(), acceptor),
+                sv_type,
+            )?;
+        }
+    }
+
+    Ok(section)
+}
+
+/// Build a TSGraph from the segments of all reads, one graph section per locus
+fn build_from_segments(mut reads: Vec<(BString, Vec<AlignedSegment>)>) -> Result<TSGraph> {
+    let mut tsg_graph = TSGraph::new();
+    for (locus_id, read_indices) in group_reads(&reads) {
+        let section = build_locus_section(locus_id.clone(), &mut reads, &read_indices)
+            .with_context(|| format!("Failed to build graph for locus {}", locus_id))?;
+        tsg_graph.graphs.insert(locus_id, section);
+    }
+
+    // drop the default graph, all sections are named after loci
+    tsg_graph.graphs.remove(&BString::from(DEFAULT_GRAPH_ID));
+    Ok(tsg_graph)
+}
+
+/// Build a TSGraph from spliced alignments in SAM format.
+///
+/// Reads are grouped into one graph section per locus. Aligned blocks between `N`
+/// operations become exon nodes, introns become splice junction edges, and junctions
+/// between split or supplementary alignments become structural variant edges
+/// (`TRA`, `INV`, `TDUP` or [`SPLIT_DELETION_TYPE`]). Block coordinates are 1-based
+/// and inclusive, like GTF exons.
+///
+/// Nodes are on the transcript strand given by the `ts` or `XS` tag of an alignment,
+/// or on its alignment strand when it has neither tag.
+pub fn from_sam<R: BufRead>(reader: R) -> Result<TSGraph> {
+    let mut reader = sam::io::Reader::new(reader);
+    let header = reader.read_header()?;
+    let reads = collect_segments(reader.records(), &header)?;
+    build_from_segments(reads)
+}
+
+/// Build a TSGraph from spliced alignments in BAM format.
+///
+/// See [`from_sam`] for how alignments are turned into nodes and edges.
+pub fn from_bam<R: Read>(reader: R) -> Result<TSGraph> {
+    let mut reader = bam::io::Reader::new(reader);
+    let header = reader.read_header()?;
+    let reads = collect_segments(reader.records(), &header)?;
+    build_from_segments(reads)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::BufReader;
+
+    const SAM: &str = "\
+@HD\tVN:1.6\tSO:unsorted
+@SQ\tSN:chr1\tLN:10000
+@SQ\tSN:chr2\tLN:10000
+r1\t0\tchr1\t100\t60\t101M99N101M99N101M\t*\t0\t0\t*\t*
+r2\t0\tchr1\t100\t60\t101M299N101M\t*\t0\t0\t*\t*
+r3\t16\tchr1\t300\t60\t5S101M99N101M\t*\t0\t0\t*\t*
+r4\t0\tchr1\t5000\t60\t50M50S\t*\t0\t0\t*\t*
+r4\t2048\tchr2\t200\t60\t50H50M\t*\t0\t0\t*\t*
+r5\t4\t*\t0\t0\t*\t*\t0\t0\t*\t*
+r6\t256\tchr1\t100\t0\t101M\t*\t0\t0\t*\t*
+";
+
+    #[test]
+    fn test_from_sam() -> Result<()> {
+        let tsg_graph = from_sam(BufReader::new(SAM.as_bytes()))?;
+        // unmapped and secondary alignments are ignored
+        assert_eq!(tsg_graph.graphs.len(), 2);
+
+        // r1 and r2 share their first and last blocks
+        let locus = tsg_graph.graph("chr1_100_600").unwrap();
+        assert_eq!(locus.nodes().len(), 5);
+        assert!(
+            locus
+                .edges()
+                .iter()
+                .all(|e| e.sv.sv_type == SPLICE_JUNCTION_TYPE)
+        );
+        let first = locus
+            .nodes()
+            .into_iter()
+            .find(|n| n.reference_start() == 100)
+            .unwrap();
+        assert_eq!(first.reads.len(), 2);
+        assert!(first.reads.iter().all(|r| r.identity == ReadIdentity::SO));
+
+        // the reverse strand read starts at its rightmost block
+        let reverse_first = locus
+            .nodes()
+            .into_iter()
+            .find(|n| n.strand == Strand::Reverse && n.reference_start() == 500)
+            .unwrap();
+        assert_eq!(reverse_first.reads[0].id, "r3");
+        assert_eq!(reverse_first.reads[0].identity, ReadIdentity::SO);
+
+        let paths = locus.traverse()?;
+        assert_eq!(paths.len(), 3);
+
+        // the supplementary alignment joins both loci with a translocation edge
+        let fusion = tsg_graph.graph("chr1_5000_5049").unwrap();
+        assert_eq!(fusion.nodes().len(), 2);
+        let edges = fusion.edges();
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].sv.sv_type, "TRA");
+        assert_eq!(edges[0].sv.breakpoint1, 5049);
+        assert_eq!(edges[0].sv.breakpoint2, 200);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_orientation() -> Result<()> {
+        // r2 and r3 are reverse aligned reads of the + transcript of r1
+        let sam = "\
+@SQ\tSN:chr1\tLN:10000
+r1\t0\tchr1\t100\t60\t101M99N101M\t*\t0\t0\t*\t*
+r2\t16\tchr1\t100\t60\t101M99N101M\t*\t0\t0\t*\t*\tts:A:-
+r3\t16\tchr1\t100\t60\t101M99N101M\t*\t0\t0\t*\t*\tXS:A:+
+";
+        let tsg_graph = from_sam(BufReader::new(sam.as_bytes()))?;
+        let locus = tsg_graph.graph("chr1_100_400").unwrap();
+        assert_eq!(locus.nodes().len(), 2);
+        assert_eq!(locus.edges().len(), 1);
+        for node in locus.nodes() {
+            assert_eq!(node.strand, Strand::Forward);
+            assert_eq!(node.reads.len(), 3);
+        }
+        let first = locus
+            .nodes()
+            .into_iter()
+            .find(|n| n.reference_start() == 100)
+            .unwrap();
+        assert!(first.reads.iter().all(|r| r.identity == ReadIdentity::SO));
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_deletion() -> Result<()> {
+        let sam = "\
+@SQ\tSN:chr1\tLN:100000
+r1\t2048\tchr1\t50000\t60\t60H40M\t*\t0\t0\t*\t*
+r1\t0\tchr1\t1000\t60\t60M40S\t*\t0\t0\t*\t*
+";
+        let tsg_graph = from_sam(BufReader::new(sam.as_bytes()))?;
+        // both segments belong to the same read, so they share one graph
+        assert_eq!(tsg_graph.graphs.len(), 1);
+        let section = tsg_graph.graph("chr1_1000_1059").unwrap();
+        let edges = section.edges();
+        assert_eq!(edges.len(), 1);
+        assert_eq!(edges[0].sv.sv_type, SPLIT_DELETION_TYPE);
+        assert_eq!(edges[0].sv.breakpoint1, 1059);
+        assert_eq!(edges[0].sv.breakpoint2, 50000);
+        Ok(())
+    }
+}
@@ -0,0 +1,620 @@
+use std::io::{Read, Write};
+
+use ahash::{HashMap, HashMapExt};
+use anyhow::{Context, Result, anyhow};
+use bstr::{BStr, BString};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use bytes::{Buf, BufMut, BytesMut};
+use petgraph::graph::NodeIndex;
+use sha2::{Digest, Sha256};
+
+use crate::graph::{
+    Attribute, EdgeData, Exons, GraphSection, Group, Header, InterGraphLink, Interval, NodeData,
+    Orientation, OrientedElement, ReadData, ReadIdentity, Strand, StructuralVariant, TSGraph,
+};
+
+/// Magic bytes at the start of every binary TSG file
+pub const BINARY_MAGIC: &[u8; 4] = b"TSGB";
+
+/// Version of the binary TSG encoding written by [`to_binary`]
+pub const BINARY_VERSION: u16 = 1;
+
+const CHECKSUM_LEN: usize = 32;
+
+/// Encode a TSGraph in the binary TSG format.
+///
+/// The file starts with [`BINARY_MAGIC`], the encoding version (u16) and the payload
+/// length (u64), followed by the payload and its SHA-256 checksum. All integers are
+/// little-endian, and strings are length-prefixed byte strings. Graphs, groups and
+/// attributes are written in sorted order, so the same graph always encodes to the
+/// same bytes.
+pub fn to_binary<W: Write>(tsg_graph: &TSGraph, writer: &mut W) -> Result<()> {
+    let mut payload = BytesMut::new();
+    encode_graph(tsg_graph, &mut payload)?;
+    let checksum = Sha256::digest(&payload);
+
+    writer.write_all(BINARY_MAGIC)?;
+    writer.write_u16::<LittleEndian>(BINARY_VERSION)?;
+    writer.write_u64::<LittleEndian>(payload.len() as u64)?;
+    writer.write_all(&payload)?;
+    writer.write_all(&checksum)?;
+    Ok(())
+}
+
+/// Decode a TSGraph from the binary TSG format written by [`to_binary`].
+///
+/// Fails if the magic bytes or version do not match, or if the checksum of the payload
+/// is wrong.
+pub fn from_binary<R: Read>(reader: &mut R) -> Result<TSGraph> {
+    let mut magic = [0u8; 4];
+    reader
+        .read_exact(&mut magic)
+        .context("Failed to read binary TSG header")?;
+    if &magic != BINARY_MAGIC {
+        return Err(anyhow!("Not a binary TSG file"));
+    }
+    let version = reader.read_u16::<LittleEndian>()?;
+    if version != BINARY_VERSION {
+        return Err(anyhow!(
+            "Unsupported binary TSG version {} (expected {})",
+            version,
+            BINARY_VERSION
+        ));
+    }
+    let length = reader.read_u64::<LittleEndian>()?;
+
+    // the length is not trusted before the checksum is checked, so the payload is
+    // read as it arrives instead of being allocated up front
+    let mut payload = Vec::new();
+    reader
+        .take(length)
+        .read_to_end(&mut payload)
+        .context("Failed to read binary TSG payload")?;
+    if (payload.len() as u64) < length {
+        return Err(anyhow!("Binary TSG payload is truncated"));
+    }
+    let mut checksum = [0u8; CHECKSUM_LEN];
+    reader
+        .read_exact(&mut checksum)
+        .context("Binary TSG checksum is missing")?;
+    if Sha256::digest(&payload).as_slice() != checksum {
+        return Err(anyhow!(
+            "Binary TSG checksum mismatch, the file is corrupted"
+        ));
+    }
+
+    let mut decoder = Decoder { buf: &payload };
+    let tsg_graph = decoder.graph()?;
+    if decoder.buf.has_remaining() {
+        return Err(anyhow!("Unexpected trailing data in binary TSG payload"));
+    }
+    Ok(tsg_graph)
+}
+
+/// Check whether the given bytes start with the binary TSG magic
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.starts_with(BINARY_MAGIC)
+}
+
+fn put_len(buf: &mut BytesMut, len: usize) -> Result<()> {
+    buf.put_u32_le(u32::try_from(len).context("Collection is too large to encode")?);
+    Ok(())
+}
+
+fn put_str(buf: &mut BytesMut, s: &BStr) -> Result<()> {
+    put_len(buf, s.len())?;
+    buf.put_slice(s);
+    Ok(())
+}
+
+fn put_attributes(buf: &mut BytesMut, attributes: &HashMap<BString, Attribute>) -> Result<()> {
+    let mut attributes: Vec<_> = attributes.values().collect();
+    attributes.sort_by(|a, b| a.tag.cmp(&b.tag));
+    put_len(buf, attributes.len())?;
+    for attr in attributes {
+        put_str(buf, attr.tag.as_ref())?;
+        buf.put_u32_le(attr.attribute_type as u32);
+        put_str(buf, attr.value.as_ref())?;
+    }
+    Ok(())
+}
+
+fn put_group(buf: &mut BytesMut, group: &Group) -> Result<()> {
+    match group {
+        Group::Unordered {
+            id,
+            elements,
+            attributes,
+        }
+        | Group::Chain {
+            id,
+            elements,
+            attributes,
+        } => {
+            buf.put_u8(if matches!(group, Group::Unordered { .. }) {
+                0
+            } else {
+                2
+            });
+            put_str(buf, id.as_ref())?;
+            put_len(buf, elements.len())?;
+            for element in elements {
+                put_str(buf, element.as_ref())?;
+            }
+            put_attributes(buf, attributes)?;
+        }
+        Group::Ordered {
+            id,
+            elements,
+            attributes,
+        } => {
+            buf.put_u8(1);
+            put_str(buf, id.as_ref())?;
+            put_len(buf, elements.len())?;
+            for element in elements {
+                put_str(buf, element.id.as_ref())?;
+                buf.put_u8(match element.orientation {
+                    None => 0,
+                    Some(Orientation::Forward) => 1,
+                    Some(Orientation::Reverse) => 2,
+                });
+            }
+            put_attributes(buf, attributes)?;
+        }
+    }
+    Ok(())
+}
+
+fn put_groups(buf: &mut BytesMut, groups: &HashMap<BString, Group>) -> Result<()> {
+    let mut groups: Vec<_> = groups.iter().collect();
+    groups.sort_by(|a, b| a.0.cmp(b.0));
+    put_len(buf, groups.len())?;
+    for (key, group) in groups {
+        put_str(buf, key.as_ref())?;
+        put_group(buf, group)?;
+    }
+    Ok(())
+}
+
+fn put_section(buf: &mut BytesMut, section: &GraphSection) -> Result<()> {
+    put_str(buf, section.id.as_ref())?;
+    put_attributes(buf, &section.attributes)?;
+
+    // nodes in index order, edges refer to their endpoints by position in this list
+    let mut node_indices: Vec<NodeIndex> = section.node_indices.values().copied().collect();
+    node_indices.sort();
+    node_indices.dedup();
+    let positions: HashMap<NodeIndex, usize> = node_indices
+        .iter()
+        .enumerate()
+        .map(|(position, &idx)| (idx, position))
+        .collect();
+    put_len(buf, node_indices.len())?;
+    for &node_idx in &node_indices {
+        let node = section
+            .node_by_idx(node_idx)
+            .context("Node index out of sync with graph")?;
+        put_str(buf, node.id.as_ref())?;
+        put_str(buf, node.reference_id.as_ref())?;
+        buf.put_u8(match node.strand {
+            Strand::Forward => 0,
+            Strand::Reverse => 1,
+        });
+        put_len(buf, node.exons.exons.len())?;
+        for exon in &node.exons.exons {
+            buf.put_u64_le(exon.start as u64);
+            buf.put_u64_le(exon.end as u64);
+        }
+        put_len(buf, node.reads.len())?;
+        for read in &node.reads {
+            put_str(buf, read.id.as_ref())?;
+            buf.put_u8(match read.identity {
+                ReadIdentity::SO => 0,
+                ReadIdentity::IN => 1,
+                ReadIdentity::SI => 2,
+            });
+            match &read.sample {
+                Some(sample) => {
+                    buf.put_u8(1);
+                    put_str(buf, sample.as_ref())?;
+                }
+                None => buf.put_u8(0),
+            }
+        }
+        match &node.sequence {
+            Some(sequence) => {
+                buf.put_u8(1);
+                put_str(buf, sequence.as_ref())?;
+            }
+            None => buf.put_u8(0),
+        }
+        put_attributes(buf, &node.attributes)?;
+    }
+
+    let mut edges = section.edge_indices.values().copied().collect::<Vec<_>>();
+    edges.sort();
+    edges.dedup();
+    put_len(buf, edges.len())?;
+    for edge_idx in edges {
+        let edge = section
+            .edge_by_idx(edge_idx)
+            .context("Edge index out of sync with graph")?;
+        let (source, sink) = section
+            .edge_endpoints(edge_idx)
+            .context("Edge index out of sync with graph")?;
+        put_str(buf, edge.id.as_ref())?;
+        put_len(buf, positions[&source])?;
+        put_len(buf, positions[&sink])?;
+        put_str(buf, edge.sv.reference_name1.as_ref())?;
+        put_str(buf, edge.sv.reference_name2.as_ref())?;
+        buf.put_u64_le(edge.sv.breakpoint1 as u64);
+        buf.put_u64_le(edge.sv.breakpoint2 as u64);
+        put_str(buf, edge.sv.sv_type.as_ref())?;
+        put_attributes(buf, &edge.attributes)?;
+    }
+
+    put_groups(buf, &section.groups)?;
+    put_groups(buf, &section.chains)?;
+    Ok(())
+}
+
+fn encode_graph(tsg_graph: &TSGraph, buf: &mut BytesMut) -> Result<()> {
+    put_len(buf, tsg_graph.headers.len())?;
+    for header in &tsg_graph.headers {
+        put_str(buf, header.tag.as_ref())?;
+        put_str(buf, header.value.as_ref())?;
+    }
+
+    let mut sections: Vec<_> = tsg_graph.graphs.values().collect();
+    sections.sort_by(|a, b| a.id.cmp(&b.id));
+    put_len(buf, sections.len())?;
+    for section in sections {
+        put_section(buf, section)?;
+    }
+
+    put_len(buf, tsg_graph.links.len())?;
+    for link in &tsg_graph.links {
+        put_str(buf, link.id.as_ref())?;
+        put_str(buf, link.source_graph.as_ref())?;
+        put_str(buf, link.source_element.as_ref())?;
+        put_str(buf, link.target_graph.as_ref())?;
+        put_str(buf, link.target_element.as_ref())?;
+        put_str(buf, link.link_type.as_ref())?;
+        put_attributes(buf, &link.attributes)?;
+    }
+    Ok(())
+}
+
+/// Bounds-checked reader over a binary TSG payload
+struct Decoder<'a> {
+    buf: &'a [u8],
+}
+
+impl Decoder<'_> {
+    fn ensure(&self, n: usize) -> Result<()> {
+        if self.buf.remaining() < n {
+            return Err(anyhow!("Binary TSG payload is truncated"));
+        }
+        Ok(())
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        self.ensure(1)?;
+        Ok(self.buf.get_u8())
+    }
+
+    fn len(&mut self) -> Result<usize> {
+        self.ensure(4)?;
+        Ok(self.buf.get_u32_le() as usize)
+    }
+
+    fn u64(&mut self) -> Result<usize> {
+        self.ensure(8)?;
+        Ok(usize::try_from(self.buf.get_u64_le())?)
+    }
+
+    fn str(&mut self) -> Result<BString> {
+        let len = self.len()?;
+        self.ensure(len)?;
+        let s = BString::from(&self.buf[..len]);
+        self.buf.advance(len);
+        Ok(s)
+    }
+
+    fn attributes(&mut self) -> Result<HashMap<BString, Attribute>> {
+        let count = self.len()?;
+        let mut attributes = HashMap::with_capacity(count);
+        for _ in 0..count {
+            let tag = self.str()?;
+            self.ensure(4)?;
+            let attribute_type = char::from_u32(self.buf.get_u32_le())
+                .context("Invalid attribute type in binary TSG")?;
+            let value = self.str()?;
+            attributes.insert(
+                tag.clone(),
+                Attribute {
+                    tag,
+                    attribute_type,
+                    value,
+                },
+            );
+        }
+        Ok(attributes)
+    }
+
+    fn ids(&mut self) -> Result<Vec<BString>> {
+        let count = self.len()?;
+        (0..count).map(|_| self.str()).collect()
+    }
+
+    fn group(&mut self) -> Result<Group> {
+        let kind = self.u8()?;
+        let id = self.str()?;
+        let group = match kind {
+            0 | 2 => {
+                let elements = self.ids()?;
+                let attributes = self.attributes()?;
+                if kind == 0 {
+                    Group::Unordered {
+                        id,
+                        elements,
+                        attributes,
+                    }
+                } else {
+                    Group::Chain {
+                        id,
+                        elements,
+                        attributes,
+                    }
+                }
+            }
+            1 => {
+                let count = self.len()?;
+                let mut elements = Vec::with_capacity(count);
+                for _ in 0..count {
+                    let id = self.str()?;
+                    let orientation = match self.u8()? {
+                        0 => None,
+                        1 => Some(Orientation::Forward),
+                        2 => Some(Orientation::Reverse),
+                        other => return Err(anyhow!("Invalid orientation {}", other)),
+                    };
+                    elements.push(OrientedElement { id, orientation });
+                }
+                Group::Ordered {
+                    id,
+                    elements,
+                    attributes: self.attributes()?,
+                }
+            }
+            other => return Err(anyhow!("Invalid group kind {}", other)),
+        };
+        Ok(group)
+    }
+
+    fn groups(&mut self) -> Result<HashMap<BString, Group>> {
+        let count = self.len()?;
+        let mut groups = HashMap::with_capacity(count);
+        for _ in 0..count {
+            let key = self.str()?;
+            groups.insert(key, self.group()?);
+        }
+        Ok(groups)
+    }
+
+    fn section(&mut self) -> Result<GraphSection> {
+        let mut section = GraphSection::new(self.str()?);
+        section.attributes = self.attributes()?;
+
+        let node_count = self.len()?;
+        let mut node_ids = Vec::with_capacity(node_count);
+        for _ in 0..node_count {
+            let id = self.str()?;
+            let reference_id = self.str()?;
+            let strand = match self.u8()? {
+                0 => Strand::Forward,
+                1 => Strand::Reverse,
+                other => return Err(anyhow!("Invalid strand {}", other)),
+            };
+            let exon_count = self.len()?;
+            let mut exons = Vec::with_capacity(exon_count);
+            for _ in 0..exon_count {
+                exons.push(Interval {
+                    start: self.u64()?,
+                    end: self.u64()?,
+                });
+            }
+            let read_count = self.len()?;
+            let mut reads = Vec::with_capacity(read_count);
+            for _ in 0..read_count {
+                let id = self.str()?;
+                let identity = match self.u8()? {
+                    0 => ReadIdentity::SO,
+                    1 => ReadIdentity::IN,
+                    2 => ReadIdentity::SI,
+                    other => return Err(anyhow!("Invalid read identity {}", other)),
+                };
+                let sample = match self.u8()? {
+                    0 => None,
+                    _ => Some(self.str()?),
+                };
+                reads.push(ReadData {
+                    id,
+                    identity,
+                    sample,
+                });
+            }
+            let sequence = match self.u8()? {
+                0 => None,
+                _ => Some(self.str()?),
+            };
+            let attributes = self.attributes()?;
+
+            node_ids.push(id.clone());
+            section.add_node(NodeData {
+                id,
+                reference_id,
+                strand,
+                exons: Exons { exons },
+                reads,
+                sequence,
+                attributes,
+            })?;
+        }
+
+        let edge_count = self.len()?;
+        for _ in 0..edge_count {
+            let id = self.str()?;
+            let (source, sink) = (self.len()?, self.len()?);
+            let sv = StructuralVariant {
+                reference_name1: self.str()?,
+                reference_name2: self.str()?,
+                breakpoint1: self.u64()?,
+                breakpoint2: self.u64()?,
+                sv_type: self.str()?,
+            };
+            let attributes = self.attributes()?;
+            let (source, sink) = (
+                node_ids.get(source).context("Edge source out of range")?,
+                node_ids.get(sink).context("Edge sink out of range")?,
+            );
+            section.add_edge(
+                source.as_ref(),
+                sink.as_ref(),
+                EdgeData { id, sv, attributes },
+            )?;
+        }
+
+        section.groups = self.groups()?;
+        section.chains = self.groups()?;
+        Ok(section)
+    }
+
+    fn graph(&mut self) -> Result<TSGraph> {
+        let mut tsg_graph = TSGraph::default();
+
+        let header_count = self.len()?;
+        for _ in 0..header_count {
+            tsg_graph.headers.push(Header {
+                tag: self.str()?,
+                value: self.str()?,
+            });
+        }
+
+        let section_count = self.len()?;
+        for _ in 0..section_count {
+            let section = self.section()?;
+            tsg_graph.graphs.insert(section.id.clone(), section);
+        }
+
+        let link_count = self.len()?;
+        for _ in 0..link_count {
+            tsg_graph.links.push(InterGraphLink {
+                id: self.str()?,
+                source_graph: self.str()?,
+                source_element: self.str()?,
+                target_graph: self.str()?,
+                target_element: self.str()?,
+                link_type: self.str()?,
+                attributes: self.attributes()?,
+            });
+        }
+        Ok(tsg_graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binary_round_trip() -> Result<()> {
+        for file in ["tests/data/test.tsg", "tests/data/test_mul.tsg"] {
+            let tsg_graph = TSGraph::from_file(file)?;
+            let mut buf = Vec::new();
+            to_binary(&tsg_graph, &mut buf)?;
+            assert!(is_binary(&buf));
+
+            let decoded = from_binary(&mut buf.as_slice())?;
+            assert_eq!(decoded.headers, tsg_graph.headers);
+            assert_eq!(decoded.graphs.len(), tsg_graph.graphs.len());
+            assert_eq!(decoded.links.len(), tsg_graph.links.len());
+            for (id, section) in &tsg_graph.graphs {
+                let restored = &decoded.graphs[id];
+                assert_eq!(restored.nodes().len(), section.nodes().len());
+                assert_eq!(restored.edges().len(), section.edges().len());
+                assert_eq!(restored.groups.len(), section.groups.len());
+                assert_eq!(restored.chains.len(), section.chains.len());
+                assert_eq!(restored.attributes.len(), section.attributes.len());
+            }
+
+            // the encoding is canonical, so equal bytes mean equal graphs
+            let mut again = Vec::new();
+            to_binary(&decoded, &mut again)?;
+            assert_eq!(buf, again);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_read_samples() -> Result<()> {
+        let tsg_graph: TSGraph =
+            "H\tsample\ts1\nN\tn1\tchr1:+:100-200\tr1:SO:s1,r2:SO\n".parse()?;
+        let mut buf = Vec::new();
+        to_binary(&tsg_graph, &mut buf)?;
+        let decoded = from_binary(&mut buf.as_slice())?;
+        let reads: Vec<String> = decoded.default_graph().unwrap().nodes()[0]
+            .reads
+            .iter()
+            .map(|read| read.to_string())
+            .collect();
+        assert_eq!(reads, vec!["r1:SO:s1", "r2:SO"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_file_detects_binary() -> Result<()> {
+        let tsg_graph = TSGraph::from_file("tests/data/test.tsg")?;
+        // the process id keeps concurrent test runs from sharing the file
+        let path = std::env::temp_dir().join(format!(
+            "tsg_test_{}_from_file_detects_binary.tsgb",
+            std::process::id()
+        ));
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(&path)?);
+        tsg_graph.to_binary(&mut writer)?;
+        drop(writer);
+
+        let decoded = TSGraph::from_file(&path)?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(decoded.headers, tsg_graph.headers);
+        assert_eq!(decoded.graphs.len(), tsg_graph.graphs.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_corruption() -> Result<()> {
+        let tsg_graph = TSGraph::from_file("tests/data/test.tsg")?;
+        let mut buf = Vec::new();
+        to_binary(&tsg_graph, &mut buf)?;
+
+        let mut corrupted = buf.clone();
+        let middle = corrupted.len() / 2;
+        corrupted[middle] ^= 0xff;
+        assert!(from_binary(&mut corrupted.as_slice()).is_err());
+
+        let truncated = &buf[..buf.len() - 1];
+        assert!(from_binary(&mut &truncated[..]).is_err());
+
+        // a corrupt length is an error, not an allocation of that size
+        let mut oversized = buf.clone();
+        oversized[6..14].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(from_binary(&mut oversized.as_slice()).is_err());
+
+        let mut future = buf.clone();
+        future[4] = 0xff;
+        assert!(from_binary(&mut future.as_slice()).is_err());
+
+        assert!(from_binary(&mut "H\tTSG\t1.0\n".as_bytes()).is_err());
+        Ok(())
+    }
+}
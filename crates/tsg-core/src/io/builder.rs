@@ -0,0 +1,130 @@
+use anyhow::{Context, Result};
+use bstr::{BStr, BString};
+
+use crate::graph::{
+    EdgeData, Exons, GraphSection, Interval, NodeData, ReadData, Strand, StructuralVariant,
+    to_hash_identifier,
+};
+
+/// SV type assigned to edges that represent ordinary splice junctions
+pub const SPLICE_JUNCTION_TYPE: &str = "SPLICE";
+
+/// An exonic block on the reference, either a GTF exon record or an aligned block of a read
+#[derive(Debug, Clone)]
+pub(crate) struct ExonBlock {
+    pub(crate) reference_id: BString,
+    pub(crate) strand: Strand,
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+}
+
+/// Classify the junction between two consecutive exons of a transcript.
+///
+/// Ordinary introns are typed as [`SPLICE_JUNCTION_TYPE`]; junctions of chimeric
+/// transcripts are typed like the structural variant that would explain them.
+pub(crate) fn junction_type(donor: &ExonBlock, acceptor: &ExonBlock) -> &'static str {
+    if donor.reference_id != acceptor.reference_id {
+        "TRA"
+    } else if donor.strand != acceptor.strand {
+        "INV"
+    } else if (donor.strand == Strand::Forward && acceptor.start < donor.end)
+        || (donor.strand == Strand::Reverse && acceptor.end > donor.start)
+    {
+        "TDUP"
+    } else {
+        SPLICE_JUNCTION_TYPE
+    }
+}
+
+/// Add the node of an exonic block, or attach the read to it if the node already exists.
+///
+/// Node ids are derived from the block coordinates, so identical blocks of different
+/// transcripts or reads collapse into one node. Returns the node id.
+pub(crate) fn add_exon_node(
+    section: &mut GraphSection,
+    exon: &ExonBlock,
+    read: ReadData,
+) -> Result<BString> {
+    let node_id: BString = to_hash_identifier(
+        &format!(
+            "{}:{}:{}-{}",
+            exon.reference_id, exon.strand, exon.start, exon.end
+        ),
+        Some(16),
+    )?
+    .into();
+
+    match section.node_indices.get(&node_id) {
+        Some(&node_idx) => {
+            let node = section
+                .node_weight_mut(node_idx)
+                .context("Node index out of sync with graph")?;
+            node.reads.push(read);
+        }
+        None => {
+            let node = NodeData::builder()
+                .id(node_id.clone())
+                .reference_id(exon.reference_id.clone())
+                .strand(exon.strand)
+                .exons(Exons {
+                    exons: vec![Interval {
+                        start: exon.start,
+                        end: exon.end,
+                    }],
+                })
+                .reads(vec![read])
+                .build();
+            section.add_node(node)?;
+        }
+    }
+    Ok(node_id)
+}
+
+/// Connect two exonic blocks with a junction edge unless the junction already exists.
+///
+/// The breakpoints are the 3' end of the donor and the 5' start of the acceptor.
+/// Returns the edge id.
+pub(crate) fn add_junction_edge(
+    section: &mut GraphSection,
+    (donor_id, donor): (&BStr, &ExonBlock),
+    (acceptor_id, acceptor): (&BStr, &ExonBlock),
+    sv_type: &str,
+) -> Result<BString> {
+    let breakpoint1 = match donor.strand {
+        Strand::Forward => donor.end,
+        Strand::Reverse => donor.start,
+    };
+    let breakpoint2 = match acceptor.strand {
+        Strand::Forward => acceptor.start,
+        Strand::Reverse => acceptor.end,
+    };
+    // exons sharing a splice site share the junction, so the nodes are part of the id
+    let edge_id: BString = to_hash_identifier(
+        &format!(
+            "{}>{}:{}:{}:{}-{}:{}:{}",
+            donor_id,
+            acceptor_id,
+            donor.reference_id,
+            donor.strand,
+            breakpoint1,
+            acceptor.reference_id,
+            acceptor.strand,
+            breakpoint2
+        ),
+        Some(16),
+    )?
+    .into();
+
+    if !section.edge_indices.contains_key(&edge_id) {
+        let sv = StructuralVariant::builder()
+            .reference_name1(donor.reference_id.clone())
+            .reference_name2(acceptor.reference_id.clone())
+            .breakpoint1(breakpoint1)
+            .breakpoint2(breakpoint2)
+            .sv_type(sv_type)
+            .build();
+        let edge = EdgeData::builder().id(edge_id.clone()).sv(sv).build();
+        section.add_edge(donor_id, acceptor_id, edge)?;
+    }
+    Ok(edge_id)
+}
@@ -0,0 +1,300 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow};
+use flate2::bufread::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use noodles::bgzf;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Compression formats supported for reading and writing TSG and related files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    /// Plain, uncompressed data
+    #[default]
+    None,
+    /// A single gzip stream
+    Gzip,
+    /// Blocked gzip as written by `bgzip`, readable by any gzip tool
+    Bgzf,
+    /// Zstandard
+    Zstd,
+}
+
+impl Compression {
+    /// Detect the compression format from the first bytes of a file.
+    ///
+    /// BGZF is a gzip stream whose extra field carries a `BC` subfield.
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(ZSTD_MAGIC) {
+            Compression::Zstd
+        } else if bytes.starts_with(GZIP_MAGIC) {
+            let has_extra = bytes.get(3).is_some_and(|flags| flags & 0x04 != 0);
+            if has_extra && bytes.get(12..14) == Some(b"BC") {
+                Compression::Bgzf
+            } else {
+                Compression::Gzip
+            }
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Choose the compression format from a file extension.
+    ///
+    /// `.gz` and `.bgz` files are written as BGZF, which any gzip reader understands,
+    /// and `.zst` files as Zstandard.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Self {
+        match path
+            .as_ref()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("gz" | "bgz") => Compression::Bgzf,
+            Some("zst" | "zstd") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    /// The conventional file extension of the format, without the leading dot
+    pub fn extension(&self) -> Option<&'static str> {
+        match self {
+            Compression::None => None,
+            Compression::Gzip | Compression::Bgzf => Some("gz"),
+            Compression::Zstd => Some("zst"),
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Compression::None => write!(f, "none"),
+            Compression::Gzip => write!(f, "gzip"),
+            Compression::Bgzf => write!(f, "bgzf"),
+            Compression::Zstd => write!(f, "zstd"),
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(Compression::None),
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            "bgzf" | "bgzip" => Ok(Compression::Bgzf),
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            _ => Err(anyhow!(
+                "Unknown compression: {} (expected none, gzip, bgzf or zstd)",
+                s
+            )),
+        }
+    }
+}
+
+/// Wrap a reader so that gzip, BGZF and zstd input is decompressed transparently.
+///
+/// The format is detected from the magic bytes, plain input is passed through.
+pub fn decompressed_reader<'a, R: BufRead + 'a>(mut reader: R) -> Result<Box<dyn BufRead + 'a>> {
+    let reader: Box<dyn BufRead + 'a> = match Compression::detect(reader.fill_buf()?) {
+        Compression::None => Box::new(reader),
+        // BGZF files are concatenated gzip members
+        Compression::Gzip | Compression::Bgzf => {
+            Box::new(BufReader::new(MultiGzDecoder::new(reader)))
+        }
+        Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+    };
+    Ok(reader)
+}
+
+/// Open a file for reading, decompressing it transparently
+pub fn open_reader<P: AsRef<Path>>(path: P) -> Result<Box<dyn BufRead>> {
+    let file = File::open(path.as_ref())
+        .map_err(|e| anyhow!("Failed to open {}: {}", path.as_ref().display(), e))?;
+    decompressed_reader(BufReader::new(file))
+}
+
+/// The encoder of a [`CompressedWriter`]
+enum Encoder<'a> {
+    Plain(Box<dyn Write + 'a>),
+    Gzip(GzEncoder<Box<dyn Write + 'a>>),
+    Bgzf(bgzf::io::Writer<Box<dyn Write + 'a>>),
+    Zstd(zstd::Encoder<'static, Box<dyn Write + 'a>>),
+}
+
+impl Write for Encoder<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Encoder::Plain(writer) => writer.write(buf),
+            Encoder::Gzip(writer) => writer.write(buf),
+            Encoder::Bgzf(writer) => writer.write(buf),
+            Encoder::Zstd(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Encoder::Plain(writer) => writer.flush(),
+            Encoder::Gzip(writer) => writer.flush(),
+            Encoder::Bgzf(writer) => writer.flush(),
+            Encoder::Zstd(writer) => writer.flush(),
+        }
+    }
+}
+
+impl Encoder<'_> {
+    /// Write the end of the compressed stream and flush the underlying writer
+    fn finish(self) -> std::io::Result<()> {
+        let mut writer = match self {
+            Encoder::Plain(writer) => writer,
+            Encoder::Gzip(writer) => writer.finish()?,
+            Encoder::Bgzf(writer) => writer.finish()?,
+            Encoder::Zstd(writer) => writer.finish()?,
+        };
+        writer.flush()
+    }
+}
+
+/// A buffered writer that compresses everything written to it.
+///
+/// [`CompressedWriter::finish`] must be called once everything is written: it writes
+/// the end of the compressed stream and reports the errors of the last writes. A
+/// writer that is dropped unfinished still finishes the stream, but ignores errors.
+pub struct CompressedWriter<'a> {
+    inner: Option<BufWriter<Encoder<'a>>>,
+}
+
+impl<'a> CompressedWriter<'a> {
+    /// Flush the buffered data, finish the compressed stream and flush the
+    /// underlying writer
+    pub fn finish(mut self) -> Result<()> {
+        let writer = self.inner.take().expect("writer is only taken by finish");
+        writer
+            .into_inner()
+            .map_err(|e| anyhow!("Failed to flush output: {}", e.error()))?
+            .finish()
+            .map_err(|e| anyhow!("Failed to finish output: {}", e))
+    }
+
+    fn writer(&mut self) -> &mut BufWriter<Encoder<'a>> {
+        self.inner.as_mut().expect("writer is only taken by finish")
+    }
+}
+
+impl Write for CompressedWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.writer().flush()
+    }
+}
+
+impl Drop for CompressedWriter<'_> {
+    fn drop(&mut self) {
+        if let Some(writer) = self.inner.take()
+            && let Ok(encoder) = writer.into_inner()
+        {
+            let _ = encoder.finish();
+        }
+    }
+}
+
+/// Wrap a writer so that everything written to it is compressed.
+///
+/// Call [`CompressedWriter::finish`] on the returned writer once done.
+pub fn compressed_writer<'a, W: Write + 'a>(
+    writer: W,
+    compression: Compression,
+) -> Result<CompressedWriter<'a>> {
+    let writer: Box<dyn Write + 'a> = Box::new(writer);
+    let encoder = match compression {
+        Compression::None => Encoder::Plain(writer),
+        Compression::Gzip => Encoder::Gzip(GzEncoder::new(writer, flate2::Compression::default())),
+        Compression::Bgzf => Encoder::Bgzf(bgzf::io::Writer::new(writer)),
+        Compression::Zstd => Encoder::Zstd(zstd::Encoder::new(writer, 0)?),
+    };
+    Ok(CompressedWriter {
+        inner: Some(BufWriter::new(encoder)),
+    })
+}
+
+/// Create a file for writing.
+///
+/// If `compression` is None, the format is chosen from the file extension
+/// (see [`Compression::from_path`]). Call [`CompressedWriter::finish`] on the
+/// returned writer once done.
+pub fn create_writer<P: AsRef<Path>>(
+    path: P,
+    compression: Option<Compression>,
+) -> Result<CompressedWriter<'static>> {
+    let path = path.as_ref();
+    let compression = compression.unwrap_or_else(|| Compression::from_path(path));
+    let file =
+        File::create(path).map_err(|e| anyhow!("Failed to create {}: {}", path.display(), e))?;
+    compressed_writer(file, compression)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_compression_round_trip() -> Result<()> {
+        let text = std::fs::read("tests/data/test.tsg")?;
+        for compression in [
+            Compression::None,
+            Compression::Gzip,
+            Compression::Bgzf,
+            Compression::Zstd,
+        ] {
+            let mut buf = Vec::new();
+            let mut writer = compressed_writer(&mut buf, compression)?;
+            writer.write_all(&text)?;
+            writer.finish()?;
+            assert_eq!(Compression::detect(&buf), compression);
+
+            let mut decoded = Vec::new();
+            decompressed_reader(buf.as_slice())?.read_to_end(&mut decoded)?;
+            assert_eq!(decoded, text);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_compression_from_path() {
+        assert_eq!(Compression::from_path("a.tsg"), Compression::None);
+        assert_eq!(Compression::from_path("a.tsg.gz"), Compression::Bgzf);
+        assert_eq!(Compression::from_path("a.tsg.ZST"), Compression::Zstd);
+        assert_eq!("gzip".parse::<Compression>().unwrap(), Compression::Gzip);
+        assert!("lz4".parse::<Compression>().is_err());
+    }
+
+    #[test]
+    fn test_read_compressed_tsg_file() -> Result<()> {
+        let tsg_graph = crate::graph::TSGraph::from_file("tests/data/sampling.tsg")?;
+        for name in ["compressed.tsg.gz", "compressed.tsg.zst"] {
+            let path =
+                std::env::temp_dir().join(format!("tsg_test_{}_{}", std::process::id(), name));
+            tsg_graph.to_file(&path)?;
+            let reloaded = crate::graph::TSGraph::from_file(&path)?;
+            std::fs::remove_file(&path)?;
+            assert_eq!(reloaded.graphs.len(), tsg_graph.graphs.len());
+            for (id, graph) in &tsg_graph.graphs {
+                assert_eq!(reloaded.graphs[id].nodes().len(), graph.nodes().len());
+            }
+        }
+        Ok(())
+    }
+}
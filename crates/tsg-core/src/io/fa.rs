@@ -1,14 +1,74 @@
-use crate::graph::TSGraph;
+use crate::graph::{GraphSection, OrfOptions, TSGPath, TSGraph, TraversalOptions};
 use anyhow::Result;
 use std::io::Write;
 
 pub fn to_fa<W: Write>(tsg_graph: &mut TSGraph, writer: &mut W) -> Result<()> {
-    let paths = tsg_graph.traverse_all_graphs()?;
+    for graph in tsg_graph.graphs.values() {
+        graph_to_fa(graph, &TraversalOptions::default(), false, writer)?;
+    }
+    Ok(())
+}
+
+/// Transcript paths of a graph section: its traversed paths, or with `from_records` its
+/// `P` records, which keep the orientation of their nodes. A section without `P` records
+/// is traversed either way.
+fn transcript_paths<'a>(
+    graph: &'a GraphSection,
+    traversal: &TraversalOptions,
+    from_records: bool,
+) -> Result<Vec<TSGPath<'a>>> {
+    if from_records {
+        let paths = graph.ordered_paths()?;
+        if !paths.is_empty() {
+            return Ok(paths);
+        }
+    }
+    graph.traverse_with(traversal)
+}
 
-    for path in paths {
+/// Write the sequences of the transcript paths of a single graph section as FASTA records.
+///
+/// The paths of the section are traversed with the given options; with `from_records`
+/// its `P` records are written instead when it has any.
+pub fn graph_to_fa<W: Write>(
+    graph: &GraphSection,
+    traversal: &TraversalOptions,
+    from_records: bool,
+    writer: &mut W,
+) -> Result<()> {
+    for path in transcript_paths(graph, traversal, from_records)? {
         let seq = path.to_fa()?;
-        writeln!(writer, ">{}", path.id().unwrap())?;
+        writeln!(writer, ">{}", path.id()?)?;
         writeln!(writer, "{}", seq)?;
     }
     Ok(())
 }
+
+/// Write the protein of the longest ORF of every transcript path of a single graph
+/// section as FASTA records; paths without an ORF are skipped. Transcript paths are
+/// chosen as in [`graph_to_fa`].
+///
+/// The ORF attributes, such as its transcript coordinates and PTC flag, are written
+/// after the path id as `tag:type:value` fields.
+pub fn graph_to_protein_fa<W: Write>(
+    graph: &GraphSection,
+    traversal: &TraversalOptions,
+    from_records: bool,
+    options: &OrfOptions,
+    writer: &mut W,
+) -> Result<()> {
+    for path in transcript_paths(graph, traversal, from_records)? {
+        let Some(orf) = path.find_orf(options)? else {
+            continue;
+        };
+        let description = orf
+            .attributes()
+            .iter()
+            .map(|attr| attr.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        writeln!(writer, ">{} {}", path.id()?, description)?;
+        writeln!(writer, "{}", orf.protein)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,931 @@
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+
+use ahash::{HashMap, HashMapExt};
+use anyhow::{Context, Result, anyhow};
+use bstr::{BString, ByteSlice};
+use petgraph::graph::NodeIndex;
+use tracing::debug;
+
+use crate::graph::{
+    Attribute, DEFAULT_GRAPH_ID, EdgeData, Exons, GraphSection, Group, Interval, NodeData,
+    Orientation, OrientedElement, ReadData, Strand, StructuralVariant,
+};
+use crate::io::SPLICE_JUNCTION_TYPE;
+
+/// Tag holding the node coordinates as `<chrom>:<strand>:<exons>`
+const EXONS_TAG: &str = "ex";
+/// Tag holding the node reads as `<id>:<identity>,...`
+const READS_TAG: &str = "rd";
+/// Tag holding the edge structural variant as `<chr1>,<chr2>,<bp1>,<bp2>,<type>`
+const SV_TAG: &str = "sv";
+/// Tag holding the edge ID on GFA 1.0 links, which have no ID column
+const ID_TAG: &str = "ID";
+/// Tag holding the edges between the segments of a GFA 1.0 path as `<id><orientation>,...`
+const EDGES_TAG: &str = "ed";
+/// Tag marking paths (GFA 1.0) and ordered groups (GFA 2.0) that hold TSG chains
+const GROUP_TYPE_TAG: &str = "gt";
+/// Value of the group type tag for chains
+const CHAIN_GROUP_TYPE: &str = "chain";
+/// Header tag holding the ID of the graph section
+const GRAPH_ID_TAG: &str = "gi";
+/// Header tag holding the GFA version
+const VERSION_TAG: &str = "VN";
+
+/// Version of the GFA specification to read or write
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GfaVersion {
+    #[default]
+    V1,
+    V2,
+}
+
+impl fmt::Display for GfaVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GfaVersion::V1 => write!(f, "1.0"),
+            GfaVersion::V2 => write!(f, "2.0"),
+        }
+    }
+}
+
+impl FromStr for GfaVersion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "1" | "1.0" | "1.1" | "1.2" => Ok(GfaVersion::V1),
+            "2" | "2.0" => Ok(GfaVersion::V2),
+            _ => Err(anyhow!("Unsupported GFA version: {}", s)),
+        }
+    }
+}
+
+fn orientation_sign(orientation: Option<Orientation>) -> char {
+    match orientation {
+        Some(Orientation::Reverse) => '-',
+        _ => '+',
+    }
+}
+
+/// Length of a segment: the sequence length if known, otherwise the number of
+/// bases covered by its closed exon intervals
+fn segment_length(node: &NodeData) -> usize {
+    match &node.sequence {
+        Some(seq) => seq.len(),
+        None => node
+            .exons
+            .exons
+            .iter()
+            .map(|exon| exon.end + 1 - exon.start)
+            .sum(),
+    }
+}
+
+fn node_tags(node: &NodeData) -> Vec<String> {
+    let mut tags = vec![format!("LN:i:{}", segment_length(node))];
+    if !node.exons.is_empty() {
+        tags.push(format!(
+            "{}:Z:{}:{}:{}",
+            EXONS_TAG, node.reference_id, node.strand, node.exons
+        ));
+    }
+    if !node.reads.is_empty() {
+        let reads = node
+            .reads
+            .iter()
+            .map(|r| r.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        tags.push(format!("{}:Z:{}", READS_TAG, reads));
+    }
+    tags.extend(
+        sorted_attributes(&node.attributes)
+            .into_iter()
+            .map(|attr| attr.to_string()),
+    );
+    tags
+}
+
+fn sorted_attributes(attributes: &HashMap<BString, Attribute>) -> Vec<&Attribute> {
+    let mut attributes = attributes.values().collect::<Vec<_>>();
+    attributes.sort_by(|a, b| a.tag.cmp(&b.tag));
+    attributes
+}
+
+/// Write a graph section in GFA format.
+///
+/// Nodes are written as segments, edges as links (GFA 1.0) or edges (GFA 2.0), and
+/// ordered groups as paths (GFA 1.0) or ordered groups (GFA 2.0). Node coordinates,
+/// reads and edge structural variants are kept in the `ex`, `rd` and `sv` tags so that
+/// [`from_gfa`] can restore them. Chains are written like ordered groups and marked
+/// with the `gt:Z:chain` tag; GFA 1.0 paths keep their edges in the `ed` tag.
+pub fn to_gfa<W: Write>(graph: &GraphSection, version: GfaVersion, writer: &mut W) -> Result<()> {
+    write!(
+        writer,
+        "H\t{}:Z:{}\t{}:Z:{}",
+        VERSION_TAG, version, GRAPH_ID_TAG, graph.id
+    )?;
+    for attr in sorted_attributes(&graph.attributes) {
+        write!(writer, "\t{}", attr)?;
+    }
+    writeln!(writer)?;
+
+    // keep the order in which elements were added to the graph
+    let mut node_indices = graph.node_indices.values().copied().collect::<Vec<_>>();
+    node_indices.sort();
+    let mut edge_indices = graph.edge_indices.values().copied().collect::<Vec<_>>();
+    edge_indices.sort();
+
+    for node_idx in &node_indices {
+        let node = graph
+            .node_by_idx(*node_idx)
+            .context("Node index out of sync with graph")?;
+        let sequence = node
+            .sequence
+            .as_ref()
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| "*".to_string());
+        let mut fields = vec![node.id.to_string()];
+        match version {
+            GfaVersion::V1 => {
+                fields.push(sequence);
+                fields.extend(node_tags(node));
+            }
+            GfaVersion::V2 => {
+                // the length has its own column, drop the LN tag
+                fields.push(segment_length(node).to_string());
+                fields.push(sequence);
+                fields.extend(node_tags(node).into_iter().skip(1));
+            }
+        }
+        writeln!(writer, "S\t{}", fields.join("\t"))?;
+    }
+
+    for edge_idx in &edge_indices {
+        let edge = graph
+            .edge_by_idx(*edge_idx)
+            .context("Edge index out of sync with graph")?;
+        let (source_idx, sink_idx) = graph
+            .edge_endpoints(*edge_idx)
+            .context("Edge endpoints not found")?;
+        let source = graph.node_by_idx(source_idx).context("Source not found")?;
+        let sink = graph.node_by_idx(sink_idx).context("Sink not found")?;
+
+        let mut tags = vec![format!("{}:Z:{}", SV_TAG, edge.sv)];
+        tags.extend(
+            sorted_attributes(&edge.attributes)
+                .into_iter()
+                .map(|attr| attr.to_string()),
+        );
+
+        match version {
+            GfaVersion::V1 => writeln!(
+                writer,
+                "L\t{}\t+\t{}\t+\t0M\t{}:Z:{}\t{}",
+                source.id,
+                sink.id,
+                ID_TAG,
+                edge.id,
+                tags.join("\t")
+            )?,
+            GfaVersion::V2 => {
+                // a dovetail overlap of length zero between the end of the source
+                // and the start of the sink
+                let source_len = segment_length(source);
+                writeln!(
+                    writer,
+                    "E\t{}\t{}+\t{}+\t{}$\t{}$\t0\t0\t*\t{}",
+                    edge.id,
+                    source.id,
+                    sink.id,
+                    source_len,
+                    source_len,
+                    tags.join("\t")
+                )?
+            }
+        }
+    }
+
+    let mut groups = graph.groups.values().collect::<Vec<_>>();
+    groups.sort_by(|a, b| group_id(a).cmp(group_id(b)));
+    for group in groups {
+        match group {
+            Group::Ordered {
+                id,
+                elements,
+                attributes,
+            } => write_ordered_group(
+                graph,
+                version,
+                id,
+                elements,
+                sorted_attributes(attributes)
+                    .into_iter()
+                    .map(|attr| attr.to_string())
+                    .collect(),
+                writer,
+            )?,
+            Group::Chain {
+                id,
+                elements,
+                attributes,
+            } => {
+                let elements = elements
+                    .iter()
+                    .map(|element| OrientedElement {
+                        id: element.clone(),
+                        orientation: Some(Orientation::Forward),
+                    })
+                    .collect::<Vec<_>>();
+                let mut tags = vec![format!("{}:Z:{}", GROUP_TYPE_TAG, CHAIN_GROUP_TYPE)];
+                tags.extend(
+                    sorted_attributes(attributes)
+                        .into_iter()
+                        .map(|attr| attr.to_string()),
+                );
+                write_ordered_group(graph, version, id, &elements, tags, writer)?;
+            }
+            Group::Unordered {
+                id,
+                elements,
+                attributes,
+            } => match version {
+                GfaVersion::V1 => {
+                    debug!("GFA 1.0 has no unordered groups, skipping {}", id);
+                }
+                GfaVersion::V2 => {
+                    let attrs = sorted_attributes(attributes)
+                        .into_iter()
+                        .map(|attr| format!("\t{}", attr))
+                        .collect::<String>();
+                    writeln!(
+                        writer,
+                        "U\t{}\t{}{}",
+                        id,
+                        elements
+                            .iter()
+                            .map(|e| e.to_string())
+                            .collect::<Vec<_>>()
+                            .join(" "),
+                        attrs
+                    )?;
+                }
+            },
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write an ordered group as a path (GFA 1.0) or an ordered group (GFA 2.0).
+///
+/// GFA 1.0 paths only list segments, so the edges between them are kept in the
+/// `ed` tag; GFA 2.0 ordered groups reference edges directly.
+fn write_ordered_group<W: Write>(
+    graph: &GraphSection,
+    version: GfaVersion,
+    id: &BString,
+    elements: &[OrientedElement],
+    mut tags: Vec<String>,
+    writer: &mut W,
+) -> Result<()> {
+    let reference = |e: &OrientedElement| format!("{}{}", e.id, orientation_sign(e.orientation));
+    let line = match version {
+        GfaVersion::V1 => {
+            let (segments, edges): (Vec<_>, Vec<_>) = elements
+                .iter()
+                .partition(|e| graph.node_indices.contains_key(&e.id));
+            if !edges.is_empty() {
+                tags.insert(
+                    0,
+                    format!(
+                        "{}:Z:{}",
+                        EDGES_TAG,
+                        edges
+                            .into_iter()
+                            .map(reference)
+                            .collect::<Vec<_>>()
+                            .join(",")
+                    ),
+                );
+            }
+            let segments = segments.into_iter().map(reference).collect::<Vec<_>>();
+            let mut fields = vec![id.to_string(), segments.join(","), "*".to_string()];
+            fields.extend(tags);
+            format!("P\t{}", fields.join("\t"))
+        }
+        GfaVersion::V2 => {
+            let references = elements.iter().map(reference).collect::<Vec<_>>();
+            let mut fields = vec![id.to_string(), references.join(" ")];
+            fields.extend(tags);
+            format!("O\t{}", fields.join("\t"))
+        }
+    };
+    writeln!(writer, "{}", line)?;
+    Ok(())
+}
+
+/// Turn the ordered elements of a path or ordered group into a `P` group, or into a
+/// chain if the group type tag marks it as one
+fn ordered_group(
+    id: BString,
+    elements: Vec<OrientedElement>,
+    mut attributes: HashMap<BString, Attribute>,
+) -> Group {
+    let chain_tag = BString::from(GROUP_TYPE_TAG);
+    if attributes
+        .get(&chain_tag)
+        .is_some_and(|attr| attr.value == CHAIN_GROUP_TYPE)
+    {
+        attributes.remove(&chain_tag);
+        Group::Chain {
+            id,
+            elements: elements.into_iter().map(|e| e.id).collect(),
+            attributes,
+        }
+    } else {
+        Group::Ordered {
+            id,
+            elements,
+            attributes,
+        }
+    }
+}
+
+fn group_id(group: &Group) -> &BString {
+    match group {
+        Group::Unordered { id, .. } | Group::Ordered { id, .. } | Group::Chain { id, .. } => id,
+    }
+}
+
+/// Parse a GFA optional field. GFA character fields (`A`) are kept as strings.
+fn parse_tag(s: &str) -> Result<Attribute> {
+    let parts: Vec<&str> = s.splitn(3, ':').collect();
+    if parts.len() == 3 && parts[1] == "A" {
+        return Ok(Attribute::builder().tag(parts[0]).value(parts[2]).build());
+    }
+    s.parse()
+}
+
+/// Split the optional fields of a GFA line into TSG tags and plain attributes
+fn parse_tags(fields: &[&str]) -> Result<HashMap<BString, Attribute>> {
+    let mut tags = HashMap::new();
+    for field in fields {
+        let attr = parse_tag(field)?;
+        tags.insert(attr.tag.clone(), attr);
+    }
+    Ok(tags)
+}
+
+/// Parse `<chrom>:<strand>:<exons>` as written to the `ex` tag
+fn parse_location(s: &BString) -> Result<(BString, Strand, Exons)> {
+    let s = s.to_str()?;
+    let mut parts = s.rsplitn(3, ':');
+    let exons = parts
+        .next()
+        .ok_or_else(|| anyhow!("Missing exons: {}", s))?;
+    let strand = parts
+        .next()
+        .ok_or_else(|| anyhow!("Missing strand: {}", s))?;
+    let reference_id = parts
+        .next()
+        .ok_or_else(|| anyhow!("Missing reference: {}", s))?;
+    Ok((reference_id.into(), strand.parse()?, exons.parse()?))
+}
+
+fn parse_segment(
+    id: &str,
+    length: Option<usize>,
+    sequence: &str,
+    fields: &[&str],
+) -> Result<NodeData> {
+    let mut tags = parse_tags(fields)?;
+    let sequence: Option<BString> = if sequence == "*" {
+        None
+    } else {
+        Some(sequence.into())
+    };
+
+    let length = match tags.remove(&BString::from("LN")) {
+        Some(attr) => Some(attr.as_int()? as usize),
+        None => length,
+    };
+
+    let (reference_id, strand, exons) = match tags.remove(&BString::from(EXONS_TAG)) {
+        Some(attr) => parse_location(&attr.value)?,
+        None => {
+            // segments without coordinates are their own reference
+            let length = length.or(sequence.as_ref().map(|s| s.len())).unwrap_or(0);
+            (
+                id.into(),
+                Strand::Forward,
+                Exons {
+                    exons: vec![Interval {
+                        start: 1,
+                        end: length,
+                    }],
+                },
+            )
+        }
+    };
+
+    let reads = match tags.remove(&BString::from(READS_TAG)) {
+        Some(attr) => attr
+            .value
+            .to_str()?
+            .split(',')
+            .map(|r| r.parse::<ReadData>())
+            .collect::<Result<Vec<_>, _>>()?,
+        None => Vec::new(),
+    };
+
+    Ok(NodeData {
+        id: id.into(),
+        reference_id,
+        strand,
+        exons,
+        reads,
+        sequence,
+        attributes: tags,
+    })
+}
+
+/// Build edge data for a link, restoring the structural variant from the `sv` tag
+fn parse_link(
+    graph: &GraphSection,
+    id: BString,
+    source: &BString,
+    sink: &BString,
+    inverted: bool,
+    fields: &[&str],
+) -> Result<EdgeData> {
+    let mut tags = parse_tags(fields)?;
+    let sv = match tags.remove(&BString::from(SV_TAG)) {
+        Some(attr) => attr.value.to_str()?.parse::<StructuralVariant>()?,
+        None => {
+            let source = graph
+                .node_by_id(source.to_str()?)
+                .ok_or_else(|| anyhow!("Link {} references unknown segment {}", id, source))?;
+            let sink = graph
+                .node_by_id(sink.to_str()?)
+                .ok_or_else(|| anyhow!("Link {} references unknown segment {}", id, sink))?;
+            StructuralVariant::builder()
+                .reference_name1(source.reference_id.clone())
+                .reference_name2(sink.reference_id.clone())
+                .breakpoint1(source.exons.exons.last().map_or(0, |e| e.end))
+                .breakpoint2(sink.exons.exons.first().map_or(0, |e| e.start))
+                .sv_type(if inverted {
+                    "INV"
+                } else {
+                    SPLICE_JUNCTION_TYPE
+                })
+                .build()
+        }
+    };
+
+    Ok(EdgeData {
+        id,
+        sv,
+        attributes: tags,
+    })
+}
+
+/// Split a GFA 2.0 reference such as `n1+` into its ID and orientation
+fn parse_reference(s: &str) -> Result<(BString, bool)> {
+    let element = s.parse::<OrientedElement>()?;
+    Ok((
+        element.id,
+        element.orientation == Some(Orientation::Reverse),
+    ))
+}
+
+/// Add a link to the graph. Links between two reverse-oriented segments are
+/// stored in the opposite direction, since `a- -> b-` is the same as `b+ -> a+`.
+fn add_link(
+    graph: &mut GraphSection,
+    id: BString,
+    (from, from_reverse): (BString, bool),
+    (to, to_reverse): (BString, bool),
+    fields: &[&str],
+) -> Result<()> {
+    let (source, sink) = if from_reverse && to_reverse {
+        (to, from)
+    } else {
+        (from, to)
+    };
+    let edge = parse_link(
+        graph,
+        id,
+        &source,
+        &sink,
+        from_reverse != to_reverse,
+        fields,
+    )?;
+    graph.add_edge(source.as_ref(), sink.as_ref(), edge)?;
+    Ok(())
+}
+
+/// Interleave the edges between consecutive segments of a GFA 1.0 path.
+///
+/// The edges come from the `ed` tag when the path has one; paths of other tools
+/// get the link between each pair of segments.
+fn path_elements(
+    graph: &GraphSection,
+    segments: &[OrientedElement],
+    edges: Option<Vec<OrientedElement>>,
+) -> Result<Vec<OrientedElement>> {
+    if let Some(edges) = edges {
+        if edges.len() + 1 != segments.len() {
+            return Err(anyhow!(
+                "Path has {} segments but {} edges",
+                segments.len(),
+                edges.len()
+            ));
+        }
+        let mut elements = Vec::with_capacity(segments.len() * 2);
+        for (segment, edge) in segments.iter().zip(edges) {
+            elements.push(segment.clone());
+            elements.push(edge);
+        }
+        elements.extend(segments.last().cloned());
+        return Ok(elements);
+    }
+
+    let node_idx = |id: &BString| -> Result<NodeIndex> {
+        graph
+            .node_indices
+            .get(id)
+            .copied()
+            .ok_or_else(|| anyhow!("Path references unknown segment {}", id))
+    };
+
+    let mut elements = Vec::with_capacity(segments.len() * 2);
+    for (idx, segment) in segments.iter().enumerate() {
+        if idx > 0 {
+            let previous = &segments[idx - 1];
+            let (source, sink) = (node_idx(&previous.id)?, node_idx(&segment.id)?);
+            let (edge_idx, orientation) = match graph.find_edge(source, sink) {
+                Some(edge_idx) => (edge_idx, Orientation::Forward),
+                None => (
+                    graph.find_edge(sink, source).ok_or_else(|| {
+                        anyhow!("No link between {} and {}", previous.id, segment.id)
+                    })?,
+                    Orientation::Reverse,
+                ),
+            };
+            let edge = graph
+                .edge_by_idx(edge_idx)
+                .context("Edge index out of sync with graph")?;
+            elements.push(OrientedElement {
+                id: edge.id.clone(),
+                orientation: Some(orientation),
+            });
+        }
+        elements.push(segment.clone());
+    }
+    Ok(elements)
+}
+
+/// Detect the GFA version from the header, falling back to the record types used
+fn detect_version(lines: &[String]) -> Result<GfaVersion> {
+    for line in lines {
+        let fields: Vec<&str> = line.split('\t').collect();
+        match fields[0] {
+            "H" => {
+                for field in &fields[1..] {
+                    if let Some(version) = field.strip_prefix("VN:Z:") {
+                        return version.parse();
+                    }
+                }
+            }
+            "E" | "O" | "U" | "F" | "G" => return Ok(GfaVersion::V2),
+            "L" | "P" | "C" | "W" => return Ok(GfaVersion::V1),
+            _ => {}
+        }
+    }
+    Ok(GfaVersion::V1)
+}
+
+/// Read a graph section from GFA 1.0 or 2.0.
+///
+/// Segments become nodes, links or edges become edges, and paths or ordered groups
+/// become `P` groups or chains. The `ex`, `rd`, `sv`, `ed` and `gt` tags written by
+/// [`to_gfa`] restore node coordinates, reads, structural variants, path edges and
+/// chains; segments without coordinates are treated as their own reference sequence.
+/// The graph ID is taken from the `gi` header tag.
+pub fn from_gfa<R: BufRead>(reader: R) -> Result<GraphSection> {
+    let lines = reader
+        .lines()
+        .filter(|line| {
+            line.as_ref()
+                .map(|l| !l.is_empty() && !l.starts_with('#'))
+                .unwrap_or(true)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let version = detect_version(&lines)?;
+
+    let mut graph = GraphSection::new(DEFAULT_GRAPH_ID.into());
+
+    // segments first, links and paths may reference segments defined later
+    for line in &lines {
+        let fields: Vec<&str> = line.split('\t').collect();
+        match (fields[0], version) {
+            ("H", _) => {
+                for attr in parse_tags(&fields[1..])?.into_values() {
+                    if attr.tag == GRAPH_ID_TAG {
+                        graph.id = attr.value;
+                    } else if attr.tag != VERSION_TAG {
+                        graph.attributes.insert(attr.tag.clone(), attr);
+                    }
+                }
+            }
+            ("S", GfaVersion::V1) if fields.len() >= 3 => {
+                let node = parse_segment(fields[1], None, fields[2], &fields[3..])?;
+                graph.add_node(node)?;
+            }
+            ("S", GfaVersion::V2) if fields.len() >= 4 => {
+                let length = fields[2]
+                    .parse()
+                    .with_context(|| format!("Invalid segment length: {}", fields[2]))?;
+                let node = parse_segment(fields[1], Some(length), fields[3], &fields[4..])?;
+                graph.add_node(node)?;
+            }
+            ("S", _) => return Err(anyhow!("Invalid segment line: {}", line)),
+            _ => {}
+        }
+    }
+
+    for line in &lines {
+        let fields: Vec<&str> = line.split('\t').collect();
+        match (fields[0], version) {
+            ("L", GfaVersion::V1) => {
+                if fields.len() < 6 {
+                    return Err(anyhow!("Invalid link line: {}", line));
+                }
+                let tags = &fields[6..];
+                let id: BString = tags
+                    .iter()
+                    .find_map(|f| f.strip_prefix("ID:Z:"))
+                    .map(|id| id.into())
+                    .unwrap_or_else(|| format!("{}_{}", fields[1], fields[3]).into());
+                let tags = tags
+                    .iter()
+                    .filter(|f| !f.starts_with("ID:Z:"))
+                    .copied()
+                    .collect::<Vec<_>>();
+                add_link(
+                    &mut graph,
+                    id,
+                    (fields[1].into(), fields[2] == "-"),
+                    (fields[3].into(), fields[4] == "-"),
+                    &tags,
+                )?;
+            }
+            ("E", GfaVersion::V2) => {
+                if fields.len() < 9 {
+                    return Err(anyhow!("Invalid edge line: {}", line));
+                }
+                let id: BString = if fields[1] == "*" {
+                    format!("{}_{}", fields[2], fields[3]).into()
+                } else {
+                    fields[1].into()
+                };
+                add_link(
+                    &mut graph,
+                    id,
+                    parse_reference(fields[2])?,
+                    parse_reference(fields[3])?,
+                    &fields[9..],
+                )?;
+            }
+            ("P", GfaVersion::V1) => {
+                if fields.len() < 3 {
+                    return Err(anyhow!("Invalid path line: {}", line));
+                }
+                let segments = fields[2]
+                    .split(',')
+                    .map(|s| s.parse::<OrientedElement>())
+                    .collect::<Result<Vec<_>, _>>()?;
+                let mut attributes = parse_tags(fields.get(4..).unwrap_or_default())?;
+                let edges = match attributes.remove(&BString::from(EDGES_TAG)) {
+                    Some(attr) => Some(
+                        attr.value
+                            .to_str()?
+                            .split(',')
+                            .map(|s| s.parse::<OrientedElement>())
+                            .collect::<Result<Vec<_>, _>>()?,
+                    ),
+                    None => None,
+                };
+                let elements = path_elements(&graph, &segments, edges)
+                    .with_context(|| format!("Invalid path {}", fields[1]))?;
+                graph.groups.insert(
+                    fields[1].into(),
+                    ordered_group(fields[1].into(), elements, attributes),
+                );
+            }
+            ("O", GfaVersion::V2) | ("U", GfaVersion::V2) => {
+                if fields.len() < 3 {
+                    return Err(anyhow!("Invalid group line: {}", line));
+                }
+                let id: BString = fields[1].into();
+                let tag_start = fields
+                    .iter()
+                    .skip(2)
+                    .position(|f| parse_tag(f).is_ok())
+                    .map_or(fields.len(), |p| p + 2);
+                let references = fields[2..tag_start].join(" ");
+                let attributes = parse_tags(&fields[tag_start..])?;
+                let group = if fields[0] == "O" {
+                    let elements = references
+                        .split_whitespace()
+                        .map(|s| s.parse::<OrientedElement>())
+                        .collect::<Result<Vec<_>, _>>()?;
+                    ordered_group(id.clone(), elements, attributes)
+                } else {
+                    Group::Unordered {
+                        id: id.clone(),
+                        elements: references.split_whitespace().map(|s| s.into()).collect(),
+                        attributes,
+                    }
+                };
+                graph.groups.insert(id, group);
+            }
+            ("H", _) | ("S", _) => {}
+            (record, _) => {
+                debug!("Ignoring unsupported GFA {} record: {}", version, record);
+            }
+        }
+    }
+
+    Ok(graph)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::{DEFAULT_GRAPH_ID, TSGraph};
+    use std::io::BufReader;
+
+    fn round_trip(version: GfaVersion) -> Result<()> {
+        let tsg_graph = TSGraph::from_file("tests/data/test.tsg")?;
+        let graph = tsg_graph.default_graph().unwrap();
+
+        let mut buffer = Vec::new();
+        to_gfa(graph, version, &mut buffer)?;
+        let gfa = from_gfa(BufReader::new(buffer.as_slice()))?;
+
+        assert_eq!(gfa.id, DEFAULT_GRAPH_ID);
+        assert_eq!(gfa.nodes().len(), graph.nodes().len());
+        assert_eq!(gfa.edges().len(), graph.edges().len());
+
+        let n1 = gfa.node_by_id("n1").unwrap();
+        assert_eq!(n1.reference_id, "chr1");
+        assert_eq!(n1.exons.to_string(), "1000-1200,1500-1700");
+        assert_eq!(n1.reads.len(), 2);
+        assert_eq!(n1.sequence, Some("ACGTACGT".into()));
+        assert_eq!(n1.attributes[&BString::from("ptc")].as_int()?, 10);
+        assert_eq!(gfa.node_by_id("n4").unwrap().strand, Strand::Reverse);
+
+        let e1 = gfa.edge_by_id("e1").unwrap();
+        assert_eq!(e1.sv.to_string(), "chr1,chr1,1700,2000,INV");
+
+        match &gfa.groups[&BString::from("transcript1")] {
+            Group::Ordered {
+                elements,
+                attributes,
+                ..
+            } => {
+                assert_eq!(elements.len(), 5);
+                assert_eq!(elements[1].id, "e1");
+                assert_eq!(attributes[&BString::from("tpm")].as_float()?, 8.2);
+            }
+            _ => panic!("Expected ordered group"),
+        }
+        match &gfa.groups[&BString::from("chain1")] {
+            Group::Chain { elements, .. } => {
+                assert_eq!(elements, &vec!["n1", "e1", "n3", "e2", "n4"]);
+            }
+            _ => panic!("Expected chain"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_gfa_records() -> Result<()> {
+        let mut graph = GraphSection::new("g1".into());
+        graph.add_node(NodeData::builder().id("s1").build())?;
+        let mut buffer = Vec::new();
+        to_gfa(&graph, GfaVersion::V2, &mut buffer)?;
+        // no empty tag field after the sequence
+        assert!(buffer.to_str()?.lines().any(|line| line == "S\ts1\t0\t*"));
+
+        let tsg_graph = TSGraph::from_file("tests/data/test.tsg")?;
+        let graph = tsg_graph.default_graph().unwrap();
+        let mut buffer = Vec::new();
+        to_gfa(graph, GfaVersion::V1, &mut buffer)?;
+        let gfa = buffer.to_str()?;
+        assert!(gfa.contains("S\tn2\tTGCATGCA\tLN:i:8"));
+        let mut graph = from_gfa(BufReader::new(buffer.as_slice()))?;
+        graph
+            .node_weight_mut(graph.node_indices[&BString::from("n2")])
+            .unwrap()
+            .sequence = None;
+        let mut buffer = Vec::new();
+        to_gfa(&graph, GfaVersion::V1, &mut buffer)?;
+        // without a sequence the length is that of the closed exon 2000-2200
+        assert!(buffer.to_str()?.contains("S\tn2\t*\tLN:i:201"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_gfa1_path_edges() -> Result<()> {
+        // the path names its edge, which is not the one the links would give
+        let gfa = "\
+H\tVN:Z:1.0
+S\ts1\tACGT
+S\ts2\tGG
+L\ts1\t+\ts2\t+\t0M\tID:Z:l1
+P\tp1\ts1+,s2+\t*\ted:Z:l2+\ttpm:f:2
+P\tc1\ts1+,s2+\t*\ted:Z:l1+\tgt:Z:chain
+P\tp2\ts1+,s2+\t*\ted:Z:l1+,l1+
+";
+        assert!(from_gfa(BufReader::new(gfa.as_bytes())).is_err());
+
+        let graph = from_gfa(BufReader::new(
+            gfa.lines()
+                .take(6)
+                .collect::<Vec<_>>()
+                .join("\n")
+                .as_bytes(),
+        ))?;
+        match &graph.groups[&BString::from("p1")] {
+            Group::Ordered {
+                elements,
+                attributes,
+                ..
+            } => {
+                assert_eq!(elements[1].id, "l2");
+                assert_eq!(attributes.len(), 1);
+            }
+            _ => panic!("Expected ordered group"),
+        }
+        match &graph.groups[&BString::from("c1")] {
+            Group::Chain {
+                elements,
+                attributes,
+                ..
+            } => {
+                assert_eq!(elements, &vec!["s1", "l1", "s2"]);
+                assert!(attributes.is_empty());
+            }
+            _ => panic!("Expected chain"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_gfa1_round_trip() -> Result<()> {
+        round_trip(GfaVersion::V1)
+    }
+
+    #[test]
+    fn test_gfa2_round_trip() -> Result<()> {
+        round_trip(GfaVersion::V2)
+    }
+
+    #[test]
+    fn test_from_plain_gfa1() -> Result<()> {
+        let gfa = "\
+H\tVN:Z:1.0
+S\ts1\tACGT
+S\ts2\t*\tLN:i:10
+S\ts3\tGG
+L\ts1\t+\ts2\t+\t0M
+L\ts3\t-\ts2\t-\t0M
+P\tp1\ts1+,s2+\t*
+";
+        let graph = from_gfa(BufReader::new(gfa.as_bytes()))?;
+        assert_eq!(graph.nodes().len(), 3);
+        let s2 = graph.node_by_id("s2").unwrap();
+        assert_eq!(
+            (s2.exons.first_exon().start, s2.exons.last_exon().end),
+            (1, 10)
+        );
+
+        // s3- -> s2- is stored as s2 -> s3
+        let edge_idx = graph.edge_indices[&BString::from("s3_s2")];
+        let (source, sink) = graph.edge_endpoints(edge_idx).unwrap();
+        assert_eq!(graph.node_by_idx(source).unwrap().id, "s2");
+        assert_eq!(graph.node_by_idx(sink).unwrap().id, "s3");
+
+        match &graph.groups[&BString::from("p1")] {
+            Group::Ordered { elements, .. } => assert_eq!(elements.len(), 3),
+            _ => panic!("Expected ordered group"),
+        }
+        Ok(())
+    }
+}
@@ -1,20 +1,318 @@
-use anyhow::Result;
+use std::io::BufRead;
 
-use crate::graph::TSGraph;
+use ahash::{HashMap, HashMapExt};
+use anyhow::{Context, Result, anyhow};
+use bstr::BString;
+
+use super::builder::{ExonBlock, add_exon_node, add_junction_edge, junction_type};
+use crate::graph::{
+    DEFAULT_GRAPH_ID, GraphSection, Group, Orientation, OrientedElement, ReadData, ReadIdentity,
+    Strand, TSGraph, TraversalOptions,
+};
 use std::io::Write;
 
 pub fn to_gtf<W: Write>(tsg_graph: &TSGraph, writer: &mut W) -> Result<()> {
-    let paths = tsg_graph.traverse_all_graphs()?;
-    for path in paths {
+    for graph in tsg_graph.graphs.values() {
+        graph_to_gtf(graph, &TraversalOptions::default(), writer)?;
+    }
+    Ok(())
+}
+
+/// Write the transcript paths of a single graph section as GTF records
+pub fn graph_to_gtf<W: Write>(
+    graph: &GraphSection,
+    traversal: &TraversalOptions,
+    writer: &mut W,
+) -> Result<()> {
+    for path in graph.traverse_with(traversal)? {
         let seq = path.to_gtf()?;
         writeln!(writer, "{}", seq)?;
     }
     Ok(())
 }
 
+/// All exons of one transcript, in the order they appear in the GTF
+#[derive(Debug, Clone)]
+pub(crate) struct GtfTranscript {
+    pub(crate) id: BString,
+    pub(crate) exons: Vec<ExonBlock>,
+}
+
+/// Parse the attribute column of a GTF (`key "value"; key "value";`) or GFF3
+/// (`key=value;key=value`) line
+fn parse_gtf_attributes(s: &str) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+    for item in s.split(';') {
+        let item = item.trim();
+        if item.is_empty() {
+            continue;
+        }
+        let gff3_pair = item
+            .split_once('=')
+            .filter(|(key, _)| !key.contains(char::is_whitespace));
+        if let Some((key, value)) = gff3_pair {
+            attributes.insert(key.to_string(), decode_gff3_value(value));
+        } else if let Some((key, value)) = item.split_once(char::is_whitespace) {
+            attributes.insert(key.to_string(), value.trim().trim_matches('"').to_string());
+        }
+    }
+    attributes
+}
+
+/// Undo the `%XX` escaping GFF3 applies to reserved characters in attribute values
+fn decode_gff3_value(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let escaped = (bytes[idx] == b'%')
+            .then(|| value.get(idx + 1..idx + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                idx += 3;
+            }
+            None => {
+                decoded.push(bytes[idx]);
+                idx += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Parse a GTF strand column, treating unknown strands as forward
+fn parse_gtf_strand(s: &str) -> Result<Strand> {
+    match s {
+        "." | "?" => Ok(Strand::Forward),
+        _ => s.parse(),
+    }
+}
+
+/// Build a single graph section from the transcripts of one gene.
+///
+/// Exons shared by several transcripts are collapsed into one node, consecutive exons
+/// of a transcript are connected by junction edges, and every transcript is recorded
+/// as an ordered group (`P` record). Each transcript is also attached to its nodes as
+/// a read (SO for the first exon, SI for the last, IN in between), so that
+/// `GraphSection::traverse` follows the annotated transcripts.
+fn build_gene_section(gene_id: &BString, transcripts: &[GtfTranscript]) -> Result<GraphSection> {
+    let mut section = GraphSection::new(gene_id.clone());
+
+    for transcript in transcripts {
+        let mut exons = transcript.exons.clone();
+        let first = exons
+            .first()
+            .ok_or_else(|| anyhow!("Transcript {} has no exons", transcript.id))?;
+        let (reference_id, strand) = (first.reference_id.clone(), first.strand);
+
+        // order collinear transcripts in the direction of transcription; chimeric
+        // transcripts keep the order of the GTF records
+        if exons
+            .iter()
+            .all(|e| e.reference_id == reference_id && e.strand == strand)
+        {
+            exons.sort_by_key(|e| e.start);
+            if strand == Strand::Reverse {
+                exons.reverse();
+            }
+        }
+
+        let mut node_ids: Vec<BString> = Vec::with_capacity(exons.len());
+        for (idx, exon) in exons.iter().enumerate() {
+            let identity = if idx == 0 {
+                ReadIdentity::SO
+            } else if idx == exons.len() - 1 {
+                ReadIdentity::SI
+            } else {
+                ReadIdentity::IN
+            };
+            let read = ReadData {
+                id: transcript.id.clone(),
+                identity,
+                sample: None,
+            };
+            let node_id = add_exon_node(&mut section, exon, read)?;
+            node_ids.push(node_id);
+        }
+
+        let mut elements = Vec::with_capacity(node_ids.len() * 2);
+        for (idx, pair) in exons.windows(2).enumerate() {
+            let (donor, acceptor) = (&pair[0], &pair[1]);
+            let edge_id = add_junction_edge(
+                &mut section,
+                (node_ids[idx].as_ref(), donor),
+                (node_ids[idx + 1].as_ref(), acceptor),
+                junction_type(donor, acceptor),
+            )?;
+
+            elements.push(OrientedElement {
+                id: node_ids[idx].clone(),
+                orientation: Some(Orientation::Forward),
+            });
+            elements.push(OrientedElement {
+                id: edge_id,
+                orientation: Some(Orientation::Forward),
+            });
+        }
+        elements.push(OrientedElement {
+            id: node_ids[node_ids.len() - 1].clone(),
+            orientation: Some(Orientation::Forward),
+        });
+
+        if section.groups.contains_key(&transcript.id) {
+            return Err(anyhow!(
+                "Transcript {} appears more than once in gene {}",
+                transcript.id,
+                gene_id
+            ));
+        }
+        section.groups.insert(
+            transcript.id.clone(),
+            Group::Ordered {
+                id: transcript.id.clone(),
+                elements,
+                attributes: HashMap::new(),
+            },
+        );
+    }
+
+    Ok(section)
+}
+
+/// Read the `exon` records of a GTF or GFF3 file as transcripts grouped by gene,
+/// keeping genes and transcripts in the order they first appear.
+///
+/// GTF exons name their gene and transcript with `gene_id` and `transcript_id`.
+/// GFF3 exons name their transcripts with `Parent=`, and the gene is the `Parent=`
+/// of the transcript record; transcripts without a parent form a gene of their own.
+pub(crate) fn read_gtf_genes<R: BufRead>(reader: R) -> Result<Vec<(BString, Vec<GtfTranscript>)>> {
+    // exons with their transcript and, for GTF, their gene
+    let mut exons: Vec<(BString, Option<BString>, ExonBlock)> = Vec::new();
+    // GFF3 transcript records: transcript ID -> gene ID
+    let mut transcript_genes: HashMap<BString, BString> = HashMap::new();
+
+    for (line_number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 9 {
+            return Err(anyhow!(
+                "Invalid GTF line {}: expected 9 columns, found {}",
+                line_number + 1,
+                fields.len()
+            ));
+        }
+
+        let attributes = parse_gtf_attributes(fields[8]);
+        if fields[2] != "exon" {
+            if let (Some(id), Some(parent)) = (attributes.get("ID"), attributes.get("Parent")) {
+                transcript_genes.insert(id.as_str().into(), parent.as_str().into());
+            }
+            continue;
+        }
+
+        let exon = ExonBlock {
+            reference_id: fields[0].into(),
+            strand: parse_gtf_strand(fields[6])
+                .with_context(|| format!("Invalid strand on GTF line {}", line_number + 1))?,
+            start: fields[3]
+                .parse()
+                .with_context(|| format!("Invalid start on GTF line {}", line_number + 1))?,
+            end: fields[4]
+                .parse()
+                .with_context(|| format!("Invalid end on GTF line {}", line_number + 1))?,
+        };
+
+        if let Some(parents) = attributes.get("Parent") {
+            // an exon shared by several transcripts lists all of them
+            for parent in parents.split(',') {
+                exons.push((parent.into(), None, exon.clone()));
+            }
+            continue;
+        }
+
+        let gene_id: BString = attributes
+            .get("gene_id")
+            .ok_or_else(|| anyhow!("Missing gene_id on GTF line {}", line_number + 1))?
+            .as_str()
+            .into();
+        let transcript_id: BString = attributes
+            .get("transcript_id")
+            .ok_or_else(|| anyhow!("Missing transcript_id on GTF line {}", line_number + 1))?
+            .as_str()
+            .into();
+        exons.push((transcript_id, Some(gene_id), exon));
+    }
+
+    let mut genes: Vec<(BString, Vec<GtfTranscript>)> = Vec::new();
+    let mut gene_index: HashMap<BString, usize> = HashMap::new();
+    let mut transcript_index: HashMap<BString, (usize, usize)> = HashMap::new();
+
+    for (transcript_id, gene_id, exon) in exons {
+        let gene_id = gene_id
+            .or_else(|| transcript_genes.get(&transcript_id).cloned())
+            .unwrap_or_else(|| transcript_id.clone());
+
+        let gene_idx = *gene_index.entry(gene_id.clone()).or_insert_with(|| {
+            genes.push((gene_id.clone(), Vec::new()));
+            genes.len() - 1
+        });
+
+        match transcript_index.get(&transcript_id) {
+            Some(&(g_idx, t_idx)) => {
+                if g_idx != gene_idx {
+                    return Err(anyhow!(
+                        "Transcript {} is assigned to more than one gene",
+                        transcript_id
+                    ));
+                }
+                genes[g_idx].1[t_idx].exons.push(exon);
+            }
+            None => {
+                let transcripts = &mut genes[gene_idx].1;
+                transcripts.push(GtfTranscript {
+                    id: transcript_id.clone(),
+                    exons: vec![exon],
+                });
+                transcript_index.insert(transcript_id, (gene_idx, transcripts.len() - 1));
+            }
+        }
+    }
+
+    Ok(genes)
+}
+
+/// Build a TSGraph from GTF or GFF3 transcript annotations.
+///
+/// Only `exon` records are used, plus the `ID=`/`Parent=` links of GFF3 transcript
+/// records. Transcripts are grouped by gene into graph sections, and every transcript
+/// becomes one `P` path through its section.
+/// Exon coordinates are taken verbatim, matching what [`to_gtf`] writes.
+pub fn from_gtf<R: BufRead>(reader: R) -> Result<TSGraph> {
+    let genes = read_gtf_genes(reader)?;
+    let mut tsg_graph = TSGraph::new();
+    for (gene_id, transcripts) in &genes {
+        let section = build_gene_section(gene_id, transcripts)
+            .with_context(|| format!("Failed to build graph for gene {}", gene_id))?;
+        tsg_graph.graphs.insert(gene_id.clone(), section);
+    }
+
+    // drop the default graph, all sections are named after genes
+    tsg_graph.graphs.remove(&BString::from(DEFAULT_GRAPH_ID));
+    Ok(tsg_graph)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::io::SPLICE_JUNCTION_TYPE;
+    use std::io::BufReader;
 
     #[test]
     fn test_to_gtf() {
@@ -25,4 +323,83 @@ mod tests {
         let mut writer = std::io::BufWriter::new(file);
         to_gtf(&tsg_graph, &mut writer).unwrap();
     }
+
+    #[test]
+    fn test_from_gtf() -> Result<()> {
+        let gtf = "\
+chr1\ttest\ttranscript\t100\t600\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";
+chr1\ttest\texon\t100\t200\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";
+chr1\ttest\texon\t300\t400\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";
+chr1\ttest\texon\t500\t600\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";
+chr1\ttest\texon\t100\t200\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t2\";
+chr1\ttest\texon\t500\t600\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t2\";
+chr2\ttest\texon\t900\t1000\t.\t-\t.\tgene_id \"g2\"; transcript_id \"t3\";
+chr2\ttest\texon\t700\t800\t.\t-\t.\tgene_id \"g2\"; transcript_id \"t3\";
+";
+        let tsg_graph = from_gtf(BufReader::new(gtf.as_bytes()))?;
+        assert_eq!(tsg_graph.graphs.len(), 2);
+
+        let g1 = tsg_graph.graph("g1").unwrap();
+        // the first and last exons are shared by t1 and t2
+        assert_eq!(g1.nodes().len(), 3);
+        assert_eq!(g1.edges().len(), 3);
+        assert_eq!(g1.groups.len(), 2);
+        assert!(
+            g1.edges()
+                .iter()
+                .all(|e| e.sv.sv_type == SPLICE_JUNCTION_TYPE)
+        );
+
+        let paths = g1.traverse()?;
+        assert_eq!(paths.len(), 2);
+
+        // minus strand transcripts are ordered from 5' to 3'
+        let g2 = tsg_graph.graph("g2").unwrap();
+        let paths = g2.traverse()?;
+        assert_eq!(paths.len(), 1);
+        let first = g2.node_by_idx(paths[0].nodes[0]).unwrap();
+        assert_eq!(first.reference_start(), 900);
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_gff3() -> Result<()> {
+        let gff = "\
+##gff-version 3
+chr1\ttest\tgene\t100\t600\t.\t+\t.\tID=g1;Name=G%3B1
+chr1\ttest\tmRNA\t100\t600\t.\t+\t.\tID=t1;Parent=g1
+chr1\ttest\tmRNA\t100\t600\t.\t+\t.\tID=t2;Parent=g1
+chr1\ttest\texon\t100\t200\t.\t+\t.\tID=e1;Parent=t1,t2
+chr1\ttest\texon\t300\t400\t.\t+\t.\tID=e2;Parent=t1
+chr1\ttest\texon\t500\t600\t.\t+\t.\tID=e3;Parent=t1,t2
+chr2\ttest\texon\t700\t800\t.\t-\t.\tParent=t3
+";
+        let attributes = parse_gtf_attributes("ID=g1;Name=G%3B1");
+        assert_eq!(attributes["Name"], "G;1");
+
+        let tsg_graph = from_gtf(BufReader::new(gff.as_bytes()))?;
+        assert_eq!(tsg_graph.graphs.len(), 2);
+
+        let g1 = tsg_graph.graph("g1").unwrap();
+        assert_eq!(g1.nodes().len(), 3);
+        assert_eq!(g1.edges().len(), 3);
+        assert_eq!(g1.groups.len(), 2);
+        assert_eq!(g1.traverse()?.len(), 2);
+
+        // a transcript without a gene record becomes its own gene
+        let t3 = tsg_graph.graph("t3").unwrap();
+        assert_eq!(t3.nodes().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_gtf_file() -> Result<()> {
+        // contains chimeric transcripts whose exons switch strands
+        let tsg_graph = TSGraph::from_gtf("tests/data/AR_in_40_2.gtf")?;
+        assert_eq!(tsg_graph.graphs.len(), 3);
+
+        let g1 = tsg_graph.graph("1").unwrap();
+        assert!(g1.edges().iter().any(|e| e.sv.sv_type == "INV"));
+        Ok(())
+    }
 }
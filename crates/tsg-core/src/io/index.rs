@@ -0,0 +1,662 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use ahash::{HashMap, HashSet, HashSetExt};
+use anyhow::{Context, Result, anyhow};
+use bstr::{BString, ByteSlice};
+use noodles::bgzf;
+
+use super::Compression;
+use crate::graph::{DEFAULT_GRAPH_ID, Exons, StructuralVariant, TSGraph};
+
+/// Version of the `.tsi` index format written by [`TsgIndex::write`]
+pub const TSI_VERSION: u32 = 2;
+
+/// File extension of TSG index files
+pub const TSI_EXTENSION: &str = "tsi";
+
+/// Genomic region covered by the nodes and edges of a graph on one reference sequence
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GenomicSpan {
+    pub reference_id: BString,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl GenomicSpan {
+    /// Check whether the span overlaps the closed interval `[start, end]` on `reference_id`
+    pub fn overlaps(&self, reference_id: &str, start: usize, end: usize) -> bool {
+        self.reference_id == reference_id && self.start <= end && start <= self.end
+    }
+}
+
+impl fmt::Display for GenomicSpan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}-{}", self.reference_id, self.start, self.end)
+    }
+}
+
+/// Location of one graph section in a TSG file
+#[derive(Debug, Clone)]
+pub struct GraphIndexEntry {
+    pub id: BString,
+    /// Byte offset of the section, a BGZF virtual position for BGZF files
+    pub offset: u64,
+    /// Uncompressed length of the section in bytes
+    pub length: u64,
+    /// Genomic span of the section on every reference sequence its node exons or edge
+    /// breakpoints touch
+    pub spans: Vec<GenomicSpan>,
+}
+
+/// Location of one inter-graph link (`L` line) in a TSG file
+#[derive(Debug, Clone)]
+pub struct LinkIndexEntry {
+    pub offset: u64,
+    pub length: u64,
+    pub source_graph: BString,
+    pub target_graph: BString,
+}
+
+/// Random-access index of a TSG file (`.tsi`).
+///
+/// The index records where every graph section starts and how long it is, together
+/// with its genomic span, so that single graphs or regions can be loaded without
+/// parsing the rest of the file. Plain text and BGZF compressed TSG files can be
+/// indexed.
+#[derive(Debug, Clone)]
+pub struct TsgIndex {
+    /// Compression of the indexed file, either none or BGZF
+    pub compression: Compression,
+    /// Size of the indexed file on disk, used to detect stale indexes
+    pub file_size: u64,
+    /// Modification time of the indexed file in nanoseconds since the Unix epoch, used
+    /// together with the size to detect stale indexes
+    pub modified: u64,
+    /// Offset and length of the lines before the first graph section
+    pub header: (u64, u64),
+    pub graphs: Vec<GraphIndexEntry>,
+    pub links: Vec<LinkIndexEntry>,
+}
+
+/// Positioned reader over a plain or BGZF compressed TSG file
+enum IndexedSource {
+    Plain(BufReader<File>),
+    Bgzf(bgzf::io::Reader<File>),
+}
+
+impl IndexedSource {
+    fn open(path: &Path, compression: Compression) -> Result<Self> {
+        let file = File::open(path)?;
+        match compression {
+            Compression::None => Ok(IndexedSource::Plain(BufReader::new(file))),
+            Compression::Bgzf => Ok(IndexedSource::Bgzf(bgzf::io::Reader::new(file))),
+            other => Err(anyhow!(
+                "Cannot index {} compressed files, compress with bgzf instead",
+                other
+            )),
+        }
+    }
+
+    fn position(&mut self) -> Result<u64> {
+        match self {
+            IndexedSource::Plain(reader) => Ok(reader.stream_position()?),
+            IndexedSource::Bgzf(reader) => Ok(u64::from(reader.virtual_position())),
+        }
+    }
+
+    fn read_line(&mut self, buf: &mut Vec<u8>) -> Result<usize> {
+        let n = match self {
+            IndexedSource::Plain(reader) => reader.read_until(b'\n', buf)?,
+            IndexedSource::Bgzf(reader) => reader.read_until(b'\n', buf)?,
+        };
+        Ok(n)
+    }
+
+    fn read_range(&mut self, offset: u64, length: u64, buf: &mut Vec<u8>) -> Result<()> {
+        match self {
+            IndexedSource::Plain(reader) => {
+                reader.seek(SeekFrom::Start(offset))?;
+                reader.take(length).read_to_end(buf)?;
+            }
+            IndexedSource::Bgzf(reader) => {
+                reader.seek(bgzf::VirtualPosition::from(offset))?;
+                reader.take(length).read_to_end(buf)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Size and modification time of a file, as recorded in the index
+fn file_stamp(path: &Path) -> Result<(u64, u64)> {
+    let metadata = std::fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos() as u64);
+    Ok((metadata.len(), modified))
+}
+
+/// Extend the span on `reference_id` to cover `[start, end]`
+fn extend_spans(spans: &mut Vec<GenomicSpan>, reference_id: &[u8], start: usize, end: usize) {
+    match spans.iter_mut().find(|s| s.reference_id == reference_id) {
+        Some(span) => {
+            span.start = span.start.min(start);
+            span.end = span.end.max(end);
+        }
+        None => spans.push(GenomicSpan {
+            reference_id: reference_id.into(),
+            start,
+            end,
+        }),
+    }
+}
+
+/// Record type (first field) of a TSG line
+fn record_type(line: &[u8]) -> &[u8] {
+    line.fields().next().unwrap_or_default()
+}
+
+impl TsgIndex {
+    /// Default index path of a TSG file, `<file>.tsi`
+    pub fn default_path<P: AsRef<Path>>(tsg_path: P) -> PathBuf {
+        let mut path = tsg_path.as_ref().as_os_str().to_owned();
+        path.push(".");
+        path.push(TSI_EXTENSION);
+        path.into()
+    }
+
+    /// Build the index of a plain or BGZF compressed TSG file.
+    ///
+    /// Only the record type of each line and the locations of `G`, `N`, `E` and `L`
+    /// lines are inspected, so building the index is much cheaper than parsing the file.
+    /// Records before the first `G` line are indexed as the default graph.
+    pub fn build<P: AsRef<Path>>(tsg_path: P) -> Result<Self> {
+        let tsg_path = tsg_path.as_ref();
+        let compression = {
+            let mut reader = BufReader::new(File::open(tsg_path)?);
+            Compression::detect(reader.fill_buf()?)
+        };
+        let (file_size, modified) = file_stamp(tsg_path)?;
+        let mut source = IndexedSource::open(tsg_path, compression)?;
+
+        let mut graphs: Vec<GraphIndexEntry> = Vec::new();
+        let mut links = Vec::new();
+        // the section being scanned, starting with the preamble of the default graph
+        let mut current = GraphIndexEntry {
+            id: DEFAULT_GRAPH_ID.into(),
+            offset: 0,
+            length: 0,
+            spans: Vec::new(),
+        };
+        let mut preamble_has_records = false;
+        // uncompressed bytes read so far, and where the current section started
+        let (mut consumed, mut section_start) = (0u64, 0u64);
+        let mut header_length = None;
+        let mut line = Vec::new();
+
+        loop {
+            let offset = source.position()?;
+            line.clear();
+            let n = source.read_line(&mut line)?;
+            if n == 0 {
+                break;
+            }
+
+            match record_type(&line) {
+                b"G" => {
+                    current.length = consumed - section_start;
+                    if header_length.is_none() {
+                        header_length = Some(consumed);
+                    }
+                    let id = line
+                        .fields()
+                        .nth(1)
+                        .ok_or_else(|| anyhow!("Invalid graph line: {}", line.as_bstr()))?;
+                    let previous = std::mem::replace(
+                        &mut current,
+                        GraphIndexEntry {
+                            id: id.into(),
+                            offset,
+                            length: 0,
+                            spans: Vec::new(),
+                        },
+                    );
+                    if previous.id != DEFAULT_GRAPH_ID || preamble_has_records {
+                        graphs.push(previous);
+                    }
+                    section_start = consumed;
+                }
+                b"N" => {
+                    preamble_has_records |= header_length.is_none();
+                    let location = line
+                        .fields()
+                        .nth(2)
+                        .ok_or_else(|| anyhow!("Invalid node line: {}", line.as_bstr()))?;
+                    let mut parts = location.splitn_str(3, ":");
+                    let (Some(reference_id), Some(_), Some(exons)) =
+                        (parts.next(), parts.next(), parts.next())
+                    else {
+                        return Err(anyhow!("Invalid node location: {}", location.as_bstr()));
+                    };
+                    let exons: Exons = exons.to_str()?.parse()?;
+                    if !exons.exons.is_empty() {
+                        let (start, end) = (exons.first_exon().start, exons.last_exon().end);
+                        extend_spans(&mut current.spans, reference_id, start, end);
+                    }
+                }
+                b"E" => {
+                    preamble_has_records |= header_length.is_none();
+                    let sv: StructuralVariant = line
+                        .fields()
+                        .nth(4)
+                        .ok_or_else(|| anyhow!("Invalid edge line: {}", line.as_bstr()))?
+                        .to_str()?
+                        .parse()?;
+                    for (reference_id, breakpoint) in [
+                        (&sv.reference_name1, sv.breakpoint1),
+                        (&sv.reference_name2, sv.breakpoint2),
+                    ] {
+                        if !reference_id.is_empty() {
+                            extend_spans(&mut current.spans, reference_id, breakpoint, breakpoint);
+                        }
+                    }
+                }
+                b"L" => {
+                    let fields: Vec<&[u8]> = line.fields().collect();
+                    if fields.len() < 4 {
+                        return Err(anyhow!("Invalid link line: {}", line.as_bstr()));
+                    }
+                    let graph_of = |element: &[u8]| -> BString {
+                        element.split_str(":").next().unwrap_or_default().into()
+                    };
+                    links.push(LinkIndexEntry {
+                        offset,
+                        length: n as u64,
+                        source_graph: graph_of(fields[2]),
+                        target_graph: graph_of(fields[3]),
+                    });
+                }
+                b"" | b"H" => {}
+                record if record.starts_with(b"#") => {}
+                _ => preamble_has_records |= header_length.is_none(),
+            }
+            consumed += n as u64;
+        }
+
+        current.length = consumed - section_start;
+        if current.id != DEFAULT_GRAPH_ID || preamble_has_records {
+            graphs.push(current);
+        }
+
+        Ok(TsgIndex {
+            compression,
+            file_size,
+            modified,
+            header: (0, header_length.unwrap_or(consumed)),
+            graphs,
+            links,
+        })
+    }
+
+    /// Look up the entry of a graph by its id
+    pub fn graph(&self, id: &str) -> Option<&GraphIndexEntry> {
+        self.graphs.iter().find(|g| g.id == id)
+    }
+
+    /// Entries of all graphs whose span overlaps the closed interval `[start, end]`
+    pub fn overlapping(
+        &self,
+        reference_id: &str,
+        start: usize,
+        end: usize,
+    ) -> Vec<&GraphIndexEntry> {
+        self.graphs
+            .iter()
+            .filter(|g| g.spans.iter().any(|s| s.overlaps(reference_id, start, end)))
+            .collect()
+    }
+
+    /// Load the given graphs from the indexed TSG file.
+    ///
+    /// Only the header lines, the requested sections and the links between requested
+    /// graphs are read and parsed.
+    pub fn read_graphs<P: AsRef<Path>>(&self, tsg_path: P, ids: &[&str]) -> Result<TSGraph> {
+        let tsg_path = tsg_path.as_ref();
+        if file_stamp(tsg_path)? != (self.file_size, self.modified) {
+            return Err(anyhow!(
+                "Index is out of date for {}, rebuild it with `tsg index`",
+                tsg_path.display()
+            ));
+        }
+        let mut source = IndexedSource::open(tsg_path, self.compression)?;
+        let entries: HashMap<&[u8], &GraphIndexEntry> =
+            self.graphs.iter().map(|g| (g.id.as_slice(), g)).collect();
+
+        let mut text = Vec::new();
+        let mut chunk = Vec::new();
+        let keep_lines = |chunk: &[u8], text: &mut Vec<u8>, types: &[&[u8]], keep: bool| {
+            for line in chunk.lines_with_terminator() {
+                if types.contains(&record_type(line)) == keep {
+                    text.extend_from_slice(line);
+                    if !line.ends_with(b"\n") {
+                        text.push(b'\n');
+                    }
+                }
+            }
+        };
+
+        // header lines
+        source.read_range(self.header.0, self.header.1, &mut chunk)?;
+        keep_lines(&chunk, &mut text, &[b"H"], true);
+
+        let mut loaded: HashSet<&[u8]> = HashSet::new();
+        for id in ids {
+            if !loaded.insert(id.as_bytes()) {
+                continue;
+            }
+            let entry = entries
+                .get(id.as_bytes())
+                .ok_or_else(|| anyhow!("Graph with ID '{}' not found in index", id))?;
+            chunk.clear();
+            source.read_range(entry.offset, entry.length, &mut chunk)?;
+            // links are added separately, headers come from the header block
+            keep_lines(&chunk, &mut text, &[b"H", b"L"], false);
+        }
+
+        for link in &self.links {
+            if loaded.contains(link.source_graph.as_slice())
+                && loaded.contains(link.target_graph.as_slice())
+            {
+                chunk.clear();
+                source.read_range(link.offset, link.length, &mut chunk)?;
+                keep_lines(&chunk, &mut text, &[b"L"], true);
+            }
+        }
+
+        TSGraph::from_reader(text.as_slice())
+    }
+
+    /// Load all graphs overlapping the closed interval `[start, end]` on `reference_id`
+    pub fn read_region<P: AsRef<Path>>(
+        &self,
+        tsg_path: P,
+        reference_id: &str,
+        start: usize,
+        end: usize,
+    ) -> Result<TSGraph> {
+        let ids: Vec<&str> = self
+            .overlapping(reference_id, start, end)
+            .iter()
+            .map(|g| g.id.to_str())
+            .collect::<Result<_, _>>()?;
+        self.read_graphs(tsg_path, &ids)
+    }
+
+    /// Write the index as tab-separated text
+    pub fn write<W: Write>(&self, writer: &mut W) -> Result<()> {
+        writeln!(
+            writer,
+            "##tsi\t{}\t{}\t{}\t{}",
+            TSI_VERSION, self.compression, self.file_size, self.modified
+        )?;
+        writeln!(writer, "H\t{}\t{}", self.header.0, self.header.1)?;
+        for graph in &self.graphs {
+            let spans = if graph.spans.is_empty() {
+                ".".to_string()
+            } else {
+                graph
+                    .spans
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            };
+            writeln!(
+                writer,
+                "G\t{}\t{}\t{}\t{}",
+                graph.id, graph.offset, graph.length, spans
+            )?;
+        }
+        for link in &self.links {
+            writeln!(
+                writer,
+                "L\t{}\t{}\t{}\t{}",
+                link.offset, link.length, link.source_graph, link.target_graph
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Write the index to a file
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let mut writer = std::io::BufWriter::new(File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Read an index written by [`Self::write`]
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
+        let mut lines = reader.lines();
+        let magic = lines.next().ok_or_else(|| anyhow!("Empty TSG index"))??;
+        let fields: Vec<&str> = magic.split('\t').collect();
+        if fields.first() != Some(&"##tsi") {
+            return Err(anyhow!("Not a TSG index file"));
+        }
+        let version: u32 = fields[1].parse()?;
+        if version != TSI_VERSION {
+            return Err(anyhow!(
+                "Unsupported TSG index version {}, rebuild it with `tsg index`",
+                version
+            ));
+        }
+        if fields.len() != 5 {
+            return Err(anyhow!("Invalid TSG index header: {}", magic));
+        }
+
+        let mut index = TsgIndex {
+            compression: fields[2].parse()?,
+            file_size: fields[3].parse()?,
+            modified: fields[4].parse()?,
+            header: (0, 0),
+            graphs: Vec::new(),
+            links: Vec::new(),
+        };
+
+        for (line_number, line) in lines.enumerate() {
+            let line = line?;
+            let fields: Vec<&str> = line.split('\t').collect();
+            let invalid = || anyhow!("Invalid TSG index line {}: {}", line_number + 2, line);
+            match fields.as_slice() {
+                ["H", offset, length] => index.header = (offset.parse()?, length.parse()?),
+                ["G", id, offset, length, spans] => {
+                    let spans = if *spans == "." {
+                        Vec::new()
+                    } else {
+                        spans
+                            .split(',')
+                            .map(|span| {
+                                let (reference_id, range) =
+                                    span.rsplit_once(':').ok_or_else(invalid)?;
+                                let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+                                Ok(GenomicSpan {
+                                    reference_id: reference_id.into(),
+                                    start: start.parse()?,
+                                    end: end.parse()?,
+                                })
+                            })
+                            .collect::<Result<Vec<_>>>()?
+                    };
+                    index.graphs.push(GraphIndexEntry {
+                        id: (*id).into(),
+                        offset: offset.parse()?,
+                        length: length.parse()?,
+                        spans,
+                    });
+                }
+                ["L", offset, length, source_graph, target_graph] => {
+                    index.links.push(LinkIndexEntry {
+                        offset: offset.parse()?,
+                        length: length.parse()?,
+                        source_graph: (*source_graph).into(),
+                        target_graph: (*target_graph).into(),
+                    })
+                }
+                [""] => {}
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(index)
+    }
+
+    /// Read an index from a file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = File::open(path.as_ref())
+            .with_context(|| format!("Failed to open index {}", path.as_ref().display()))?;
+        Self::from_reader(BufReader::new(file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::GenomicRegion;
+
+    /// Path in the temp dir that is unique to this test process
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("tsg_index_{}_{}", std::process::id(), name))
+    }
+
+    fn indexed_copy(name: &str, compression: Compression) -> Result<PathBuf> {
+        let path = temp_path(name);
+        let text = std::fs::read("tests/data/test_mul.tsg")?;
+        let mut writer = crate::io::compressed_writer(File::create(&path)?, compression)?;
+        writer.write_all(&text)?;
+        writer.finish()?;
+        TsgIndex::build(&path)?.to_file(TsgIndex::default_path(&path))?;
+        Ok(path)
+    }
+
+    #[test]
+    fn test_build_index() -> Result<()> {
+        let index = TsgIndex::build("tests/data/test_mul.tsg")?;
+        assert_eq!(index.compression, Compression::None);
+        assert_eq!(index.graphs.len(), 2);
+        assert_eq!(index.links.len(), 1);
+
+        let gene_a = index.graph("gene_a").unwrap();
+        assert_eq!(
+            gene_a.spans,
+            vec![GenomicSpan {
+                reference_id: "chr17".into(),
+                start: 41196312,
+                end: 41203134
+            }]
+        );
+        assert_eq!(index.overlapping("chr13", 32316000, 32316001).len(), 1);
+        assert!(index.overlapping("chr13", 1, 100).is_empty());
+
+        let mut buf = Vec::new();
+        index.write(&mut buf)?;
+        let reloaded = TsgIndex::from_reader(buf.as_slice())?;
+        assert_eq!(reloaded.header, index.header);
+        assert_eq!(reloaded.modified, index.modified);
+        assert_eq!(reloaded.graphs.len(), index.graphs.len());
+        assert_eq!(
+            reloaded.graph("gene_b").unwrap().spans,
+            index.graph("gene_b").unwrap().spans
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_indexed_graphs() -> Result<()> {
+        let full = TSGraph::from_file("tests/data/test_mul.tsg")?;
+        for (name, compression) in [
+            ("plain.tsg", Compression::None),
+            ("bgzf.tsg.gz", Compression::Bgzf),
+        ] {
+            let path = indexed_copy(name, compression)?;
+
+            let tsg = TSGraph::from_file_with_ids(&path, &["gene_b"])?;
+            assert_eq!(tsg.graphs.len(), 1);
+            assert_eq!(tsg.headers.len(), full.headers.len());
+            assert!(tsg.links.is_empty());
+            assert_eq!(
+                tsg.graph("gene_b").unwrap().nodes().len(),
+                full.graph("gene_b").unwrap().nodes().len()
+            );
+
+            let both = TSGraph::from_file_with_ids(&path, &["gene_a", "gene_b"])?;
+            assert_eq!(both.links.len(), 1);
+
+            let region = TSGraph::from_file_in_region(&path, "chr17", 41199700, 41199800)?;
+            assert!(region.graph("gene_a").is_some());
+            assert!(region.graph("gene_b").is_none());
+
+            assert!(TSGraph::from_file_with_ids(&path, &["gene_c"]).is_err());
+            std::fs::remove_file(TsgIndex::default_path(&path))?;
+            std::fs::remove_file(&path)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_edge_breakpoints() -> Result<()> {
+        let path = temp_path("breakpoints.tsg");
+        std::fs::write(
+            &path,
+            "H\tTSG\t1.0\n\
+             G\tfusion\n\
+             N\tn1\tchr1:+:100-200\tr1:SO\n\
+             N\tn2\tchr1:+:300-400\tr1:SI\n\
+             E\te1\tn1\tn2\tchr1,chr2,200,5000,TRA\n",
+        )?;
+        let index = TsgIndex::build(&path)?;
+        let fusion = index.graph("fusion").unwrap();
+        assert_eq!(fusion.spans.len(), 2);
+        assert_eq!(fusion.spans[1].to_string(), "chr2:5000-5000");
+
+        // the indexed and the full query agree on a region hit by the breakpoint only
+        let region: GenomicRegion = "chr2:4990-5010".parse()?;
+        let full = TSGraph::from_file(&path)?;
+        assert_eq!(full.graphs_in_region(&region).len(), 1);
+        assert_eq!(index.overlapping("chr2", region.start, region.end).len(), 1);
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_stale_index() -> Result<()> {
+        let path = indexed_copy("stale.tsg", Compression::None)?;
+        let index = TsgIndex::from_file(TsgIndex::default_path(&path))?;
+        assert!(index.read_graphs(&path, &["gene_a"]).is_ok());
+
+        // same size, different modification time
+        let modified = std::fs::metadata(&path)?.modified()?;
+        File::options()
+            .write(true)
+            .open(&path)?
+            .set_modified(modified + std::time::Duration::from_secs(1))?;
+        let error = index.read_graphs(&path, &["gene_a"]).unwrap_err();
+        assert!(error.to_string().contains("out of date"));
+
+        std::fs::remove_file(TsgIndex::default_path(&path))?;
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_graph_index() -> Result<()> {
+        let index = TsgIndex::build("tests/data/test.tsg")?;
+        assert_eq!(index.graphs.len(), 1);
+        assert_eq!(index.graphs[0].id, DEFAULT_GRAPH_ID);
+        assert!(TsgIndex::build("tests/data/test.tsg.missing").is_err());
+        Ok(())
+    }
+}
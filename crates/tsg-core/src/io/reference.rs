@@ -0,0 +1,145 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow};
+use bstr::{BString, ByteVec};
+use noodles::fasta;
+use rayon::prelude::*;
+
+use crate::graph::{GraphSection, Strand, reverse_complement};
+
+type IndexedFastaReader = fasta::io::IndexedReader<fasta::io::BufReader<File>>;
+
+/// An indexed reference genome used to fill node sequences.
+///
+/// The `.fai` index is read once and shared by all readers; a plain FASTA without an
+/// index is indexed in memory. BGZF compressed FASTA files need both `.fai` and `.gzi`.
+#[derive(Debug, Clone)]
+pub struct ReferenceGenome {
+    path: PathBuf,
+    index: fasta::fai::Index,
+}
+
+impl ReferenceGenome {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut index_path = path.as_os_str().to_owned();
+        index_path.push(".fai");
+        let index_path = PathBuf::from(index_path);
+
+        let index = if index_path.exists() {
+            fasta::fai::fs::read(&index_path)
+                .with_context(|| format!("Failed to read {}", index_path.display()))?
+        } else {
+            fasta::fs::index(&path).with_context(|| {
+                format!(
+                    "Failed to index {}, run `samtools faidx` on it first",
+                    path.display()
+                )
+            })?
+        };
+        Ok(Self { path, index })
+    }
+
+    fn reader(&self) -> Result<IndexedFastaReader> {
+        fasta::io::indexed_reader::Builder::default()
+            .set_index(self.index.clone())
+            .build_from_path(&self.path)
+            .with_context(|| format!("Failed to open {}", self.path.display()))
+    }
+
+    /// Fill the sequence of every node of the graph with the bases of its exons.
+    ///
+    /// Exons are 1-based and inclusive; multi-exon nodes get their exons concatenated.
+    /// Sequences of `-` strand nodes are reverse complemented, so that every node reads
+    /// 5' to 3' along its strand. Nodes without exons are left untouched.
+    pub fn annotate_graph(&self, graph: &mut GraphSection) -> Result<()> {
+        let mut reader = self.reader()?;
+        annotate_with_reader(&mut reader, graph)
+    }
+
+    /// Fill the node sequences of many graphs, in parallel over graphs
+    pub fn annotate_graphs(&self, graphs: Vec<&mut GraphSection>) -> Result<()> {
+        graphs.into_par_iter().try_for_each_init(
+            || self.reader(),
+            |reader, graph| match reader {
+                Ok(reader) => annotate_with_reader(reader, graph),
+                Err(e) => Err(anyhow!("{:#}", e)),
+            },
+        )
+    }
+}
+
+fn annotate_with_reader(reader: &mut IndexedFastaReader, graph: &mut GraphSection) -> Result<()> {
+    let node_indices: Vec<_> = graph.node_indices.values().copied().collect();
+    for node_idx in node_indices {
+        let node = graph
+            .node_weight_mut(node_idx)
+            .ok_or_else(|| anyhow!("Node not found for index: {}", node_idx.index()))?;
+        if node.exons.exons.is_empty() {
+            continue;
+        }
+
+        let mut sequence = BString::from("");
+        for exon in &node.exons.exons {
+            let region = format!("{}:{}-{}", node.reference_id, exon.start, exon.end);
+            let record = reader
+                .query(&region.parse()?)
+                .with_context(|| format!("Failed to fetch {} for node {}", region, node.id))?;
+            sequence.push_str(record.sequence().as_ref());
+        }
+        if node.strand == Strand::Reverse {
+            sequence = reverse_complement(&sequence);
+        }
+        node.sequence = Some(sequence);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graph::TSGraph;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_annotate_sequences() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("tsg_reference_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let fasta = dir.join("ref.fa");
+        std::fs::write(&fasta, ">chr1\nAACCGGTTAC\nGATTACAGGG\n")?;
+
+        let mut tsg = TSGraph::from_str(
+            "G\tg1\n\
+             N\tn1\tchr1:+:1-4,9-10\tr1:SO\n\
+             N\tn2\tchr1:-:11-17\tr1:SI\n\
+             E\te1\tn1\tn2\tchr1,chr1,10,11,splice\n\
+             G\tg2\n\
+             N\tn1\tchr1:+:3-6\tr2:SO\n",
+        )?;
+        tsg.annotate_node_with_sequence(&fasta)?;
+
+        let g1 = tsg.graph("g1").unwrap();
+        assert_eq!(
+            g1.node_by_id("n1").unwrap().sequence.as_ref().unwrap(),
+            "AACCAC"
+        );
+        assert_eq!(
+            g1.node_by_id("n2").unwrap().sequence.as_ref().unwrap(),
+            "TGTAATC"
+        );
+        let g2 = tsg.graph("g2").unwrap();
+        assert_eq!(
+            g2.node_by_id("n1").unwrap().sequence.as_ref().unwrap(),
+            "CCGG"
+        );
+
+        // the minus-strand node is reverse complemented along the path
+        let path = &g1.traverse()?[0];
+        assert_eq!(path.to_fa()?, "AACCACTGTAATC");
+
+        assert!(ReferenceGenome::from_path(dir.join("missing.fa")).is_err());
+        std::fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
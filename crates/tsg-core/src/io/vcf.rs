@@ -1,4 +1,4 @@
-use crate::graph::TSGraph;
+use crate::graph::{GraphSection, TSGraph, TraversalOptions};
 use anyhow::Result;
 use std::io::Write;
 
@@ -42,14 +42,29 @@ static VCF_HEADER: &[&str] = &[
 ];
 
 pub fn to_vcf<W: Write>(tsg_graph: &TSGraph, writer: &mut W) -> Result<()> {
-    let paths = tsg_graph.traverse_all_graphs()?;
+    write_vcf_header(writer)?;
+    for graph in tsg_graph.graphs.values() {
+        graph_to_vcf(graph, &TraversalOptions::default(), writer)?;
+    }
+    Ok(())
+}
 
-    let header = VCF_HEADER;
-    for line in header {
+/// Write the VCF meta-information and column header lines
+pub fn write_vcf_header<W: Write>(writer: &mut W) -> Result<()> {
+    for line in VCF_HEADER {
         writeln!(writer, "{}", line)?;
     }
+    Ok(())
+}
 
-    for path in paths {
+/// Write the junctions of the transcript paths of a single graph section as VCF records,
+/// without the header (see [`write_vcf_header`])
+pub fn graph_to_vcf<W: Write>(
+    graph: &GraphSection,
+    traversal: &TraversalOptions,
+    writer: &mut W,
+) -> Result<()> {
+    for path in graph.traverse_with(traversal)? {
         let seq = path.to_vcf()?;
         writeln!(writer, "{}", seq)?;
     }